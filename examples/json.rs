@@ -0,0 +1,29 @@
+//! Converts packets between JSON and bytes, to write test fixtures by hand and inspect captures.
+//!
+//! ```text
+//! cargo run --example json -- encode fixture.json fixture.bin
+//! cargo run --example json -- decode capture.bin
+//! ```
+
+use std::io;
+
+use tora::config::ToraConfig;
+use tora::schema::Reflect;
+use tora::Reflect;
+
+#[allow(dead_code)]
+#[derive(Reflect)]
+enum Packet {
+    Login { user: String, version: u16 },
+    Move { x: f32, y: f32 },
+    Chat { message: String, to: Option<u32> },
+    Logout,
+}
+
+fn main() -> io::Result<()> {
+    tora::json::cli(
+        &Packet::schema(),
+        &ToraConfig::DEFAULT,
+        std::env::args().skip(1),
+    )
+}
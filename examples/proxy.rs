@@ -0,0 +1,22 @@
+//! Forwards framed traffic between clients and a server, printing every frame in hexadecimal.
+//!
+//! ```text
+//! cargo run --example proxy -- 127.0.0.1:4000 127.0.0.1:5000
+//! ```
+
+use std::io;
+
+use tora::config::ToraConfig;
+use tora::proxy::Proxy;
+use tora::testing::to_hex;
+
+fn main() -> io::Result<()> {
+    let mut args = std::env::args().skip(1);
+
+    let (Some(listen), Some(upstream)) = (args.next(), args.next()) else {
+        eprintln!("Usage: proxy <listen address> <server address>");
+        std::process::exit(2);
+    };
+
+    Proxy::new(|payload: &[u8], _: &ToraConfig| Ok(to_hex(payload))).listen(listen, upstream)
+}
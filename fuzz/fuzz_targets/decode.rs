@@ -0,0 +1,9 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+mod packet;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = tora::fuzz::try_decode::<packet::Packet>(data);
+});
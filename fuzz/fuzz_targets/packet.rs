@@ -0,0 +1,23 @@
+use tora::{ReadEnum, ReadStruct, WriteEnum, WriteStruct};
+
+/// A packet exercising most built-in implementations. Replace it with your own packet types.
+#[derive(Debug, PartialEq, ReadEnum, WriteEnum)]
+#[type_variant_id(u16)]
+pub enum Packet {
+    Ping(u64),
+    Join(Join),
+    Chat {
+        sender: Option<String>,
+        text: String,
+    },
+    Batch(Vec<Packet>),
+    Position([i32; 3], char),
+}
+
+#[derive(Debug, PartialEq, ReadStruct, WriteStruct)]
+pub struct Join {
+    pub id: u32,
+    pub name: String,
+    pub roles: Vec<(u8, bool)>,
+    pub banned: Result<(), String>,
+}
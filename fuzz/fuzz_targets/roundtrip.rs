@@ -0,0 +1,7 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+mod packet;
+
+fuzz_target!(|data: &[u8]| tora::fuzz::roundtrip::<packet::Packet>(data));
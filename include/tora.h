@@ -0,0 +1,288 @@
+#ifndef TORA_H
+#define TORA_H
+
+/* Generated by cbindgen from src/ffi.rs, do not edit. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// The result of a C ABI call.
+typedef enum {
+  TORA_STATUS_OK = 0,
+  // A pointer argument was null.
+  TORA_STATUS_NULL_POINTER,
+  // The buffer of the encoder or the output is too small.
+  TORA_STATUS_BUFFER_TOO_SMALL,
+  // The decoder reached the end of its data.
+  TORA_STATUS_UNEXPECTED_EOF,
+  // The data does not hold a valid value.
+  TORA_STATUS_INVALID_DATA,
+  // The value cannot be encoded.
+  TORA_STATUS_INVALID_INPUT,
+  // Any other error.
+  TORA_STATUS_ERROR,
+} ToraStatus;
+
+// A handle to a [Schema], owned by C code.
+typedef struct ToraSchema ToraSchema;
+
+// Encodes values into a caller-owned buffer.
+//
+// `len` is the amount of bytes encoded so far, at the start of `data`.
+typedef struct {
+  uint8_t *data;
+  size_t capacity;
+  size_t len;
+} ToraEncoder;
+
+// Decodes values from a caller-owned buffer.
+//
+// `position` is the amount of bytes decoded so far, from the start of `data`.
+typedef struct {
+  const uint8_t *data;
+  size_t len;
+  size_t position;
+} ToraDecoder;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// Returns a static, nul-terminated description of the status code.
+//
+// Takes the code as an integer, so any value C passes is valid; unknown codes are described as
+// such.
+const char *tora_status_message(uint32_t status);
+
+// Constructs an encoder writing to the buffer of `capacity` bytes.
+ToraEncoder tora_encoder(uint8_t *data, size_t capacity);
+
+// Constructs a decoder reading the `len` bytes of the buffer.
+ToraDecoder tora_decoder(const uint8_t *data, size_t len);
+
+// Encodes a u8.
+//
+// # Safety
+//
+// The encoder must be null or valid, and its buffer must be valid for writes of `capacity`
+// bytes.
+ToraStatus tora_encode_u8(ToraEncoder *encoder, uint8_t value);
+
+// Encodes a u16.
+//
+// # Safety
+//
+// See [tora_encode_u8].
+ToraStatus tora_encode_u16(ToraEncoder *encoder, uint16_t value);
+
+// Encodes a u32.
+//
+// # Safety
+//
+// See [tora_encode_u8].
+ToraStatus tora_encode_u32(ToraEncoder *encoder, uint32_t value);
+
+// Encodes a u64.
+//
+// # Safety
+//
+// See [tora_encode_u8].
+ToraStatus tora_encode_u64(ToraEncoder *encoder, uint64_t value);
+
+// Encodes an i8.
+//
+// # Safety
+//
+// See [tora_encode_u8].
+ToraStatus tora_encode_i8(ToraEncoder *encoder, int8_t value);
+
+// Encodes an i16.
+//
+// # Safety
+//
+// See [tora_encode_u8].
+ToraStatus tora_encode_i16(ToraEncoder *encoder, int16_t value);
+
+// Encodes an i32.
+//
+// # Safety
+//
+// See [tora_encode_u8].
+ToraStatus tora_encode_i32(ToraEncoder *encoder, int32_t value);
+
+// Encodes an i64.
+//
+// # Safety
+//
+// See [tora_encode_u8].
+ToraStatus tora_encode_i64(ToraEncoder *encoder, int64_t value);
+
+// Encodes an f32.
+//
+// # Safety
+//
+// See [tora_encode_u8].
+ToraStatus tora_encode_f32(ToraEncoder *encoder, float value);
+
+// Encodes an f64.
+//
+// # Safety
+//
+// See [tora_encode_u8].
+ToraStatus tora_encode_f64(ToraEncoder *encoder, double value);
+
+// Encodes a bool.
+//
+// # Safety
+//
+// See [tora_encode_u8].
+ToraStatus tora_encode_bool(ToraEncoder *encoder, bool value);
+
+// Encodes a length prefix, as written before the elements of a list.
+//
+// # Safety
+//
+// See [tora_encode_u8].
+ToraStatus tora_encode_length(ToraEncoder *encoder, size_t len);
+
+// Encodes the UTF-8 string of `len` bytes, which need not be nul-terminated.
+//
+// Returns [ToraStatus::InvalidInput] if the string is not valid UTF-8.
+//
+// # Safety
+//
+// See [tora_encode_u8]. `string` must be valid for reads of `len` bytes.
+ToraStatus tora_encode_string(ToraEncoder *encoder, const char *string, size_t len);
+
+// Decodes a u8.
+//
+// # Safety
+//
+// The decoder must be null or valid, and its buffer must be valid for reads of `len` bytes.
+// `out` must be null or valid for writes.
+ToraStatus tora_decode_u8(ToraDecoder *decoder, uint8_t *out);
+
+// Decodes a u16.
+//
+// # Safety
+//
+// See [tora_decode_u8].
+ToraStatus tora_decode_u16(ToraDecoder *decoder, uint16_t *out);
+
+// Decodes a u32.
+//
+// # Safety
+//
+// See [tora_decode_u8].
+ToraStatus tora_decode_u32(ToraDecoder *decoder, uint32_t *out);
+
+// Decodes a u64.
+//
+// # Safety
+//
+// See [tora_decode_u8].
+ToraStatus tora_decode_u64(ToraDecoder *decoder, uint64_t *out);
+
+// Decodes an i8.
+//
+// # Safety
+//
+// See [tora_decode_u8].
+ToraStatus tora_decode_i8(ToraDecoder *decoder, int8_t *out);
+
+// Decodes an i16.
+//
+// # Safety
+//
+// See [tora_decode_u8].
+ToraStatus tora_decode_i16(ToraDecoder *decoder, int16_t *out);
+
+// Decodes an i32.
+//
+// # Safety
+//
+// See [tora_decode_u8].
+ToraStatus tora_decode_i32(ToraDecoder *decoder, int32_t *out);
+
+// Decodes an i64.
+//
+// # Safety
+//
+// See [tora_decode_u8].
+ToraStatus tora_decode_i64(ToraDecoder *decoder, int64_t *out);
+
+// Decodes an f32.
+//
+// # Safety
+//
+// See [tora_decode_u8].
+ToraStatus tora_decode_f32(ToraDecoder *decoder, float *out);
+
+// Decodes an f64.
+//
+// # Safety
+//
+// See [tora_decode_u8].
+ToraStatus tora_decode_f64(ToraDecoder *decoder, double *out);
+
+// Decodes a bool.
+//
+// # Safety
+//
+// See [tora_decode_u8].
+ToraStatus tora_decode_bool(ToraDecoder *decoder, bool *out);
+
+// Decodes a length prefix, as written before the elements of a list.
+//
+// # Safety
+//
+// See [tora_decode_u8].
+ToraStatus tora_decode_length(ToraDecoder *decoder, size_t *out);
+
+// Decodes a string into the buffer of `capacity` bytes, followed by a nul terminator. Stores
+// its length, without the terminator, in `out_len`.
+//
+// Returns [ToraStatus::BufferTooSmall] if the buffer cannot hold the string and its terminator.
+//
+// # Safety
+//
+// See [tora_decode_u8]. `out` must be valid for writes of `capacity` bytes, and `out_len` must
+// be null or valid for writes.
+ToraStatus tora_decode_string(ToraDecoder *decoder, char *out, size_t capacity, size_t *out_len);
+
+// Frees a schema handle. Does nothing if the handle is null.
+//
+// # Safety
+//
+// The handle must be null, or returned by [ToraSchema::into_raw] and not freed yet.
+void tora_schema_free(ToraSchema *schema);
+
+// Stores the amount of bytes values of the schema always encode to in `out`.
+//
+// Returns [ToraStatus::InvalidInput] if the size varies.
+//
+// # Safety
+//
+// The handle must be null or valid, and `out` must be null or valid for writes.
+ToraStatus tora_schema_fixed_size(const ToraSchema *schema, size_t *out);
+
+// Checks that the `len` bytes of `data` start with a valid value of the schema, storing the
+// amount of bytes it occupies, never more than `len`, in `consumed`.
+//
+// # Safety
+//
+// The handle must be null or valid, `data` must be valid for reads of `len` bytes, and
+// `consumed` must be null or valid for writes.
+ToraStatus tora_schema_validate(const ToraSchema *schema,
+                                const uint8_t *data,
+                                size_t len,
+                                size_t *consumed);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* TORA_H */
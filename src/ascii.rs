@@ -0,0 +1,149 @@
+//! Strings restricted to 7-bit ASCII.
+
+use std::fmt;
+use std::io;
+use std::io::{ErrorKind, Read, Write};
+use std::ops::Deref;
+
+use crate::config::ToraConfig;
+use crate::read::FromReader;
+use crate::schema::{Reflect, Schema};
+use crate::write::SerializeIo;
+
+/// A string that only contains ASCII characters, for fields such as tokens and identifiers.
+///
+/// Written like a [String], honoring the configured string format. Reading a string containing
+/// non-ASCII characters returns [ErrorKind::InvalidData], and constructing one returns
+/// [ErrorKind::InvalidInput], so an AsciiString always holds valid content when written.
+///
+/// ```
+/// use std::io::ErrorKind;
+///
+/// use tora::ascii::AsciiString;
+/// use tora::testing::assert_decode_error;
+///
+/// let token = AsciiString::new("a1b2c3").unwrap();
+/// tora::assert_roundtrip!(token);
+///
+/// assert!(AsciiString::new("Grüße").is_err());
+/// assert_decode_error::<AsciiString>("Grüße\0".as_bytes(), ErrorKind::InvalidData);
+/// ```
+#[derive(Clone, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct AsciiString(String);
+
+impl AsciiString {
+    /// Constructs an AsciiString, returning [ErrorKind::InvalidInput] if the string contains
+    /// non-ASCII characters.
+    pub fn new<S>(s: S) -> io::Result<Self>
+    where
+        S: Into<String>,
+    {
+        let s = s.into();
+
+        match s.is_ascii() {
+            true => Ok(Self(s)),
+            false => Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                "String contains non-ASCII characters",
+            )),
+        }
+    }
+
+    /// Returns the string as a str.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Returns the underlying String.
+    pub fn into_string(self) -> String {
+        self.0
+    }
+}
+
+impl Deref for AsciiString {
+    type Target = str;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl AsRef<str> for AsciiString {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for AsciiString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl TryFrom<String> for AsciiString {
+    type Error = io::Error;
+
+    fn try_from(s: String) -> io::Result<Self> {
+        Self::new(s)
+    }
+}
+
+impl TryFrom<&str> for AsciiString {
+    type Error = io::Error;
+
+    fn try_from(s: &str) -> io::Result<Self> {
+        Self::new(s)
+    }
+}
+
+impl From<AsciiString> for String {
+    fn from(s: AsciiString) -> Self {
+        s.0
+    }
+}
+
+impl FromReader for AsciiString {
+    fn from_reader<R>(r: &mut R) -> io::Result<Self>
+    where
+        R: Read,
+    {
+        Self::from_reader_with(r, &ToraConfig::DEFAULT)
+    }
+
+    fn from_reader_with<R>(r: &mut R, config: &ToraConfig) -> io::Result<Self>
+    where
+        R: Read,
+    {
+        let s = String::from_reader_with(r, config)?;
+
+        match s.is_ascii() {
+            true => Ok(Self(s)),
+            false => Err(io::Error::new(
+                ErrorKind::InvalidData,
+                "String contains non-ASCII characters",
+            )),
+        }
+    }
+}
+
+impl SerializeIo for AsciiString {
+    fn serialize<W>(&self, w: &mut W) -> io::Result<()>
+    where
+        W: Write,
+    {
+        self.0.serialize(w)
+    }
+
+    fn serialize_with<W>(&self, w: &mut W, config: &ToraConfig) -> io::Result<()>
+    where
+        W: Write,
+    {
+        self.0.serialize_with(w, config)
+    }
+}
+
+impl Reflect for AsciiString {
+    fn schema() -> Schema {
+        Schema::String
+    }
+}
@@ -0,0 +1,390 @@
+//! Reading and writing values narrower than a byte.
+//!
+//! A [BitWriter] packs fields of any width up to 64 bits back to back, and a [BitReader] reads
+//! them back, for compact codecs and hardware protocols whose fields are 3, 5 or 11 bits wide.
+//!
+//! Both implement the standard [Read] and [Write] traits, so byte-level values can be mixed with
+//! bit fields through [ToraRead](crate::read::ToraRead) and
+//! [ToraWrite](crate::write::ToraWrite). Bytes written while the writer is not aligned on a byte
+//! boundary are split across two bytes, exactly as writing them as 8-bit fields would.
+//!
+//! ```
+//! use std::io;
+//!
+//! use tora::bits::{BitReader, BitWriter};
+//! use tora::read::ToraRead;
+//! use tora::write::ToraWrite;
+//!
+//! fn main() -> io::Result<()> {
+//!     let mut writer = BitWriter::new(Vec::new());
+//!     writer.write_bits(0b101, 3)?;
+//!     writer.write_bits(0b11111, 5)?;
+//!     writer.write_bits(0x7FF, 11)?;
+//!     writer.align()?;
+//!     writer.writes(&0xABCDu16)?;
+//!
+//!     let bytes = writer.into_inner()?;
+//!     assert_eq!(bytes, [0b1011_1111, 0xFF, 0b1110_0000, 0xCD, 0xAB]);
+//!
+//!     let mut reader = BitReader::new(bytes.as_slice());
+//!     assert_eq!(reader.read_bits(3)?, 0b101);
+//!     assert_eq!(reader.read_bits(5)?, 0b11111);
+//!     assert_eq!(reader.read_bits(11)?, 0x7FF);
+//!     reader.align();
+//!     assert_eq!(reader.reads::<u16>()?, 0xABCD);
+//!     Ok(())
+//! }
+//! ```
+
+use std::io;
+use std::io::{ErrorKind, Read, Write};
+
+/// The order bits are packed in within each byte.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub enum BitOrder {
+    /// Fields fill bytes from their most significant bit, and are written most significant bit
+    /// first, as in most network and hardware protocols.
+    #[default]
+    MsbFirst,
+    /// Fields fill bytes from their least significant bit, and are written least significant bit
+    /// first, as in DEFLATE.
+    LsbFirst,
+}
+
+/// Returns an error if `n` is not a valid field width.
+fn check_width(n: u32) -> io::Result<()> {
+    if n > 64 {
+        return Err(io::Error::new(
+            ErrorKind::InvalidInput,
+            "Bit fields are at most 64 bits wide",
+        ));
+    }
+    Ok(())
+}
+
+/// A writer packing fields of any width into bytes.
+///
+/// Bits of an incomplete byte are kept until the byte is complete, or the writer is
+/// [aligned](Self::align). Dropping the writer discards them; call [BitWriter::into_inner] first.
+///
+/// If the underlying writer fails, the field being written is still taken, and its complete
+/// bytes are kept and written before any further bit.
+///
+/// ```
+/// use std::io::{self, ErrorKind, Write};
+///
+/// use tora::bits::BitWriter;
+///
+/// /// Fails its first write.
+/// struct Flaky(Vec<u8>, bool);
+///
+/// impl Write for Flaky {
+///     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+///         if !std::mem::replace(&mut self.1, true) {
+///             return Err(ErrorKind::WouldBlock.into());
+///         }
+///         self.0.write(buf)
+///     }
+///
+///     fn flush(&mut self) -> io::Result<()> {
+///         Ok(())
+///     }
+/// }
+///
+/// fn main() -> io::Result<()> {
+///     let mut writer = BitWriter::new(Flaky(Vec::new(), false));
+///     writer.write_bits(0xF, 4)?;
+///
+///     let e = writer.write_bits(0xA, 4).unwrap_err();
+///     assert_eq!(e.kind(), ErrorKind::WouldBlock);
+///
+///     writer.write_bits(0b1, 1)?;
+///     assert_eq!(writer.into_inner()?.0, [0xFA, 0b1000_0000]);
+///     Ok(())
+/// }
+/// ```
+#[derive(Debug)]
+pub struct BitWriter<W> {
+    inner: W,
+    order: BitOrder,
+    /// The incomplete byte.
+    acc: u8,
+    /// The amount of bits of the incomplete byte.
+    len: u32,
+    /// The complete bytes not written yet.
+    pending: Vec<u8>,
+}
+
+impl<W> BitWriter<W>
+where
+    W: Write,
+{
+    /// Constructs a BitWriter packing bits most significant bit first.
+    pub fn new(inner: W) -> Self {
+        Self::with_order(inner, BitOrder::MsbFirst)
+    }
+
+    /// Constructs a BitWriter packing bits in the given order.
+    pub fn with_order(inner: W, order: BitOrder) -> Self {
+        Self {
+            inner,
+            order,
+            acc: 0,
+            len: 0,
+            pending: Vec::new(),
+        }
+    }
+
+    /// Writes the complete bytes not written yet.
+    fn write_pending(&mut self) -> io::Result<()> {
+        while !self.pending.is_empty() {
+            match self.inner.write(&self.pending) {
+                Ok(0) => {
+                    return Err(io::Error::new(
+                        ErrorKind::WriteZero,
+                        "Failed to write the complete bytes",
+                    ))
+                }
+                Ok(n) => {
+                    self.pending.drain(..n);
+                }
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
+
+    /// Adds a bit to the incomplete byte, keeping the byte once complete.
+    fn push_bit(&mut self, bit: bool) {
+        let shift = match self.order {
+            BitOrder::MsbFirst => 7 - self.len,
+            BitOrder::LsbFirst => self.len,
+        };
+        self.acc |= (bit as u8) << shift;
+        self.len += 1;
+
+        if self.len == 8 {
+            self.pending.push(self.acc);
+            self.acc = 0;
+            self.len = 0;
+        }
+    }
+
+    /// Writes a single bit.
+    pub fn write_bit(&mut self, bit: bool) -> io::Result<()> {
+        self.write_bits(bit as u64, 1)
+    }
+
+    /// Writes the `n` low bits of `value`.
+    ///
+    /// Returns [ErrorKind::InvalidInput] if `n` exceeds 64, or `value` does not fit in `n` bits.
+    pub fn write_bits(&mut self, value: u64, n: u32) -> io::Result<()> {
+        check_width(n)?;
+        if n < 64 && value >> n != 0 {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                format!("{value} does not fit in {n} bits"),
+            ));
+        }
+
+        self.write_pending()?;
+
+        for i in 0..n {
+            let i = match self.order {
+                BitOrder::MsbFirst => n - 1 - i,
+                BitOrder::LsbFirst => i,
+            };
+            self.push_bit(value >> i & 1 != 0);
+        }
+        self.write_pending()
+    }
+
+    /// Pads the incomplete byte, if any, with zero bits, and writes it.
+    pub fn align(&mut self) -> io::Result<()> {
+        if self.len != 0 {
+            self.pending.push(self.acc);
+            self.acc = 0;
+            self.len = 0;
+        }
+        self.write_pending()
+    }
+
+    /// Returns true if the writer is on a byte boundary.
+    pub fn is_aligned(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns a reference to the underlying writer.
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    /// Aligns and flushes the writer, then returns the underlying writer.
+    pub fn into_inner(mut self) -> io::Result<W> {
+        self.align()?;
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+}
+
+impl<W> Write for BitWriter<W>
+where
+    W: Write,
+{
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.write_pending()?;
+
+        if self.is_aligned() {
+            return self.inner.write(buf);
+        }
+        for (i, byte) in buf.iter().enumerate() {
+            // The byte is taken even if writing it fails, and the error reported by the next call.
+            if self.write_bits(*byte as u64, 8).is_err() {
+                return Ok(i + 1);
+            }
+        }
+        Ok(buf.len())
+    }
+
+    /// Writes the complete bytes not written yet, and flushes the underlying writer. The bits of
+    /// an incomplete byte are kept.
+    fn flush(&mut self) -> io::Result<()> {
+        self.write_pending()?;
+        self.inner.flush()
+    }
+}
+
+/// A reader unpacking fields of any width from bytes.
+///
+/// Bytes are read one at a time while unaligned, so slow readers should be buffered. Reading
+/// bytes while unaligned stops before the input runs out, keeping the bits left.
+///
+/// ```
+/// use std::io::{self, Read};
+///
+/// use tora::bits::BitReader;
+///
+/// fn main() -> io::Result<()> {
+///     let mut reader = BitReader::new([0b1011_0110].as_slice());
+///     assert_eq!(reader.read_bits(3)?, 0b101);
+///
+///     assert_eq!(reader.read(&mut [0; 4])?, 0);
+///     assert_eq!(reader.read_bits(5)?, 0b10110);
+///     Ok(())
+/// }
+/// ```
+#[derive(Debug)]
+pub struct BitReader<R> {
+    inner: R,
+    order: BitOrder,
+    /// The byte being read.
+    acc: u8,
+    /// The amount of bits of the byte not read yet.
+    len: u32,
+}
+
+impl<R> BitReader<R>
+where
+    R: Read,
+{
+    /// Constructs a BitReader unpacking bits most significant bit first.
+    pub fn new(inner: R) -> Self {
+        Self::with_order(inner, BitOrder::MsbFirst)
+    }
+
+    /// Constructs a BitReader unpacking bits in the given order.
+    pub fn with_order(inner: R, order: BitOrder) -> Self {
+        Self {
+            inner,
+            order,
+            acc: 0,
+            len: 0,
+        }
+    }
+
+    /// Reads a single bit.
+    pub fn read_bit(&mut self) -> io::Result<bool> {
+        if self.len == 0 {
+            let mut byte = [0];
+            self.inner.read_exact(&mut byte)?;
+            self.acc = byte[0];
+            self.len = 8;
+        }
+
+        let shift = match self.order {
+            BitOrder::MsbFirst => self.len - 1,
+            BitOrder::LsbFirst => 8 - self.len,
+        };
+        self.len -= 1;
+        Ok(self.acc >> shift & 1 != 0)
+    }
+
+    /// Reads a field of `n` bits.
+    ///
+    /// Returns [ErrorKind::InvalidInput] if `n` exceeds 64.
+    pub fn read_bits(&mut self, n: u32) -> io::Result<u64> {
+        check_width(n)?;
+        let mut value = 0;
+
+        for i in 0..n {
+            let bit = self.read_bit()? as u64;
+            match self.order {
+                BitOrder::MsbFirst => value = value << 1 | bit,
+                BitOrder::LsbFirst => value |= bit << i,
+            }
+        }
+        Ok(value)
+    }
+
+    /// Discards the bits left in the current byte, if any.
+    pub fn align(&mut self) {
+        self.len = 0;
+    }
+
+    /// Returns true if the reader is on a byte boundary.
+    pub fn is_aligned(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns a reference to the underlying reader.
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Returns the underlying reader, discarding the bits left in the current byte.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R> Read for BitReader<R>
+where
+    R: Read,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.is_aligned() {
+            return self.inner.read(buf);
+        }
+
+        for (i, byte) in buf.iter_mut().enumerate() {
+            let (acc, len) = (self.acc, self.len);
+
+            match self.read_bits(8) {
+                Ok(value) => *byte = value as u8,
+                Err(e) => {
+                    // Only reading the next byte can fail, so the bits left in the current one
+                    // are kept for later reads.
+                    (self.acc, self.len) = (acc, len);
+
+                    return match e.kind() {
+                        _ if i > 0 => Ok(i),
+                        ErrorKind::UnexpectedEof => Ok(0),
+                        _ => Err(e),
+                    };
+                }
+            }
+        }
+        Ok(buf.len())
+    }
+}
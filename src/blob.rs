@@ -0,0 +1,173 @@
+//! Streaming reads of large byte fields.
+//!
+//! Reading a `Vec<u8>` holds every byte of it in memory at once. A [BlobReader] reads the length
+//! prefix of the field instead, then hands out its bytes as they arrive, so large payloads can be
+//! piped to a file or a hash function through [io::copy] or [BlobReader::for_each_chunk].
+//!
+//! Blobs are written as any `Vec<u8>` or `&[u8]` is, or as a [String] in the
+//! [LengthPrefixed](crate::config::StringFormat::LengthPrefixed) string format.
+//!
+//! ```
+//! use std::io;
+//! use std::io::Cursor;
+//!
+//! use tora::blob::BlobReader;
+//! use tora::read::ToraRead;
+//!
+//! fn main() -> io::Result<()> {
+//!     let payload: &[u8] = &[7; 100_000];
+//!     let bytes = tora::testing::to_bytes(&(payload, 5u8));
+//!     let mut cursor = Cursor::new(bytes);
+//!
+//!     let mut blob = BlobReader::new(&mut cursor)?;
+//!     assert_eq!(blob.len(), 100_000);
+//!
+//!     let mut sum = 0u64;
+//!     blob.for_each_chunk(|chunk| {
+//!         sum += chunk.iter().map(|&b| b as u64).sum::<u64>();
+//!         Ok(())
+//!     })?;
+//!
+//!     assert_eq!(sum, 700_000);
+//!     assert_eq!(cursor.reads::<u8>()?, 5);
+//!     Ok(())
+//! }
+//! ```
+
+use std::io;
+use std::io::{ErrorKind, Read};
+use std::sync::Arc;
+
+use crate::cancel::Cancellation;
+use crate::config::ToraConfig;
+
+/// The size of the buffer used by [BlobReader::for_each_chunk].
+const CHUNK_SIZE: usize = 8192;
+
+/// A reader over the bytes of a length-prefixed byte field.
+///
+/// Reads end with the field, leaving the underlying reader positioned at the next one once every
+/// byte was read. Use [BlobReader::finish] to skip the bytes left unread.
+#[derive(Debug)]
+pub struct BlobReader<'r, R> {
+    inner: &'r mut R,
+    len: u64,
+    remaining: u64,
+    cancellation: Option<Arc<Cancellation>>,
+}
+
+impl<'r, R> BlobReader<'r, R>
+where
+    R: Read,
+{
+    /// Reads the length prefix of a byte field.
+    pub fn new(r: &'r mut R) -> io::Result<Self> {
+        Self::with_config(r, &ToraConfig::DEFAULT)
+    }
+
+    /// Reads the configured length prefix of a byte field.
+    ///
+    /// Returns [ErrorKind::InvalidData] if the length exceeds the configured maximum length.
+    pub fn with_config(r: &'r mut R, config: &ToraConfig) -> io::Result<Self> {
+        let len = config.read_length(r)? as u64;
+
+        Ok(Self {
+            inner: r,
+            len,
+            remaining: len,
+            cancellation: config.cancellation.clone(),
+        })
+    }
+
+    /// Returns the length of the field in bytes.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Returns true if the field holds no bytes.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the amount of bytes of the field left to read.
+    pub fn remaining(&self) -> u64 {
+        self.remaining
+    }
+
+    /// Reads the rest of the field, passing each chunk of bytes to `f` as it is read.
+    ///
+    /// Stops at the first error returned by `f`, and returns a
+    /// [Cancelled](crate::cancel::Cancelled) error if the configured cancellation flag is
+    /// cancelled between two chunks.
+    pub fn for_each_chunk<F>(mut self, mut f: F) -> io::Result<()>
+    where
+        F: FnMut(&[u8]) -> io::Result<()>,
+    {
+        let mut buf = [0; CHUNK_SIZE];
+
+        loop {
+            match self.read(&mut buf) {
+                Ok(0) => return Ok(()),
+                Ok(n) => f(&buf[..n])?,
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Skips the bytes of the field left unread.
+    ///
+    /// ```
+    /// use std::io;
+    /// use std::io::{Cursor, Read};
+    ///
+    /// use tora::blob::BlobReader;
+    /// use tora::read::ToraRead;
+    ///
+    /// fn main() -> io::Result<()> {
+    ///     let mut cursor = Cursor::new([3, 0, 0, 0, 1, 2, 3, 9]);
+    ///
+    ///     let mut blob = BlobReader::new(&mut cursor)?;
+    ///     let mut magic = [0];
+    ///     blob.read_exact(&mut magic)?;
+    ///     blob.finish()?;
+    ///
+    ///     assert_eq!(magic, [1]);
+    ///     assert_eq!(cursor.reads::<u8>()?, 9);
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn finish(self) -> io::Result<()> {
+        self.for_each_chunk(|_| Ok(()))
+    }
+}
+
+impl<R> Read for BlobReader<'_, R>
+where
+    R: Read,
+{
+    /// Reads bytes of the field, returning `Ok(0)` once all of them were read.
+    ///
+    /// Returns [ErrorKind::UnexpectedEof] if the underlying reader ends before the field does, and
+    /// a [Cancelled](crate::cancel::Cancelled) error if the configured cancellation flag is
+    /// cancelled.
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.remaining == 0 || buf.is_empty() {
+            return Ok(0);
+        }
+        if let Some(cancellation) = &self.cancellation {
+            cancellation.check()?;
+        }
+        let max = buf
+            .len()
+            .min(self.remaining.try_into().unwrap_or(usize::MAX));
+
+        match self.inner.read(&mut buf[..max])? {
+            0 => Err(io::Error::new(ErrorKind::UnexpectedEof, "Truncated blob")),
+            n => {
+                self.remaining -= n as u64;
+                Ok(n)
+            }
+        }
+    }
+}
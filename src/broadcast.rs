@@ -0,0 +1,239 @@
+//! Sending one message to many connections, serializing it once.
+//!
+//! A [Broadcast] serializes each message into a [SharedFrame], a reference-counted frame holding
+//! its length prefix and payload, and queues the same frame on every subscriber. Subscribers only
+//! keep their offset in the frames they have not fully written yet, so a message sent to 500
+//! connections is serialized once and stored once.
+//!
+//! Frames are written as by [ToraStream](crate::stream::ToraStream), so subscribers read them
+//! with a `ToraStream` or [FrameReader](crate::stream::FrameReader). Non-blocking writers are
+//! supported: a subscriber whose writer returns [ErrorKind::WouldBlock] keeps the rest of its
+//! frames queued until [Broadcast::write_pending] is called.
+//!
+//! ```
+//! use std::io;
+//!
+//! use tora::broadcast::Broadcast;
+//! use tora::stream::FrameReader;
+//!
+//! fn main() -> io::Result<()> {
+//!     let mut broadcast = Broadcast::new();
+//!     let first = broadcast.subscribe(Vec::new());
+//!     let second = broadcast.subscribe(Vec::new());
+//!
+//!     let failed = broadcast.send(&(7u8, "World update"))?;
+//!     assert!(failed.is_empty());
+//!
+//!     for id in [first, second] {
+//!         let bytes = broadcast.unsubscribe(id).unwrap();
+//!         let mut reader = FrameReader::<(u8, String), _>::new(bytes.as_slice());
+//!         assert_eq!(reader.recv()?, (7, "World update".to_string()));
+//!     }
+//!     Ok(())
+//! }
+//! ```
+
+use std::collections::VecDeque;
+use std::io;
+use std::io::{ErrorKind, Write};
+use std::ops::Deref;
+use std::sync::Arc;
+
+use crate::config::ToraConfig;
+use crate::write::SerializeIo;
+
+/// A serialized frame, shared by every connection it is sent to.
+///
+/// Cloning a SharedFrame only increments its reference count.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct SharedFrame(Arc<[u8]>);
+
+impl SharedFrame {
+    /// Serializes the value as a frame, prefixed with its length, using the given configuration.
+    pub fn new<T>(value: &T, config: &ToraConfig) -> io::Result<Self>
+    where
+        T: SerializeIo + ?Sized,
+    {
+        let mut payload = Vec::new();
+        value.serialize_with(&mut payload, config)?;
+
+        let mut frame = Vec::with_capacity(payload.len() + 8);
+        config.write_length(&mut frame, payload.len())?;
+        frame.extend_from_slice(&payload);
+        Ok(Self(frame.into()))
+    }
+}
+
+impl Deref for SharedFrame {
+    type Target = [u8];
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+/// Identifies a subscriber of a [Broadcast].
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct SubscriberId(u64);
+
+#[derive(Debug)]
+struct Subscriber<W> {
+    id: SubscriberId,
+    writer: W,
+    frames: VecDeque<SharedFrame>,
+    /// The amount of bytes of the first frame already written.
+    offset: usize,
+}
+
+impl<W> Subscriber<W>
+where
+    W: Write,
+{
+    /// Writes the queued frames until they are all written, or the writer would block.
+    fn write_queued(&mut self) -> io::Result<()> {
+        while let Some(frame) = self.frames.front() {
+            match self.writer.write(&frame[self.offset..]) {
+                Ok(0) => return Err(ErrorKind::WriteZero.into()),
+                Ok(n) => self.offset += n,
+                Err(e) if e.kind() == ErrorKind::WouldBlock => return Ok(()),
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            }
+
+            if self.offset == frame.len() {
+                self.frames.pop_front();
+                self.offset = 0;
+            }
+        }
+
+        match self.writer.flush() {
+            Err(e) if e.kind() == ErrorKind::WouldBlock => Ok(()),
+            result => result,
+        }
+    }
+
+    /// Returns the amount of bytes queued but not written yet.
+    fn pending(&self) -> usize {
+        self.frames.iter().map(|f| f.len()).sum::<usize>() - self.offset
+    }
+}
+
+/// A set of connections receiving the same messages, each serialized once.
+///
+/// A subscriber whose writer fails is removed, and its error returned by the call that
+/// encountered it.
+#[derive(Debug)]
+pub struct Broadcast<W> {
+    subscribers: Vec<Subscriber<W>>,
+    config: ToraConfig,
+    next_id: u64,
+}
+
+impl<W> Default for Broadcast<W>
+where
+    W: Write,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<W> Broadcast<W>
+where
+    W: Write,
+{
+    /// Constructs a Broadcast without subscribers, using the default configuration.
+    pub fn new() -> Self {
+        Self::with_config(ToraConfig::DEFAULT)
+    }
+
+    /// Constructs a Broadcast without subscribers, using the given configuration.
+    pub fn with_config(config: ToraConfig) -> Self {
+        Self {
+            subscribers: Vec::new(),
+            config,
+            next_id: 0,
+        }
+    }
+
+    /// Adds a subscriber receiving the messages sent from now on.
+    pub fn subscribe(&mut self, writer: W) -> SubscriberId {
+        let id = SubscriberId(self.next_id);
+        self.next_id += 1;
+
+        self.subscribers.push(Subscriber {
+            id,
+            writer,
+            frames: VecDeque::new(),
+            offset: 0,
+        });
+        id
+    }
+
+    /// Removes a subscriber, returning its writer. Frames not written yet are discarded.
+    pub fn unsubscribe(&mut self, id: SubscriberId) -> Option<W> {
+        let i = self.subscribers.iter().position(|s| s.id == id)?;
+        Some(self.subscribers.remove(i).writer)
+    }
+
+    /// Serializes the value once, then sends it to every subscriber.
+    ///
+    /// Returns the subscribers removed after their writer failed, along with the error.
+    pub fn send<T>(&mut self, value: &T) -> io::Result<Vec<(SubscriberId, io::Error)>>
+    where
+        T: SerializeIo + ?Sized,
+    {
+        let frame = SharedFrame::new(value, &self.config)?;
+        Ok(self.send_frame(&frame))
+    }
+
+    /// Sends a frame already serialized to every subscriber.
+    ///
+    /// Returns the subscribers removed after their writer failed, along with the error.
+    pub fn send_frame(&mut self, frame: &SharedFrame) -> Vec<(SubscriberId, io::Error)> {
+        for subscriber in &mut self.subscribers {
+            subscriber.frames.push_back(frame.clone());
+        }
+        self.write_pending()
+    }
+
+    /// Writes the frames queued on subscribers whose writer blocked.
+    ///
+    /// Returns the subscribers removed after their writer failed, along with the error.
+    pub fn write_pending(&mut self) -> Vec<(SubscriberId, io::Error)> {
+        let mut failed = Vec::new();
+
+        self.subscribers
+            .retain_mut(|subscriber| match subscriber.write_queued() {
+                Ok(()) => true,
+                Err(e) => {
+                    failed.push((subscriber.id, e));
+                    false
+                }
+            });
+        failed
+    }
+
+    /// Returns the amount of bytes queued for the subscriber, or [None] if it is not subscribed.
+    pub fn pending(&self, id: SubscriberId) -> Option<usize> {
+        self.subscribers
+            .iter()
+            .find(|s| s.id == id)
+            .map(Subscriber::pending)
+    }
+
+    /// Returns the amount of subscribers.
+    pub fn len(&self) -> usize {
+        self.subscribers.len()
+    }
+
+    /// Returns true if there are no subscribers.
+    pub fn is_empty(&self) -> bool {
+        self.subscribers.is_empty()
+    }
+
+    /// Returns the configuration used to serialize messages.
+    pub fn config(&self) -> &ToraConfig {
+        &self.config
+    }
+}
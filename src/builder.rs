@@ -0,0 +1,26 @@
+//! Support for the builders generated by `#[tora(builder)]`.
+
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::io;
+use std::io::ErrorKind;
+
+/// Returned when building a value without setting a field that has no default.
+///
+/// Holds the name of the missing field.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct MissingField(pub &'static str);
+
+impl Display for MissingField {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Missing field `{}`", self.0)
+    }
+}
+
+impl Error for MissingField {}
+
+impl From<MissingField> for io::Error {
+    fn from(value: MissingField) -> Self {
+        io::Error::new(ErrorKind::InvalidInput, value)
+    }
+}
@@ -0,0 +1,119 @@
+//! Cancellation of long-running reads.
+//!
+//! A [Cancellation] shared with
+//! [ToraConfig::cancellation](crate::config::ToraConfig::cancellation) is checked periodically
+//! while reading collections, byte sequences, blobs and frames. Once cancelled, the read in
+//! progress fails with a [Cancelled] error, so a large import can be stopped from another thread.
+//! Its kind is [ErrorKind::Other] rather than [ErrorKind::Interrupted], which readers retry.
+//!
+//! A cancelled read stops partway through a value, leaving the reader at an unknown position.
+//! Drop the reader or stream it was reading from, rather than reading from it again.
+//!
+//! ```
+//! use std::io::Cursor;
+//! use std::sync::Arc;
+//!
+//! use tora::cancel::{was_cancelled, Cancellation};
+//! use tora::config::ToraConfig;
+//! use tora::read::ToraRead;
+//!
+//! let import = Arc::new(Cancellation::new());
+//! let config = ToraConfig::DEFAULT.cancellation(import.clone());
+//! let bytes = tora::testing::to_bytes(&vec![0u16; 100_000]);
+//!
+//! // Usually called from a UI thread while the read is in progress.
+//! import.cancel();
+//!
+//! let e = Cursor::new(bytes).reads_with::<Vec<u16>>(&config).unwrap_err();
+//! assert!(was_cancelled(&e));
+//! ```
+
+use std::error::Error;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::io;
+#[cfg(doc)]
+use std::io::ErrorKind;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// The amount of elements read between two checks of the cancellation flag.
+pub(crate) const CHECK_ELEMENTS: usize = 1024;
+
+/// The amount of bytes read between two checks of the cancellation flag.
+pub(crate) const CHECK_BYTES: usize = 64 * 1024;
+
+/// A flag cancelling the reads configured with it.
+///
+/// Shared through an [Arc](std::sync::Arc) between the thread cancelling and
+/// [ToraConfig::cancellation](crate::config::ToraConfig::cancellation). Flags compare and hash by
+/// identity.
+#[derive(Debug, Default)]
+pub struct Cancellation {
+    cancelled: AtomicBool,
+}
+
+impl Cancellation {
+    /// Constructs a flag which is not cancelled.
+    pub const fn new() -> Self {
+        Self {
+            cancelled: AtomicBool::new(false),
+        }
+    }
+
+    /// Cancels the reads configured with this flag.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    /// Clears the flag, so it can be reused for another operation.
+    pub fn reset(&self) {
+        self.cancelled.store(false, Ordering::Relaxed);
+    }
+
+    /// Returns true if [Cancellation::cancel] was called since construction or the last reset.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// Returns a [Cancelled] error if the flag is cancelled.
+    pub fn check(&self) -> io::Result<()> {
+        match self.is_cancelled() {
+            true => Err(io::Error::other(Cancelled)),
+            false => Ok(()),
+        }
+    }
+}
+
+/// The error of a cancelled read, wrapped in an [io::Error] of kind [ErrorKind::Other].
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct Cancelled;
+
+impl fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Read cancelled")
+    }
+}
+
+impl Error for Cancelled {}
+
+/// Returns true if the error is the [Cancelled] error of a cancelled read.
+pub fn was_cancelled(e: &io::Error) -> bool {
+    e.get_ref().is_some_and(|e| e.is::<Cancelled>())
+}
+
+impl PartialEq for Cancellation {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+impl Eq for Cancellation {}
+
+impl Hash for Cancellation {
+    fn hash<H>(&self, state: &mut H)
+    where
+        H: Hasher,
+    {
+        std::ptr::hash(self, state)
+    }
+}
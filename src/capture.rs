@@ -0,0 +1,305 @@
+//! Recording and replaying the frames of a connection.
+//!
+//! A [Capture] attached to a [ToraStream](crate::stream::ToraStream), or one of its halves,
+//! records every frame sent and received, along with its direction and the time since the capture
+//! started. A [Replay] reads the capture back, optionally pausing between frames as long as the
+//! original traffic did, so a handler can be fed the exact traffic of a misbehaving client.
+//!
+//! ```
+//! use std::io;
+//! use std::io::Cursor;
+//! use std::sync::Arc;
+//!
+//! use tora::capture::{Capture, Direction, Replay};
+//! use tora::stream::ToraStream;
+//!
+//! fn main() -> io::Result<()> {
+//!     let capture = Arc::new(Capture::new(Vec::new())?);
+//!
+//!     let mut stream = ToraStream::<String, _>::new(Cursor::new(Vec::new()))
+//!         .with_capture(capture.clone());
+//!     stream.send(&"Hello".to_string())?;
+//!     drop(stream);
+//!
+//!     let bytes = Arc::into_inner(capture).unwrap().into_inner()?;
+//!
+//!     let mut sent = Vec::new();
+//!     Replay::new(Cursor::new(bytes))?.unthrottled().run(|frame| {
+//!         assert_eq!(frame.direction, Direction::Outbound);
+//!         sent.push(frame.decode::<String>()?);
+//!         Ok(())
+//!     })?;
+//!
+//!     assert_eq!(sent, ["Hello"]);
+//!     Ok(())
+//! }
+//! ```
+//!
+//! # File format
+//!
+//! A capture starts with the 8 bytes `TORACAP\x01`. Each frame is then written as a `u64` holding
+//! the microseconds since the capture started, a `u8` direction (0 inbound, 1 outbound), a `u32`
+//! length and the payload, all little endian.
+
+use std::fmt;
+use std::fs::File;
+use std::io;
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+use std::io::BufReader;
+use std::io::{BufWriter, ErrorKind, Read, Write};
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+use std::path::Path;
+use std::sync::{Mutex, PoisonError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::config::ToraConfig;
+use crate::read::{FromReader, ToraRead};
+use crate::stream::decode_frame;
+use crate::write::ToraWrite;
+
+const MAGIC: &[u8; 8] = b"TORACAP\x01";
+
+/// The direction a frame travelled in, from the point of view of the recording side.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Direction {
+    /// The frame was received.
+    Inbound,
+    /// The frame was sent.
+    Outbound,
+}
+
+/// A recorded frame.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CapturedFrame {
+    /// The time between the start of the capture and the frame.
+    pub elapsed: Duration,
+    pub direction: Direction,
+    /// The serialized value, without the frame's length prefix.
+    pub payload: Vec<u8>,
+}
+
+impl CapturedFrame {
+    /// Deserializes the payload using the default configuration.
+    pub fn decode<T>(&self) -> io::Result<T>
+    where
+        T: FromReader,
+    {
+        self.decode_with(&ToraConfig::DEFAULT)
+    }
+
+    /// Deserializes the payload using the given configuration.
+    ///
+    /// Returns [ErrorKind::InvalidData] if the value does not occupy the whole payload.
+    pub fn decode_with<T>(&self, config: &ToraConfig) -> io::Result<T>
+    where
+        T: FromReader,
+    {
+        decode_frame(&self.payload, config)
+    }
+}
+
+/// Records frames to a writer.
+///
+/// Shared between both halves of a connection through an [Arc](std::sync::Arc).
+pub struct Capture<W = BufWriter<File>>
+where
+    W: ?Sized,
+{
+    start: Instant,
+    writer: Mutex<W>,
+}
+
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+impl Capture {
+    /// Creates a capture file at the given path.
+    pub fn create<P>(path: P) -> io::Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        Self::new(BufWriter::new(File::create(path)?))
+    }
+}
+
+impl<W> Capture<W>
+where
+    W: Write,
+{
+    /// Constructs a Capture writing to the given writer, starting now.
+    pub fn new(mut writer: W) -> io::Result<Self> {
+        writer.write_all(MAGIC)?;
+
+        Ok(Self {
+            start: Instant::now(),
+            writer: Mutex::new(writer),
+        })
+    }
+
+    /// Flushes and returns the underlying writer.
+    pub fn into_inner(self) -> io::Result<W> {
+        let mut writer = self
+            .writer
+            .into_inner()
+            .unwrap_or_else(PoisonError::into_inner);
+
+        writer.flush()?;
+        Ok(writer)
+    }
+}
+
+impl<W> Capture<W>
+where
+    W: Write + ?Sized,
+{
+    /// Records a frame.
+    pub fn record(&self, direction: Direction, payload: &[u8]) -> io::Result<()> {
+        let elapsed = self.start.elapsed().as_micros() as u64;
+        let len = u32::try_from(payload.len())
+            .map_err(|_| io::Error::new(ErrorKind::InvalidInput, "Frame exceeds u32::MAX bytes"))?;
+
+        let mut header = Vec::with_capacity(13);
+        header.writes(&elapsed)?;
+        header.writes(&match direction {
+            Direction::Inbound => 0u8,
+            Direction::Outbound => 1u8,
+        })?;
+        header.writes(&len)?;
+
+        let mut w = self.writer.lock().unwrap_or_else(PoisonError::into_inner);
+        w.write_all(&header)?;
+        w.write_all(payload)
+    }
+
+    /// Flushes the frames recorded so far.
+    pub fn flush(&self) -> io::Result<()> {
+        self.writer
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .flush()
+    }
+}
+
+impl<W> fmt::Debug for Capture<W>
+where
+    W: ?Sized,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Capture")
+            .field("start", &self.start)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Reads the frames of a capture, pausing between them to reproduce the original timing.
+#[derive(Debug)]
+pub struct Replay<R> {
+    reader: R,
+    speed: f64,
+}
+
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+impl Replay<BufReader<File>> {
+    /// Opens the capture file at the given path.
+    pub fn open<P>(path: P) -> io::Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        Self::new(BufReader::new(File::open(path)?))
+    }
+}
+
+impl<R> Replay<R>
+where
+    R: Read,
+{
+    /// Constructs a Replay reading the capture from the given reader, at the original speed.
+    ///
+    /// Returns [ErrorKind::InvalidData] if the reader does not start with a capture header.
+    pub fn new(mut reader: R) -> io::Result<Self> {
+        if reader.reads::<[u8; 8]>()? != *MAGIC {
+            return Err(io::Error::new(ErrorKind::InvalidData, "Not a tora capture"));
+        }
+        Ok(Self { reader, speed: 1.0 })
+    }
+
+    /// Replays the frames `factor` times faster than they were recorded.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the factor is not positive.
+    pub fn with_speed(mut self, factor: f64) -> Self {
+        assert!(factor > 0.0, "The replay speed must be positive");
+        self.speed = factor;
+        self
+    }
+
+    /// Replays the frames without pausing between them.
+    pub fn unthrottled(self) -> Self {
+        self.with_speed(f64::INFINITY)
+    }
+
+    /// Reads the next frame without pausing, returning None at the end of the capture.
+    ///
+    /// Returns [ErrorKind::UnexpectedEof] if the capture ends partway through a frame.
+    ///
+    /// ```
+    /// use std::io::{self, ErrorKind};
+    ///
+    /// use tora::capture::Replay;
+    ///
+    /// fn main() -> io::Result<()> {
+    ///     assert!(Replay::new(&b"TORACAP\x01"[..])?.next_frame()?.is_none());
+    ///
+    ///     // The timestamp of a frame, cut off after 3 of its 8 bytes.
+    ///     let mut replay = Replay::new(&b"TORACAP\x01\x10\x00\x00"[..])?;
+    ///     assert_eq!(replay.next_frame().unwrap_err().kind(), ErrorKind::UnexpectedEof);
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn next_frame(&mut self) -> io::Result<Option<CapturedFrame>> {
+        let Some(elapsed) = self.reader.reads_opt::<u64>()? else {
+            return Ok(None);
+        };
+        let direction = match self.reader.reads::<u8>()? {
+            0 => Direction::Inbound,
+            1 => Direction::Outbound,
+            _ => return Err(io::Error::new(ErrorKind::InvalidData, "Invalid direction")),
+        };
+        let len = self.reader.reads::<u32>()? as usize;
+
+        let mut payload = Vec::new();
+        (&mut self.reader)
+            .take(len as u64)
+            .read_to_end(&mut payload)?;
+
+        if payload.len() != len {
+            return Err(io::Error::new(ErrorKind::UnexpectedEof, "Truncated frame"));
+        }
+
+        Ok(Some(CapturedFrame {
+            elapsed: Duration::from_micros(elapsed),
+            direction,
+            payload,
+        }))
+    }
+
+    /// Passes every frame to the handler, each at its recorded time divided by the speed.
+    ///
+    /// Stops at the first error returned by the handler.
+    pub fn run<F>(mut self, mut handler: F) -> io::Result<()>
+    where
+        F: FnMut(CapturedFrame) -> io::Result<()>,
+    {
+        let start = Instant::now();
+
+        while let Some(frame) = self.next_frame()? {
+            let due = frame.elapsed.div_f64(self.speed);
+
+            if let Some(remaining) = due.checked_sub(start.elapsed()) {
+                thread::sleep(remaining);
+            }
+            handler(frame)?;
+        }
+        Ok(())
+    }
+}
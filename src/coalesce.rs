@@ -0,0 +1,297 @@
+//! Coalescing small messages into fewer writes.
+//!
+//! A [CoalescingWriter] buffers serialized messages and writes them to the underlying writer at
+//! once when its [FlushPolicy] is due: once enough bytes or messages are buffered, or once the
+//! oldest buffered message waited long enough. This is similar to Nagle's algorithm, but the
+//! thresholds are chosen by the application, and [CoalescingWriter::flush] always writes
+//! immediately.
+//!
+//! The delay is checked whenever a message is sent, and by [CoalescingWriter::flush_if_due], which
+//! an event loop calls once [CoalescingWriter::deadline] passes.
+//!
+//! ```
+//! use std::io;
+//! use std::time::Duration;
+//!
+//! use tora::coalesce::{CoalescingWriter, FlushPolicy};
+//! use tora::stream::FrameReader;
+//!
+//! fn main() -> io::Result<()> {
+//!     let policy = FlushPolicy::new()
+//!         .max_messages(3)
+//!         .max_delay(Duration::from_millis(5));
+//!     let mut writer = CoalescingWriter::with_policy(Vec::new(), policy);
+//!
+//!     writer.send_frame(&1u32)?;
+//!     writer.send_frame(&2u32)?;
+//!     assert!(writer.get_ref().is_empty());
+//!
+//!     // The third message reaches the threshold, writing all three at once.
+//!     writer.send_frame(&3u32)?;
+//!     assert_eq!(writer.pending(), 0);
+//!
+//!     let bytes = writer.into_inner()?;
+//!     let mut reader = FrameReader::<u32, _>::new(bytes.as_slice());
+//!     assert_eq!([reader.recv()?, reader.recv()?, reader.recv()?], [1, 2, 3]);
+//!     Ok(())
+//! }
+//! ```
+
+use std::io;
+use std::io::{ErrorKind, Write};
+use std::time::{Duration, Instant};
+
+use crate::config::ToraConfig;
+use crate::write::SerializeIo;
+
+/// When a [CoalescingWriter] flushes its buffered messages.
+///
+/// Each threshold is optional; the writer flushes once any of the set thresholds is reached. A
+/// policy without thresholds only flushes when asked to.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct FlushPolicy {
+    /// The amount of buffered bytes that triggers a flush.
+    pub max_bytes: Option<usize>,
+    /// The amount of buffered messages that triggers a flush.
+    pub max_messages: Option<usize>,
+    /// The time the oldest buffered message may wait before a flush.
+    pub max_delay: Option<Duration>,
+}
+
+impl FlushPolicy {
+    /// The policy used by [CoalescingWriter::new], flushing once 16 KiB are buffered.
+    pub const DEFAULT: Self = Self::new().max_bytes(16 * 1024);
+
+    /// Constructs a policy without thresholds.
+    pub const fn new() -> Self {
+        Self {
+            max_bytes: None,
+            max_messages: None,
+            max_delay: None,
+        }
+    }
+
+    /// Flushes once `bytes` bytes are buffered.
+    pub const fn max_bytes(mut self, bytes: usize) -> Self {
+        self.max_bytes = Some(bytes);
+        self
+    }
+
+    /// Flushes once `messages` messages are buffered.
+    pub const fn max_messages(mut self, messages: usize) -> Self {
+        self.max_messages = Some(messages);
+        self
+    }
+
+    /// Flushes once the oldest buffered message waited for `delay`.
+    pub const fn max_delay(mut self, delay: Duration) -> Self {
+        self.max_delay = Some(delay);
+        self
+    }
+}
+
+/// A writer buffering serialized messages, and writing them at once when its [FlushPolicy] is
+/// due.
+///
+/// Messages still buffered when the writer is dropped are lost; call [CoalescingWriter::flush] or
+/// [CoalescingWriter::into_inner] first.
+#[derive(Debug)]
+pub struct CoalescingWriter<W> {
+    inner: W,
+    config: ToraConfig,
+    policy: FlushPolicy,
+    buf: Vec<u8>,
+    /// Holds a message while its frame length is computed.
+    scratch: Vec<u8>,
+    pending: usize,
+    /// When the oldest buffered message was sent.
+    oldest: Option<Instant>,
+}
+
+impl<W> CoalescingWriter<W>
+where
+    W: Write,
+{
+    /// Constructs a CoalescingWriter using the default policy and configuration.
+    pub fn new(inner: W) -> Self {
+        Self::with_policy(inner, FlushPolicy::DEFAULT)
+    }
+
+    /// Constructs a CoalescingWriter using the given policy and the default configuration.
+    pub fn with_policy(inner: W, policy: FlushPolicy) -> Self {
+        Self::with_config(inner, policy, ToraConfig::DEFAULT)
+    }
+
+    /// Constructs a CoalescingWriter using the given policy and configuration.
+    pub fn with_config(inner: W, policy: FlushPolicy, config: ToraConfig) -> Self {
+        Self {
+            inner,
+            config,
+            policy,
+            buf: Vec::new(),
+            scratch: Vec::new(),
+            pending: 0,
+            oldest: None,
+        }
+    }
+
+    /// Serializes the value into the buffer, then flushes if the policy is due.
+    pub fn send<T>(&mut self, value: &T) -> io::Result<()>
+    where
+        T: SerializeIo + ?Sized,
+    {
+        let len = self.buf.len();
+
+        if let Err(e) = value.serialize_with(&mut self.buf, &self.config) {
+            self.buf.truncate(len);
+            return Err(e);
+        }
+        self.sent()
+    }
+
+    /// Serializes the value into the buffer as a frame prefixed with its length, as read by a
+    /// [ToraStream](crate::stream::ToraStream), then flushes if the policy is due.
+    pub fn send_frame<T>(&mut self, value: &T) -> io::Result<()>
+    where
+        T: SerializeIo + ?Sized,
+    {
+        self.scratch.clear();
+        value.serialize_with(&mut self.scratch, &self.config)?;
+
+        self.config
+            .write_length(&mut self.buf, self.scratch.len())?;
+        self.buf.extend_from_slice(&self.scratch);
+        self.sent()
+    }
+
+    /// Counts a message buffered by [Self::send] or [Self::send_frame].
+    fn sent(&mut self) -> io::Result<()> {
+        self.pending += 1;
+        self.oldest.get_or_insert_with(Instant::now);
+        self.flush_if_due()
+    }
+
+    /// Flushes if any threshold of the policy is reached, including the delay of the oldest
+    /// buffered message.
+    pub fn flush_if_due(&mut self) -> io::Result<()> {
+        if self.pending == 0 {
+            return Ok(());
+        }
+        let FlushPolicy {
+            max_bytes,
+            max_messages,
+            ..
+        } = self.policy;
+
+        let due = max_bytes.is_some_and(|max| self.buf.len() >= max)
+            || max_messages.is_some_and(|max| self.pending >= max)
+            || self
+                .deadline()
+                .is_some_and(|deadline| Instant::now() >= deadline);
+
+        match due {
+            true => self.flush(),
+            false => Ok(()),
+        }
+    }
+
+    /// Returns when the oldest buffered message reaches the delay of the policy, if any.
+    pub fn deadline(&self) -> Option<Instant> {
+        Some(self.oldest? + self.policy.max_delay?)
+    }
+
+    /// Writes every buffered message to the underlying writer, then flushes it.
+    ///
+    /// If writing fails, only the bytes not written yet are kept buffered, so flushing again
+    /// resumes where the failed flush stopped without writing any byte twice.
+    ///
+    /// ```
+    /// use std::io::{self, ErrorKind, Write};
+    ///
+    /// use tora::coalesce::{CoalescingWriter, FlushPolicy};
+    ///
+    /// /// Accepts two bytes per write, failing every other write.
+    /// struct Flaky(Vec<u8>, bool);
+    ///
+    /// impl Write for Flaky {
+    ///     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    ///         self.1 = !self.1;
+    ///         if !self.1 {
+    ///             return Err(ErrorKind::WouldBlock.into());
+    ///         }
+    ///         let n = buf.len().min(2);
+    ///         self.0.extend_from_slice(&buf[..n]);
+    ///         Ok(n)
+    ///     }
+    ///
+    ///     fn flush(&mut self) -> io::Result<()> {
+    ///         Ok(())
+    ///     }
+    /// }
+    ///
+    /// fn main() -> io::Result<()> {
+    ///     let flaky = Flaky(Vec::new(), false);
+    ///     let mut writer = CoalescingWriter::with_policy(flaky, FlushPolicy::new());
+    ///     writer.send(&0x0403_0201u32)?;
+    ///
+    ///     assert_eq!(writer.flush().unwrap_err().kind(), ErrorKind::WouldBlock);
+    ///     assert_eq!(writer.buffered(), 2);
+    ///
+    ///     writer.flush()?;
+    ///     assert_eq!(writer.get_ref().0, [1, 2, 3, 4]);
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn flush(&mut self) -> io::Result<()> {
+        let mut written = 0;
+
+        let result = loop {
+            if written == self.buf.len() {
+                break Ok(());
+            }
+            match self.inner.write(&self.buf[written..]) {
+                Ok(0) => {
+                    break Err(io::Error::new(
+                        ErrorKind::WriteZero,
+                        "Failed to write the buffered messages",
+                    ))
+                }
+                Ok(n) => written += n,
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => break Err(e),
+            }
+        };
+        self.buf.drain(..written);
+        result?;
+
+        self.pending = 0;
+        self.oldest = None;
+        self.inner.flush()
+    }
+
+    /// Returns the amount of buffered bytes.
+    pub fn buffered(&self) -> usize {
+        self.buf.len()
+    }
+
+    /// Returns the amount of buffered messages.
+    pub fn pending(&self) -> usize {
+        self.pending
+    }
+
+    /// Returns the policy of this writer.
+    pub fn policy(&self) -> &FlushPolicy {
+        &self.policy
+    }
+
+    /// Returns a reference to the underlying writer.
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    /// Flushes the buffered messages, then returns the underlying writer.
+    pub fn into_inner(mut self) -> io::Result<W> {
+        self.flush()?;
+        Ok(self.inner)
+    }
+}
@@ -0,0 +1,291 @@
+//! Per-stream overrides of how a type is encoded.
+//!
+//! A [Codec] encodes and decodes values of a single type. Registered in [Codecs] and referenced
+//! by [ToraConfig::codecs], it replaces the built-in encoding of that type wherever it is read or
+//! written with the configuration, including the fields of derived structs and enums. The same
+//! types can then be written with variable length integers on the wire and fixed width integers
+//! on disk, without wrapping every field.
+//!
+//! Codecs are honored by the primitives, [String], [Duration](std::time::Duration), and types
+//! deriving `ReadStruct`, `ReadEnum`, `WriteStruct` or `WriteEnum`. Length prefixes are written
+//! as integers, so a codec registered for the configured prefix type applies to them too.
+//! [ConstSize](crate::layout::ConstSize) and [Reflect](crate::schema::Reflect) describe the
+//! built-in encoding, and do not account for codecs.
+//!
+//! ```
+//! use std::io;
+//! use std::io::Cursor;
+//! use std::sync::LazyLock;
+//!
+//! use tora::codec::{Codecs, VarInt};
+//! use tora::config::ToraConfig;
+//! use tora::read::ToraRead;
+//! use tora::write::ToraWrite;
+//! use tora::{ReadStruct, WriteStruct};
+//!
+//! #[derive(Debug, PartialEq, ReadStruct, WriteStruct)]
+//! struct Position {
+//!     entity: u64,
+//!     x: i32,
+//! }
+//!
+//! static WIRE: LazyLock<Codecs> = LazyLock::new(|| {
+//!     let mut codecs = Codecs::new();
+//!     codecs.register::<u64, _>(VarInt).register::<i32, _>(VarInt);
+//!     codecs
+//! });
+//!
+//! fn main() -> io::Result<()> {
+//!     let config = ToraConfig::DEFAULT.codecs(&WIRE);
+//!     let position = Position { entity: 7, x: -2 };
+//!
+//!     let mut bytes = Vec::new();
+//!     bytes.writes_with(&position, &config)?;
+//!     assert_eq!(bytes, [7, 3]);
+//!
+//!     let read: Position = Cursor::new(bytes).reads_with(&config)?;
+//!     assert_eq!(read, position);
+//!     Ok(())
+//! }
+//! ```
+
+use std::any::{type_name, Any, TypeId};
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::io;
+use std::io::{ErrorKind, Read, Write};
+
+use crate::config::ToraConfig;
+
+/// Encodes and decodes values of type [T], in place of their built-in encoding.
+///
+/// Codecs are given the configuration without its codecs, so they can fall back to the built-in
+/// encoding of [T] without recursing into themselves.
+pub trait Codec<T>: Send + Sync {
+    /// Writes the value.
+    fn encode(&self, value: &T, w: &mut dyn Write, config: &ToraConfig) -> io::Result<()>;
+
+    /// Reads a value written by [Codec::encode].
+    fn decode(&self, r: &mut dyn Read, config: &ToraConfig) -> io::Result<T>;
+}
+
+/// The codec of a type, along with its name for debugging.
+struct Entry {
+    type_name: &'static str,
+    /// The `Box<dyn Codec<T>>`.
+    codec: Box<dyn Any + Send + Sync>,
+}
+
+/// Maps types to the codecs overriding their encoding.
+///
+/// Registries are referenced by [ToraConfig::codecs] for the lifetime of the program, usually
+/// from a `static` or a leaked `Box`. As codecs cannot be compared, registries compare and hash
+/// by identity.
+#[derive(Default)]
+pub struct Codecs {
+    codecs: HashMap<TypeId, Entry>,
+}
+
+impl Codecs {
+    /// Constructs an empty registry.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers the codec encoding values of type [T].
+    ///
+    /// # Panics
+    ///
+    /// Panics if a codec was already registered for [T].
+    pub fn register<T, C>(&mut self, codec: C) -> &mut Self
+    where
+        T: 'static,
+        C: Codec<T> + 'static,
+    {
+        let entry = Entry {
+            type_name: type_name::<T>(),
+            codec: Box::new(Box::new(codec) as Box<dyn Codec<T>>),
+        };
+        if self.codecs.insert(TypeId::of::<T>(), entry).is_some() {
+            panic!("A codec was registered twice for {}", type_name::<T>());
+        }
+        self
+    }
+
+    /// Removes the codec registered for [T], returning true if there was one.
+    pub fn unregister<T>(&mut self) -> bool
+    where
+        T: 'static,
+    {
+        self.codecs.remove(&TypeId::of::<T>()).is_some()
+    }
+
+    /// Returns the codec registered for [T], if any.
+    pub fn get<T>(&self) -> Option<&dyn Codec<T>>
+    where
+        T: 'static,
+    {
+        self.codecs
+            .get(&TypeId::of::<T>())
+            .and_then(|entry| entry.codec.downcast_ref::<Box<dyn Codec<T>>>())
+            .map(|codec| &**codec)
+    }
+
+    /// Returns true if a codec is registered for [T].
+    pub fn contains<T>(&self) -> bool
+    where
+        T: 'static,
+    {
+        self.codecs.contains_key(&TypeId::of::<T>())
+    }
+}
+
+impl fmt::Debug for Codecs {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set()
+            .entries(self.codecs.values().map(|entry| entry.type_name))
+            .finish()
+    }
+}
+
+impl PartialEq for Codecs {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+impl Eq for Codecs {}
+
+impl Hash for Codecs {
+    fn hash<H>(&self, state: &mut H)
+    where
+        H: Hasher,
+    {
+        std::ptr::hash(self, state)
+    }
+}
+
+/// Writes an integer as LEB128: 7 bits per byte, least significant first, with the high bit set
+/// on every byte but the last.
+pub(crate) fn write_varint<W>(w: &mut W, mut value: u64) -> io::Result<()>
+where
+    W: Write + ?Sized,
+{
+    let mut bytes = Vec::with_capacity(10);
+
+    while value >= 0x80 {
+        bytes.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    bytes.push(value as u8);
+    w.write_all(&bytes)
+}
+
+/// Reads an integer written by [write_varint].
+///
+/// Returns [ErrorKind::InvalidData] if the integer overflows a [u64].
+pub(crate) fn read_varint<R>(r: &mut R) -> io::Result<u64>
+where
+    R: Read + ?Sized,
+{
+    let mut value = 0u64;
+
+    for shift in (0..64).step_by(7) {
+        let mut byte = [0];
+        r.read_exact(&mut byte)?;
+        let byte = byte[0];
+        let bits = (byte & 0x7F) as u64;
+
+        if (bits << shift) >> shift != bits {
+            break;
+        }
+        value |= bits << shift;
+
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(io::Error::new(
+        ErrorKind::InvalidData,
+        "Variable length integer overflows u64",
+    ))
+}
+
+/// Writes integers as LEB128, in a variable amount of bytes.
+///
+/// Values below 128 take a single byte. Signed integers are zigzag encoded first, so small
+/// negative values are as short as small positive ones. Reading a value too large for the
+/// integer type returns [ErrorKind::InvalidData].
+///
+/// ```
+/// use tora::codec::{Codec, VarInt};
+/// use tora::config::ToraConfig;
+///
+/// let mut bytes = Vec::new();
+/// Codec::<u32>::encode(&VarInt, &300, &mut bytes, &ToraConfig::DEFAULT).unwrap();
+/// Codec::<i16>::encode(&VarInt, &-1, &mut bytes, &ToraConfig::DEFAULT).unwrap();
+///
+/// assert_eq!(bytes, [0xAC, 0x02, 0x01]);
+/// ```
+#[derive(Clone, Copy, Debug, Default)]
+pub struct VarInt;
+
+macro_rules! varint_unsigned {
+    ($($t:ty),*) => {
+        $(
+        impl Codec<$t> for VarInt {
+            fn encode(
+                &self,
+                value: &$t,
+                w: &mut dyn Write,
+                _config: &ToraConfig,
+            ) -> io::Result<()> {
+                write_varint(w, *value as u64)
+            }
+
+            fn decode(&self, r: &mut dyn Read, _config: &ToraConfig) -> io::Result<$t> {
+                <$t>::try_from(read_varint(r)?).map_err(|_| {
+                    io::Error::new(
+                        ErrorKind::InvalidData,
+                        concat!("Variable length integer overflows ", stringify!($t)),
+                    )
+                })
+            }
+        }
+        )*
+    };
+}
+
+macro_rules! varint_signed {
+    ($($t:ty),*) => {
+        $(
+        impl Codec<$t> for VarInt {
+            fn encode(
+                &self,
+                value: &$t,
+                w: &mut dyn Write,
+                _config: &ToraConfig,
+            ) -> io::Result<()> {
+                let value = *value as i64;
+                write_varint(w, ((value << 1) ^ (value >> 63)) as u64)
+            }
+
+            fn decode(&self, r: &mut dyn Read, _config: &ToraConfig) -> io::Result<$t> {
+                let zigzag = read_varint(r)?;
+                let value = (zigzag >> 1) as i64 ^ -((zigzag & 1) as i64);
+
+                <$t>::try_from(value).map_err(|_| {
+                    io::Error::new(
+                        ErrorKind::InvalidData,
+                        concat!("Variable length integer overflows ", stringify!($t)),
+                    )
+                })
+            }
+        }
+        )*
+    };
+}
+
+varint_unsigned!(u16, u32, u64, usize);
+varint_signed!(i16, i32, i64);
@@ -0,0 +1,126 @@
+//! Structure-of-arrays encoding of struct sequences.
+//!
+//! A `Vec` of structs is written row by row by default, each struct after the other. Wrapped in
+//! [Columns], it is written column by column instead: the first field of every struct, then the
+//! second field of every struct, and so on. Neighbouring values of the same field tend to be
+//! similar, so columns compress far better, and columns of primitives are read with the bulk path
+//! of [FromReader::from_reader_vec].
+//!
+//! The element type implements [Columnar], usually through its derive macro.
+//!
+//! ```
+//! use tora::columnar::Columns;
+//! use tora::Columnar;
+//!
+//! #[derive(Columnar, Debug, PartialEq)]
+//! struct Sample {
+//!     sensor: u8,
+//!     value: u16,
+//! }
+//!
+//! let batch = Columns(vec![
+//!     Sample { sensor: 1, value: 10 },
+//!     Sample { sensor: 2, value: 20 },
+//! ]);
+//!
+//! tora::assert_bytes_eq!(batch, "02 00 00 00 01 02 0a 00 14 00");
+//! tora::assert_roundtrip!(batch);
+//! ```
+
+use std::io;
+use std::io::{Read, Write};
+use std::ops::{Deref, DerefMut};
+
+use crate::config::ToraConfig;
+use crate::read::FromReader;
+use crate::write::SerializeIo;
+
+/// A struct which can be written and read as columns of its fields.
+///
+/// Derived with `#[derive(Columnar)]`, which writes the fields in their wire order, honoring the
+/// `order`, `get` and `set` attributes.
+pub trait Columnar: Sized {
+    /// Writes the values column by column, without a length prefix.
+    fn write_columns<W>(values: &[Self], w: &mut W, config: &ToraConfig) -> io::Result<()>
+    where
+        W: Write;
+
+    /// Reads `len` values written by [Columnar::write_columns].
+    fn read_columns<R>(r: &mut R, len: usize, config: &ToraConfig) -> io::Result<Vec<Self>>
+    where
+        R: Read;
+}
+
+/// A sequence of structs written column by column.
+///
+/// Written as the configured length prefix, then the columns of [Columnar::write_columns].
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq)]
+pub struct Columns<T>(pub Vec<T>);
+
+impl<T> From<Vec<T>> for Columns<T> {
+    fn from(values: Vec<T>) -> Self {
+        Self(values)
+    }
+}
+
+impl<T> From<Columns<T>> for Vec<T> {
+    fn from(columns: Columns<T>) -> Self {
+        columns.0
+    }
+}
+
+impl<T> Deref for Columns<T> {
+    type Target = Vec<T>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for Columns<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl<T> FromReader for Columns<T>
+where
+    T: Columnar,
+{
+    fn from_reader<R>(r: &mut R) -> io::Result<Self>
+    where
+        R: Read,
+    {
+        Self::from_reader_with(r, &ToraConfig::DEFAULT)
+    }
+
+    /// Returns [ErrorKind::InvalidData](io::ErrorKind::InvalidData) if the length exceeds the
+    /// configured maximum length.
+    fn from_reader_with<R>(r: &mut R, config: &ToraConfig) -> io::Result<Self>
+    where
+        R: Read,
+    {
+        let len = config.read_length(r)?;
+        T::read_columns(r, len, config).map(Self)
+    }
+}
+
+impl<T> SerializeIo for Columns<T>
+where
+    T: Columnar,
+{
+    fn serialize<W>(&self, w: &mut W) -> io::Result<()>
+    where
+        W: Write,
+    {
+        self.serialize_with(w, &ToraConfig::DEFAULT)
+    }
+
+    fn serialize_with<W>(&self, w: &mut W, config: &ToraConfig) -> io::Result<()>
+    where
+        W: Write,
+    {
+        config.write_length(w, self.0.len())?;
+        T::write_columns(&self.0, w, config)
+    }
+}
@@ -0,0 +1,747 @@
+//! Compact encodings for values whose default encoding wastes space.
+//!
+//! The wrappers of this module can be used as the fields of derived structs and enums.
+//!
+//! ```
+//! use tora::compact::PackedBools;
+//! use tora::{ReadStruct, WriteStruct};
+//!
+//! #[derive(Debug, PartialEq, ReadStruct, WriteStruct)]
+//! struct Tile {
+//!     id: u16,
+//!     // Written in 2 bytes instead of 12.
+//!     walls: PackedBools<12>,
+//! }
+//!
+//! let mut walls = PackedBools::<12>::default();
+//! walls[0] = true;
+//! walls[9] = true;
+//!
+//! tora::assert_bytes_eq!(Tile { id: 1, walls }, "01 00 01 02");
+//! tora::assert_roundtrip!(Tile { id: 1, walls });
+//! ```
+//!
+//! Durations are written as a [u64] of seconds and a [u32] of nanoseconds by default. The
+//! [Secs], [Millis], [Micros] and [Nanos] wrappers write them as an integer of the unit and width
+//! a protocol needs instead, and [VarIntDuration] as a variable amount of bytes.
+//!
+//! ```
+//! use std::time::Duration;
+//!
+//! use tora::compact::{Millis, VarIntDuration};
+//! use tora::{ReadStruct, WriteStruct};
+//!
+//! #[derive(Debug, PartialEq, ReadStruct, WriteStruct)]
+//! struct Cooldowns {
+//!     attack: Millis<u16>,
+//!     respawn: VarIntDuration,
+//! }
+//!
+//! let cooldowns = Cooldowns {
+//!     attack: Millis::try_from(Duration::from_millis(750)).unwrap(),
+//!     respawn: Duration::from_secs(5).into(),
+//! };
+//! tora::assert_bytes_eq!(cooldowns, "ee 02 88 27");
+//! assert_eq!(Duration::from(cooldowns.attack), Duration::from_millis(750));
+//! ```
+//!
+//! Sequences made mostly of repeated values, such as the tiles of a map, can be written as runs
+//! of equal values with [Rle], and sequences of integers close to the previous one, such as IDs
+//! and timestamps, as bit-packed deltas with [PackedInts].
+
+use std::io;
+use std::io::{ErrorKind, Read, Write};
+use std::ops::{Deref, DerefMut};
+use std::time::Duration;
+
+use crate::bits::{BitOrder, BitReader, BitWriter};
+use crate::cancel::CHECK_ELEMENTS;
+use crate::codec::{read_varint, write_varint};
+use crate::config::ToraConfig;
+use crate::layout::ConstSize;
+use crate::read::{preallocation, try_reserve, FromReader};
+use crate::schema::{Reflect, Schema};
+use crate::write::SerializeIo;
+
+/// An array of bools packed into bits, written in `ceil(N / 8)` bytes.
+///
+/// The bool at index `i` is stored in bit `i % 8` of byte `i / 8`, counting from the least
+/// significant bit. The unused bits of the last byte are zero, and reading non-zero unused bits
+/// returns [ErrorKind::InvalidData].
+///
+/// ```
+/// use std::io::ErrorKind;
+///
+/// use tora::compact::PackedBools;
+/// use tora::testing::assert_decode_error;
+///
+/// tora::assert_bytes_eq!(PackedBools([true, false, true]), "05");
+/// assert_decode_error::<PackedBools<3>>(&[0x0D], ErrorKind::InvalidData);
+/// ```
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct PackedBools<const N: usize>(pub [bool; N]);
+
+impl<const N: usize> PackedBools<N> {
+    /// The amount of bytes the bools are packed into.
+    pub const BYTES: usize = N.div_ceil(8);
+
+    /// Returns the unpacked bools.
+    pub const fn into_inner(self) -> [bool; N] {
+        self.0
+    }
+}
+
+impl<const N: usize> Default for PackedBools<N> {
+    fn default() -> Self {
+        Self([false; N])
+    }
+}
+
+impl<const N: usize> From<[bool; N]> for PackedBools<N> {
+    fn from(bools: [bool; N]) -> Self {
+        Self(bools)
+    }
+}
+
+impl<const N: usize> From<PackedBools<N>> for [bool; N] {
+    fn from(packed: PackedBools<N>) -> Self {
+        packed.0
+    }
+}
+
+impl<const N: usize> Deref for PackedBools<N> {
+    type Target = [bool; N];
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<const N: usize> DerefMut for PackedBools<N> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl<const N: usize> FromReader for PackedBools<N> {
+    fn from_reader<R>(r: &mut R) -> io::Result<Self>
+    where
+        R: Read,
+    {
+        let mut bytes = vec![0; Self::BYTES];
+        r.read_exact(&mut bytes)?;
+
+        let mut bools = [false; N];
+        for (i, b) in bools.iter_mut().enumerate() {
+            *b = bytes[i / 8] & (1 << (i % 8)) != 0;
+        }
+
+        let unused = (Self::BYTES * 8 - N) as u32;
+        if let Some(&last) = bytes.last() {
+            if last.checked_shr(8 - unused).unwrap_or(0) != 0 {
+                return Err(io::Error::new(
+                    ErrorKind::InvalidData,
+                    "Packed bools have non-zero unused bits",
+                ));
+            }
+        }
+        Ok(Self(bools))
+    }
+
+    fn from_reader_with<R>(r: &mut R, _config: &ToraConfig) -> io::Result<Self>
+    where
+        R: Read,
+    {
+        Self::from_reader(r)
+    }
+}
+
+impl<const N: usize> SerializeIo for PackedBools<N> {
+    fn serialize<W>(&self, w: &mut W) -> io::Result<()>
+    where
+        W: Write,
+    {
+        let mut bytes = vec![0u8; Self::BYTES];
+
+        for (i, &b) in self.0.iter().enumerate() {
+            bytes[i / 8] |= (b as u8) << (i % 8);
+        }
+        w.write_all(&bytes)
+    }
+
+    fn serialize_with<W>(&self, w: &mut W, _config: &ToraConfig) -> io::Result<()>
+    where
+        W: Write,
+    {
+        self.serialize(w)
+    }
+}
+
+impl<const N: usize> ConstSize for PackedBools<N> {
+    const SIZE: usize = Self::BYTES;
+}
+
+/// Described as the array of bytes the bools are packed into.
+impl<const N: usize> Reflect for PackedBools<N> {
+    fn schema() -> Schema {
+        Schema::Array(Box::new(Schema::U8), Self::BYTES)
+    }
+}
+
+macro_rules! duration_unit {
+    ($($(#[$doc:meta])* $name:ident, $unit:literal, $as_unit:expr, $from_unit:path;)*) => {
+        $(
+        $(#[$doc])*
+        ///
+        #[doc = concat!(
+            "Converts from a [Duration] with `try_from`, truncating to whole ",
+            $unit,
+            ", and"
+        )]
+        /// failing if the amount does not fit in [T].
+        #[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+        pub struct $name<T = u32>(pub T);
+
+        impl<T> From<$name<T>> for Duration
+        where
+            T: Into<u64>,
+        {
+            fn from(value: $name<T>) -> Self {
+                $from_unit(value.0.into())
+            }
+        }
+
+        impl<T> TryFrom<Duration> for $name<T>
+        where
+            T: TryFrom<u128>,
+        {
+            type Error = T::Error;
+
+            fn try_from(duration: Duration) -> Result<Self, Self::Error> {
+                let as_unit: fn(&Duration) -> u128 = $as_unit;
+                T::try_from(as_unit(&duration)).map(Self)
+            }
+        }
+
+        impl<T> FromReader for $name<T>
+        where
+            T: FromReader,
+        {
+            fn from_reader<R>(r: &mut R) -> io::Result<Self>
+            where
+                R: Read,
+            {
+                T::from_reader(r).map(Self)
+            }
+
+            fn from_reader_with<R>(r: &mut R, config: &ToraConfig) -> io::Result<Self>
+            where
+                R: Read,
+            {
+                T::from_reader_with(r, config).map(Self)
+            }
+        }
+
+        impl<T> SerializeIo for $name<T>
+        where
+            T: SerializeIo,
+        {
+            fn serialize<W>(&self, w: &mut W) -> io::Result<()>
+            where
+                W: Write,
+            {
+                self.0.serialize(w)
+            }
+
+            fn serialize_with<W>(&self, w: &mut W, config: &ToraConfig) -> io::Result<()>
+            where
+                W: Write,
+            {
+                self.0.serialize_with(w, config)
+            }
+        }
+
+        impl<T> ConstSize for $name<T>
+        where
+            T: ConstSize,
+        {
+            const SIZE: usize = T::SIZE;
+        }
+
+        impl<T> Reflect for $name<T>
+        where
+            T: Reflect,
+        {
+            fn schema() -> Schema {
+                T::schema()
+            }
+        }
+        )*
+    };
+}
+
+duration_unit! {
+    /// A duration written as an integer amount of seconds.
+    Secs, "seconds", |d| d.as_secs() as u128, Duration::from_secs;
+    /// A duration written as an integer amount of milliseconds.
+    Millis, "milliseconds", Duration::as_millis, Duration::from_millis;
+    /// A duration written as an integer amount of microseconds.
+    Micros, "microseconds", Duration::as_micros, Duration::from_micros;
+    /// A duration written as an integer amount of nanoseconds.
+    Nanos, "nanoseconds", Duration::as_nanos, Duration::from_nanos;
+}
+
+/// A duration written as its whole milliseconds in a variable amount of bytes.
+///
+/// Each byte holds 7 bits of the amount, least significant first, with the high bit set on every
+/// byte but the last. Durations below 128 milliseconds take a single byte, and below 4.5 hours
+/// at most three. Sub-millisecond precision is truncated on write.
+///
+/// Writing a duration of more than [u64::MAX] milliseconds returns [ErrorKind::InvalidInput].
+///
+/// ```
+/// use std::io::ErrorKind;
+/// use std::time::Duration;
+///
+/// use tora::compact::VarIntDuration;
+/// use tora::testing::assert_decode_error;
+///
+/// tora::assert_bytes_eq!(VarIntDuration(Duration::from_millis(100)), "64");
+/// tora::assert_roundtrip!(VarIntDuration(Duration::from_millis(u64::MAX)));
+///
+/// let overflow = [0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x02];
+/// assert_decode_error::<VarIntDuration>(&overflow, ErrorKind::InvalidData);
+/// ```
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct VarIntDuration(pub Duration);
+
+impl From<Duration> for VarIntDuration {
+    fn from(duration: Duration) -> Self {
+        Self(duration)
+    }
+}
+
+impl From<VarIntDuration> for Duration {
+    fn from(value: VarIntDuration) -> Self {
+        value.0
+    }
+}
+
+impl FromReader for VarIntDuration {
+    /// Returns [ErrorKind::InvalidData] if the amount overflows a [u64].
+    fn from_reader<R>(r: &mut R) -> io::Result<Self>
+    where
+        R: Read,
+    {
+        read_varint(r).map(|millis| Self(Duration::from_millis(millis)))
+    }
+
+    fn from_reader_with<R>(r: &mut R, _config: &ToraConfig) -> io::Result<Self>
+    where
+        R: Read,
+    {
+        Self::from_reader(r)
+    }
+}
+
+impl SerializeIo for VarIntDuration {
+    fn serialize<W>(&self, w: &mut W) -> io::Result<()>
+    where
+        W: Write,
+    {
+        let millis = u64::try_from(self.0.as_millis()).map_err(|_| {
+            io::Error::new(
+                ErrorKind::InvalidInput,
+                "Duration exceeds u64::MAX milliseconds",
+            )
+        })?;
+        write_varint(w, millis)
+    }
+
+    fn serialize_with<W>(&self, w: &mut W, _config: &ToraConfig) -> io::Result<()>
+    where
+        W: Write,
+    {
+        self.serialize(w)
+    }
+}
+
+/// The amount of values [Rle], and [PackedInts] whose deltas are all equal, decode at most when
+/// the configuration sets no maximum length.
+pub const DEFAULT_MAX_DECODED: usize = 1 << 24;
+
+/// Returns [ErrorKind::InvalidData] if `len` decoded values exceed the configured maximum length,
+/// or [DEFAULT_MAX_DECODED] if none is set.
+pub(crate) fn check_decoded_length(len: usize, config: &ToraConfig) -> io::Result<()> {
+    match config.max_length {
+        Some(_) => config.check_length(len),
+        None if len > DEFAULT_MAX_DECODED => Err(io::Error::new(
+            ErrorKind::InvalidData,
+            "Decoded length exceeds the default maximum",
+        )),
+        None => Ok(()),
+    }
+}
+
+/// A sequence written as runs of equal values, for tile maps, voxel columns and other sequences
+/// made mostly of repeated values.
+///
+/// Written as the configured length prefix holding the amount of runs, followed by each run as
+/// its length in a variable amount of bytes, as by [VarIntDuration], and the repeated value.
+///
+/// Reading a run of length zero returns [ErrorKind::InvalidData], as does reading more values
+/// in total than the configured maximum length, or than [DEFAULT_MAX_DECODED] if none is set. A
+/// few bytes of runs expand into any amount of values, so only that maximum bounds the memory
+/// untrusted input makes the reader allocate.
+///
+/// ```
+/// use std::io::ErrorKind;
+///
+/// use tora::compact::Rle;
+/// use tora::testing::assert_decode_error;
+///
+/// let mut column = vec![0u8; 100];
+/// column.extend([3; 4]);
+///
+/// // Two runs: 100 zeros, then 4 threes.
+/// tora::assert_bytes_eq!(Rle(column.clone()), "02 00 00 00 64 00 04 03");
+/// tora::assert_roundtrip!(Rle(column));
+///
+/// assert_decode_error::<Rle<u8>>(&[1, 0, 0, 0, 0, 3], ErrorKind::InvalidData);
+///
+/// // A single run of 2^40 values.
+/// let bomb = [1, 0, 0, 0, 0x80, 0x80, 0x80, 0x80, 0x80, 0x20, 3];
+/// assert_decode_error::<Rle<u8>>(&bomb, ErrorKind::InvalidData);
+/// ```
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq)]
+pub struct Rle<T>(pub Vec<T>);
+
+impl<T> Rle<T>
+where
+    T: PartialEq,
+{
+    /// Returns the amount of runs the values are written as.
+    pub fn runs(&self) -> usize {
+        self.0.chunk_by(|a, b| a == b).count()
+    }
+
+    /// Returns the values.
+    pub fn into_inner(self) -> Vec<T> {
+        self.0
+    }
+}
+
+impl<T> From<Vec<T>> for Rle<T> {
+    fn from(values: Vec<T>) -> Self {
+        Self(values)
+    }
+}
+
+impl<T> From<Rle<T>> for Vec<T> {
+    fn from(rle: Rle<T>) -> Self {
+        rle.0
+    }
+}
+
+impl<T> Deref for Rle<T> {
+    type Target = Vec<T>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for Rle<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl<T> FromReader for Rle<T>
+where
+    T: FromReader + Clone,
+{
+    fn from_reader<R>(r: &mut R) -> io::Result<Self>
+    where
+        R: Read,
+    {
+        Self::from_reader_with(r, &ToraConfig::DEFAULT)
+    }
+
+    /// Returns [ErrorKind::OutOfMemory] if the values cannot be allocated.
+    fn from_reader_with<R>(r: &mut R, config: &ToraConfig) -> io::Result<Self>
+    where
+        R: Read,
+    {
+        let runs = config.read_length(r)?;
+        let mut values = Vec::new();
+
+        for i in 0..runs {
+            if i % CHECK_ELEMENTS == 0 {
+                config.check_cancelled()?;
+            }
+            let run = usize::try_from(read_varint(r)?)
+                .ok()
+                .filter(|&run| run != 0)
+                .ok_or_else(|| io::Error::new(ErrorKind::InvalidData, "Invalid run length"))?;
+            let len = values.len().saturating_add(run);
+            check_decoded_length(len, config)?;
+
+            let value = T::from_reader_with(r, config)?;
+            try_reserve(&mut values, run)?;
+            values.resize(len, value);
+        }
+        Ok(Self(values))
+    }
+}
+
+impl<T> SerializeIo for Rle<T>
+where
+    T: SerializeIo + PartialEq,
+{
+    fn serialize<W>(&self, w: &mut W) -> io::Result<()>
+    where
+        W: Write,
+    {
+        self.serialize_with(w, &ToraConfig::DEFAULT)
+    }
+
+    fn serialize_with<W>(&self, w: &mut W, config: &ToraConfig) -> io::Result<()>
+    where
+        W: Write,
+    {
+        config.write_length(w, self.runs())?;
+
+        for run in self.0.chunk_by(|a, b| a == b) {
+            write_varint(w, run.len() as u64)?;
+            run[0].serialize_with(w, config)?;
+        }
+        Ok(())
+    }
+}
+
+/// Sign-extends the `bits` low bits of `value`.
+fn sign_extend(value: u64, bits: u32) -> i64 {
+    ((value << (64 - bits)) as i64) >> (64 - bits)
+}
+
+/// Writes the values of an integer type `bits` wide as the body of [PackedInts], without the
+/// length prefix.
+pub(crate) fn write_packed<I, W>(w: &mut W, mut values: I, bits: u32) -> io::Result<()>
+where
+    I: Iterator<Item = u64> + Clone,
+    W: Write,
+{
+    let Some(first) = values.next() else {
+        return Ok(());
+    };
+    write_varint(w, first)?;
+
+    let deltas = values.scan(first, move |prev, value| {
+        let delta = sign_extend(value.wrapping_sub(*prev), bits);
+        *prev = value;
+        Some(delta)
+    });
+    let Some(min) = deltas.clone().min() else {
+        return Ok(());
+    };
+    let width = deltas
+        .clone()
+        .map(|delta| delta.wrapping_sub(min) as u64)
+        .max()
+        .map_or(0, |max| u64::BITS - max.leading_zeros());
+
+    write_varint(w, ((min << 1) ^ (min >> 63)) as u64)?;
+    w.write_all(&[width as u8])?;
+
+    let mut packed = BitWriter::with_order(w, BitOrder::LsbFirst);
+    for delta in deltas {
+        packed.write_bits(delta.wrapping_sub(min) as u64, width)?;
+    }
+    packed.into_inner().map(|_| ())
+}
+
+/// Reads `len` values of an integer type `bits` wide written by [write_packed], passing each to
+/// `f`.
+///
+/// With `decoded`, `len` was read from the input and `f` collects the values, so zero-width
+/// deltas, which expand a few bytes into any amount of values, are limited as by [Rle].
+pub(crate) fn read_packed<R, F>(
+    r: &mut R,
+    len: usize,
+    bits: u32,
+    config: &ToraConfig,
+    decoded: bool,
+    mut f: F,
+) -> io::Result<()>
+where
+    R: Read,
+    F: FnMut(u64),
+{
+    if len == 0 {
+        return Ok(());
+    }
+    let mask = u64::MAX >> (64 - bits);
+
+    let mut value = read_varint(r)?;
+    if value & !mask != 0 {
+        return Err(io::Error::new(
+            ErrorKind::InvalidData,
+            "Packed integer overflows its type",
+        ));
+    }
+    f(value);
+
+    if len == 1 {
+        return Ok(());
+    }
+    let zigzag = read_varint(r)?;
+    let min = (zigzag >> 1) as i64 ^ -((zigzag & 1) as i64);
+
+    let mut width = [0];
+    r.read_exact(&mut width)?;
+    let width = width[0] as u32;
+    if width > bits {
+        return Err(io::Error::new(
+            ErrorKind::InvalidData,
+            "Packed deltas are wider than their type",
+        ));
+    }
+    if decoded && width == 0 {
+        check_decoded_length(len, config)?;
+    }
+
+    let mut packed = BitReader::with_order(r, BitOrder::LsbFirst);
+    for i in 1..len {
+        if i % CHECK_ELEMENTS == 0 {
+            config.check_cancelled()?;
+        }
+        let delta = min.wrapping_add(packed.read_bits(width)? as i64);
+        value = value.wrapping_add(delta as u64) & mask;
+        f(value);
+    }
+    Ok(())
+}
+
+/// A sequence of integers written as the bit-packed deltas between consecutive values, for
+/// monotonic IDs, timestamps and other sequences whose values are close to the previous one.
+///
+/// Written as the configured length prefix, followed by the first value, if any, in a variable
+/// amount of bytes as by [VarIntDuration]. If there are more values, their deltas to the previous
+/// value follow: the smallest delta, zigzag encoded in a variable amount of bytes, then a byte
+/// holding the width `n` of the largest difference between a delta and the smallest, then each
+/// difference in `n` bits, least significant bit first, padded to a whole byte.
+///
+/// Sequences increasing at a steady rate take a few bits per value, and constant steps none.
+/// Deltas wrap around, so any sequence can be written, but values jumping back and forth take as
+/// many bits as their type. Equal deltas take no bits at all, so reading them is limited to the
+/// configured maximum length, or [DEFAULT_MAX_DECODED] values if none is set.
+///
+/// [IntSequenceFormat::Packed](crate::config::IntSequenceFormat::Packed) writes every sequence
+/// of [u32] and [u64] this way, without wrapping them.
+///
+/// ```
+/// use std::io::ErrorKind;
+///
+/// use tora::compact::PackedInts;
+/// use tora::testing::assert_decode_error;
+///
+/// // The length, 1000, then a delta of 1 in zero bits.
+/// let ids: Vec<u32> = (1000..1005).collect();
+/// tora::assert_bytes_eq!(PackedInts(ids), "05 00 00 00 e8 07 02 00");
+///
+/// // The length, 100, the smallest delta of 5, then the deltas 10, 15 and 5 minus 5 in 4 bits.
+/// let timestamps = PackedInts(vec![100u64, 110, 125, 130]);
+/// tora::assert_bytes_eq!(timestamps, "04 00 00 00 64 0a 04 a5 00");
+///
+/// tora::assert_roundtrip!(PackedInts(vec![u64::MAX, 0, 7, u64::MAX / 2]));
+///
+/// // Four billion zeros, in seven bytes.
+/// let bomb = [0xFF, 0xFF, 0xFF, 0xFF, 0, 0, 0];
+/// assert_decode_error::<PackedInts<u64>>(&bomb, ErrorKind::InvalidData);
+/// ```
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq)]
+pub struct PackedInts<T>(pub Vec<T>);
+
+impl<T> PackedInts<T> {
+    /// Returns the values.
+    pub fn into_inner(self) -> Vec<T> {
+        self.0
+    }
+}
+
+impl<T> From<Vec<T>> for PackedInts<T> {
+    fn from(values: Vec<T>) -> Self {
+        Self(values)
+    }
+}
+
+impl<T> From<PackedInts<T>> for Vec<T> {
+    fn from(packed: PackedInts<T>) -> Self {
+        packed.0
+    }
+}
+
+impl<T> Deref for PackedInts<T> {
+    type Target = Vec<T>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for PackedInts<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+macro_rules! packed_ints {
+    ($($t:ty),*) => {
+        $(
+        impl FromReader for PackedInts<$t> {
+            fn from_reader<R>(r: &mut R) -> io::Result<Self>
+            where
+                R: Read,
+            {
+                Self::from_reader_with(r, &ToraConfig::DEFAULT)
+            }
+
+            /// Returns [ErrorKind::InvalidData] if a value overflows the integer type, and
+            /// [ErrorKind::OutOfMemory] if the values cannot be allocated.
+            fn from_reader_with<R>(r: &mut R, config: &ToraConfig) -> io::Result<Self>
+            where
+                R: Read,
+            {
+                let len = config.read_length(r)?;
+                let mut values = Vec::new();
+                try_reserve(&mut values, preallocation::<$t>(len))?;
+
+                read_packed(r, len, <$t>::BITS, config, true, |value| {
+                    values.push(value as $t)
+                })?;
+                Ok(Self(values))
+            }
+        }
+
+        impl SerializeIo for PackedInts<$t> {
+            fn serialize<W>(&self, w: &mut W) -> io::Result<()>
+            where
+                W: Write,
+            {
+                self.serialize_with(w, &ToraConfig::DEFAULT)
+            }
+
+            fn serialize_with<W>(&self, w: &mut W, config: &ToraConfig) -> io::Result<()>
+            where
+                W: Write,
+            {
+                config.write_length(w, self.0.len())?;
+                write_packed(w, self.0.iter().map(|&value| value as u64), <$t>::BITS)
+            }
+        }
+        )*
+    };
+}
+
+packed_ints!(u32, u64);
@@ -0,0 +1,328 @@
+//! Runtime configuration of the wire format.
+//!
+//! A [ToraConfig] is passed through [reads_with](crate::read::ToraRead::reads_with) and
+//! [writes_with](crate::write::ToraWrite::writes_with), and is honored by the built-in
+//! implementations and the derive macros.
+//!
+//! ```
+//! use std::io;
+//! use std::io::Cursor;
+//!
+//! use tora::config::{Endian, LengthPrefix, StringFormat, ToraConfig};
+//! use tora::read::ToraRead;
+//! use tora::write::ToraWrite;
+//!
+//! fn main() -> io::Result<()> {
+//!     let config = ToraConfig::DEFAULT
+//!         .endian(Endian::Big)
+//!         .length_prefix(LengthPrefix::U8)
+//!         .string_format(StringFormat::LengthPrefixed);
+//!
+//!     let mut bytes = Vec::new();
+//!     bytes.writes_with(&(1u16, "Hi"), &config)?;
+//!
+//!     assert_eq!(bytes, [0, 1, 2, b'H', b'i']);
+//!
+//!     let (n, s): (u16, String) = Cursor::new(bytes).reads_with(&config)?;
+//!     assert_eq!((n, s.as_str()), (1, "Hi"));
+//!     Ok(())
+//! }
+//! ```
+
+use std::io;
+use std::io::{ErrorKind, Read, Write};
+use std::sync::Arc;
+
+use crate::cancel::Cancellation;
+use crate::codec::{Codec, Codecs};
+use crate::read::FromReader;
+use crate::write::SerializeIo;
+
+/// The byte order numbers are written in.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub enum Endian {
+    #[default]
+    Little,
+    Big,
+}
+
+/// The integer type used to prefix the length of dynamically sized values.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub enum LengthPrefix {
+    U8,
+    U16,
+    #[default]
+    U32,
+    U64,
+}
+
+/// How strings are delimited on the wire.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub enum StringFormat {
+    /// The UTF-8 bytes, followed by a NUL `0x00` byte.
+    #[default]
+    NulTerminated,
+    /// A length prefix holding the amount of bytes, followed by the UTF-8 bytes.
+    LengthPrefixed,
+}
+
+/// How floats are written and validated.
+///
+/// Floats may hold the same value in several encodings: NaN has many payloads, and negative zero
+/// equals positive zero. Canonicalizing them makes the serialized bytes of equal values identical,
+/// so they can be hashed and compared across platforms.
+///
+/// ```
+/// use std::io::Cursor;
+///
+/// use tora::config::{FloatFormat, ToraConfig};
+/// use tora::read::ToraRead;
+/// use tora::testing::to_bytes_with;
+///
+/// let config = ToraConfig::DEFAULT.float_format(FloatFormat::Canonical);
+/// assert_eq!(to_bytes_with(&-0.0f32, &config), to_bytes_with(&0.0f32, &config));
+/// assert_eq!(
+///     to_bytes_with(&f64::from_bits(0x7FF8_0000_0000_0001), &config),
+///     to_bytes_with(&f64::NAN, &config),
+/// );
+///
+/// let finite = ToraConfig::DEFAULT.float_format(FloatFormat::Finite);
+/// let infinity = to_bytes_with(&f32::INFINITY, &ToraConfig::DEFAULT);
+/// assert!(Cursor::new(infinity).reads_with::<f32>(&finite).is_err());
+/// ```
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub enum FloatFormat {
+    /// The bits of the float, as is.
+    #[default]
+    Raw,
+    /// NaN is written as the canonical quiet NaN, and negative zero as positive zero.
+    Canonical,
+    /// Like [Canonical](Self::Canonical), but infinities and NaN are rejected.
+    ///
+    /// Writing them returns [ErrorKind::InvalidInput], and reading them
+    /// [ErrorKind::InvalidData].
+    Finite,
+}
+
+/// How sequences of [u32] and [u64] are written.
+///
+/// Applies to [Vec]s, slices and arrays of these integers, which are read back the same way.
+/// [ConstSize](crate::layout::ConstSize) and [Reflect](crate::schema::Reflect) describe the plain
+/// format.
+///
+/// ```
+/// use tora::config::{IntSequenceFormat, ToraConfig};
+/// use tora::testing::to_bytes_with;
+///
+/// let config = ToraConfig::DEFAULT.int_sequence_format(IntSequenceFormat::Packed);
+/// let ids: Vec<u64> = (1000..2000).collect();
+///
+/// assert_eq!(to_bytes_with(&ids, &ToraConfig::DEFAULT).len(), 8004);
+/// assert_eq!(to_bytes_with(&ids, &config).len(), 8);
+/// ```
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub enum IntSequenceFormat {
+    /// Every integer, one after the other.
+    #[default]
+    Plain,
+    /// The deltas between consecutive integers, bit-packed, as by
+    /// [PackedInts](crate::compact::PackedInts). Takes precedence over the codecs registered for
+    /// the integers.
+    Packed,
+}
+
+/// Configuration of the wire format.
+///
+/// The default configuration matches the format written by [SerializeIo::serialize]. Other
+/// configurations are built from [ToraConfig::DEFAULT] with the methods setting each field, as
+/// the struct may gain fields in any release.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+#[non_exhaustive]
+pub struct ToraConfig {
+    /// The byte order of numbers.
+    pub endian: Endian,
+    /// The width of length prefixes on collections and length-prefixed strings.
+    pub length_prefix: LengthPrefix,
+    /// How strings are delimited.
+    pub string_format: StringFormat,
+    /// How floats are canonicalized and validated.
+    pub float_format: FloatFormat,
+    /// How sequences of [u32] and [u64] are written.
+    pub int_sequence_format: IntSequenceFormat,
+    /// The maximum amount of elements in a collection, or bytes in a string, accepted on read.
+    pub max_length: Option<usize>,
+    /// The codecs overriding the encoding of individual types.
+    pub codecs: Option<&'static Codecs>,
+    /// The flag checked periodically by long-running reads, which fail once it is cancelled. The
+    /// reader is left partway through a value, and must not be read from again.
+    pub cancellation: Option<Arc<Cancellation>>,
+}
+
+impl ToraConfig {
+    /// The default configuration, the format of [WireVersion::V1](crate::version::WireVersion::V1).
+    pub const DEFAULT: Self = Self {
+        endian: Endian::Little,
+        length_prefix: LengthPrefix::U32,
+        string_format: StringFormat::NulTerminated,
+        float_format: FloatFormat::Raw,
+        int_sequence_format: IntSequenceFormat::Plain,
+        max_length: None,
+        codecs: None,
+        cancellation: None,
+    };
+
+    /// Sets the byte order of numbers.
+    pub const fn endian(mut self, endian: Endian) -> Self {
+        self.endian = endian;
+        self
+    }
+
+    /// Sets the width of length prefixes.
+    pub const fn length_prefix(mut self, length_prefix: LengthPrefix) -> Self {
+        self.length_prefix = length_prefix;
+        self
+    }
+
+    /// Sets how strings are delimited.
+    pub const fn string_format(mut self, string_format: StringFormat) -> Self {
+        self.string_format = string_format;
+        self
+    }
+
+    /// Sets how floats are canonicalized and validated.
+    pub const fn float_format(mut self, float_format: FloatFormat) -> Self {
+        self.float_format = float_format;
+        self
+    }
+
+    /// Sets how sequences of [u32] and [u64] are written.
+    pub const fn int_sequence_format(mut self, int_sequence_format: IntSequenceFormat) -> Self {
+        self.int_sequence_format = int_sequence_format;
+        self
+    }
+
+    /// Rejects collections of more than `max` elements, and strings of more than `max` bytes, on
+    /// read.
+    pub const fn max_length(mut self, max: usize) -> Self {
+        self.max_length = Some(max);
+        self
+    }
+
+    /// Sets the codecs overriding the encoding of individual types.
+    pub const fn codecs(mut self, codecs: &'static Codecs) -> Self {
+        self.codecs = Some(codecs);
+        self
+    }
+
+    /// Sets the flag checked periodically by long-running reads.
+    pub fn cancellation(mut self, cancellation: Arc<Cancellation>) -> Self {
+        self.cancellation = Some(cancellation);
+        self
+    }
+
+    /// Returns the codec registered for [T], if any.
+    pub fn codec<T>(&self) -> Option<&'static dyn Codec<T>>
+    where
+        T: 'static,
+    {
+        self.codecs?.get::<T>()
+    }
+
+    /// Reads a [T] with its registered codec, or returns None if there is none.
+    pub fn read_codec<T, R>(&self, r: &mut R) -> Option<io::Result<T>>
+    where
+        T: 'static,
+        R: Read,
+    {
+        let codec = self.codec::<T>()?;
+        Some(codec.decode(r, &self.without_codecs()))
+    }
+
+    /// Writes a [T] with its registered codec, or returns None if there is none.
+    pub fn write_codec<T, W>(&self, value: &T, w: &mut W) -> Option<io::Result<()>>
+    where
+        T: 'static,
+        W: Write,
+    {
+        let codec = self.codec::<T>()?;
+        Some(codec.encode(value, w, &self.without_codecs()))
+    }
+
+    /// Returns this configuration without its codecs, as given to the codecs themselves.
+    fn without_codecs(&self) -> Self {
+        Self {
+            codecs: None,
+            ..self.clone()
+        }
+    }
+
+    /// Reads a length prefix, checking it against the configured maximum length.
+    ///
+    /// Returns [ErrorKind::InvalidData] if the length exceeds the maximum length.
+    pub fn read_length<R>(&self, r: &mut R) -> io::Result<usize>
+    where
+        R: Read,
+    {
+        let len = match self.length_prefix {
+            LengthPrefix::U8 => u8::from_reader_with(r, self)? as u64,
+            LengthPrefix::U16 => u16::from_reader_with(r, self)? as u64,
+            LengthPrefix::U32 => u32::from_reader_with(r, self)? as u64,
+            LengthPrefix::U64 => u64::from_reader_with(r, self)?,
+        };
+        let len = usize::try_from(len)
+            .map_err(|_| io::Error::new(ErrorKind::InvalidData, "Length exceeds usize::MAX"))?;
+
+        self.check_length(len)?;
+        Ok(len)
+    }
+
+    /// Writes a length prefix.
+    ///
+    /// Returns [ErrorKind::InvalidInput] if the length does not fit in the configured prefix.
+    pub fn write_length<W>(&self, w: &mut W, len: usize) -> io::Result<()>
+    where
+        W: Write,
+    {
+        fn narrow<T>(len: usize) -> io::Result<T>
+        where
+            T: TryFrom<usize>,
+        {
+            T::try_from(len).map_err(|_| {
+                io::Error::new(ErrorKind::InvalidInput, "Length exceeds the length prefix")
+            })
+        }
+
+        match self.length_prefix {
+            LengthPrefix::U8 => narrow::<u8>(len)?.serialize_with(w, self),
+            LengthPrefix::U16 => narrow::<u16>(len)?.serialize_with(w, self),
+            LengthPrefix::U32 => narrow::<u32>(len)?.serialize_with(w, self),
+            LengthPrefix::U64 => narrow::<u64>(len)?.serialize_with(w, self),
+        }
+    }
+
+    /// Returns [ErrorKind::InvalidData] if the length exceeds the configured maximum length.
+    pub fn check_length(&self, len: usize) -> io::Result<()> {
+        match self.max_length {
+            Some(max) if len > max => Err(io::Error::new(
+                ErrorKind::InvalidData,
+                "Length exceeds the configured maximum",
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns a [Cancelled](crate::cancel::Cancelled) error if the configured cancellation flag is
+    /// cancelled.
+    pub fn check_cancelled(&self) -> io::Result<()> {
+        match &self.cancellation {
+            Some(cancellation) => cancellation.check(),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Default for ToraConfig {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
@@ -0,0 +1,422 @@
+//! Digests verifying the integrity of streams and files.
+//!
+//! A [Digest] hashes the bytes fed to it. [DigestWriter] feeds it every byte written through it,
+//! then appends the digest, and [DigestReader] feeds it every byte read through it, then checks
+//! the digest that follows. The algorithm is chosen per stream or file by the digest passed to
+//! them, so the same code can trade integrity strength for speed:
+//!
+//! * [Crc32] detects accidental corruption, such as flipped bits and truncated writes, in 4 bytes.
+//! * [XxHash64] is faster on large inputs, and its 8 bytes make collisions far less likely.
+//! * `Blake3`, with the `blake3` feature, is a cryptographic hash whose collisions are
+//!   practically impossible, at the cost of speed and 32 bytes.
+//!
+//! ```
+//! use std::io;
+//! use std::io::ErrorKind;
+//!
+//! use tora::digest::{Crc32, DigestReader, DigestWriter};
+//! use tora::read::ToraRead;
+//! use tora::write::ToraWrite;
+//!
+//! fn main() -> io::Result<()> {
+//!     let mut writer = DigestWriter::new(Vec::new(), Crc32::new());
+//!     writer.writes(&(7u32, "Save game"))?;
+//!     let mut bytes = writer.finish()?;
+//!
+//!     let mut reader = DigestReader::new(bytes.as_slice(), Crc32::new());
+//!     let value: (u32, String) = reader.reads()?;
+//!     reader.verify()?;
+//!     assert_eq!(value, (7, "Save game".to_string()));
+//!
+//!     bytes[2] ^= 1;
+//!     let mut reader = DigestReader::new(bytes.as_slice(), Crc32::new());
+//!     reader.reads::<(u32, String)>()?;
+//!     assert_eq!(reader.verify().unwrap_err().kind(), ErrorKind::InvalidData);
+//!     Ok(())
+//! }
+//! ```
+
+use std::io;
+use std::io::{ErrorKind, Read, Write};
+
+/// A hash computed incrementally over a sequence of bytes.
+pub trait Digest {
+    /// The amount of bytes of the digest.
+    const SIZE: usize;
+
+    /// Feeds bytes to the digest.
+    fn update(&mut self, bytes: &[u8]);
+
+    /// Returns the digest of the bytes fed so far, in [Digest::SIZE] bytes.
+    fn finish(&self) -> Vec<u8>;
+}
+
+/// The lookup table of [Crc32], holding the remainder of every byte.
+const CRC32_TABLE: [u32; 256] = {
+    let mut table = [0; 256];
+    let mut i = 0;
+
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = match crc & 1 {
+                1 => (crc >> 1) ^ 0xEDB8_8320,
+                _ => crc >> 1,
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+/// The CRC-32 checksum used by Ethernet, gzip and PNG, written as a little endian [u32].
+///
+/// ```
+/// use tora::digest::{Crc32, Digest};
+///
+/// let mut crc = Crc32::new();
+/// crc.update(b"123456789");
+/// assert_eq!(crc.finish(), 0xCBF43926u32.to_le_bytes());
+/// ```
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct Crc32 {
+    state: u32,
+}
+
+impl Crc32 {
+    /// Constructs a Crc32 of no bytes.
+    pub const fn new() -> Self {
+        Self { state: !0 }
+    }
+
+    /// Returns the checksum of the bytes fed so far.
+    pub const fn value(&self) -> u32 {
+        !self.state
+    }
+}
+
+impl Default for Crc32 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Digest for Crc32 {
+    const SIZE: usize = 4;
+
+    fn update(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.state =
+                CRC32_TABLE[((self.state ^ byte as u32) & 0xFF) as usize] ^ (self.state >> 8);
+        }
+    }
+
+    fn finish(&self) -> Vec<u8> {
+        self.value().to_le_bytes().to_vec()
+    }
+}
+
+const PRIME64_1: u64 = 0x9E37_79B1_85EB_CA87;
+const PRIME64_2: u64 = 0xC2B2_AE3D_27D4_EB4F;
+const PRIME64_3: u64 = 0x1656_67B1_9E37_79F9;
+const PRIME64_4: u64 = 0x85EB_CA77_C2B2_AE63;
+const PRIME64_5: u64 = 0x27D4_EB2F_1656_67C5;
+
+/// Mixes 8 bytes of input into an accumulator of [XxHash64].
+const fn xxh64_round(acc: u64, input: u64) -> u64 {
+    acc.wrapping_add(input.wrapping_mul(PRIME64_2))
+        .rotate_left(31)
+        .wrapping_mul(PRIME64_1)
+}
+
+/// Merges an accumulator of [XxHash64] into the hash.
+const fn xxh64_merge(hash: u64, acc: u64) -> u64 {
+    (hash ^ xxh64_round(0, acc))
+        .wrapping_mul(PRIME64_1)
+        .wrapping_add(PRIME64_4)
+}
+
+/// Reads 8 little endian bytes from the start of the slice.
+fn read_u64(bytes: &[u8]) -> u64 {
+    u64::from_le_bytes(bytes[..8].try_into().unwrap())
+}
+
+/// The 64-bit xxHash, written as a little endian [u64].
+///
+/// ```
+/// use tora::digest::{Digest, XxHash64};
+///
+/// let mut hash = XxHash64::new();
+/// hash.update(b"abc");
+/// assert_eq!(hash.value(), 0x44BC2CF5AD770999);
+/// ```
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct XxHash64 {
+    seed: u64,
+    acc: [u64; 4],
+    /// The bytes not mixed into the accumulators yet.
+    buf: [u8; 32],
+    buf_len: usize,
+    /// The amount of bytes fed so far.
+    total: u64,
+}
+
+impl XxHash64 {
+    /// Constructs an XxHash64 of no bytes, with a seed of zero.
+    pub const fn new() -> Self {
+        Self::with_seed(0)
+    }
+
+    /// Constructs an XxHash64 of no bytes, with the given seed.
+    pub const fn with_seed(seed: u64) -> Self {
+        Self {
+            seed,
+            acc: [
+                seed.wrapping_add(PRIME64_1).wrapping_add(PRIME64_2),
+                seed.wrapping_add(PRIME64_2),
+                seed,
+                seed.wrapping_sub(PRIME64_1),
+            ],
+            buf: [0; 32],
+            buf_len: 0,
+            total: 0,
+        }
+    }
+
+    /// Mixes a 32 byte stripe into the accumulators.
+    fn stripe(&mut self, stripe: &[u8]) {
+        for (i, acc) in self.acc.iter_mut().enumerate() {
+            *acc = xxh64_round(*acc, read_u64(&stripe[i * 8..]));
+        }
+    }
+
+    /// Returns the hash of the bytes fed so far.
+    pub fn value(&self) -> u64 {
+        let mut hash = match self.total >= 32 {
+            true => {
+                let [a, b, c, d] = self.acc;
+                let hash = a
+                    .rotate_left(1)
+                    .wrapping_add(b.rotate_left(7))
+                    .wrapping_add(c.rotate_left(12))
+                    .wrapping_add(d.rotate_left(18));
+                self.acc
+                    .iter()
+                    .fold(hash, |hash, &acc| xxh64_merge(hash, acc))
+            }
+            false => self.seed.wrapping_add(PRIME64_5),
+        };
+        hash = hash.wrapping_add(self.total);
+
+        let mut rest = &self.buf[..self.buf_len];
+        while rest.len() >= 8 {
+            hash ^= xxh64_round(0, read_u64(rest));
+            hash = hash
+                .rotate_left(27)
+                .wrapping_mul(PRIME64_1)
+                .wrapping_add(PRIME64_4);
+            rest = &rest[8..];
+        }
+        if rest.len() >= 4 {
+            let word = u32::from_le_bytes(rest[..4].try_into().unwrap()) as u64;
+            hash ^= word.wrapping_mul(PRIME64_1);
+            hash = hash
+                .rotate_left(23)
+                .wrapping_mul(PRIME64_2)
+                .wrapping_add(PRIME64_3);
+            rest = &rest[4..];
+        }
+        for &byte in rest {
+            hash ^= (byte as u64).wrapping_mul(PRIME64_5);
+            hash = hash.rotate_left(11).wrapping_mul(PRIME64_1);
+        }
+
+        hash ^= hash >> 33;
+        hash = hash.wrapping_mul(PRIME64_2);
+        hash ^= hash >> 29;
+        hash = hash.wrapping_mul(PRIME64_3);
+        hash ^ (hash >> 32)
+    }
+}
+
+impl Default for XxHash64 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Digest for XxHash64 {
+    const SIZE: usize = 8;
+
+    fn update(&mut self, mut bytes: &[u8]) {
+        self.total += bytes.len() as u64;
+
+        if self.buf_len > 0 {
+            let n = bytes.len().min(32 - self.buf_len);
+            self.buf[self.buf_len..self.buf_len + n].copy_from_slice(&bytes[..n]);
+            self.buf_len += n;
+            bytes = &bytes[n..];
+
+            if self.buf_len < 32 {
+                return;
+            }
+            let buf = self.buf;
+            self.stripe(&buf);
+            self.buf_len = 0;
+        }
+
+        let mut stripes = bytes.chunks_exact(32);
+        for stripe in &mut stripes {
+            self.stripe(stripe);
+        }
+        let rest = stripes.remainder();
+        self.buf[..rest.len()].copy_from_slice(rest);
+        self.buf_len = rest.len();
+    }
+
+    fn finish(&self) -> Vec<u8> {
+        self.value().to_le_bytes().to_vec()
+    }
+}
+
+/// The BLAKE3 cryptographic hash, in 32 bytes.
+///
+/// Requires the `blake3` feature.
+#[cfg(feature = "blake3")]
+#[derive(Clone, Debug, Default)]
+pub struct Blake3 {
+    hasher: blake3::Hasher,
+}
+
+#[cfg(feature = "blake3")]
+impl Blake3 {
+    /// Constructs a Blake3 of no bytes.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[cfg(feature = "blake3")]
+impl Digest for Blake3 {
+    const SIZE: usize = 32;
+
+    fn update(&mut self, bytes: &[u8]) {
+        self.hasher.update(bytes);
+    }
+
+    fn finish(&self) -> Vec<u8> {
+        self.hasher.finalize().as_bytes().to_vec()
+    }
+}
+
+/// A writer feeding the bytes written through it to a digest, then appending the digest with
+/// [DigestWriter::finish].
+#[derive(Debug)]
+pub struct DigestWriter<W, D> {
+    inner: W,
+    digest: D,
+}
+
+impl<W, D> DigestWriter<W, D>
+where
+    W: Write,
+    D: Digest,
+{
+    /// Wraps the writer, feeding the given digest.
+    pub fn new(inner: W, digest: D) -> Self {
+        Self { inner, digest }
+    }
+
+    /// Returns the digest of the bytes written so far.
+    pub fn digest(&self) -> &D {
+        &self.digest
+    }
+
+    /// Returns a reference to the underlying writer.
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    /// Writes the digest, flushes the writer, then returns the underlying writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.inner.write_all(&self.digest.finish())?;
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+}
+
+impl<W, D> Write for DigestWriter<W, D>
+where
+    W: Write,
+    D: Digest,
+{
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.digest.update(&buf[..n]);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// A reader feeding the bytes read through it to a digest, then checking the digest following
+/// them with [DigestReader::verify].
+///
+/// Every byte read through the DigestReader is fed to the digest, so readers reading ahead, such
+/// as a [BufReader](std::io::BufReader), must be wrapped by it rather than wrap it.
+#[derive(Debug)]
+pub struct DigestReader<R, D> {
+    inner: R,
+    digest: D,
+}
+
+impl<R, D> DigestReader<R, D>
+where
+    R: Read,
+    D: Digest,
+{
+    /// Wraps the reader, feeding the given digest.
+    pub fn new(inner: R, digest: D) -> Self {
+        Self { inner, digest }
+    }
+
+    /// Returns the digest of the bytes read so far.
+    pub fn digest(&self) -> &D {
+        &self.digest
+    }
+
+    /// Returns a reference to the underlying reader.
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Reads the digest following the bytes read so far, then returns the underlying reader.
+    ///
+    /// Returns [ErrorKind::InvalidData] if it does not match the digest of the bytes read.
+    pub fn verify(mut self) -> io::Result<R> {
+        let mut expected = vec![0; D::SIZE];
+        self.inner.read_exact(&mut expected)?;
+
+        if expected != self.digest.finish() {
+            return Err(io::Error::new(ErrorKind::InvalidData, "Digest mismatch"));
+        }
+        Ok(self.inner)
+    }
+}
+
+impl<R, D> Read for DigestReader<R, D>
+where
+    R: Read,
+    D: Digest,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.digest.update(&buf[..n]);
+        Ok(n)
+    }
+}
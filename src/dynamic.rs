@@ -0,0 +1,562 @@
+//! Serialization of trait objects.
+//!
+//! [SerializeIo] is not object safe, as its methods are generic over the writer. [SerializeDyn]
+//! is its object safe companion, implemented for every [SerializeIo] type.
+//!
+//! Heterogeneous packets are held as `Box<dyn Packet>`, written after their [Tagged::TAG], and
+//! read back by looking the tag up in a [PacketRegistry]. Other trait objects and tag types are
+//! read through a [Registry].
+//!
+//! ```
+//! use std::io;
+//! use std::io::Cursor;
+//!
+//! use tora::dynamic::{Packet, PacketRegistry, Tagged};
+//! use tora::read::ToraRead;
+//! use tora::write::ToraWrite;
+//! use tora::{ReadStruct, WriteStruct};
+//!
+//! #[derive(Debug, PartialEq, ReadStruct, WriteStruct)]
+//! struct Chat {
+//!     message: String,
+//! }
+//!
+//! impl Tagged for Chat {
+//!     const TAG: u32 = 1;
+//! }
+//!
+//! #[derive(Debug, PartialEq, ReadStruct, WriteStruct)]
+//! struct Move {
+//!     x: i32,
+//! }
+//!
+//! impl Tagged for Move {
+//!     const TAG: u32 = 2;
+//! }
+//!
+//! fn main() -> io::Result<()> {
+//!     let mut registry = PacketRegistry::new();
+//!     registry.register::<Chat>().register::<Move>();
+//!
+//!     let packets: Vec<Box<dyn Packet>> = vec![
+//!         Box::new(Chat { message: "Hi".to_string() }),
+//!         Box::new(Move { x: 5 }),
+//!     ];
+//!
+//!     let mut bytes = Vec::new();
+//!     bytes.writes(&packets)?;
+//!
+//!     let read: Vec<Box<dyn Packet>> = Cursor::new(bytes).reads_seed(&mut registry)?;
+//!
+//!     assert_eq!(read[1].downcast_ref::<Move>(), Some(&Move { x: 5 }));
+//!     Ok(())
+//! }
+//! ```
+
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::Hash;
+use std::io;
+use std::io::{ErrorKind, Read, Write};
+use std::sync::Arc;
+
+use crate::config::ToraConfig;
+use crate::read::{FromReader, FromReaderSeed, ToraRead};
+use crate::write::{SerializeIo, ToraWrite};
+
+/// An object safe version of [SerializeIo].
+///
+/// Implemented for every type implementing [SerializeIo].
+pub trait SerializeDyn {
+    /// Serialize this value into the given writer, honoring the given configuration.
+    fn serialize_dyn(&self, w: &mut dyn Write, config: &ToraConfig) -> io::Result<()>;
+}
+
+impl<T> SerializeDyn for T
+where
+    T: SerializeIo,
+{
+    fn serialize_dyn(&self, mut w: &mut dyn Write, config: &ToraConfig) -> io::Result<()> {
+        self.serialize_with(&mut w, config)
+    }
+}
+
+impl SerializeIo for dyn SerializeDyn {
+    fn serialize<W>(&self, w: &mut W) -> io::Result<()>
+    where
+        W: Write,
+    {
+        self.serialize_dyn(w, &ToraConfig::DEFAULT)
+    }
+
+    fn serialize_with<W>(&self, w: &mut W, config: &ToraConfig) -> io::Result<()>
+    where
+        W: Write,
+    {
+        self.serialize_dyn(w, config)
+    }
+}
+
+/// A type identified on the wire by a unique tag.
+pub trait Tagged {
+    /// The tag written before values of this type.
+    const TAG: u32;
+}
+
+/// An object safe packet, written after its tag.
+///
+/// Implemented for every [Tagged] type implementing [SerializeIo].
+pub trait Packet: SerializeDyn + Any {
+    /// Returns the tag of this packet's type.
+    fn tag(&self) -> u32;
+}
+
+impl<T> Packet for T
+where
+    T: Tagged + SerializeIo + Any,
+{
+    fn tag(&self) -> u32 {
+        T::TAG
+    }
+}
+
+impl dyn Packet {
+    /// Returns true if this packet is of type [T].
+    pub fn is<T>(&self) -> bool
+    where
+        T: Packet,
+    {
+        (self as &dyn Any).is::<T>()
+    }
+
+    /// Returns a reference to this packet as [T], if it is of that type.
+    pub fn downcast_ref<T>(&self) -> Option<&T>
+    where
+        T: Packet,
+    {
+        (self as &dyn Any).downcast_ref()
+    }
+
+    /// Returns this packet as [T], or itself if it is of another type.
+    pub fn downcast<T>(self: Box<Self>) -> Result<Box<T>, Box<Self>>
+    where
+        T: Packet,
+    {
+        if self.is::<T>() {
+            Ok((self as Box<dyn Any>).downcast().unwrap())
+        } else {
+            Err(self)
+        }
+    }
+}
+
+impl fmt::Debug for dyn Packet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Packet")
+            .field("tag", &self.tag())
+            .finish_non_exhaustive()
+    }
+}
+
+/// Writes the packet's tag as a u32, followed by the packet.
+impl SerializeIo for dyn Packet {
+    fn serialize<W>(&self, w: &mut W) -> io::Result<()>
+    where
+        W: Write,
+    {
+        self.serialize_with(w, &ToraConfig::DEFAULT)
+    }
+
+    fn serialize_with<W>(&self, w: &mut W, config: &ToraConfig) -> io::Result<()>
+    where
+        W: Write,
+    {
+        w.writes_with(&self.tag(), config)?;
+        self.serialize_dyn(w, config)
+    }
+}
+
+type ReadFn<M> = Arc<dyn Fn(&mut dyn Read, &ToraConfig) -> io::Result<Box<M>> + Send + Sync>;
+
+/// Maps tags of type [K] to functions reading values as `Box<M>`, usually a trait object.
+///
+/// Types can be registered at runtime, such as by plugins, so the set of values read does not
+/// have to be known at compile time.
+///
+/// ```
+/// use std::io;
+/// use std::io::Cursor;
+///
+/// use tora::dynamic::{Registry, SerializeDyn};
+/// use tora::{ReadStruct, WriteStruct};
+///
+/// trait Message: SerializeDyn {
+///     fn describe(&self) -> String;
+/// }
+///
+/// #[derive(ReadStruct, WriteStruct)]
+/// struct Greet {
+///     name: String,
+/// }
+///
+/// impl Message for Greet {
+///     fn describe(&self) -> String {
+///         format!("Hello, {}", self.name)
+///     }
+/// }
+///
+/// fn main() -> io::Result<()> {
+///     let mut registry = Registry::<String, dyn Message>::new();
+///     registry.register_with("greet".to_string(), |g: Greet| Box::new(g));
+///
+///     let mut bytes = Vec::new();
+///     registry.write(&mut bytes, &"greet".to_string(), &Greet { name: "John".to_string() })?;
+///
+///     let message = registry.read(&mut Cursor::new(bytes))?;
+///     assert_eq!(message.describe(), "Hello, John");
+///     Ok(())
+/// }
+/// ```
+pub struct Registry<K, M>
+where
+    M: ?Sized,
+{
+    readers: HashMap<K, ReadFn<M>>,
+    config: ToraConfig,
+}
+
+impl<K, M> Registry<K, M>
+where
+    K: Eq + Hash,
+    M: ?Sized,
+{
+    /// Constructs an empty Registry, reading and writing using the default configuration.
+    pub fn new() -> Self {
+        Self::with_config(ToraConfig::DEFAULT)
+    }
+
+    /// Constructs an empty Registry, reading and writing using the given configuration.
+    pub fn with_config(config: ToraConfig) -> Self {
+        Self {
+            readers: HashMap::new(),
+            config,
+        }
+    }
+
+    /// Returns the configuration values are read and written with.
+    pub const fn config(&self) -> &ToraConfig {
+        &self.config
+    }
+
+    /// Registers a function reading the value identified by the given tag.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a function was already registered under the same tag.
+    pub fn register_fn<F>(&mut self, tag: K, f: F) -> &mut Self
+    where
+        F: Fn(&mut dyn Read, &ToraConfig) -> io::Result<Box<M>> + Send + Sync + 'static,
+    {
+        if self.readers.insert(tag, Arc::new(f)).is_some() {
+            panic!("A tag was registered twice");
+        }
+        self
+    }
+
+    /// Registers the type [T] under the given tag, converting values read into `Box<M>` with
+    /// `into`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a function was already registered under the same tag.
+    pub fn register_with<T>(&mut self, tag: K, into: fn(T) -> Box<M>) -> &mut Self
+    where
+        T: FromReader + 'static,
+        M: 'static,
+    {
+        self.register_fn(tag, move |mut r, config| {
+            Ok(into(T::from_reader_with(&mut r, config)?))
+        })
+    }
+
+    /// Removes the function registered under the given tag, returning true if there was one.
+    pub fn unregister(&mut self, tag: &K) -> bool {
+        self.readers.remove(tag).is_some()
+    }
+
+    /// Returns true if a function is registered under the given tag.
+    pub fn contains(&self, tag: &K) -> bool {
+        self.readers.contains_key(tag)
+    }
+
+    /// Reads the value identified by the given tag.
+    ///
+    /// Returns [ErrorKind::InvalidData] if no function is registered under the tag.
+    pub fn read_tagged<R>(&self, mut r: &mut R, tag: &K) -> io::Result<Box<M>>
+    where
+        R: Read,
+    {
+        let read = self
+            .readers
+            .get(tag)
+            .ok_or_else(|| io::Error::new(ErrorKind::InvalidData, "Unknown tag"))?;
+        read(&mut r, &self.config)
+    }
+
+    /// Reads a tag, followed by the value identified by it.
+    ///
+    /// Returns [ErrorKind::InvalidData] if no function is registered under the tag.
+    pub fn read<R>(&self, r: &mut R) -> io::Result<Box<M>>
+    where
+        R: Read,
+        K: FromReader,
+    {
+        let tag = r.reads_with(&self.config)?;
+        self.read_tagged(r, &tag)
+    }
+
+    /// Writes the given tag, followed by the value.
+    pub fn write<W>(&self, w: &mut W, tag: &K, value: &dyn SerializeDyn) -> io::Result<()>
+    where
+        W: Write,
+        K: SerializeIo,
+    {
+        w.writes_with(tag, &self.config)?;
+        value.serialize_dyn(w, &self.config)
+    }
+}
+
+impl<K, M> Clone for Registry<K, M>
+where
+    K: Clone,
+    M: ?Sized,
+{
+    fn clone(&self) -> Self {
+        Self {
+            readers: self.readers.clone(),
+            config: self.config.clone(),
+        }
+    }
+}
+
+impl<K, M> Default for Registry<K, M>
+where
+    K: Eq + Hash,
+    M: ?Sized,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Maps packet tags to the types they identify.
+///
+/// Used as a seed to read `Box<dyn Packet>` values.
+pub type PacketRegistry = Registry<u32, dyn Packet>;
+
+impl Registry<u32, dyn Packet> {
+    /// Registers the packet type [T] under its tag.
+    ///
+    /// # Panics
+    ///
+    /// Panics if another type was already registered under the same tag.
+    pub fn register<T>(&mut self) -> &mut Self
+    where
+        T: Packet + Tagged + FromReader,
+    {
+        self.register_with(T::TAG, |packet: T| Box::new(packet))
+    }
+
+    /// Registers every packet type submitted with [register_packet](crate::register_packet).
+    ///
+    /// # Panics
+    ///
+    /// Panics if two types were registered under the same tag.
+    #[cfg(feature = "inventory")]
+    pub fn register_submitted(&mut self) -> &mut Self {
+        for submission in inventory::iter::<PacketSubmission> {
+            (submission.register)(self);
+        }
+        self
+    }
+}
+
+/// A packet type submitted with [register_packet](crate::register_packet).
+#[cfg(feature = "inventory")]
+pub struct PacketSubmission {
+    register: fn(&mut PacketRegistry),
+}
+
+#[cfg(feature = "inventory")]
+impl PacketSubmission {
+    #[doc(hidden)]
+    pub const fn new<T>() -> Self
+    where
+        T: Packet + Tagged + FromReader,
+    {
+        fn register<T>(registry: &mut PacketRegistry)
+        where
+            T: Packet + Tagged + FromReader,
+        {
+            registry.register::<T>();
+        }
+
+        Self {
+            register: register::<T>,
+        }
+    }
+}
+
+#[cfg(feature = "inventory")]
+inventory::collect!(PacketSubmission);
+
+/// Submits packet types at link time, to be registered by
+/// [PacketRegistry::register_submitted](Registry::register_submitted).
+///
+/// Can be invoked from any module or crate linked into the program, so adding a packet type
+/// does not require changing a central registration function.
+///
+/// ```
+/// use tora::dynamic::{PacketRegistry, Tagged};
+/// use tora::{register_packet, ReadStruct, WriteStruct};
+///
+/// #[derive(ReadStruct, WriteStruct)]
+/// struct Ping(u64);
+///
+/// impl Tagged for Ping {
+///     const TAG: u32 = 7;
+/// }
+///
+/// register_packet!(Ping);
+///
+/// let mut registry = PacketRegistry::new();
+/// registry.register_submitted();
+///
+/// assert!(registry.contains(&7));
+/// ```
+#[cfg(feature = "inventory")]
+#[macro_export]
+macro_rules! register_packet {
+    ($($packet:ty),+ $(,)?) => {
+        $(
+            $crate::__private::inventory::submit! {
+                $crate::dynamic::PacketSubmission::new::<$packet>()
+            }
+        )+
+    };
+}
+
+/// Reads a u32 tag, followed by the packet registered under it.
+impl FromReaderSeed<PacketRegistry> for Box<dyn Packet> {
+    fn from_reader_seed<R>(r: &mut R, seed: &mut PacketRegistry) -> io::Result<Self>
+    where
+        R: Read,
+    {
+        seed.read(r)
+    }
+}
+
+type Handler<C, R> = Box<dyn FnMut(Box<dyn Packet>, &mut C) -> R>;
+
+/// Dispatches packets to handlers registered for their concrete type.
+///
+/// Handlers receive the downcast packet and a context of type [C], such as the connection the
+/// packet was received from, and return an [R].
+///
+/// ```
+/// use tora::dynamic::{HandlerMap, Packet, Tagged};
+/// use tora::WriteStruct;
+///
+/// #[derive(WriteStruct)]
+/// struct Chat(String);
+///
+/// impl Tagged for Chat {
+///     const TAG: u32 = 1;
+/// }
+///
+/// #[derive(WriteStruct)]
+/// struct Ping(u8);
+///
+/// impl Tagged for Ping {
+///     const TAG: u32 = 2;
+/// }
+///
+/// let mut handlers = HandlerMap::<Vec<String>>::new();
+/// handlers
+///     .on(|chat: Chat, log: &mut Vec<String>| log.push(chat.0))
+///     .fallback(|packet, log| log.push(format!("Unhandled packet {}", packet.tag())));
+///
+/// let mut log = Vec::new();
+/// handlers.dispatch(Box::new(Chat("Hello".to_string())), &mut log).unwrap();
+/// handlers.dispatch(Box::new(Ping(0)), &mut log).unwrap();
+///
+/// assert_eq!(log, ["Hello", "Unhandled packet 2"]);
+/// ```
+pub struct HandlerMap<C, R = ()> {
+    handlers: HashMap<TypeId, Handler<C, R>>,
+    fallback: Option<Handler<C, R>>,
+}
+
+impl<C, R> HandlerMap<C, R> {
+    /// Constructs a HandlerMap without handlers.
+    pub fn new() -> Self {
+        Self {
+            handlers: HashMap::new(),
+            fallback: None,
+        }
+    }
+
+    /// Registers the handler of packets of type [T], replacing any previous handler.
+    pub fn on<T, F>(&mut self, mut handler: F) -> &mut Self
+    where
+        T: Packet,
+        F: FnMut(T, &mut C) -> R + 'static,
+    {
+        let handler = move |packet: Box<dyn Packet>, context: &mut C| match packet.downcast::<T>() {
+            Ok(packet) => handler(*packet, context),
+            Err(_) => unreachable!("Packet dispatched to the handler of another type"),
+        };
+        self.handlers.insert(TypeId::of::<T>(), Box::new(handler));
+        self
+    }
+
+    /// Registers the handler of packets without a handler of their own.
+    pub fn fallback<F>(&mut self, handler: F) -> &mut Self
+    where
+        F: FnMut(Box<dyn Packet>, &mut C) -> R + 'static,
+    {
+        self.fallback = Some(Box::new(handler));
+        self
+    }
+
+    /// Returns true if a handler is registered for packets of type [T].
+    pub fn handles<T>(&self) -> bool
+    where
+        T: Packet,
+    {
+        self.handlers.contains_key(&TypeId::of::<T>())
+    }
+
+    /// Passes the packet to the handler of its type, or the fallback handler.
+    ///
+    /// Returns the packet back if neither exist.
+    pub fn dispatch(
+        &mut self,
+        packet: Box<dyn Packet>,
+        context: &mut C,
+    ) -> Result<R, Box<dyn Packet>> {
+        let type_id = (&*packet as &dyn Any).type_id();
+
+        match self.handlers.get_mut(&type_id).or(self.fallback.as_mut()) {
+            Some(handler) => Ok(handler(packet, context)),
+            None => Err(packet),
+        }
+    }
+}
+
+impl<C, R> Default for HandlerMap<C, R> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
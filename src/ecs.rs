@@ -0,0 +1,152 @@
+//! Snapshots of ECS-style columnar data.
+//!
+//! An entity component system stores each component type in a column: a slice of components,
+//! each belonging to the entity at the same index. A [Column] writes such a slice directly, using
+//! the bulk paths of [SerializeIo::serialize_slice] and [FromReader::from_reader_vec], without
+//! first collecting the components into a `Vec` of structs.
+//!
+//! With the `bevy` feature, the `bevy` module serializes `bevy_reflect` types.
+//!
+//! ```
+//! use std::io;
+//! use std::io::Cursor;
+//!
+//! use tora::ecs::{Column, ColumnBuf};
+//! use tora::read::ToraRead;
+//! use tora::write::ToraWrite;
+//!
+//! fn main() -> io::Result<()> {
+//!     let entities = [3u64, 8, 9];
+//!     let health = [100u8, 75, 30];
+//!
+//!     let mut bytes = Vec::new();
+//!     bytes.writes(&Column::new(&entities, &health))?;
+//!
+//!     let column: ColumnBuf<u64, u8> = Cursor::new(bytes).reads()?;
+//!     assert_eq!(column.entities, entities);
+//!     assert_eq!(column.components, health);
+//!     Ok(())
+//! }
+//! ```
+
+use std::io;
+use std::io::{Read, Write};
+
+use crate::config::ToraConfig;
+use crate::read::FromReader;
+use crate::write::SerializeIo;
+
+#[cfg(feature = "bevy")]
+pub mod bevy;
+
+/// A borrowed column of components, keyed by the entity at the same index.
+///
+/// Written as the configured length prefix, then every entity, then every component. Read back as
+/// a [ColumnBuf].
+#[derive(Clone, Copy, Debug)]
+pub struct Column<'a, E, C> {
+    entities: &'a [E],
+    components: &'a [C],
+}
+
+impl<'a, E, C> Column<'a, E, C> {
+    /// Constructs a Column of the components of the given entities.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the slices have different lengths.
+    pub fn new(entities: &'a [E], components: &'a [C]) -> Self {
+        assert_eq!(
+            entities.len(),
+            components.len(),
+            "Every component must belong to exactly one entity"
+        );
+        Self {
+            entities,
+            components,
+        }
+    }
+
+    /// Returns the entities of this column.
+    pub const fn entities(&self) -> &'a [E] {
+        self.entities
+    }
+
+    /// Returns the components of this column, in the order of their entities.
+    pub const fn components(&self) -> &'a [C] {
+        self.components
+    }
+}
+
+impl<E, C> SerializeIo for Column<'_, E, C>
+where
+    E: SerializeIo,
+    C: SerializeIo,
+{
+    fn serialize<W>(&self, w: &mut W) -> io::Result<()>
+    where
+        W: Write,
+    {
+        self.serialize_with(w, &ToraConfig::DEFAULT)
+    }
+
+    fn serialize_with<W>(&self, w: &mut W, config: &ToraConfig) -> io::Result<()>
+    where
+        W: Write,
+    {
+        config.write_length(w, self.entities.len())?;
+        E::serialize_slice(self.entities, w, config)?;
+        C::serialize_slice(self.components, w, config)
+    }
+}
+
+/// An owned column of components, as read from a [Column].
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq)]
+pub struct ColumnBuf<E, C> {
+    pub entities: Vec<E>,
+    /// The components, each belonging to the entity at the same index.
+    pub components: Vec<C>,
+}
+
+impl<E, C> ColumnBuf<E, C> {
+    /// Borrows this column as a [Column].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the entities and components have different lengths.
+    pub fn as_column(&self) -> Column<'_, E, C> {
+        Column::new(&self.entities, &self.components)
+    }
+
+    /// Returns an iterator over the entities and their components.
+    pub fn iter(&self) -> impl Iterator<Item = (&E, &C)> {
+        self.entities.iter().zip(&self.components)
+    }
+}
+
+impl<E, C> FromReader for ColumnBuf<E, C>
+where
+    E: FromReader,
+    C: FromReader,
+{
+    fn from_reader<R>(r: &mut R) -> io::Result<Self>
+    where
+        R: Read,
+    {
+        Self::from_reader_with(r, &ToraConfig::DEFAULT)
+    }
+
+    /// Returns [ErrorKind::InvalidData](io::ErrorKind::InvalidData) if the length exceeds the
+    /// configured maximum length.
+    fn from_reader_with<R>(r: &mut R, config: &ToraConfig) -> io::Result<Self>
+    where
+        R: Read,
+    {
+        let len = config.read_length(r)?;
+
+        Ok(Self {
+            entities: E::from_reader_vec(r, len, config)?,
+            components: C::from_reader_vec(r, len, config)?,
+        })
+    }
+}
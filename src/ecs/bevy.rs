@@ -0,0 +1,318 @@
+//! Serialization of `bevy_reflect` types.
+//!
+//! Reflected values are written in the same format as the equivalent type deriving `WriteStruct`
+//! or `WriteEnum` with the default variant IDs, so a reflected component can be read back as a
+//! derived packet and the other way around. Enum variants are written as their index, in a [u8].
+//! Opaque values are supported for the primitives, [String] and [Duration]. Sets can be written,
+//! but not read, as their type information does not describe their values.
+//!
+//! ```
+//! use std::io;
+//! use std::io::Cursor;
+//!
+//! use bevy_reflect::Reflect;
+//! use tora::ecs::bevy::{from_reader_reflect, Reflected};
+//! use tora::write::ToraWrite;
+//! use tora::WriteStruct;
+//!
+//! #[derive(Debug, PartialEq, Reflect, WriteStruct)]
+//! struct Health {
+//!     current: u16,
+//!     shield: Option<u16>,
+//!     effects: Vec<String>,
+//! }
+//!
+//! fn main() -> io::Result<()> {
+//!     let health = Health { current: 75, shield: Some(20), effects: vec!["Poison".to_string()] };
+//!
+//!     let mut bytes = Vec::new();
+//!     bytes.writes(&Reflected(&health))?;
+//!     assert_eq!(bytes, tora::testing::to_bytes(&health));
+//!
+//!     let read: Health = from_reader_reflect(&mut Cursor::new(bytes))?;
+//!     assert_eq!(read, health);
+//!     Ok(())
+//! }
+//! ```
+
+use std::io;
+use std::io::{ErrorKind, Read, Write};
+use std::time::Duration;
+
+use bevy_reflect::{
+    DynamicArray, DynamicEnum, DynamicList, DynamicMap, DynamicStruct, DynamicTuple,
+    DynamicTupleStruct, DynamicVariant, FromReflect, Map, PartialReflect, ReflectRef, TypeInfo,
+    Typed, VariantInfo,
+};
+
+use crate::config::ToraConfig;
+use crate::read::ToraRead;
+use crate::write::{SerializeIo, ToraWrite};
+
+/// Serializes a reflected value.
+///
+/// Returns [ErrorKind::InvalidInput] if the value holds an unsupported opaque type, or an enum
+/// with more than 256 variants.
+#[derive(Clone, Copy)]
+pub struct Reflected<'a>(pub &'a dyn PartialReflect);
+
+impl SerializeIo for Reflected<'_> {
+    fn serialize<W>(&self, w: &mut W) -> io::Result<()>
+    where
+        W: Write,
+    {
+        self.serialize_with(w, &ToraConfig::DEFAULT)
+    }
+
+    fn serialize_with<W>(&self, w: &mut W, config: &ToraConfig) -> io::Result<()>
+    where
+        W: Write,
+    {
+        write_reflect(w, self.0, config)
+    }
+}
+
+/// Reads a value of type [T] through its reflected type information.
+pub fn from_reader_reflect<T, R>(r: &mut R) -> io::Result<T>
+where
+    T: FromReflect + Typed,
+    R: Read,
+{
+    from_reader_reflect_with(r, &ToraConfig::DEFAULT)
+}
+
+/// Reads a value of type [T] through its reflected type information, honoring the given
+/// configuration.
+pub fn from_reader_reflect_with<T, R>(r: &mut R, config: &ToraConfig) -> io::Result<T>
+where
+    T: FromReflect + Typed,
+    R: Read,
+{
+    let value = read_dynamic(r, T::type_info(), config)?;
+
+    T::from_reflect(&*value).ok_or_else(|| {
+        io::Error::new(
+            ErrorKind::InvalidData,
+            format!("Could not construct {} from its fields", T::type_path()),
+        )
+    })
+}
+
+/// Reads a value described by the type information as a dynamic value.
+///
+/// The value can be turned into a concrete type with [FromReflect], or applied to an existing
+/// value, such as a component of an entity.
+pub fn read_dynamic<R>(
+    r: &mut R,
+    info: &'static TypeInfo,
+    config: &ToraConfig,
+) -> io::Result<Box<dyn PartialReflect>>
+where
+    R: Read,
+{
+    /// Boxes a dynamic value, marking it as representing the read type.
+    macro_rules! represented {
+        ($dynamic:expr) => {{
+            let mut dynamic = $dynamic;
+            dynamic.set_represented_type(Some(info));
+            Ok(Box::new(dynamic))
+        }};
+    }
+
+    match info {
+        TypeInfo::Struct(s) => {
+            let mut dynamic = DynamicStruct::default();
+
+            for field in s.iter() {
+                dynamic.insert_boxed(field.name(), read_field(r, field.type_info(), config)?);
+            }
+            represented!(dynamic)
+        }
+        TypeInfo::TupleStruct(s) => {
+            let mut dynamic = DynamicTupleStruct::default();
+
+            for field in s.iter() {
+                dynamic.insert_boxed(read_field(r, field.type_info(), config)?);
+            }
+            represented!(dynamic)
+        }
+        TypeInfo::Tuple(t) => {
+            let mut dynamic = DynamicTuple::default();
+
+            for field in t.iter() {
+                dynamic.insert_boxed(read_field(r, field.type_info(), config)?);
+            }
+            represented!(dynamic)
+        }
+        TypeInfo::List(list) => {
+            let len = config.read_length(r)?;
+            let mut dynamic = DynamicList::default();
+
+            for _ in 0..len {
+                dynamic.push_box(read_field(r, list.item_info(), config)?);
+            }
+            represented!(dynamic)
+        }
+        TypeInfo::Array(array) => {
+            let items = (0..array.capacity())
+                .map(|_| read_field(r, array.item_info(), config))
+                .collect::<io::Result<Vec<_>>>()?;
+
+            represented!(DynamicArray::new(items.into_boxed_slice()))
+        }
+        TypeInfo::Map(map) => {
+            let len = config.read_length(r)?;
+            let mut dynamic = DynamicMap::default();
+
+            for _ in 0..len {
+                let key = read_field(r, map.key_info(), config)?;
+                dynamic.insert_boxed(key, read_field(r, map.value_info(), config)?);
+            }
+            represented!(dynamic)
+        }
+        TypeInfo::Enum(e) => {
+            let index = r.reads_with::<u8>(config)? as usize;
+            let variant = e
+                .variant_at(index)
+                .ok_or_else(|| io::Error::new(ErrorKind::InvalidData, "Unknown variant"))?;
+
+            let fields = match variant {
+                VariantInfo::Unit(_) => DynamicVariant::Unit,
+                VariantInfo::Tuple(variant) => {
+                    let mut fields = DynamicTuple::default();
+
+                    for field in variant.iter() {
+                        fields.insert_boxed(read_field(r, field.type_info(), config)?);
+                    }
+                    DynamicVariant::Tuple(fields)
+                }
+                VariantInfo::Struct(variant) => {
+                    let mut fields = DynamicStruct::default();
+
+                    for field in variant.iter() {
+                        let value = read_field(r, field.type_info(), config)?;
+                        fields.insert_boxed(field.name(), value);
+                    }
+                    DynamicVariant::Struct(fields)
+                }
+            };
+            represented!(DynamicEnum::new_with_index(index, variant.name(), fields))
+        }
+        TypeInfo::Opaque(_) => read_opaque(r, info, config),
+        TypeInfo::Set(_) => Err(io::Error::new(
+            ErrorKind::InvalidInput,
+            "Sets cannot be read, as their values have no type information",
+        )),
+    }
+}
+
+/// Reads a nested value, whose type information is missing for generic types without [Typed].
+fn read_field<R>(
+    r: &mut R,
+    info: Option<&'static TypeInfo>,
+    config: &ToraConfig,
+) -> io::Result<Box<dyn PartialReflect>>
+where
+    R: Read,
+{
+    let info = info.ok_or_else(|| {
+        io::Error::new(ErrorKind::InvalidInput, "A field has no type information")
+    })?;
+    read_dynamic(r, info, config)
+}
+
+macro_rules! opaque_types {
+    ($($t:ty),*) => {
+        fn read_opaque<R>(
+            r: &mut R,
+            info: &'static TypeInfo,
+            config: &ToraConfig,
+        ) -> io::Result<Box<dyn PartialReflect>>
+        where
+            R: Read,
+        {
+            $(
+            if info.is::<$t>() {
+                return Ok(Box::new(r.reads_with::<$t>(config)?));
+            }
+            )*
+            Err(unsupported(info.type_path()))
+        }
+
+        fn write_opaque<W>(
+            w: &mut W,
+            value: &dyn PartialReflect,
+            config: &ToraConfig,
+        ) -> io::Result<()>
+        where
+            W: Write,
+        {
+            $(
+            if let Some(value) = value.try_downcast_ref::<$t>() {
+                return w.writes_with(value, config);
+            }
+            )*
+            Err(unsupported(value.reflect_type_path()))
+        }
+    };
+}
+
+opaque_types!(
+    bool, u8, u16, u32, u64, u128, i8, i16, i32, i64, i128, f32, f64, usize, char, String, Duration
+);
+
+fn unsupported(type_path: &str) -> io::Error {
+    io::Error::new(
+        ErrorKind::InvalidInput,
+        format!("Unsupported opaque type {type_path}"),
+    )
+}
+
+fn write_reflect<W>(w: &mut W, value: &dyn PartialReflect, config: &ToraConfig) -> io::Result<()>
+where
+    W: Write,
+{
+    match value.reflect_ref() {
+        ReflectRef::Struct(s) => s
+            .iter_fields()
+            .try_for_each(|field| write_reflect(w, field, config)),
+        ReflectRef::TupleStruct(s) => s
+            .iter_fields()
+            .try_for_each(|field| write_reflect(w, field, config)),
+        ReflectRef::Tuple(t) => t
+            .iter_fields()
+            .try_for_each(|field| write_reflect(w, field, config)),
+        ReflectRef::List(list) => {
+            config.write_length(w, list.len())?;
+            list.iter()
+                .try_for_each(|item| write_reflect(w, item, config))
+        }
+        ReflectRef::Array(array) => array
+            .iter()
+            .try_for_each(|item| write_reflect(w, item, config)),
+        ReflectRef::Map(map) => {
+            config.write_length(w, map.len())?;
+            map.iter().try_for_each(|(key, value)| {
+                write_reflect(w, key, config)?;
+                write_reflect(w, value, config)
+            })
+        }
+        ReflectRef::Set(set) => {
+            config.write_length(w, set.len())?;
+            set.iter()
+                .try_for_each(|value| write_reflect(w, value, config))
+        }
+        ReflectRef::Enum(e) => {
+            let index = u8::try_from(e.variant_index()).map_err(|_| {
+                io::Error::new(ErrorKind::InvalidInput, "Variant index exceeds u8::MAX")
+            })?;
+
+            w.writes_with(&index, config)?;
+            e.iter_fields()
+                .try_for_each(|field| write_reflect(w, field.value(), config))
+        }
+        ReflectRef::Opaque(value) => write_opaque(w, value, config),
+        #[allow(unreachable_patterns)]
+        _ => Err(unsupported(value.reflect_type_path())),
+    }
+}
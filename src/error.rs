@@ -0,0 +1,154 @@
+//! Errors that can be sent over the wire.
+
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::io::{ErrorKind, Read, Write};
+
+use crate::config::ToraConfig;
+use crate::read::{FromReader, ToraRead};
+use crate::schema::{FieldSchema, Reflect, Schema, StructSchema};
+use crate::write::{SerializeIo, ToraWrite};
+
+/// The error kinds with a stable code, in code order. Any other kind is sent as [ErrorKind::Other].
+const KINDS: [ErrorKind; 20] = [
+    ErrorKind::Other,
+    ErrorKind::NotFound,
+    ErrorKind::PermissionDenied,
+    ErrorKind::ConnectionRefused,
+    ErrorKind::ConnectionReset,
+    ErrorKind::ConnectionAborted,
+    ErrorKind::NotConnected,
+    ErrorKind::AddrInUse,
+    ErrorKind::AddrNotAvailable,
+    ErrorKind::BrokenPipe,
+    ErrorKind::AlreadyExists,
+    ErrorKind::WouldBlock,
+    ErrorKind::InvalidInput,
+    ErrorKind::InvalidData,
+    ErrorKind::TimedOut,
+    ErrorKind::WriteZero,
+    ErrorKind::Interrupted,
+    ErrorKind::Unsupported,
+    ErrorKind::UnexpectedEof,
+    ErrorKind::OutOfMemory,
+];
+
+/// An error that can be serialized, so fallible responses can be sent as `Result<T, WireError>`.
+///
+/// Converts from and to [io::Error], keeping its kind and message. The kind is sent as a stable
+/// code, so peers built against different Rust versions agree on it. Kinds without a code, and
+/// unknown codes, become [ErrorKind::Other].
+///
+/// ```
+/// use std::io;
+/// use std::io::ErrorKind;
+///
+/// use tora::WireError;
+///
+/// fn lookup(name: &str) -> io::Result<u32> {
+///     Err(io::Error::new(ErrorKind::NotFound, format!("No user named {name}")))
+/// }
+///
+/// let response: Result<u32, WireError> = lookup("John").map_err(WireError::from);
+/// let received: Result<u32, WireError> = tora::testing::roundtrip(&response);
+///
+/// let e = io::Error::from(received.unwrap_err());
+/// assert_eq!(e.kind(), ErrorKind::NotFound);
+/// assert_eq!(e.to_string(), "No user named John");
+/// ```
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct WireError {
+    /// The code of the error kind.
+    pub kind: u8,
+    pub message: String,
+}
+
+impl WireError {
+    /// Constructs a WireError of the given kind.
+    pub fn new<S>(kind: ErrorKind, message: S) -> Self
+    where
+        S: Into<String>,
+    {
+        Self {
+            kind: KINDS.iter().position(|&k| k == kind).unwrap_or(0) as u8,
+            message: message.into(),
+        }
+    }
+
+    /// Returns the kind of this error.
+    pub fn error_kind(&self) -> ErrorKind {
+        KINDS
+            .get(self.kind as usize)
+            .copied()
+            .unwrap_or(ErrorKind::Other)
+    }
+}
+
+impl fmt::Display for WireError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl Error for WireError {}
+
+impl From<io::Error> for WireError {
+    fn from(e: io::Error) -> Self {
+        Self::new(e.kind(), e.to_string())
+    }
+}
+
+impl From<WireError> for io::Error {
+    fn from(e: WireError) -> Self {
+        io::Error::new(e.error_kind(), e.message)
+    }
+}
+
+impl FromReader for WireError {
+    fn from_reader<R>(r: &mut R) -> io::Result<Self>
+    where
+        R: Read,
+    {
+        Self::from_reader_with(r, &ToraConfig::DEFAULT)
+    }
+
+    fn from_reader_with<R>(r: &mut R, config: &ToraConfig) -> io::Result<Self>
+    where
+        R: Read,
+    {
+        Ok(Self {
+            kind: r.reads_with(config)?,
+            message: r.reads_with(config)?,
+        })
+    }
+}
+
+impl SerializeIo for WireError {
+    fn serialize<W>(&self, w: &mut W) -> io::Result<()>
+    where
+        W: Write,
+    {
+        self.serialize_with(w, &ToraConfig::DEFAULT)
+    }
+
+    fn serialize_with<W>(&self, w: &mut W, config: &ToraConfig) -> io::Result<()>
+    where
+        W: Write,
+    {
+        w.writes_with(&self.kind, config)?;
+        w.writes_with(&self.message, config)
+    }
+}
+
+impl Reflect for WireError {
+    fn schema() -> Schema {
+        Schema::Struct(StructSchema::new(
+            "WireError",
+            vec![
+                FieldSchema::new("kind", Schema::U8),
+                FieldSchema::new("message", Schema::String),
+            ],
+        ))
+    }
+}
@@ -0,0 +1,718 @@
+//! A C ABI for embedding tora in other languages.
+//!
+//! Packets are encoded field by field into a caller-owned buffer through a [ToraEncoder], and
+//! decoded field by field from one through a [ToraDecoder], using the default configuration.
+//! Every function returns a [ToraStatus], and leaves the encoder or decoder untouched when it
+//! fails, so a call failing with [ToraStatus::BufferTooSmall] can be retried with a larger buffer.
+//!
+//! Schema handles let C code validate whole packets against the types of the Rust side, which
+//! exports them through its own functions:
+//!
+//! ```
+//! use tora::ffi::ToraSchema;
+//! use tora::schema::Reflect;
+//! use tora::Reflect;
+//!
+//! #[derive(Reflect)]
+//! struct Login {
+//!     user: String,
+//!     attempts: u8,
+//! }
+//!
+//! #[no_mangle]
+//! pub extern "C" fn game_login_schema() -> *mut ToraSchema {
+//!     ToraSchema::into_raw(Login::schema())
+//! }
+//! # unsafe { tora::ffi::tora_schema_free(game_login_schema()) };
+//! ```
+//!
+//! The C header is generated with cbindgen, from `cbindgen.toml` at the root of the crate, into
+//! `include/tora.h`:
+//!
+//! ```text
+//! cbindgen --config cbindgen.toml --output include/tora.h
+//! ```
+//!
+//! The crate builds as a Rust library only, so the shared or static library C links against is
+//! built explicitly:
+//!
+//! ```text
+//! cargo rustc --release --features ffi --crate-type cdylib
+//! cargo rustc --release --features ffi --crate-type staticlib
+//! ```
+//!
+//! ```c
+//! uint8_t buf[64];
+//! ToraEncoder encoder = tora_encoder(buf, sizeof buf);
+//! tora_encode_u32(&encoder, 7);
+//! tora_encode_string(&encoder, "john", 4);
+//!
+//! ToraDecoder decoder = tora_decoder(buf, encoder.len);
+//! uint32_t id;
+//! char name[16];
+//! size_t name_len;
+//! tora_decode_u32(&decoder, &id);
+//! tora_decode_string(&decoder, name, sizeof name, &name_len);
+//! ```
+//!
+//! The same calls made from Rust, along with the statuses of failing ones:
+//!
+//! ```
+//! use std::ffi::CStr;
+//! use std::ptr;
+//!
+//! use tora::ffi::*;
+//! use tora::schema::Schema;
+//!
+//! let mut buf = [0u8; 9];
+//! let mut encoder = tora_encoder(buf.as_mut_ptr(), 8);
+//! let (mut id, mut name, mut name_len, mut consumed) = (0, [0; 8], 0, 0);
+//!
+//! unsafe {
+//!     assert_eq!(tora_encode_u32(&mut encoder, 7), ToraStatus::Ok);
+//!     // "john" and its nul terminator need 5 bytes, of which 4 are left.
+//!     let status = tora_encode_string(&mut encoder, c"john".as_ptr(), 4);
+//!     assert_eq!(status, ToraStatus::BufferTooSmall);
+//!     assert_eq!(tora_encode_u32(ptr::null_mut(), 7), ToraStatus::NullPointer);
+//!
+//!     // The failed calls left the encoder untouched, so a larger buffer can be used.
+//!     encoder.capacity = buf.len();
+//!     assert_eq!(tora_encode_string(&mut encoder, c"john".as_ptr(), 4), ToraStatus::Ok);
+//!
+//!     let mut decoder = tora_decoder(buf.as_ptr(), encoder.len);
+//!     assert_eq!(tora_decode_u32(&mut decoder, &mut id), ToraStatus::Ok);
+//!     // "john" and its nul terminator do not fit 4 bytes, and the decoder is left untouched.
+//!     let status = tora_decode_string(&mut decoder, name.as_mut_ptr(), 4, &mut name_len);
+//!     assert_eq!(status, ToraStatus::BufferTooSmall);
+//!     let status = tora_decode_string(&mut decoder, name.as_mut_ptr(), 8, &mut name_len);
+//!     assert_eq!(status, ToraStatus::Ok);
+//!     assert_eq!(tora_decode_u8(&mut decoder, &mut 0), ToraStatus::UnexpectedEof);
+//!
+//!     assert_eq!((id, name_len), (7, 4));
+//!     assert_eq!(CStr::from_ptr(name.as_ptr()), c"john");
+//!
+//!     let schema = ToraSchema::into_raw(Schema::Tuple(vec![Schema::U32, Schema::String]));
+//!     let status = tora_schema_validate(schema, buf.as_ptr(), buf.len(), &mut consumed);
+//!     assert_eq!((status, consumed), (ToraStatus::Ok, 9));
+//!     let status = tora_schema_validate(schema, buf.as_ptr(), 6, &mut consumed);
+//!     assert_eq!(status, ToraStatus::UnexpectedEof);
+//!     tora_schema_free(schema);
+//!
+//!     let message = CStr::from_ptr(tora_status_message(ToraStatus::UnexpectedEof as u32));
+//!     assert_eq!(message, c"Unexpected end of data");
+//!     assert_eq!(CStr::from_ptr(tora_status_message(1000)), c"Unknown status");
+//! }
+//! ```
+
+use std::ffi::c_char;
+use std::io;
+use std::io::ErrorKind;
+use std::slice;
+
+use crate::config::ToraConfig;
+use crate::read::FromReader;
+use crate::schema::{walk, Schema, Visit};
+use crate::write::SerializeIo;
+
+/// The result of a C ABI call.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ToraStatus {
+    Ok = 0,
+    /// A pointer argument was null.
+    NullPointer,
+    /// The buffer of the encoder or the output is too small.
+    BufferTooSmall,
+    /// The decoder reached the end of its data.
+    UnexpectedEof,
+    /// The data does not hold a valid value.
+    InvalidData,
+    /// The value cannot be encoded.
+    InvalidInput,
+    /// Any other error.
+    Error,
+}
+
+impl From<io::Error> for ToraStatus {
+    fn from(e: io::Error) -> Self {
+        match e.kind() {
+            ErrorKind::UnexpectedEof => Self::UnexpectedEof,
+            ErrorKind::WriteZero => Self::BufferTooSmall,
+            ErrorKind::InvalidData => Self::InvalidData,
+            ErrorKind::InvalidInput => Self::InvalidInput,
+            _ => Self::Error,
+        }
+    }
+}
+
+impl ToraStatus {
+    /// Every status, indexed by its code.
+    const ALL: [Self; 7] = [
+        Self::Ok,
+        Self::NullPointer,
+        Self::BufferTooSmall,
+        Self::UnexpectedEof,
+        Self::InvalidData,
+        Self::InvalidInput,
+        Self::Error,
+    ];
+}
+
+/// Returns a static, nul-terminated description of the status code.
+///
+/// Takes the code as an integer, so any value C passes is valid; unknown codes are described as
+/// such.
+#[no_mangle]
+pub extern "C" fn tora_status_message(status: u32) -> *const c_char {
+    let message: &'static [u8] = match ToraStatus::ALL.get(status as usize) {
+        Some(ToraStatus::Ok) => b"Ok\0",
+        Some(ToraStatus::NullPointer) => b"A pointer argument was null\0",
+        Some(ToraStatus::BufferTooSmall) => b"The buffer is too small\0",
+        Some(ToraStatus::UnexpectedEof) => b"Unexpected end of data\0",
+        Some(ToraStatus::InvalidData) => b"Invalid data\0",
+        Some(ToraStatus::InvalidInput) => b"Invalid input\0",
+        Some(ToraStatus::Error) => b"Error\0",
+        None => b"Unknown status\0",
+    };
+    message.as_ptr().cast()
+}
+
+/// Encodes values into a caller-owned buffer.
+///
+/// `len` is the amount of bytes encoded so far, at the start of `data`.
+#[repr(C)]
+#[derive(Debug)]
+pub struct ToraEncoder {
+    pub data: *mut u8,
+    pub capacity: usize,
+    pub len: usize,
+}
+
+/// Constructs an encoder writing to the buffer of `capacity` bytes.
+#[no_mangle]
+pub extern "C" fn tora_encoder(data: *mut u8, capacity: usize) -> ToraEncoder {
+    ToraEncoder {
+        data,
+        capacity,
+        len: 0,
+    }
+}
+
+/// Encodes the value after the bytes encoded so far.
+///
+/// # Safety
+///
+/// The encoder must be null or valid, and its buffer must be valid for writes of `capacity`
+/// bytes.
+unsafe fn encode<T>(encoder: *mut ToraEncoder, value: &T) -> ToraStatus
+where
+    T: SerializeIo + ?Sized,
+{
+    let Some(encoder) = (unsafe { encoder.as_mut() }) else {
+        return ToraStatus::NullPointer;
+    };
+    if encoder.data.is_null() || encoder.len > encoder.capacity {
+        return ToraStatus::NullPointer;
+    }
+
+    let buf = unsafe { slice::from_raw_parts_mut(encoder.data, encoder.capacity) };
+    let mut remaining = &mut buf[encoder.len..];
+    let available = remaining.len();
+
+    match value.serialize_with(&mut remaining, &ToraConfig::DEFAULT) {
+        Ok(()) => {
+            encoder.len += available - remaining.len();
+            ToraStatus::Ok
+        }
+        Err(e) => e.into(),
+    }
+}
+
+/// Decodes values from a caller-owned buffer.
+///
+/// `position` is the amount of bytes decoded so far, from the start of `data`.
+#[repr(C)]
+#[derive(Debug)]
+pub struct ToraDecoder {
+    pub data: *const u8,
+    pub len: usize,
+    pub position: usize,
+}
+
+/// Constructs a decoder reading the `len` bytes of the buffer.
+#[no_mangle]
+pub extern "C" fn tora_decoder(data: *const u8, len: usize) -> ToraDecoder {
+    ToraDecoder {
+        data,
+        len,
+        position: 0,
+    }
+}
+
+impl ToraDecoder {
+    /// Returns the bytes left to decode.
+    ///
+    /// # Safety
+    ///
+    /// The buffer must be valid for reads of `len` bytes.
+    unsafe fn remaining(&self) -> Option<&[u8]> {
+        if self.data.is_null() || self.position > self.len {
+            return None;
+        }
+        let data = unsafe { slice::from_raw_parts(self.data, self.len) };
+        Some(&data[self.position..])
+    }
+}
+
+/// Decodes a value after the bytes decoded so far, storing it in `out`.
+///
+/// # Safety
+///
+/// The decoder must be null or valid, and its buffer must be valid for reads of `len` bytes.
+/// `out` must be null or valid for writes.
+unsafe fn decode<T>(decoder: *mut ToraDecoder, out: *mut T) -> ToraStatus
+where
+    T: FromReader,
+{
+    let Some(decoder) = (unsafe { decoder.as_mut() }) else {
+        return ToraStatus::NullPointer;
+    };
+    let Some(mut remaining) = (unsafe { decoder.remaining() }) else {
+        return ToraStatus::NullPointer;
+    };
+    if out.is_null() {
+        return ToraStatus::NullPointer;
+    }
+    let available = remaining.len();
+
+    match T::from_reader_with(&mut remaining, &ToraConfig::DEFAULT) {
+        Ok(value) => {
+            decoder.position += available - remaining.len();
+            unsafe { out.write(value) };
+            ToraStatus::Ok
+        }
+        Err(e) => e.into(),
+    }
+}
+
+/// Encodes a u8.
+///
+/// # Safety
+///
+/// The encoder must be null or valid, and its buffer must be valid for writes of `capacity`
+/// bytes.
+#[no_mangle]
+pub unsafe extern "C" fn tora_encode_u8(encoder: *mut ToraEncoder, value: u8) -> ToraStatus {
+    unsafe { encode(encoder, &value) }
+}
+
+/// Encodes a u16.
+///
+/// # Safety
+///
+/// See [tora_encode_u8].
+#[no_mangle]
+pub unsafe extern "C" fn tora_encode_u16(encoder: *mut ToraEncoder, value: u16) -> ToraStatus {
+    unsafe { encode(encoder, &value) }
+}
+
+/// Encodes a u32.
+///
+/// # Safety
+///
+/// See [tora_encode_u8].
+#[no_mangle]
+pub unsafe extern "C" fn tora_encode_u32(encoder: *mut ToraEncoder, value: u32) -> ToraStatus {
+    unsafe { encode(encoder, &value) }
+}
+
+/// Encodes a u64.
+///
+/// # Safety
+///
+/// See [tora_encode_u8].
+#[no_mangle]
+pub unsafe extern "C" fn tora_encode_u64(encoder: *mut ToraEncoder, value: u64) -> ToraStatus {
+    unsafe { encode(encoder, &value) }
+}
+
+/// Encodes an i8.
+///
+/// # Safety
+///
+/// See [tora_encode_u8].
+#[no_mangle]
+pub unsafe extern "C" fn tora_encode_i8(encoder: *mut ToraEncoder, value: i8) -> ToraStatus {
+    unsafe { encode(encoder, &value) }
+}
+
+/// Encodes an i16.
+///
+/// # Safety
+///
+/// See [tora_encode_u8].
+#[no_mangle]
+pub unsafe extern "C" fn tora_encode_i16(encoder: *mut ToraEncoder, value: i16) -> ToraStatus {
+    unsafe { encode(encoder, &value) }
+}
+
+/// Encodes an i32.
+///
+/// # Safety
+///
+/// See [tora_encode_u8].
+#[no_mangle]
+pub unsafe extern "C" fn tora_encode_i32(encoder: *mut ToraEncoder, value: i32) -> ToraStatus {
+    unsafe { encode(encoder, &value) }
+}
+
+/// Encodes an i64.
+///
+/// # Safety
+///
+/// See [tora_encode_u8].
+#[no_mangle]
+pub unsafe extern "C" fn tora_encode_i64(encoder: *mut ToraEncoder, value: i64) -> ToraStatus {
+    unsafe { encode(encoder, &value) }
+}
+
+/// Encodes an f32.
+///
+/// # Safety
+///
+/// See [tora_encode_u8].
+#[no_mangle]
+pub unsafe extern "C" fn tora_encode_f32(encoder: *mut ToraEncoder, value: f32) -> ToraStatus {
+    unsafe { encode(encoder, &value) }
+}
+
+/// Encodes an f64.
+///
+/// # Safety
+///
+/// See [tora_encode_u8].
+#[no_mangle]
+pub unsafe extern "C" fn tora_encode_f64(encoder: *mut ToraEncoder, value: f64) -> ToraStatus {
+    unsafe { encode(encoder, &value) }
+}
+
+/// Encodes a bool.
+///
+/// # Safety
+///
+/// See [tora_encode_u8].
+#[no_mangle]
+pub unsafe extern "C" fn tora_encode_bool(encoder: *mut ToraEncoder, value: bool) -> ToraStatus {
+    unsafe { encode(encoder, &value) }
+}
+
+/// Encodes a length prefix, as written before the elements of a list.
+///
+/// # Safety
+///
+/// See [tora_encode_u8].
+#[no_mangle]
+pub unsafe extern "C" fn tora_encode_length(encoder: *mut ToraEncoder, len: usize) -> ToraStatus {
+    struct Length(usize);
+
+    impl SerializeIo for Length {
+        fn serialize<W>(&self, w: &mut W) -> io::Result<()>
+        where
+            W: io::Write,
+        {
+            ToraConfig::DEFAULT.write_length(w, self.0)
+        }
+    }
+
+    unsafe { encode(encoder, &Length(len)) }
+}
+
+/// Encodes the UTF-8 string of `len` bytes, which need not be nul-terminated.
+///
+/// Returns [ToraStatus::InvalidInput] if the string is not valid UTF-8.
+///
+/// # Safety
+///
+/// See [tora_encode_u8]. `string` must be valid for reads of `len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn tora_encode_string(
+    encoder: *mut ToraEncoder,
+    string: *const c_char,
+    len: usize,
+) -> ToraStatus {
+    if string.is_null() {
+        return ToraStatus::NullPointer;
+    }
+    let bytes = unsafe { slice::from_raw_parts(string.cast::<u8>(), len) };
+
+    match std::str::from_utf8(bytes) {
+        Ok(string) => unsafe { encode(encoder, string) },
+        Err(_) => ToraStatus::InvalidInput,
+    }
+}
+
+/// Decodes a u8.
+///
+/// # Safety
+///
+/// The decoder must be null or valid, and its buffer must be valid for reads of `len` bytes.
+/// `out` must be null or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn tora_decode_u8(decoder: *mut ToraDecoder, out: *mut u8) -> ToraStatus {
+    unsafe { decode(decoder, out) }
+}
+
+/// Decodes a u16.
+///
+/// # Safety
+///
+/// See [tora_decode_u8].
+#[no_mangle]
+pub unsafe extern "C" fn tora_decode_u16(decoder: *mut ToraDecoder, out: *mut u16) -> ToraStatus {
+    unsafe { decode(decoder, out) }
+}
+
+/// Decodes a u32.
+///
+/// # Safety
+///
+/// See [tora_decode_u8].
+#[no_mangle]
+pub unsafe extern "C" fn tora_decode_u32(decoder: *mut ToraDecoder, out: *mut u32) -> ToraStatus {
+    unsafe { decode(decoder, out) }
+}
+
+/// Decodes a u64.
+///
+/// # Safety
+///
+/// See [tora_decode_u8].
+#[no_mangle]
+pub unsafe extern "C" fn tora_decode_u64(decoder: *mut ToraDecoder, out: *mut u64) -> ToraStatus {
+    unsafe { decode(decoder, out) }
+}
+
+/// Decodes an i8.
+///
+/// # Safety
+///
+/// See [tora_decode_u8].
+#[no_mangle]
+pub unsafe extern "C" fn tora_decode_i8(decoder: *mut ToraDecoder, out: *mut i8) -> ToraStatus {
+    unsafe { decode(decoder, out) }
+}
+
+/// Decodes an i16.
+///
+/// # Safety
+///
+/// See [tora_decode_u8].
+#[no_mangle]
+pub unsafe extern "C" fn tora_decode_i16(decoder: *mut ToraDecoder, out: *mut i16) -> ToraStatus {
+    unsafe { decode(decoder, out) }
+}
+
+/// Decodes an i32.
+///
+/// # Safety
+///
+/// See [tora_decode_u8].
+#[no_mangle]
+pub unsafe extern "C" fn tora_decode_i32(decoder: *mut ToraDecoder, out: *mut i32) -> ToraStatus {
+    unsafe { decode(decoder, out) }
+}
+
+/// Decodes an i64.
+///
+/// # Safety
+///
+/// See [tora_decode_u8].
+#[no_mangle]
+pub unsafe extern "C" fn tora_decode_i64(decoder: *mut ToraDecoder, out: *mut i64) -> ToraStatus {
+    unsafe { decode(decoder, out) }
+}
+
+/// Decodes an f32.
+///
+/// # Safety
+///
+/// See [tora_decode_u8].
+#[no_mangle]
+pub unsafe extern "C" fn tora_decode_f32(decoder: *mut ToraDecoder, out: *mut f32) -> ToraStatus {
+    unsafe { decode(decoder, out) }
+}
+
+/// Decodes an f64.
+///
+/// # Safety
+///
+/// See [tora_decode_u8].
+#[no_mangle]
+pub unsafe extern "C" fn tora_decode_f64(decoder: *mut ToraDecoder, out: *mut f64) -> ToraStatus {
+    unsafe { decode(decoder, out) }
+}
+
+/// Decodes a bool.
+///
+/// # Safety
+///
+/// See [tora_decode_u8].
+#[no_mangle]
+pub unsafe extern "C" fn tora_decode_bool(decoder: *mut ToraDecoder, out: *mut bool) -> ToraStatus {
+    unsafe { decode(decoder, out) }
+}
+
+/// Decodes a length prefix, as written before the elements of a list.
+///
+/// # Safety
+///
+/// See [tora_decode_u8].
+#[no_mangle]
+pub unsafe extern "C" fn tora_decode_length(
+    decoder: *mut ToraDecoder,
+    out: *mut usize,
+) -> ToraStatus {
+    #[repr(transparent)]
+    struct Length(usize);
+
+    impl FromReader for Length {
+        fn from_reader<R>(r: &mut R) -> io::Result<Self>
+        where
+            R: io::Read,
+        {
+            ToraConfig::DEFAULT.read_length(r).map(Length)
+        }
+    }
+
+    unsafe { decode(decoder, out.cast::<Length>()) }
+}
+
+/// Decodes a string into the buffer of `capacity` bytes, followed by a nul terminator. Stores
+/// its length, without the terminator, in `out_len`.
+///
+/// Returns [ToraStatus::BufferTooSmall] if the buffer cannot hold the string and its terminator.
+///
+/// # Safety
+///
+/// See [tora_decode_u8]. `out` must be valid for writes of `capacity` bytes, and `out_len` must
+/// be null or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn tora_decode_string(
+    decoder: *mut ToraDecoder,
+    out: *mut c_char,
+    capacity: usize,
+    out_len: *mut usize,
+) -> ToraStatus {
+    if decoder.is_null() || out.is_null() || out_len.is_null() {
+        return ToraStatus::NullPointer;
+    }
+    let start = unsafe { (*decoder).position };
+    let mut string = String::new();
+
+    match unsafe { decode(decoder, &mut string) } {
+        ToraStatus::Ok if string.len() < capacity => {
+            let out = unsafe { slice::from_raw_parts_mut(out.cast::<u8>(), capacity) };
+            out[..string.len()].copy_from_slice(string.as_bytes());
+            out[string.len()] = 0;
+
+            unsafe { out_len.write(string.len()) };
+            ToraStatus::Ok
+        }
+        ToraStatus::Ok => {
+            unsafe { (*decoder).position = start };
+            ToraStatus::BufferTooSmall
+        }
+        status => status,
+    }
+}
+
+/// A handle to a [Schema], owned by C code.
+#[derive(Debug)]
+pub struct ToraSchema {
+    schema: Schema,
+}
+
+impl ToraSchema {
+    /// Moves the schema to the heap, returning a handle to be freed with [tora_schema_free].
+    pub fn into_raw(schema: Schema) -> *mut ToraSchema {
+        Box::into_raw(Box::new(Self { schema }))
+    }
+}
+
+/// Frees a schema handle. Does nothing if the handle is null.
+///
+/// # Safety
+///
+/// The handle must be null, or returned by [ToraSchema::into_raw] and not freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn tora_schema_free(schema: *mut ToraSchema) {
+    if !schema.is_null() {
+        drop(unsafe { Box::from_raw(schema) });
+    }
+}
+
+/// Stores the amount of bytes values of the schema always encode to in `out`.
+///
+/// Returns [ToraStatus::InvalidInput] if the size varies.
+///
+/// # Safety
+///
+/// The handle must be null or valid, and `out` must be null or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn tora_schema_fixed_size(
+    schema: *const ToraSchema,
+    out: *mut usize,
+) -> ToraStatus {
+    let Some(schema) = (unsafe { schema.as_ref() }) else {
+        return ToraStatus::NullPointer;
+    };
+    if out.is_null() {
+        return ToraStatus::NullPointer;
+    }
+
+    match schema.schema.fixed_size() {
+        Some(size) => {
+            unsafe { out.write(size) };
+            ToraStatus::Ok
+        }
+        None => ToraStatus::InvalidInput,
+    }
+}
+
+/// Checks that the `len` bytes of `data` start with a valid value of the schema, storing the
+/// amount of bytes it occupies, never more than `len`, in `consumed`.
+///
+/// # Safety
+///
+/// The handle must be null or valid, `data` must be valid for reads of `len` bytes, and
+/// `consumed` must be null or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn tora_schema_validate(
+    schema: *const ToraSchema,
+    data: *const u8,
+    len: usize,
+    consumed: *mut usize,
+) -> ToraStatus {
+    let Some(schema) = (unsafe { schema.as_ref() }) else {
+        return ToraStatus::NullPointer;
+    };
+    if data.is_null() || consumed.is_null() {
+        return ToraStatus::NullPointer;
+    }
+    let data = unsafe { slice::from_raw_parts(data, len) };
+
+    match walk(
+        data,
+        &schema.schema,
+        &ToraConfig::DEFAULT,
+        &mut |_: Visit| {},
+    ) {
+        // The walk never reads past the data, but C relies on `consumed` staying within it.
+        Ok(read) if read <= len => {
+            unsafe { consumed.write(read) };
+            ToraStatus::Ok
+        }
+        Ok(_) => ToraStatus::Error,
+        Err(e) => e.into(),
+    }
+}
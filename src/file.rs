@@ -0,0 +1,265 @@
+//! Reading and writing values from and to files.
+
+use std::fs::File;
+use std::io;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use crate::digest::{Digest, DigestReader, DigestWriter};
+use crate::instrument::Instrumented;
+#[cfg(feature = "tracing")]
+use crate::instrument::TracingInstrument;
+use crate::read::FromReader;
+use crate::write::SerializeIo;
+
+/// The instrument reporting values read from and written to files.
+#[cfg(feature = "tracing")]
+const FILE_INSTRUMENT: TracingInstrument = TracingInstrument;
+
+/// The instrument reporting values read from and written to files.
+#[cfg(not(feature = "tracing"))]
+const FILE_INSTRUMENT: () = ();
+
+/// Serialize the content and write it to the file at the given path.
+///
+/// With the `tracing` feature, the operation is wrapped in a `DEBUG` span. With the `io_uring`
+/// feature on Linux, the content is serialized in memory and written with a single io_uring
+/// operation.
+pub fn write_to_file<P, C>(path: P, content: &C) -> io::Result<()>
+where
+    P: AsRef<Path>,
+    C: SerializeIo,
+{
+    #[cfg(feature = "tracing")]
+    let _span = tracing::debug_span!("write_to_file", path = %path.as_ref().display()).entered();
+
+    let file = File::create(path)?;
+
+    #[cfg(all(feature = "io_uring", target_os = "linux"))]
+    {
+        let mut buf = Vec::new();
+        Instrumented::new(&mut buf, FILE_INSTRUMENT).writes(content)?;
+        crate::uring::write_file(&file, &buf)
+    }
+    #[cfg(not(all(feature = "io_uring", target_os = "linux")))]
+    Instrumented::new(file, FILE_INSTRUMENT).writes(content)
+}
+
+/// Try to deserialize [T] from the file at the given path.
+///
+/// With the `tracing` feature, the operation is wrapped in a `DEBUG` span. With the `io_uring`
+/// feature on Linux, the whole file is read into memory with a single io_uring operation.
+pub fn read_from_file<T, P>(path: P) -> io::Result<T>
+where
+    P: AsRef<Path>,
+    T: FromReader,
+{
+    #[cfg(feature = "tracing")]
+    let _span = tracing::debug_span!("read_from_file", path = %path.as_ref().display()).entered();
+
+    let file = File::open(path)?;
+
+    #[cfg(all(feature = "io_uring", target_os = "linux"))]
+    {
+        let buf = crate::uring::read_file(&file)?;
+        Instrumented::new(buf.as_slice(), FILE_INSTRUMENT).reads()
+    }
+    #[cfg(not(all(feature = "io_uring", target_os = "linux")))]
+    Instrumented::new(file, FILE_INSTRUMENT).reads()
+}
+
+/// The progress of a file operation, passed to the callback of [read_from_file_with_progress]
+/// and [write_to_file_with_progress].
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct Progress {
+    /// The amount of bytes read or written so far.
+    pub processed: u64,
+    /// The total amount of bytes, if known.
+    pub total: Option<u64>,
+}
+
+impl Progress {
+    /// Returns the fraction of the operation completed, between 0 and 1, if the total is known.
+    pub fn fraction(&self) -> Option<f64> {
+        self.total.map(|total| match total {
+            0 => 1.0,
+            total => self.processed as f64 / total as f64,
+        })
+    }
+}
+
+/// Reports the bytes passing through the inner reader or writer to a callback.
+struct ProgressAdapter<T, F> {
+    inner: T,
+    progress: Progress,
+    callback: F,
+}
+
+impl<T, F> ProgressAdapter<T, F>
+where
+    F: FnMut(Progress),
+{
+    fn advance(&mut self, n: usize) {
+        if n > 0 {
+            self.progress.processed += n as u64;
+            (self.callback)(self.progress);
+        }
+    }
+}
+
+impl<T, F> Read for ProgressAdapter<T, F>
+where
+    T: Read,
+    F: FnMut(Progress),
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.advance(n);
+        Ok(n)
+    }
+}
+
+impl<T, F> Write for ProgressAdapter<T, F>
+where
+    T: Write,
+    F: FnMut(Progress),
+{
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.advance(n);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Serialize the content and write it to the file at the given path, reporting the amount of
+/// bytes written to `progress` as the file grows.
+///
+/// The total is not known ahead of the write, so it is reported as [None]. Writes are buffered,
+/// so `progress` is called once per buffer flushed rather than once per value.
+pub fn write_to_file_with_progress<P, C, F>(path: P, content: &C, progress: F) -> io::Result<()>
+where
+    P: AsRef<Path>,
+    C: SerializeIo,
+    F: FnMut(Progress),
+{
+    #[cfg(feature = "tracing")]
+    let _span = tracing::debug_span!("write_to_file", path = %path.as_ref().display()).entered();
+
+    let file = ProgressAdapter {
+        inner: File::create(path)?,
+        progress: Progress {
+            processed: 0,
+            total: None,
+        },
+        callback: progress,
+    };
+    let mut writer = BufWriter::new(file);
+
+    Instrumented::new(&mut writer, FILE_INSTRUMENT).writes(content)?;
+    writer.flush()
+}
+
+/// Try to deserialize [T] from the file at the given path, reporting the amount of bytes read
+/// to `progress` along with the size of the file.
+///
+/// Reads are buffered, so `progress` is called once per buffer filled rather than once per value.
+///
+/// ```
+/// use std::io;
+///
+/// fn main() -> io::Result<()> {
+///     let path = std::env::temp_dir().join("tora_progress_doctest.bin");
+///     tora::write_to_file(&path, &vec![7u64; 10_000])?;
+///
+///     let mut last = None;
+///     let values: Vec<u64> = tora::read_from_file_with_progress(&path, |p| last = Some(p))?;
+///
+///     let last = last.unwrap();
+///     assert_eq!(values.len(), 10_000);
+///     assert_eq!(last.processed, 80_004);
+///     assert_eq!(last.fraction(), Some(1.0));
+///
+///     std::fs::remove_file(path)
+/// }
+/// ```
+pub fn read_from_file_with_progress<T, P, F>(path: P, progress: F) -> io::Result<T>
+where
+    P: AsRef<Path>,
+    T: FromReader,
+    F: FnMut(Progress),
+{
+    #[cfg(feature = "tracing")]
+    let _span = tracing::debug_span!("read_from_file", path = %path.as_ref().display()).entered();
+
+    let file = File::open(path)?;
+    let total = file.metadata()?.len();
+
+    let file = ProgressAdapter {
+        inner: file,
+        progress: Progress {
+            processed: 0,
+            total: Some(total),
+        },
+        callback: progress,
+    };
+    Instrumented::new(BufReader::new(file), FILE_INSTRUMENT).reads()
+}
+
+/// Serialize the content and write it to the file at the given path, followed by its digest.
+///
+/// The file is read back with [read_from_file_with_digest], given the same kind of digest.
+pub fn write_to_file_with_digest<P, C, D>(path: P, content: &C, digest: D) -> io::Result<()>
+where
+    P: AsRef<Path>,
+    C: SerializeIo,
+    D: Digest,
+{
+    #[cfg(feature = "tracing")]
+    let _span = tracing::debug_span!("write_to_file", path = %path.as_ref().display()).entered();
+
+    let mut writer = DigestWriter::new(BufWriter::new(File::create(path)?), digest);
+
+    Instrumented::new(&mut writer, FILE_INSTRUMENT).writes(content)?;
+    writer.finish().map(|_| ())
+}
+
+/// Try to deserialize [T] from the file at the given path, then check the digest following it.
+///
+/// Returns [ErrorKind::InvalidData](std::io::ErrorKind::InvalidData) if the digest does not
+/// match the bytes read.
+///
+/// ```
+/// use std::io;
+///
+/// use tora::digest::XxHash64;
+///
+/// fn main() -> io::Result<()> {
+///     let path = std::env::temp_dir().join("tora_digest_doctest.bin");
+///     tora::write_to_file_with_digest(&path, &vec![7u64; 100], XxHash64::new())?;
+///
+///     let values: Vec<u64> = tora::read_from_file_with_digest(&path, XxHash64::new())?;
+///     assert_eq!(values, [7; 100]);
+///     assert_eq!(std::fs::metadata(&path)?.len(), 4 + 800 + 8);
+///
+///     std::fs::remove_file(path)
+/// }
+/// ```
+pub fn read_from_file_with_digest<T, P, D>(path: P, digest: D) -> io::Result<T>
+where
+    P: AsRef<Path>,
+    T: FromReader,
+    D: Digest,
+{
+    #[cfg(feature = "tracing")]
+    let _span = tracing::debug_span!("read_from_file", path = %path.as_ref().display()).entered();
+
+    let mut reader = DigestReader::new(BufReader::new(File::open(path)?), digest);
+
+    let value = Instrumented::new(&mut reader, FILE_INSTRUMENT).reads()?;
+    reader.verify()?;
+    Ok(value)
+}
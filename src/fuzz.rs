@@ -0,0 +1,84 @@
+//! Entry points for fuzzing the decoding of untrusted input.
+//!
+//! Each function takes the raw input of a fuzzer, such as `cargo fuzz`, and panics only if a
+//! decoded value misbehaves. Decoding errors are expected and ignored. The `fuzz` directory of
+//! the repository holds example `cargo fuzz` targets.
+//!
+//! Input is decoded with [config], which limits lengths to the size of the input, so a small
+//! input cannot claim a huge collection and exhaust memory before failing.
+//!
+//! ```
+//! use tora::fuzz;
+//! use tora::{ReadEnum, WriteEnum};
+//!
+//! #[derive(Debug, PartialEq, ReadEnum, WriteEnum)]
+//! enum Packet {
+//!     Ping(u64),
+//!     Chat(String),
+//!     Batch(Vec<Packet>),
+//! }
+//!
+//! // In a fuzz target, this would be
+//! // `fuzz_target!(|data: &[u8]| fuzz::roundtrip::<Packet>(data));`
+//! fuzz::roundtrip::<Packet>(&[1, b'H', b'i', 0]);
+//! assert!(fuzz::try_decode::<Packet>(&[2, 255, 255, 255, 255]).is_none());
+//! ```
+
+use std::fmt::Debug;
+use std::io::Cursor;
+
+use crate::config::ToraConfig;
+use crate::read::FromReader;
+use crate::write::SerializeIo;
+
+/// Returns the configuration fuzz input is decoded with.
+///
+/// Limits the length of collections and strings to the length of the input.
+pub fn config(data: &[u8]) -> ToraConfig {
+    ToraConfig {
+        max_length: Some(data.len()),
+        ..ToraConfig::DEFAULT
+    }
+}
+
+/// Attempts to decode [T] from the input, returning None if decoding fails.
+pub fn try_decode<T>(data: &[u8]) -> Option<T>
+where
+    T: FromReader,
+{
+    T::from_reader_with(&mut Cursor::new(data), &config(data)).ok()
+}
+
+/// Attempts to decode [T] from the input, and checks that a decoded value survives a round trip.
+///
+/// # Panics
+///
+/// Panics if the decoded value fails to serialize, if its encoding fails to decode, or if
+/// decoding it again returns a different value or encoding. Types holding floats compare NaN as
+/// unequal to itself, so their targets should use [try_decode] instead.
+pub fn roundtrip<T>(data: &[u8])
+where
+    T: FromReader + SerializeIo + PartialEq + Debug,
+{
+    let Some(value) = try_decode::<T>(data) else {
+        return;
+    };
+
+    let mut bytes = Vec::new();
+    if let Err(e) = value.serialize(&mut bytes) {
+        panic!("Failed to serialize a decoded value {value:?}: {e}");
+    }
+
+    let decoded = match T::from_reader(&mut Cursor::new(&bytes)) {
+        Ok(decoded) => decoded,
+        Err(e) => panic!("Failed to decode the encoding of {value:?}: {e}"),
+    };
+    assert_eq!(value, decoded, "The value changed after a round trip");
+
+    // The input may encode a value in several ways, but its re-encoding must be stable.
+    let mut again = Vec::new();
+    if let Err(e) = decoded.serialize(&mut again) {
+        panic!("Failed to serialize a decoded value {decoded:?}: {e}");
+    }
+    assert_eq!(bytes, again, "The encoding changed after a round trip");
+}
@@ -0,0 +1,581 @@
+//! Instrumentation of serialization and deserialization.
+//!
+//! An [Instrumented] transport reports every value it reads or writes to an [Instrument], along
+//! with the type name, the amount of bytes and the time taken. The transport only needs to be
+//! wrapped once, for example through an [InstrumentLayer], instead of at every call site.
+//!
+//! [Totals] keeps running totals of all values, while [Stats] breaks them down per type, with
+//! size extremes and latency percentiles.
+//!
+//! ```
+//! use std::io;
+//! use std::io::Cursor;
+//! use std::sync::Arc;
+//!
+//! use tora::instrument::{Instrumented, Totals};
+//!
+//! fn main() -> io::Result<()> {
+//!     let totals = Arc::new(Totals::new());
+//!     let mut writer = Instrumented::new(Vec::new(), totals.clone());
+//!
+//!     writer.writes(&"Hello")?;
+//!     writer.writes(&5u32)?;
+//!
+//!     let mut reader = Instrumented::new(Cursor::new(writer.into_inner()), totals.clone());
+//!     assert_eq!(reader.reads::<String>()?, "Hello");
+//!
+//!     assert_eq!(totals.serialized().count, 2);
+//!     assert_eq!(totals.serialized().bytes, 10);
+//!     assert_eq!(totals.deserialized().bytes, 6);
+//!     Ok(())
+//! }
+//! ```
+
+use std::collections::HashMap;
+use std::io;
+use std::io::{Read, Write};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+use crate::layer::Layer;
+use crate::read::{ByteCountReader, FromReader, ToraRead};
+use crate::write::{ByteCountWriter, SerializeIo, ToraWrite};
+
+/// Receives a report of each value read or written through an [Instrumented] transport.
+///
+/// All methods have empty default implementations.
+pub trait Instrument {
+    /// Called after a value was serialized successfully.
+    fn on_serialize(&self, type_name: &'static str, bytes: u64, elapsed: Duration) {
+        let _ = (type_name, bytes, elapsed);
+    }
+
+    /// Called after a value was deserialized successfully.
+    fn on_deserialize(&self, type_name: &'static str, bytes: u64, elapsed: Duration) {
+        let _ = (type_name, bytes, elapsed);
+    }
+
+    /// Called after a value failed to serialize.
+    fn on_serialize_error(&self, type_name: &'static str, error: &io::Error) {
+        let _ = (type_name, error);
+    }
+
+    /// Called after a value failed to deserialize.
+    fn on_deserialize_error(&self, type_name: &'static str, error: &io::Error) {
+        let _ = (type_name, error);
+    }
+}
+
+/// Reports nothing.
+impl Instrument for () {}
+
+/// An instrument emitting a [tracing] event for each value.
+///
+/// Successful values are emitted at the `DEBUG` level, failures at the `WARN` level.
+#[cfg(feature = "tracing")]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TracingInstrument;
+
+#[cfg(feature = "tracing")]
+impl Instrument for TracingInstrument {
+    fn on_serialize(&self, type_name: &'static str, bytes: u64, elapsed: Duration) {
+        tracing::debug!(type_name, bytes, elapsed = ?elapsed, "Serialized value");
+    }
+
+    fn on_deserialize(&self, type_name: &'static str, bytes: u64, elapsed: Duration) {
+        tracing::debug!(type_name, bytes, elapsed = ?elapsed, "Deserialized value");
+    }
+
+    fn on_serialize_error(&self, type_name: &'static str, error: &io::Error) {
+        tracing::warn!(type_name, error = %error, "Failed to serialize value");
+    }
+
+    fn on_deserialize_error(&self, type_name: &'static str, error: &io::Error) {
+        tracing::warn!(type_name, error = %error, "Failed to deserialize value");
+    }
+}
+
+impl<I> Instrument for &I
+where
+    I: Instrument + ?Sized,
+{
+    fn on_serialize(&self, type_name: &'static str, bytes: u64, elapsed: Duration) {
+        (**self).on_serialize(type_name, bytes, elapsed)
+    }
+
+    fn on_deserialize(&self, type_name: &'static str, bytes: u64, elapsed: Duration) {
+        (**self).on_deserialize(type_name, bytes, elapsed)
+    }
+
+    fn on_serialize_error(&self, type_name: &'static str, error: &io::Error) {
+        (**self).on_serialize_error(type_name, error)
+    }
+
+    fn on_deserialize_error(&self, type_name: &'static str, error: &io::Error) {
+        (**self).on_deserialize_error(type_name, error)
+    }
+}
+
+impl<I> Instrument for Arc<I>
+where
+    I: Instrument + ?Sized,
+{
+    fn on_serialize(&self, type_name: &'static str, bytes: u64, elapsed: Duration) {
+        (**self).on_serialize(type_name, bytes, elapsed)
+    }
+
+    fn on_deserialize(&self, type_name: &'static str, bytes: u64, elapsed: Duration) {
+        (**self).on_deserialize(type_name, bytes, elapsed)
+    }
+
+    fn on_serialize_error(&self, type_name: &'static str, error: &io::Error) {
+        (**self).on_serialize_error(type_name, error)
+    }
+
+    fn on_deserialize_error(&self, type_name: &'static str, error: &io::Error) {
+        (**self).on_deserialize_error(type_name, error)
+    }
+}
+
+/// A snapshot of the totals in one direction.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Total {
+    /// The amount of values.
+    pub count: u64,
+    /// The amount of bytes.
+    pub bytes: u64,
+    /// The amount of failed values.
+    pub errors: u64,
+    /// The time spent on the values.
+    pub elapsed: Duration,
+}
+
+#[derive(Default)]
+struct AtomicTotal {
+    count: AtomicU64,
+    bytes: AtomicU64,
+    errors: AtomicU64,
+    nanos: AtomicU64,
+}
+
+impl AtomicTotal {
+    fn add(&self, bytes: u64, elapsed: Duration) {
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.bytes.fetch_add(bytes, Ordering::Relaxed);
+        self.nanos
+            .fetch_add(elapsed.as_nanos() as u64, Ordering::Relaxed);
+    }
+
+    fn snapshot(&self) -> Total {
+        Total {
+            count: self.count.load(Ordering::Relaxed),
+            bytes: self.bytes.load(Ordering::Relaxed),
+            errors: self.errors.load(Ordering::Relaxed),
+            elapsed: Duration::from_nanos(self.nanos.load(Ordering::Relaxed)),
+        }
+    }
+}
+
+/// An instrument keeping running totals of all values, safe to share between threads.
+#[derive(Default)]
+pub struct Totals {
+    serialized: AtomicTotal,
+    deserialized: AtomicTotal,
+}
+
+impl Totals {
+    /// Constructs Totals starting at zero.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the totals of serialized values.
+    pub fn serialized(&self) -> Total {
+        self.serialized.snapshot()
+    }
+
+    /// Returns the totals of deserialized values.
+    pub fn deserialized(&self) -> Total {
+        self.deserialized.snapshot()
+    }
+}
+
+impl Instrument for Totals {
+    fn on_serialize(&self, _type_name: &'static str, bytes: u64, elapsed: Duration) {
+        self.serialized.add(bytes, elapsed);
+    }
+
+    fn on_deserialize(&self, _type_name: &'static str, bytes: u64, elapsed: Duration) {
+        self.deserialized.add(bytes, elapsed);
+    }
+
+    fn on_serialize_error(&self, _type_name: &'static str, _error: &io::Error) {
+        self.serialized.errors.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn on_deserialize_error(&self, _type_name: &'static str, _error: &io::Error) {
+        self.deserialized.errors.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// The amount of buckets per power of two in a [Histogram].
+const SUB_BUCKETS: u64 = 16;
+
+/// A histogram of durations in nanoseconds, with a relative error of at most 1/16.
+#[derive(Clone, Default)]
+struct Histogram {
+    buckets: Vec<u64>,
+    count: u64,
+    max: u64,
+}
+
+impl Histogram {
+    fn bucket(nanos: u64) -> usize {
+        if nanos < SUB_BUCKETS {
+            return nanos as usize;
+        }
+        let exponent = 63 - nanos.leading_zeros() as u64;
+        let sub = (nanos >> (exponent - 4)) & (SUB_BUCKETS - 1);
+
+        ((exponent - 3) * SUB_BUCKETS + sub) as usize
+    }
+
+    /// Returns the largest duration in the bucket.
+    fn upper_bound(bucket: usize) -> u64 {
+        let bucket = bucket as u64;
+
+        if bucket < SUB_BUCKETS {
+            return bucket;
+        }
+        let exponent = bucket / SUB_BUCKETS + 3;
+        let sub = bucket % SUB_BUCKETS;
+
+        (SUB_BUCKETS + sub + 1)
+            .checked_mul(1 << (exponent - 4))
+            .map_or(u64::MAX, |end| end - 1)
+    }
+
+    fn record(&mut self, elapsed: Duration) {
+        let nanos = u64::try_from(elapsed.as_nanos()).unwrap_or(u64::MAX);
+        let bucket = Self::bucket(nanos);
+
+        if bucket >= self.buckets.len() {
+            self.buckets.resize(bucket + 1, 0);
+        }
+        self.buckets[bucket] += 1;
+        self.count += 1;
+        self.max = self.max.max(nanos);
+    }
+
+    fn percentile(&self, q: f64) -> Duration {
+        let rank = ((q * self.count as f64).ceil() as u64).max(1);
+        let mut seen = 0;
+
+        for (bucket, &count) in self.buckets.iter().enumerate() {
+            seen += count;
+
+            if seen >= rank {
+                return Duration::from_nanos(Self::upper_bound(bucket).min(self.max));
+            }
+        }
+        Duration::ZERO
+    }
+
+    fn latencies(&self) -> Latencies {
+        Latencies {
+            p50: self.percentile(0.5),
+            p90: self.percentile(0.9),
+            p99: self.percentile(0.99),
+            max: Duration::from_nanos(self.max),
+        }
+    }
+}
+
+/// Percentiles of the time taken by values.
+///
+/// Percentiles are accurate to within 1/16 of their value.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Latencies {
+    pub p50: Duration,
+    pub p90: Duration,
+    pub p99: Duration,
+    pub max: Duration,
+}
+
+/// Statistics of the values of one type, in one direction.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct OpStats {
+    /// The amount of values.
+    pub count: u64,
+    /// The amount of failed values.
+    pub errors: u64,
+    /// The total amount of bytes.
+    pub bytes: u64,
+    /// The amount of bytes of the smallest value, or 0 if there were none.
+    pub min_bytes: u64,
+    /// The amount of bytes of the largest value.
+    pub max_bytes: u64,
+    pub latencies: Latencies,
+}
+
+/// Statistics of the values of one type.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct TypeStats {
+    pub serialized: OpStats,
+    pub deserialized: OpStats,
+}
+
+#[derive(Clone, Default)]
+struct OpRecord {
+    stats: OpStats,
+    histogram: Histogram,
+}
+
+impl OpRecord {
+    fn add(&mut self, bytes: u64, elapsed: Duration) {
+        let stats = &mut self.stats;
+
+        stats.min_bytes = match stats.count {
+            0 => bytes,
+            _ => stats.min_bytes.min(bytes),
+        };
+        stats.max_bytes = stats.max_bytes.max(bytes);
+        stats.count += 1;
+        stats.bytes += bytes;
+
+        self.histogram.record(elapsed);
+    }
+
+    fn snapshot(&self) -> OpStats {
+        OpStats {
+            latencies: self.histogram.latencies(),
+            ..self.stats
+        }
+    }
+}
+
+#[derive(Default)]
+struct TypeRecord {
+    serialized: OpRecord,
+    deserialized: OpRecord,
+}
+
+/// An instrument collecting statistics per type, safe to share between threads.
+///
+/// Records the amount of values and bytes, the smallest and largest values, and latency
+/// percentiles. Types are identified by their [type name](std::any::type_name).
+///
+/// ```
+/// use std::io;
+/// use std::sync::Arc;
+///
+/// use tora::instrument::{Instrumented, Stats};
+///
+/// fn main() -> io::Result<()> {
+///     let stats = Arc::new(Stats::new());
+///     let mut writer = Instrumented::new(Vec::new(), stats.clone());
+///
+///     writer.writes(&"Hello")?;
+///     writer.writes(&"Hi")?;
+///
+///     let strings = stats.get::<&str>().unwrap().serialized;
+///     assert_eq!(strings.count, 2);
+///     assert_eq!(strings.max_bytes, 6);
+///
+///     // Packets must fit in a single datagram.
+///     assert!(strings.max_bytes <= 64);
+///     Ok(())
+/// }
+/// ```
+#[derive(Default)]
+pub struct Stats {
+    types: Mutex<HashMap<&'static str, TypeRecord>>,
+}
+
+impl Stats {
+    /// Constructs empty Stats.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn update<F>(&self, type_name: &'static str, f: F)
+    where
+        F: FnOnce(&mut TypeRecord),
+    {
+        let mut types = self.types.lock().unwrap_or_else(PoisonError::into_inner);
+        f(types.entry(type_name).or_default());
+    }
+
+    /// Returns the statistics of the type [T], if any of its values were reported.
+    pub fn get<T>(&self) -> Option<TypeStats>
+    where
+        T: ?Sized,
+    {
+        self.get_by_name(std::any::type_name::<T>())
+    }
+
+    /// Returns the statistics of the type with the given name, if any of its values were reported.
+    pub fn get_by_name(&self, type_name: &str) -> Option<TypeStats> {
+        let types = self.types.lock().unwrap_or_else(PoisonError::into_inner);
+
+        types.get(type_name).map(|record| TypeStats {
+            serialized: record.serialized.snapshot(),
+            deserialized: record.deserialized.snapshot(),
+        })
+    }
+
+    /// Returns the statistics of every reported type, by type name.
+    pub fn snapshot(&self) -> HashMap<&'static str, TypeStats> {
+        let types = self.types.lock().unwrap_or_else(PoisonError::into_inner);
+
+        types
+            .iter()
+            .map(|(&name, record)| {
+                let stats = TypeStats {
+                    serialized: record.serialized.snapshot(),
+                    deserialized: record.deserialized.snapshot(),
+                };
+                (name, stats)
+            })
+            .collect()
+    }
+
+    /// Discards all statistics.
+    pub fn reset(&self) {
+        self.types
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clear();
+    }
+}
+
+impl Instrument for Stats {
+    fn on_serialize(&self, type_name: &'static str, bytes: u64, elapsed: Duration) {
+        self.update(type_name, |r| r.serialized.add(bytes, elapsed));
+    }
+
+    fn on_deserialize(&self, type_name: &'static str, bytes: u64, elapsed: Duration) {
+        self.update(type_name, |r| r.deserialized.add(bytes, elapsed));
+    }
+
+    fn on_serialize_error(&self, type_name: &'static str, _error: &io::Error) {
+        self.update(type_name, |r| r.serialized.stats.errors += 1);
+    }
+
+    fn on_deserialize_error(&self, type_name: &'static str, _error: &io::Error) {
+        self.update(type_name, |r| r.deserialized.stats.errors += 1);
+    }
+}
+
+/// A transport reporting each value read or written through it to an [Instrument].
+pub struct Instrumented<T, I> {
+    inner: T,
+    instrument: I,
+}
+
+impl<T, I> Instrumented<T, I>
+where
+    I: Instrument,
+{
+    /// Constructs an Instrumented transport reporting to the given instrument.
+    pub const fn new(inner: T, instrument: I) -> Self {
+        Self { inner, instrument }
+    }
+
+    /// Try to read and deserialize a type, reporting it to the instrument.
+    pub fn reads<V>(&mut self) -> io::Result<V>
+    where
+        T: Read,
+        V: FromReader,
+    {
+        let type_name = std::any::type_name::<V>();
+        let start = Instant::now();
+
+        let mut counter = ByteCountReader::new(&mut self.inner);
+
+        match counter.reads() {
+            Ok(value) => {
+                let bytes = counter.count();
+                self.instrument
+                    .on_deserialize(type_name, bytes, start.elapsed());
+                Ok(value)
+            }
+            Err(e) => {
+                self.instrument.on_deserialize_error(type_name, &e);
+                Err(e)
+            }
+        }
+    }
+
+    /// Serialize and write the given data, reporting it to the instrument.
+    pub fn writes<S>(&mut self, s: &S) -> io::Result<()>
+    where
+        T: Write,
+        S: SerializeIo,
+    {
+        let type_name = std::any::type_name::<S>();
+        let start = Instant::now();
+
+        let mut counter = ByteCountWriter::new(&mut self.inner);
+
+        match counter.writes(s) {
+            Ok(()) => {
+                let bytes = counter.count();
+                self.instrument
+                    .on_serialize(type_name, bytes, start.elapsed());
+                Ok(())
+            }
+            Err(e) => {
+                self.instrument.on_serialize_error(type_name, &e);
+                Err(e)
+            }
+        }
+    }
+
+    /// Returns a reference to the instrument.
+    pub const fn instrument(&self) -> &I {
+        &self.instrument
+    }
+
+    /// Returns a reference to the inner transport.
+    pub const fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    /// Returns a mutable reference to the inner transport.
+    ///
+    /// Values read or written directly through the inner transport are not reported.
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+
+    /// Consumes this transport, returning the inner transport.
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+/// A layer wrapping transports in an [Instrumented] transport.
+#[derive(Clone, Debug, Default)]
+pub struct InstrumentLayer<I> {
+    instrument: I,
+}
+
+impl<I> InstrumentLayer<I> {
+    /// Constructs an InstrumentLayer reporting to clones of the given instrument.
+    pub const fn new(instrument: I) -> Self {
+        Self { instrument }
+    }
+}
+
+impl<T, I> Layer<T> for InstrumentLayer<I>
+where
+    I: Instrument + Clone,
+{
+    type Output = Instrumented<T, I>;
+
+    fn layer(&self, inner: T) -> Self::Output {
+        Instrumented::new(inner, self.instrument.clone())
+    }
+}
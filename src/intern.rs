@@ -0,0 +1,119 @@
+//! Deduplication of decoded strings.
+//!
+//! Datasets often repeat the same few strings, such as keys or names, many times over. Read as
+//! [Arc<str>] with an [Interner] as their seed, identical strings share a single allocation.
+//!
+//! ```
+//! use std::io;
+//! use std::io::Cursor;
+//! use std::sync::Arc;
+//!
+//! use tora::intern::Interner;
+//! use tora::read::ToraRead;
+//! use tora::ReadStruct;
+//!
+//! #[derive(ReadStruct)]
+//! #[tora(seed = Interner)]
+//! struct Label {
+//!     #[tora(seed)]
+//!     key: Arc<str>,
+//!     value: u8,
+//! }
+//!
+//! fn main() -> io::Result<()> {
+//!     let mut interner = Interner::new();
+//!     let mut cursor = Cursor::new(b"\x02\0\0\0color\0\x01color\0\x02");
+//!
+//!     let labels: Vec<Label> = cursor.reads_seed(&mut interner)?;
+//!
+//!     assert!(Arc::ptr_eq(&labels[0].key, &labels[1].key));
+//!     assert_eq!(interner.len(), 1);
+//!     Ok(())
+//! }
+//! ```
+
+use std::collections::HashSet;
+use std::io;
+use std::io::Read;
+use std::sync::Arc;
+
+use crate::read::{FromReaderSeed, ToraRead};
+
+/// A bounded cache of strings, shared by the [Arc<str>] values read with it as their seed.
+///
+/// Once the cache holds as many strings as its capacity, new strings are still read, but no
+/// longer cached, so memory stays bounded when most strings are unique.
+#[derive(Clone, Debug)]
+pub struct Interner {
+    strings: HashSet<Arc<str>>,
+    capacity: usize,
+}
+
+impl Interner {
+    /// The capacity of interners constructed with [Interner::new].
+    pub const DEFAULT_CAPACITY: usize = 4096;
+
+    /// Constructs an empty interner caching up to [Interner::DEFAULT_CAPACITY] strings.
+    pub fn new() -> Self {
+        Self::with_capacity(Self::DEFAULT_CAPACITY)
+    }
+
+    /// Constructs an empty interner caching up to `capacity` strings.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            strings: HashSet::new(),
+            capacity,
+        }
+    }
+
+    /// Returns the shared instance of the string, caching it if it is not cached yet and the
+    /// cache is not full.
+    pub fn intern(&mut self, s: &str) -> Arc<str> {
+        if let Some(shared) = self.strings.get(s) {
+            return shared.clone();
+        }
+        let shared = Arc::<str>::from(s);
+
+        if self.strings.len() < self.capacity {
+            self.strings.insert(shared.clone());
+        }
+        shared
+    }
+
+    /// Returns the amount of cached strings.
+    pub fn len(&self) -> usize {
+        self.strings.len()
+    }
+
+    /// Returns true if no strings are cached.
+    pub fn is_empty(&self) -> bool {
+        self.strings.is_empty()
+    }
+
+    /// Returns the maximum amount of cached strings.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Removes every cached string. Values already read keep their allocations.
+    pub fn clear(&mut self) {
+        self.strings.clear();
+    }
+}
+
+impl Default for Interner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FromReaderSeed<Interner> for Arc<str> {
+    /// Reads a [String], returning the shared instance of identical strings read before.
+    fn from_reader_seed<R>(r: &mut R, seed: &mut Interner) -> io::Result<Self>
+    where
+        R: Read,
+    {
+        let s = r.reads::<String>()?;
+        Ok(seed.intern(&s))
+    }
+}
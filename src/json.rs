@@ -0,0 +1,887 @@
+//! Conversion between the wire format and JSON, guided by a [Schema].
+//!
+//! [from_json] encodes a JSON document, such as a test fixture written by hand, into the bytes
+//! the described type serializes to. [to_json] decodes bytes, such as a captured frame, into a
+//! JSON document for inspection, with one value per line so that changes diff cleanly.
+//!
+//! Values map to JSON as follows:
+//!
+//! * `()` is `null`, bools are booleans, and integers are numbers of any size.
+//! * Floats are numbers, or the strings `"NaN"`, `"inf"` and `"-inf"`.
+//! * Chars and strings are strings.
+//! * `Option`s are `null` or their value. `Some(None)` cannot be told apart from `None`.
+//! * `Result`s are an object with a single `"Ok"` or `"Err"` key.
+//! * Collections, arrays and tuples are arrays.
+//! * Structs are objects holding every field, tuple struct fields being named by their index.
+//! * Enum variants without fields are their name, and other variants an object with their name
+//!   as single key, holding an object of their fields. Fallback variants read from an unknown ID
+//!   are an object with their name as single key, holding the ID.
+//!
+//! Padding is written as zero bytes and skipped, and does not appear in the JSON.
+//!
+//! ```
+//! use std::io;
+//!
+//! use tora::config::ToraConfig;
+//! use tora::json::{from_json, to_json};
+//! use tora::schema::Reflect;
+//! use tora::{Reflect, WriteStruct};
+//!
+//! #[derive(Reflect, WriteStruct)]
+//! struct Move {
+//!     id: u8,
+//!     to: (i16, i16),
+//!     note: Option<String>,
+//! }
+//!
+//! fn main() -> io::Result<()> {
+//!     let schema = Move::schema();
+//!     let bytes = tora::testing::to_bytes(&Move { id: 7, to: (-1, 2), note: None });
+//!
+//!     let json = to_json(&bytes, &schema, &ToraConfig::DEFAULT)?;
+//!     assert_eq!(json, "{\n  \"id\": 7,\n  \"to\": [-1, 2],\n  \"note\": null\n}");
+//!
+//!     let fixture = r#"{ "id": 7, "to": [-1, 2], "note": null }"#;
+//!     assert_eq!(from_json(fixture, &schema, &ToraConfig::DEFAULT)?, bytes);
+//!     Ok(())
+//! }
+//! ```
+
+use std::io;
+use std::io::{Cursor, ErrorKind};
+
+use crate::compact::check_decoded_length;
+use crate::config::ToraConfig;
+use crate::read::ToraRead;
+use crate::schema::{format_path, read_primitive, EnumSchema, FieldSchema, Schema, Segment, Value};
+use crate::write::SerializeIo;
+
+/// The deepest nesting of JSON arrays and objects [from_json] parses.
+const MAX_DEPTH: usize = 128;
+
+/// Decodes the value described by `schema`, which must occupy all of `bytes`, into a JSON
+/// document indented by two spaces.
+///
+/// Returns [ErrorKind::InvalidData] if the bytes do not hold a valid value of the schema, or
+/// bytes are left after it. Collections of zero-size elements, such as `()`, take no bytes, so
+/// their length is limited to the configured maximum length, or to
+/// [DEFAULT_MAX_DECODED](crate::compact::DEFAULT_MAX_DECODED) elements if none is set.
+///
+/// ```
+/// use std::io::ErrorKind;
+///
+/// use tora::config::ToraConfig;
+/// use tora::json::to_json;
+/// use tora::schema::Reflect;
+///
+/// let bytes = u32::MAX.to_le_bytes();
+/// let err = to_json(&bytes, &<Vec<()>>::schema(), &ToraConfig::DEFAULT).unwrap_err();
+/// assert_eq!(err.kind(), ErrorKind::InvalidData);
+/// ```
+pub fn to_json(bytes: &[u8], schema: &Schema, config: &ToraConfig) -> io::Result<String> {
+    let mut decoder = Decoder {
+        cursor: Cursor::new(bytes),
+        config,
+        out: String::new(),
+        indent: 0,
+    };
+    decoder.value(schema)?;
+
+    if decoder.cursor.position() as usize != bytes.len() {
+        return Err(io::Error::new(
+            ErrorKind::InvalidData,
+            "Bytes are left after the value",
+        ));
+    }
+    Ok(decoder.out)
+}
+
+/// Encodes a JSON document into the bytes of the value described by `schema`.
+///
+/// Returns [ErrorKind::InvalidData] if the document is not valid JSON, nests arrays and objects
+/// more than 128 deep, or does not match the schema, with the path of the offending value.
+///
+/// ```
+/// use std::io::ErrorKind;
+///
+/// use tora::config::ToraConfig;
+/// use tora::json::from_json;
+/// use tora::schema::Reflect;
+///
+/// let json = "[".repeat(100_000);
+/// let err = from_json(&json, &<Vec<u8>>::schema(), &ToraConfig::DEFAULT).unwrap_err();
+/// assert_eq!(err.kind(), ErrorKind::InvalidData);
+/// ```
+pub fn from_json(json: &str, schema: &Schema, config: &ToraConfig) -> io::Result<Vec<u8>> {
+    let mut parser = Parser {
+        bytes: json.as_bytes(),
+        pos: 0,
+        depth: 0,
+    };
+    let document = parser.document()?;
+
+    let mut encoder = Encoder {
+        config,
+        path: Vec::new(),
+    };
+    let mut bytes = Vec::new();
+    encoder.value(&document, schema, &mut bytes)?;
+    Ok(bytes)
+}
+
+/// Runs a command line converter between JSON and the value described by `schema`.
+///
+/// The arguments, program name excluded, are `encode` or `decode`, then optionally the input and
+/// output paths, defaulting to the standard input and output. `encode` converts JSON to bytes,
+/// and `decode` bytes to JSON.
+///
+/// Returns [ErrorKind::InvalidInput] with the usage if the arguments are invalid.
+///
+/// ```no_run
+/// use std::io;
+///
+/// use tora::config::ToraConfig;
+/// use tora::schema::Reflect;
+/// use tora::Reflect;
+///
+/// #[derive(Reflect)]
+/// struct Login {
+///     user: String,
+///     password: String,
+/// }
+///
+/// fn main() -> io::Result<()> {
+///     tora::json::cli(&Login::schema(), &ToraConfig::DEFAULT, std::env::args().skip(1))
+/// }
+/// ```
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+pub fn cli<I>(schema: &Schema, config: &ToraConfig, args: I) -> io::Result<()>
+where
+    I: IntoIterator<Item = String>,
+{
+    use std::fs;
+    use std::io::{Read, Write};
+
+    let mut args = args.into_iter();
+    let (command, input, output) = (args.next(), args.next(), args.next());
+
+    let usage = || {
+        io::Error::new(
+            ErrorKind::InvalidInput,
+            "Usage: <encode|decode> [input path] [output path]",
+        )
+    };
+    if args.next().is_some() {
+        return Err(usage());
+    }
+
+    let input = match input {
+        Some(path) => fs::read(path)?,
+        None => {
+            let mut input = Vec::new();
+            io::stdin().read_to_end(&mut input)?;
+            input
+        }
+    };
+
+    let converted = match command.as_deref() {
+        Some("encode") => {
+            let json = String::from_utf8(input).map_err(|_| {
+                io::Error::new(ErrorKind::InvalidData, "JSON input is not valid UTF-8")
+            })?;
+            from_json(&json, schema, config)?
+        }
+        Some("decode") => {
+            let mut json = to_json(&input, schema, config)?;
+            json.push('\n');
+            json.into_bytes()
+        }
+        _ => return Err(usage()),
+    };
+
+    match output {
+        Some(path) => fs::write(path, converted),
+        None => io::stdout().write_all(&converted),
+    }
+}
+
+/// Returns true if the schema is decoded by [read_primitive].
+fn is_primitive(schema: &Schema) -> bool {
+    !matches!(
+        schema,
+        Schema::Option(_)
+            | Schema::Result(_, _)
+            | Schema::Vec(_)
+            | Schema::Array(_, _)
+            | Schema::Tuple(_)
+            | Schema::Struct(_)
+            | Schema::Enum(_)
+    )
+}
+
+/// Appends the string to `out` as a JSON string.
+fn push_string(out: &mut String, s: &str) {
+    out.push('"');
+
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c < ' ' => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+/// Appends the primitive to `out` as JSON.
+fn push_primitive(out: &mut String, value: &Value) {
+    match value {
+        Value::Unit => out.push_str("null"),
+        Value::F32(n) if !n.is_finite() => push_string(out, &n.to_string()),
+        Value::F64(n) if !n.is_finite() => push_string(out, &n.to_string()),
+        Value::Char(c) => push_string(out, c.encode_utf8(&mut [0; 4])),
+        Value::String(s) => push_string(out, s),
+        value => out.push_str(&value.to_string()),
+    }
+}
+
+/// Decodes bytes into JSON.
+struct Decoder<'a> {
+    cursor: Cursor<&'a [u8]>,
+    config: &'a ToraConfig,
+    out: String,
+    indent: usize,
+}
+
+impl Decoder<'_> {
+    /// Starts a new line at the current indentation.
+    fn newline(&mut self) {
+        self.out.push('\n');
+        self.out.push_str(&"  ".repeat(self.indent));
+    }
+
+    /// Writes `len` elements between the brackets, one per line, or all on the same line if
+    /// `inline` is true.
+    fn elements<F>(&mut self, len: usize, inline: bool, mut element: F) -> io::Result<()>
+    where
+        F: FnMut(&mut Self, usize) -> io::Result<()>,
+    {
+        self.out.push('[');
+        self.indent += 1;
+
+        for i in 0..len {
+            if i > 0 {
+                self.out.push(',');
+                if inline {
+                    self.out.push(' ');
+                }
+            }
+            if !inline {
+                self.newline();
+            }
+            element(self, i)?;
+        }
+
+        self.indent -= 1;
+        if len > 0 && !inline {
+            self.newline();
+        }
+        self.out.push(']');
+        Ok(())
+    }
+
+    /// Writes an object holding a single key.
+    fn single_key<F>(&mut self, key: &str, value: F) -> io::Result<()>
+    where
+        F: FnOnce(&mut Self) -> io::Result<()>,
+    {
+        self.out.push('{');
+        self.indent += 1;
+        self.newline();
+        push_string(&mut self.out, key);
+        self.out.push_str(": ");
+        value(self)?;
+        self.indent -= 1;
+        self.newline();
+        self.out.push('}');
+        Ok(())
+    }
+
+    fn fields(&mut self, fields: &[FieldSchema]) -> io::Result<()> {
+        self.out.push('{');
+        self.indent += 1;
+
+        for (i, field) in fields.iter().enumerate() {
+            if i > 0 {
+                self.out.push(',');
+            }
+            self.newline();
+            push_string(&mut self.out, &field.name);
+            self.out.push_str(": ");
+
+            self.cursor.skip(field.pad_before)?;
+            self.value(&field.schema)?;
+            self.cursor.skip(field.pad_after)?;
+        }
+
+        self.indent -= 1;
+        if !fields.is_empty() {
+            self.newline();
+        }
+        self.out.push('}');
+        Ok(())
+    }
+
+    fn variant(&mut self, schema: &EnumSchema) -> io::Result<()> {
+        let (id, variant) = schema.read_variant(&mut self.cursor, self.config)?;
+
+        let end = match schema.length_prefixed {
+            true => {
+                let len = self.config.read_length(&mut self.cursor)?;
+                let end = (self.cursor.position() as usize)
+                    .checked_add(len)
+                    .filter(|&end| end <= self.cursor.get_ref().len())
+                    .ok_or_else(|| {
+                        io::Error::new(ErrorKind::UnexpectedEof, "Variant exceeds the input")
+                    })?;
+                Some(end)
+            }
+            false => None,
+        };
+
+        if variant.fallback {
+            self.single_key(&variant.name, |this| {
+                this.out.push_str(&id.to_string());
+                Ok(())
+            })?;
+        } else if variant.fields.is_empty() {
+            push_string(&mut self.out, &variant.name);
+        } else {
+            self.single_key(&variant.name, |this| this.fields(&variant.fields))?;
+        }
+
+        if let Some(end) = end {
+            if self.cursor.position() as usize > end {
+                return Err(io::Error::new(
+                    ErrorKind::InvalidData,
+                    "Variant exceeds its length prefix",
+                ));
+            }
+            self.cursor.set_position(end as u64);
+        }
+        Ok(())
+    }
+
+    fn value(&mut self, schema: &Schema) -> io::Result<()> {
+        if let Some(value) = read_primitive(&mut self.cursor, schema, self.config)? {
+            push_primitive(&mut self.out, &value);
+            return Ok(());
+        }
+
+        match schema {
+            Schema::Option(inner) => match self.cursor.reads_with::<bool>(self.config)? {
+                true => self.value(inner),
+                false => {
+                    self.out.push_str("null");
+                    Ok(())
+                }
+            },
+            Schema::Result(ok, err) => match self.cursor.reads_with::<bool>(self.config)? {
+                true => self.single_key("Err", |this| this.value(err)),
+                false => self.single_key("Ok", |this| this.value(ok)),
+            },
+            Schema::Vec(inner) => {
+                let len = self.config.read_length(&mut self.cursor)?;
+                if inner.fixed_size() == Some(0) {
+                    check_decoded_length(len, self.config)?;
+                }
+                self.elements(len, is_primitive(inner), |this, _| this.value(inner))
+            }
+            Schema::Array(inner, len) => {
+                self.elements(*len, is_primitive(inner), |this, _| this.value(inner))
+            }
+            Schema::Tuple(items) => {
+                let inline = items.iter().all(is_primitive);
+                self.elements(items.len(), inline, |this, i| this.value(&items[i]))
+            }
+            Schema::Struct(s) => self.fields(&s.fields),
+            Schema::Enum(e) => self.variant(e),
+            _ => unreachable!("Primitives are decoded by read_primitive"),
+        }
+    }
+}
+
+/// A parsed JSON value.
+#[derive(Debug)]
+enum Json {
+    Null,
+    Bool(bool),
+    /// The number as written, converted once its schema is known.
+    Number(String),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    /// Returns the name of the kind of this value, for error messages.
+    fn kind(&self) -> &'static str {
+        match self {
+            Self::Null => "null",
+            Self::Bool(_) => "a boolean",
+            Self::Number(_) => "a number",
+            Self::String(_) => "a string",
+            Self::Array(_) => "an array",
+            Self::Object(_) => "an object",
+        }
+    }
+}
+
+/// Parses JSON text.
+struct Parser<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    /// The amount of arrays and objects the parser is within.
+    depth: usize,
+}
+
+impl Parser<'_> {
+    fn error(&self, message: &str) -> io::Error {
+        io::Error::new(
+            ErrorKind::InvalidData,
+            format!("{message} at byte {} of the JSON", self.pos),
+        )
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(b' ' | b'\t' | b'\n' | b'\r') = self.bytes.get(self.pos) {
+            self.pos += 1;
+        }
+    }
+
+    fn peek(&mut self) -> Option<u8> {
+        self.skip_whitespace();
+        self.bytes.get(self.pos).copied()
+    }
+
+    fn expect(&mut self, byte: u8) -> io::Result<()> {
+        if self.peek() != Some(byte) {
+            return Err(self.error(&format!("Expected '{}'", byte as char)));
+        }
+        self.pos += 1;
+        Ok(())
+    }
+
+    fn document(&mut self) -> io::Result<Json> {
+        let value = self.value()?;
+
+        if self.peek().is_some() {
+            return Err(self.error("Unexpected characters after the document"));
+        }
+        Ok(value)
+    }
+
+    fn literal(&mut self, literal: &str, value: Json) -> io::Result<Json> {
+        if !self.bytes[self.pos..].starts_with(literal.as_bytes()) {
+            return Err(self.error("Invalid literal"));
+        }
+        self.pos += literal.len();
+        Ok(value)
+    }
+
+    fn value(&mut self) -> io::Result<Json> {
+        match self.peek() {
+            Some(b'n') => self.literal("null", Json::Null),
+            Some(b't') => self.literal("true", Json::Bool(true)),
+            Some(b'f') => self.literal("false", Json::Bool(false)),
+            Some(b'"') => self.string().map(Json::String),
+            Some(b'[') => self.nested(Self::array),
+            Some(b'{') => self.nested(Self::object),
+            Some(b'-' | b'0'..=b'9') => Ok(self.number()),
+            Some(_) => Err(self.error("Unexpected character")),
+            None => Err(self.error("Unexpected end")),
+        }
+    }
+
+    /// Parses an array or object, failing instead of overflowing the stack on deep nesting.
+    fn nested<F>(&mut self, parse: F) -> io::Result<Json>
+    where
+        F: FnOnce(&mut Self) -> io::Result<Json>,
+    {
+        if self.depth == MAX_DEPTH {
+            return Err(self.error("Too deeply nested"));
+        }
+        self.depth += 1;
+        let json = parse(self);
+        self.depth -= 1;
+        json
+    }
+
+    fn number(&mut self) -> Json {
+        let start = self.pos;
+
+        while let Some(b'-' | b'+' | b'.' | b'e' | b'E' | b'0'..=b'9') = self.bytes.get(self.pos) {
+            self.pos += 1;
+        }
+        // The bytes are ASCII.
+        Json::Number(String::from_utf8_lossy(&self.bytes[start..self.pos]).into_owned())
+    }
+
+    fn hex4(&mut self) -> io::Result<u32> {
+        let hex = self
+            .bytes
+            .get(self.pos..self.pos + 4)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+            .ok_or_else(|| self.error("Invalid unicode escape"))?;
+        self.pos += 4;
+        Ok(hex)
+    }
+
+    fn escape(&mut self) -> io::Result<char> {
+        let byte = *self
+            .bytes
+            .get(self.pos)
+            .ok_or_else(|| self.error("Unexpected end"))?;
+        self.pos += 1;
+
+        Ok(match byte {
+            b'"' => '"',
+            b'\\' => '\\',
+            b'/' => '/',
+            b'b' => '\u{8}',
+            b'f' => '\u{c}',
+            b'n' => '\n',
+            b'r' => '\r',
+            b't' => '\t',
+            b'u' => {
+                let mut code = self.hex4()?;
+
+                if (0xD800..0xDC00).contains(&code) {
+                    if !self.bytes[self.pos..].starts_with(b"\\u") {
+                        return Err(self.error("Unpaired surrogate"));
+                    }
+                    self.pos += 2;
+                    let low = self.hex4()?;
+
+                    if !(0xDC00..0xE000).contains(&low) {
+                        return Err(self.error("Unpaired surrogate"));
+                    }
+                    code = 0x10000 + ((code - 0xD800) << 10) + (low - 0xDC00);
+                }
+                char::from_u32(code).ok_or_else(|| self.error("Unpaired surrogate"))?
+            }
+            _ => return Err(self.error("Invalid escape")),
+        })
+    }
+
+    fn string(&mut self) -> io::Result<String> {
+        self.expect(b'"')?;
+        let mut s = String::new();
+
+        loop {
+            let start = self.pos;
+            while let Some(&byte) = self.bytes.get(self.pos) {
+                if byte == b'"' || byte == b'\\' || byte < b' ' {
+                    break;
+                }
+                self.pos += 1;
+            }
+            // The input is a str, and the run stops at ASCII bytes only.
+            s.push_str(std::str::from_utf8(&self.bytes[start..self.pos]).unwrap());
+
+            match self.bytes.get(self.pos) {
+                Some(b'"') => {
+                    self.pos += 1;
+                    return Ok(s);
+                }
+                Some(b'\\') => {
+                    self.pos += 1;
+                    s.push(self.escape()?);
+                }
+                Some(_) => return Err(self.error("Control character in string")),
+                None => return Err(self.error("Unterminated string")),
+            }
+        }
+    }
+
+    fn array(&mut self) -> io::Result<Json> {
+        self.expect(b'[')?;
+        let mut elements = Vec::new();
+
+        if self.peek() == Some(b']') {
+            self.pos += 1;
+            return Ok(Json::Array(elements));
+        }
+        loop {
+            elements.push(self.value()?);
+
+            match self.peek() {
+                Some(b',') => self.pos += 1,
+                Some(b']') => {
+                    self.pos += 1;
+                    return Ok(Json::Array(elements));
+                }
+                _ => return Err(self.error("Expected ',' or ']'")),
+            }
+        }
+    }
+
+    fn object(&mut self) -> io::Result<Json> {
+        self.expect(b'{')?;
+        let mut members = Vec::new();
+
+        if self.peek() == Some(b'}') {
+            self.pos += 1;
+            return Ok(Json::Object(members));
+        }
+        loop {
+            if self.peek() != Some(b'"') {
+                return Err(self.error("Expected a key"));
+            }
+            let key = self.string()?;
+            self.expect(b':')?;
+            members.push((key, self.value()?));
+
+            match self.peek() {
+                Some(b',') => self.pos += 1,
+                Some(b'}') => {
+                    self.pos += 1;
+                    return Ok(Json::Object(members));
+                }
+                _ => return Err(self.error("Expected ',' or '}'")),
+            }
+        }
+    }
+}
+
+/// Encodes parsed JSON into bytes.
+struct Encoder<'a, 's> {
+    config: &'a ToraConfig,
+    /// The path to the value being encoded.
+    path: Vec<Segment<'s>>,
+}
+
+impl<'s> Encoder<'_, 's> {
+    fn error(&self, message: &str) -> io::Error {
+        let message = match self.path.is_empty() {
+            true => format!("{message} at the root"),
+            false => format!("{message} at {}", format_path(&self.path)),
+        };
+        io::Error::new(ErrorKind::InvalidData, message)
+    }
+
+    fn mismatch(&self, expected: &str, json: &Json) -> io::Error {
+        self.error(&format!("Expected {expected}, found {}", json.kind()))
+    }
+
+    fn nested(
+        &mut self,
+        segment: Segment<'s>,
+        json: &Json,
+        schema: &'s Schema,
+        out: &mut Vec<u8>,
+    ) -> io::Result<()> {
+        self.path.push(segment);
+        self.value(json, schema, out)?;
+        self.path.pop();
+        Ok(())
+    }
+
+    /// Converts a number to the primitive of the given schema.
+    fn number(&self, text: &str, schema: &Schema) -> io::Result<Value> {
+        fn parse<T>(text: &str) -> Option<T>
+        where
+            T: std::str::FromStr,
+        {
+            text.parse().ok()
+        }
+
+        let value = match schema {
+            Schema::U8 => parse(text).map(Value::U8),
+            Schema::U16 => parse(text).map(Value::U16),
+            Schema::U32 => parse(text).map(Value::U32),
+            Schema::U64 => parse(text).map(Value::U64),
+            Schema::U128 => parse(text).map(Value::U128),
+            Schema::I8 => parse(text).map(Value::I8),
+            Schema::I16 => parse(text).map(Value::I16),
+            Schema::I32 => parse(text).map(Value::I32),
+            Schema::I64 => parse(text).map(Value::I64),
+            Schema::I128 => parse(text).map(Value::I128),
+            Schema::F32 => parse(text).map(Value::F32),
+            Schema::F64 => parse(text).map(Value::F64),
+            Schema::Usize => parse(text).map(Value::Usize),
+            _ => None,
+        };
+        value.ok_or_else(|| self.error(&format!("{text} is not a valid {schema:?}")))
+    }
+
+    /// Converts JSON to the primitive of the given schema.
+    fn primitive(&self, json: &Json, schema: &Schema) -> io::Result<Value> {
+        Ok(match (schema, json) {
+            (Schema::Unit, Json::Null) => Value::Unit,
+            (Schema::Unit, json) => return Err(self.mismatch("null", json)),
+            (Schema::Bool, Json::Bool(b)) => Value::Bool(*b),
+            (Schema::Bool, json) => return Err(self.mismatch("a boolean", json)),
+            (Schema::F32 | Schema::F64, Json::String(text)) => self.number(text, schema)?,
+            (Schema::Char, Json::String(s)) => {
+                let mut chars = s.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => Value::Char(c),
+                    _ => return Err(self.error("Expected a single character")),
+                }
+            }
+            (Schema::Char, json) => return Err(self.mismatch("a string", json)),
+            (Schema::String, Json::String(s)) => Value::String(s.clone()),
+            (Schema::String, json) => return Err(self.mismatch("a string", json)),
+            (schema, Json::Number(text)) => self.number(text, schema)?,
+            (_, json) => return Err(self.mismatch("a number", json)),
+        })
+    }
+
+    fn elements(
+        &mut self,
+        json: &Json,
+        len: Option<usize>,
+        schema: impl Fn(usize) -> &'s Schema,
+        out: &mut Vec<u8>,
+    ) -> io::Result<()> {
+        let Json::Array(elements) = json else {
+            return Err(self.mismatch("an array", json));
+        };
+        match len {
+            Some(len) if elements.len() != len => {
+                return Err(self.error(&format!(
+                    "Expected {len} elements, found {}",
+                    elements.len()
+                )));
+            }
+            Some(_) => {}
+            None => self.config.write_length(out, elements.len())?,
+        }
+
+        for (i, element) in elements.iter().enumerate() {
+            self.nested(Segment::Index(i), element, schema(i), out)?;
+        }
+        Ok(())
+    }
+
+    fn fields(
+        &mut self,
+        json: &Json,
+        fields: &'s [FieldSchema],
+        out: &mut Vec<u8>,
+    ) -> io::Result<()> {
+        let Json::Object(members) = json else {
+            return Err(self.mismatch("an object", json));
+        };
+        if let Some((key, _)) = members
+            .iter()
+            .find(|(key, _)| !fields.iter().any(|f| &f.name == key))
+        {
+            return Err(self.error(&format!("Unknown field `{key}`")));
+        }
+
+        for field in fields {
+            let Some((_, value)) = members.iter().find(|(key, _)| *key == field.name) else {
+                return Err(self.error(&format!("Missing field `{}`", field.name)));
+            };
+            out.resize(out.len() + field.pad_before, 0);
+            self.nested(Segment::Field(&field.name), value, &field.schema, out)?;
+            out.resize(out.len() + field.pad_after, 0);
+        }
+        Ok(())
+    }
+
+    fn variant(
+        &mut self,
+        json: &Json,
+        schema: &'s EnumSchema,
+        out: &mut Vec<u8>,
+    ) -> io::Result<()> {
+        let (name, mut fields) = match json {
+            Json::String(name) => (name, None),
+            Json::Object(members) if members.len() == 1 => (&members[0].0, Some(&members[0].1)),
+            json => {
+                return Err(self.mismatch("a variant name or an object with a single key", json))
+            }
+        };
+        let Some(variant) = schema.variants.iter().find(|v| v.name == *name) else {
+            return Err(self.error(&format!("Unknown variant `{name}`")));
+        };
+
+        let id = match (variant.fallback, fields) {
+            // The fallback variant keeps the unknown ID it was read from.
+            (true, Some(id @ Json::Number(_))) => {
+                fields = None;
+                Some(self.primitive(id, &schema.id)?)
+            }
+            _ => None,
+        };
+        let id = id.or_else(|| match *schema.id {
+            Schema::U8 => u8::try_from(variant.id).ok().map(Value::U8),
+            Schema::U16 => u16::try_from(variant.id).ok().map(Value::U16),
+            Schema::U32 => u32::try_from(variant.id).ok().map(Value::U32),
+            Schema::U64 => Some(Value::U64(variant.id)),
+            Schema::I8 => i8::try_from(variant.id).ok().map(Value::I8),
+            Schema::I16 => i16::try_from(variant.id).ok().map(Value::I16),
+            Schema::I32 => i32::try_from(variant.id).ok().map(Value::I32),
+            Schema::I64 => i64::try_from(variant.id).ok().map(Value::I64),
+            _ => None,
+        });
+        let id = id.ok_or_else(|| self.error("The variant ID does not fit its schema"))?;
+        id.serialize_with(out, self.config)?;
+
+        let empty = Json::Object(Vec::new());
+        let fields = fields.unwrap_or(&empty);
+
+        self.path.push(Segment::Variant(&variant.name));
+        if schema.length_prefixed {
+            let mut payload = Vec::new();
+            self.fields(fields, &variant.fields, &mut payload)?;
+            self.config.write_length(out, payload.len())?;
+            out.extend_from_slice(&payload);
+        } else {
+            self.fields(fields, &variant.fields, out)?;
+        }
+        self.path.pop();
+        Ok(())
+    }
+
+    fn value(&mut self, json: &Json, schema: &'s Schema, out: &mut Vec<u8>) -> io::Result<()> {
+        match schema {
+            Schema::Option(inner) => match json {
+                Json::Null => false.serialize_with(out, self.config),
+                json => {
+                    true.serialize_with(out, self.config)?;
+                    self.value(json, inner, out)
+                }
+            },
+            Schema::Result(ok, err) => match json {
+                Json::Object(members) if members.len() == 1 => {
+                    let (key, value) = &members[0];
+                    let (is_err, schema, name) = match key.as_str() {
+                        "Ok" => (false, ok, "Ok"),
+                        "Err" => (true, err, "Err"),
+                        _ => return Err(self.error("Expected an `Ok` or `Err` key")),
+                    };
+                    is_err.serialize_with(out, self.config)?;
+                    self.nested(Segment::Variant(name), value, schema, out)
+                }
+                json => Err(self.mismatch("an object with a single key", json)),
+            },
+            Schema::Vec(inner) => self.elements(json, None, |_| inner, out),
+            Schema::Array(inner, len) => self.elements(json, Some(*len), |_| inner, out),
+            Schema::Tuple(items) => self.elements(json, Some(items.len()), |i| &items[i], out),
+            Schema::Struct(s) => self.fields(json, &s.fields, out),
+            Schema::Enum(e) => self.variant(json, e, out),
+            schema => self
+                .primitive(json, schema)?
+                .serialize_with(out, self.config),
+        }
+    }
+}
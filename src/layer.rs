@@ -0,0 +1,293 @@
+//! Composable adapters around readers and writers.
+//!
+//! A [Layer] wraps a transport in another, such as a buffer, a byte counter, a
+//! [digest](crate::digest) or a compressor. Layers are composed declaratively with a [Pipeline]
+//! instead of nesting wrapper types by hand.
+//!
+//! ```
+//! use std::io;
+//! use std::io::Write;
+//!
+//! use tora::layer::{BufWriteLayer, CountWriteLayer, Pipeline};
+//! use tora::write::ToraWrite;
+//!
+//! fn main() -> io::Result<()> {
+//!     let pipeline = Pipeline::new()
+//!         .layer(CountWriteLayer)
+//!         .layer(BufWriteLayer::with_capacity(1024));
+//!
+//!     // Equivalent to ByteCountWriter::new(BufWriter::with_capacity(1024, Vec::new())).
+//!     let mut writer = pipeline.wrap(Vec::new());
+//!
+//!     writer.writes(&7u32)?;
+//!     writer.flush()?;
+//!
+//!     assert_eq!(writer.count(), 4);
+//!     assert_eq!(writer.get_ref().get_ref(), &[7, 0, 0, 0]);
+//!     Ok(())
+//! }
+//! ```
+
+use std::io::{BufReader, BufWriter, Read, Write};
+
+use crate::digest::{Digest, DigestReader, DigestWriter};
+use crate::read::ByteCountReader;
+use crate::write::ByteCountWriter;
+
+/// Wraps a transport of type [T] in another transport.
+pub trait Layer<T> {
+    /// The wrapped transport.
+    type Output;
+
+    /// Wraps the given transport.
+    fn layer(&self, inner: T) -> Self::Output;
+}
+
+/// A layer that returns the transport unchanged.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Identity;
+
+impl<T> Layer<T> for Identity {
+    type Output = T;
+
+    fn layer(&self, inner: T) -> Self::Output {
+        inner
+    }
+}
+
+/// Two layers applied in sequence, [Inner] first.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Stack<Inner, Outer> {
+    inner: Inner,
+    outer: Outer,
+}
+
+impl<Inner, Outer> Stack<Inner, Outer> {
+    /// Constructs a Stack applying `inner`, then `outer`.
+    pub const fn new(inner: Inner, outer: Outer) -> Self {
+        Self { inner, outer }
+    }
+}
+
+impl<T, Inner, Outer> Layer<T> for Stack<Inner, Outer>
+where
+    Inner: Layer<T>,
+    Outer: Layer<Inner::Output>,
+{
+    type Output = Outer::Output;
+
+    fn layer(&self, inner: T) -> Self::Output {
+        self.outer.layer(self.inner.layer(inner))
+    }
+}
+
+/// A layer created from a closure.
+///
+/// Returned by [layer_fn].
+#[derive(Clone, Copy, Debug)]
+pub struct LayerFn<F> {
+    f: F,
+}
+
+impl<T, F, O> Layer<T> for LayerFn<F>
+where
+    F: Fn(T) -> O,
+{
+    type Output = O;
+
+    fn layer(&self, inner: T) -> Self::Output {
+        (self.f)(inner)
+    }
+}
+
+/// Returns a layer wrapping transports with the given closure.
+///
+/// ```
+/// use std::io::{BufReader, Cursor};
+///
+/// use tora::layer::{layer_fn, Layer};
+///
+/// let buffered = layer_fn(BufReader::new);
+/// let reader = buffered.layer(Cursor::new([1, 2, 3]));
+/// ```
+pub const fn layer_fn<F>(f: F) -> LayerFn<F> {
+    LayerFn { f }
+}
+
+/// Composes layers, wrapping transports with all of them at once.
+///
+/// Layers are applied outermost first: the first layer added is the last to wrap the transport,
+/// and therefore the first to see each read or write.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Pipeline<L> {
+    layer: L,
+}
+
+impl Pipeline<Identity> {
+    /// Constructs an empty Pipeline.
+    pub const fn new() -> Self {
+        Self { layer: Identity }
+    }
+}
+
+impl<L> Pipeline<L> {
+    /// Adds a layer below the layers already in this pipeline.
+    pub fn layer<N>(self, layer: N) -> Pipeline<Stack<N, L>> {
+        Pipeline {
+            layer: Stack::new(layer, self.layer),
+        }
+    }
+
+    /// Wraps the given transport with every layer in this pipeline.
+    pub fn wrap<T>(&self, transport: T) -> L::Output
+    where
+        L: Layer<T>,
+    {
+        self.layer.layer(transport)
+    }
+
+    /// Returns the composed layer.
+    pub fn into_inner(self) -> L {
+        self.layer
+    }
+}
+
+/// A layer wrapping readers in a [ByteCountReader].
+#[derive(Clone, Copy, Debug, Default)]
+pub struct CountReadLayer;
+
+impl<R> Layer<R> for CountReadLayer
+where
+    R: Read,
+{
+    type Output = ByteCountReader<R>;
+
+    fn layer(&self, inner: R) -> Self::Output {
+        ByteCountReader::new(inner)
+    }
+}
+
+/// A layer wrapping writers in a [ByteCountWriter].
+#[derive(Clone, Copy, Debug, Default)]
+pub struct CountWriteLayer;
+
+impl<W> Layer<W> for CountWriteLayer
+where
+    W: Write,
+{
+    type Output = ByteCountWriter<W>;
+
+    fn layer(&self, inner: W) -> Self::Output {
+        ByteCountWriter::new(inner)
+    }
+}
+
+/// A layer wrapping readers in a [BufReader].
+#[derive(Clone, Copy, Debug)]
+pub struct BufReadLayer {
+    capacity: usize,
+}
+
+impl BufReadLayer {
+    /// Constructs a BufReadLayer with the given buffer capacity.
+    pub const fn with_capacity(capacity: usize) -> Self {
+        Self { capacity }
+    }
+}
+
+impl Default for BufReadLayer {
+    fn default() -> Self {
+        Self::with_capacity(8 * 1024)
+    }
+}
+
+impl<R> Layer<R> for BufReadLayer
+where
+    R: Read,
+{
+    type Output = BufReader<R>;
+
+    fn layer(&self, inner: R) -> Self::Output {
+        BufReader::with_capacity(self.capacity, inner)
+    }
+}
+
+/// A layer wrapping writers in a [BufWriter].
+#[derive(Clone, Copy, Debug)]
+pub struct BufWriteLayer {
+    capacity: usize,
+}
+
+impl BufWriteLayer {
+    /// Constructs a BufWriteLayer with the given buffer capacity.
+    pub const fn with_capacity(capacity: usize) -> Self {
+        Self { capacity }
+    }
+}
+
+impl Default for BufWriteLayer {
+    fn default() -> Self {
+        Self::with_capacity(8 * 1024)
+    }
+}
+
+impl<W> Layer<W> for BufWriteLayer
+where
+    W: Write,
+{
+    type Output = BufWriter<W>;
+
+    fn layer(&self, inner: W) -> Self::Output {
+        BufWriter::with_capacity(self.capacity, inner)
+    }
+}
+
+/// A layer wrapping readers in a [DigestReader], feeding a copy of the given digest.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DigestReadLayer<D> {
+    digest: D,
+}
+
+impl<D> DigestReadLayer<D> {
+    /// Constructs a DigestReadLayer feeding copies of the given digest.
+    pub const fn new(digest: D) -> Self {
+        Self { digest }
+    }
+}
+
+impl<R, D> Layer<R> for DigestReadLayer<D>
+where
+    R: Read,
+    D: Digest + Clone,
+{
+    type Output = DigestReader<R, D>;
+
+    fn layer(&self, inner: R) -> Self::Output {
+        DigestReader::new(inner, self.digest.clone())
+    }
+}
+
+/// A layer wrapping writers in a [DigestWriter], feeding a copy of the given digest.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DigestWriteLayer<D> {
+    digest: D,
+}
+
+impl<D> DigestWriteLayer<D> {
+    /// Constructs a DigestWriteLayer feeding copies of the given digest.
+    pub const fn new(digest: D) -> Self {
+        Self { digest }
+    }
+}
+
+impl<W, D> Layer<W> for DigestWriteLayer<D>
+where
+    W: Write,
+    D: Digest + Clone,
+{
+    type Output = DigestWriter<W, D>;
+
+    fn layer(&self, inner: W) -> Self::Output {
+        DigestWriter::new(inner, self.digest.clone())
+    }
+}
@@ -0,0 +1,291 @@
+//! Support for fixed memory layouts.
+
+use std::cell::{Cell, RefCell};
+use std::io;
+use std::io::{ErrorKind, Seek, SeekFrom, Write};
+use std::mem::{align_of, size_of};
+use std::sync::{Mutex, RwLock};
+use std::time::Duration;
+
+use crate::config::ToraConfig;
+use crate::schema::{format_path, Reflect, Segment};
+use crate::write::{SerializeIo, ToraWrite};
+
+/// Marks a type as always serializing to the same amount of bytes.
+///
+/// Can be derived with the `ConstSize` derive macro for structs whose fields are all ConstSize,
+/// and for enums without fields.
+///
+/// ```
+/// use tora::layout::ConstSize;
+///
+/// assert_eq!(<(u8, [u16; 4], char)>::SIZE, 13);
+/// ```
+pub trait ConstSize {
+    /// The amount of bytes this type serializes to.
+    const SIZE: usize;
+}
+
+macro_rules! const_size_impl {
+    ($($t:ty),*) => {
+        $(
+        impl ConstSize for $t {
+            const SIZE: usize = size_of::<$t>();
+        }
+        )*
+    };
+}
+
+const_size_impl!(u8, u16, u32, u64, u128, i8, i16, i32, i64, i128, f32, f64);
+
+/// Written as a [u64] on every target.
+impl ConstSize for usize {
+    const SIZE: usize = 8;
+}
+
+impl ConstSize for bool {
+    const SIZE: usize = 1;
+}
+
+impl ConstSize for char {
+    const SIZE: usize = 4;
+}
+
+impl ConstSize for () {
+    const SIZE: usize = 0;
+}
+
+impl ConstSize for Duration {
+    const SIZE: usize = 12;
+}
+
+impl<T, const N: usize> ConstSize for [T; N]
+where
+    T: ConstSize,
+{
+    const SIZE: usize = T::SIZE * N;
+}
+
+impl<T, Z> ConstSize for (T, Z)
+where
+    T: ConstSize,
+    Z: ConstSize,
+{
+    const SIZE: usize = T::SIZE + Z::SIZE;
+}
+
+impl<T, Z, H> ConstSize for (T, Z, H)
+where
+    T: ConstSize,
+    Z: ConstSize,
+    H: ConstSize,
+{
+    const SIZE: usize = T::SIZE + Z::SIZE + H::SIZE;
+}
+
+impl<T> ConstSize for Box<T>
+where
+    T: ConstSize,
+{
+    const SIZE: usize = T::SIZE;
+}
+
+impl<T> ConstSize for Mutex<T>
+where
+    T: ConstSize,
+{
+    const SIZE: usize = T::SIZE;
+}
+
+impl<T> ConstSize for RwLock<T>
+where
+    T: ConstSize,
+{
+    const SIZE: usize = T::SIZE;
+}
+
+impl<T> ConstSize for Cell<T>
+where
+    T: ConstSize,
+{
+    const SIZE: usize = T::SIZE;
+}
+
+impl<T> ConstSize for RefCell<T>
+where
+    T: ConstSize,
+{
+    const SIZE: usize = T::SIZE;
+}
+
+/// Tracks the offsets of fields laid out as in a `#[repr(C)]` struct.
+///
+/// Used by `#[tora(repr_c)]` to compute the alignment padding between fields. Each field is
+/// assumed to be serialized in exactly `size_of::<T>()` bytes, which holds for integers, floats,
+/// arrays of them, and other `#[tora(repr_c)]` structs. It does not hold for [usize] on 32-bit
+/// targets, where it is serialized in 8 bytes.
+///
+/// ```
+/// use tora::layout::ReprC;
+///
+/// // struct { a: u8, b: u32, c: u16 }
+/// let mut layout = ReprC::new();
+///
+/// assert_eq!(layout.field::<u8>(), 0);
+/// assert_eq!(layout.field::<u32>(), 3);
+/// assert_eq!(layout.field::<u16>(), 0);
+/// assert_eq!(layout.finish(), 2);
+/// assert_eq!(layout.size(), 12);
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct ReprC {
+    offset: usize,
+    align: usize,
+}
+
+impl ReprC {
+    /// Constructs a layout starting at offset zero.
+    pub const fn new() -> Self {
+        Self {
+            offset: 0,
+            align: 1,
+        }
+    }
+
+    /// Returns the padding needed before a field of type [T], then advances past the field.
+    pub const fn field<T>(&mut self) -> usize {
+        let align = align_of::<T>();
+        let padding = Self::padding(self.offset, align);
+
+        self.offset += padding + size_of::<T>();
+
+        if align > self.align {
+            self.align = align;
+        }
+        padding
+    }
+
+    /// Returns the trailing padding needed to round the size up to the struct's alignment, then
+    /// advances past it.
+    pub const fn finish(&mut self) -> usize {
+        let padding = Self::padding(self.offset, self.align);
+        self.offset += padding;
+        padding
+    }
+
+    /// Returns the current offset, which is the size of the struct after [ReprC::finish].
+    pub const fn size(&self) -> usize {
+        self.offset
+    }
+
+    const fn padding(offset: usize, align: usize) -> usize {
+        (align - offset % align) % align
+    }
+}
+
+impl Default for ReprC {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Overwrites a single field of a record in a table of [T] records, without reading or
+/// rewriting the rest of the record.
+///
+/// The table is a sequence of records written back to back, each [ConstSize::SIZE] bytes. The
+/// field is located by its path through the schema of [T], and must have the same schema as the
+/// value. A memory-mapped table can be updated by passing a `Cursor<&mut [u8]>` over it.
+///
+/// Returns [ErrorKind::InvalidInput] if no fixed-size field is found at the path, or if its
+/// schema differs from the value's.
+///
+/// ```
+/// use std::io;
+/// use std::io::Cursor;
+///
+/// use tora::layout::{update_field, ConstSize};
+/// use tora::read::ToraRead;
+/// use tora::schema::{Reflect, Segment};
+/// use tora::write::ToraWrite;
+/// use tora::{ConstSize, ReadStruct, Reflect, WriteStruct};
+///
+/// #[derive(ConstSize, Debug, PartialEq, ReadStruct, Reflect, WriteStruct)]
+/// struct Account {
+///     id: u64,
+///     balance: i64,
+///     frozen: bool,
+/// }
+///
+/// fn main() -> io::Result<()> {
+///     let mut table = Cursor::new(Vec::new());
+///     for id in 0..3 {
+///         table.writes(&Account { id, balance: 100, frozen: false })?;
+///     }
+///
+///     update_field::<Account, _, _>(&mut table, 1, &[Segment::Field("frozen")], &true)?;
+///
+///     table.set_position(Account::SIZE as u64);
+///     let account: Account = table.reads()?;
+///     assert_eq!(account, Account { id: 1, balance: 100, frozen: true });
+///     Ok(())
+/// }
+/// ```
+pub fn update_field<T, V, F>(
+    table: &mut F,
+    record: u64,
+    path: &[Segment],
+    value: &V,
+) -> io::Result<()>
+where
+    T: ConstSize + Reflect,
+    V: SerializeIo + Reflect,
+    F: Write + Seek,
+{
+    update_field_with::<T, V, F>(table, record, path, value, &ToraConfig::DEFAULT)
+}
+
+/// Overwrites a single field of a record in a table of [T] records, honoring the given
+/// configuration.
+///
+/// See [update_field].
+pub fn update_field_with<T, V, F>(
+    table: &mut F,
+    record: u64,
+    path: &[Segment],
+    value: &V,
+    config: &ToraConfig,
+) -> io::Result<()>
+where
+    T: ConstSize + Reflect,
+    V: SerializeIo + Reflect,
+    F: Write + Seek,
+{
+    let schema = T::schema();
+    let (offset, field) = schema
+        .offset_of(path)
+        .filter(|(_, field)| field.fixed_size().is_some())
+        .ok_or_else(|| {
+            io::Error::new(
+                ErrorKind::InvalidInput,
+                format!("No fixed-size field at `{}`", format_path(path)),
+            )
+        })?;
+
+    if *field != V::schema() {
+        return Err(io::Error::new(
+            ErrorKind::InvalidInput,
+            format!(
+                "The value does not match the schema of `{}`",
+                format_path(path)
+            ),
+        ));
+    }
+
+    let position = (T::SIZE as u64)
+        .checked_mul(record)
+        .and_then(|start| start.checked_add(offset as u64))
+        .ok_or_else(|| io::Error::new(ErrorKind::InvalidInput, "Record offset overflows u64"))?;
+
+    table.seek(SeekFrom::Start(position))?;
+    table.writes_with(value, config)
+}
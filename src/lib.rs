@@ -41,35 +41,85 @@
 //! }
 //! ```
 
-use std::fs::File;
-use std::io;
-use std::path::Path;
-
 #[cfg(feature = "tora_derive")]
 pub use tora_derive::*;
 
-use crate::read::{FromReader, ToraRead};
-use crate::write::{SerializeIo, ToraWrite};
+pub use crate::error::WireError;
+// File systems are unavailable to browsers.
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+pub use crate::file::{
+    read_from_file, read_from_file_with_digest, read_from_file_with_progress, write_to_file,
+    write_to_file_with_digest, write_to_file_with_progress, Progress,
+};
 
+pub mod ascii;
+pub mod bits;
+pub mod blob;
+pub mod broadcast;
+pub mod builder;
+pub mod cancel;
+pub mod capture;
+pub mod coalesce;
+pub mod codec;
+pub mod columnar;
+pub mod compact;
+pub mod config;
+pub mod digest;
+pub mod dynamic;
+#[cfg(feature = "ecs")]
+pub mod ecs;
+pub mod error;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+mod file;
+pub mod fuzz;
+pub mod instrument;
+pub mod intern;
+pub mod json;
+pub mod layer;
+pub mod layout;
+pub mod mux;
+pub mod patch;
+pub mod peek;
+pub mod pipeline;
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+pub mod process;
+pub mod protocol;
+pub mod proxy;
+#[cfg(feature = "python")]
+pub mod python;
 pub mod read;
+pub mod replicate;
+pub mod resume;
+pub mod schema;
+#[cfg(feature = "shm")]
+pub mod shm;
+pub mod skip;
+pub mod slice;
+pub mod stream;
+pub mod testing;
+#[cfg(feature = "tls")]
+pub mod tls;
+#[cfg(all(feature = "io_uring", target_os = "linux"))]
+pub mod uring;
+pub mod version;
+#[cfg(all(feature = "wasm", target_arch = "wasm32"))]
+pub mod wasm;
+#[cfg(feature = "websocket")]
+pub mod websocket;
 pub mod write;
 
-/// Serialize the content and write it to the file at the given path.
-pub fn write_to_file<P, C>(path: P, content: &C) -> io::Result<()>
-where
-    P: AsRef<Path>,
-    C: SerializeIo,
-{
-    let mut file = File::create(path)?;
-    file.writes(content)
-}
+#[doc(hidden)]
+pub mod __private {
+    use std::io;
+
+    #[cfg(feature = "inventory")]
+    pub use inventory;
 
-/// Try to deserialize [T] from the file at the given path.
-pub fn read_from_file<T, P>(path: P) -> io::Result<T>
-where
-    P: AsRef<Path>,
-    T: FromReader,
-{
-    let mut file = File::open(path)?;
-    file.reads()
+    /// Reserves the bounded capacity [crate::read] reserves for `len` elements read from the
+    /// input.
+    pub fn reserve<T>(buf: &mut Vec<T>, len: usize) -> io::Result<()> {
+        crate::read::try_reserve(buf, crate::read::preallocation::<T>(len))
+    }
 }
@@ -0,0 +1,475 @@
+//! Independent typed channels sharing one connection.
+//!
+//! A [Multiplexer] splits a connection into [Channel]s identified by a `u16`, each carrying
+//! values of its own type, so a client can keep control messages, bulk transfers and telemetry
+//! apart without opening a connection for each.
+//!
+//! Every channel has its own flow control: a sender may have at most [window](Multiplexer::window)
+//! frames in flight on a channel before the receiver consumes them. A slow consumer therefore only
+//! stalls its own channel, instead of every channel on the connection.
+//!
+//! ```
+//! use std::io;
+//! use std::net::{TcpListener, TcpStream};
+//! use std::thread;
+//!
+//! use tora::mux::Multiplexer;
+//!
+//! const CONTROL: u16 = 0;
+//! const TELEMETRY: u16 = 1;
+//!
+//! fn main() -> io::Result<()> {
+//!     let listener = TcpListener::bind("127.0.0.1:0")?;
+//!     let address = listener.local_addr()?;
+//!
+//!     let client = thread::spawn(move || -> io::Result<()> {
+//!         let mux = Multiplexer::from_tcp(TcpStream::connect(address)?)?;
+//!
+//!         mux.channel::<f32>(TELEMETRY).send(&0.5)?;
+//!         mux.channel::<String>(CONTROL).send(&"Stop".to_string())
+//!     });
+//!
+//!     let mux = Multiplexer::from_tcp(listener.accept()?.0)?;
+//!     let control = mux.channel::<String>(CONTROL);
+//!     let telemetry = mux.channel::<f32>(TELEMETRY);
+//!
+//!     // Receiving on one channel buffers frames arriving for the others.
+//!     assert_eq!(control.recv()?, "Stop");
+//!     assert_eq!(telemetry.recv()?, 0.5);
+//!
+//!     client.join().unwrap()
+//! }
+//! ```
+//!
+//! # Wire format
+//!
+//! Each frame starts with a `u8` kind and the `u16` channel ID. A data frame (kind 0) continues
+//! with a length prefix and the serialized value. A credit frame (kind 1) continues with a `u32`,
+//! the amount of frames the receiver consumed since it last granted credit.
+//!
+//! Frames for channels not opened yet are kept, but only for up to 64 such channels at a time.
+//! Exceeding that, the window of a channel, or granting more credit than the window is a
+//! protocol error ending the connection.
+
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::io;
+use std::io::{BufReader, ErrorKind, Read, Write};
+use std::marker::PhantomData;
+use std::net::TcpStream;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError};
+
+use crate::config::ToraConfig;
+use crate::read::{read_bytes, FromReader};
+use crate::stream::decode_frame;
+use crate::write::SerializeIo;
+
+const DATA: u8 = 0;
+const CREDIT: u8 = 1;
+
+/// The default amount of frames that may be in flight on a channel.
+pub(crate) const DEFAULT_WINDOW: usize = 16;
+
+/// The most channels not opened yet that frames are kept for.
+const MAX_UNOPENED_CHANNELS: usize = 64;
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+enum Frame {
+    Data(u16, Vec<u8>),
+    Credit(u16, usize),
+}
+
+struct ChannelState {
+    /// Received frames, not yet consumed.
+    queue: VecDeque<Vec<u8>>,
+    /// The amount of frames that may still be sent before the peer grants more credit.
+    credit: usize,
+    /// The amount of frames consumed since credit was last granted to the peer.
+    consumed: usize,
+    /// Whether a [Channel] handle exists for this channel.
+    open: bool,
+}
+
+struct State {
+    channels: HashMap<u16, ChannelState>,
+    /// Whether a thread is reading a frame from the connection.
+    reading: bool,
+    /// The error that ended the connection.
+    error: Option<(ErrorKind, String)>,
+}
+
+struct Shared<R, W> {
+    state: Mutex<State>,
+    /// Notified whenever a frame was read, or reading failed.
+    changed: Condvar,
+    reader: Mutex<BufReader<R>>,
+    writer: Mutex<W>,
+    config: ToraConfig,
+    window: usize,
+}
+
+impl State {
+    fn channel(&mut self, id: u16, window: usize) -> &mut ChannelState {
+        self.channels.entry(id).or_insert_with(|| ChannelState {
+            queue: VecDeque::new(),
+            credit: window,
+            consumed: 0,
+            open: false,
+        })
+    }
+}
+
+impl<R, W> Shared<R, W>
+where
+    R: Read,
+    W: Write,
+{
+    /// Reads frames from the connection until `f` returns a value.
+    ///
+    /// Only one thread reads at a time; the others wait for it to deliver their frames.
+    fn wait_until<T, F>(&self, mut f: F) -> io::Result<T>
+    where
+        F: FnMut(&mut State) -> Option<T>,
+    {
+        let mut state = lock(&self.state);
+
+        loop {
+            if let Some(value) = f(&mut state) {
+                return Ok(value);
+            }
+            if let Some((kind, ref message)) = state.error {
+                return Err(io::Error::new(kind, message.clone()));
+            }
+            if state.reading {
+                state = self
+                    .changed
+                    .wait(state)
+                    .unwrap_or_else(PoisonError::into_inner);
+                continue;
+            }
+
+            state.reading = true;
+            drop(state);
+
+            let frame = self.read_frame();
+
+            state = lock(&self.state);
+            state.reading = false;
+
+            if let Err(e) = frame.and_then(|frame| self.apply(&mut state, frame)) {
+                state.error = Some((e.kind(), e.to_string()));
+            }
+            self.changed.notify_all();
+        }
+    }
+
+    fn read_frame(&self) -> io::Result<Frame> {
+        let mut r = lock(&self.reader);
+        let r = &mut *r;
+
+        let kind = u8::from_reader_with(r, &self.config)?;
+        let channel = u16::from_reader_with(r, &self.config)?;
+
+        match kind {
+            DATA => {
+                let len = self.config.read_length(r)?;
+                let mut payload = Vec::new();
+
+                if read_bytes(r, &mut payload, len, &self.config)? != len {
+                    return Err(io::Error::new(ErrorKind::UnexpectedEof, "Truncated frame"));
+                }
+
+                Ok(Frame::Data(channel, payload))
+            }
+            CREDIT => {
+                let credit = u32::from_reader_with(r, &self.config)?;
+                Ok(Frame::Credit(channel, credit as usize))
+            }
+            _ => Err(io::Error::new(ErrorKind::InvalidData, "Unknown frame kind")),
+        }
+    }
+
+    fn apply(&self, state: &mut State, frame: Frame) -> io::Result<()> {
+        match frame {
+            Frame::Data(id, payload) => {
+                let unopened = |c: &ChannelState| !c.open && !c.queue.is_empty();
+                let known = state
+                    .channels
+                    .get(&id)
+                    .is_some_and(|c| c.open || !c.queue.is_empty());
+
+                if !known
+                    && state.channels.values().filter(|c| unopened(c)).count()
+                        >= MAX_UNOPENED_CHANNELS
+                {
+                    return Err(io::Error::new(
+                        ErrorKind::InvalidData,
+                        "Peer sent frames to too many unopened channels",
+                    ));
+                }
+                let channel = state.channel(id, self.window);
+
+                if channel.queue.len() >= self.window {
+                    return Err(io::Error::new(
+                        ErrorKind::InvalidData,
+                        "Peer exceeded the channel window",
+                    ));
+                }
+                channel.queue.push_back(payload);
+            }
+            Frame::Credit(id, credit) => {
+                let channel = state.channel(id, self.window);
+
+                // The peer only grants credit for frames it consumed, so never beyond the window.
+                channel.credit = channel
+                    .credit
+                    .checked_add(credit)
+                    .filter(|&credit| credit <= self.window)
+                    .ok_or_else(|| {
+                        io::Error::new(
+                            ErrorKind::InvalidData,
+                            "Peer granted credit beyond the channel window",
+                        )
+                    })?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes a frame at once, so frames of different channels never interleave.
+    fn write_frame<F>(&self, kind: u8, channel: u16, body: F) -> io::Result<()>
+    where
+        F: FnOnce(&mut Vec<u8>) -> io::Result<()>,
+    {
+        let mut frame = Vec::new();
+        kind.serialize_with(&mut frame, &self.config)?;
+        channel.serialize_with(&mut frame, &self.config)?;
+        body(&mut frame)?;
+
+        let mut w = lock(&self.writer);
+        w.write_all(&frame)?;
+        w.flush()
+    }
+}
+
+/// A connection split into independent typed channels.
+///
+/// Cloning a Multiplexer returns another handle to the same connection.
+pub struct Multiplexer<R = TcpStream, W = TcpStream> {
+    shared: Arc<Shared<R, W>>,
+}
+
+impl<R, W> Multiplexer<R, W>
+where
+    R: Read,
+    W: Write,
+{
+    /// Constructs a Multiplexer reading from and writing to the given halves of a connection.
+    pub fn new(reader: R, writer: W) -> Self {
+        Self::with_config(reader, writer, ToraConfig::DEFAULT)
+    }
+
+    /// Constructs a Multiplexer using the given configuration.
+    pub fn with_config(reader: R, writer: W, config: ToraConfig) -> Self {
+        Self::with_window(reader, writer, config, DEFAULT_WINDOW)
+    }
+
+    /// Constructs a Multiplexer allowing `window` frames in flight per channel.
+    ///
+    /// Both ends of the connection must use the same window.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the window is zero.
+    pub fn with_window(reader: R, writer: W, config: ToraConfig, window: usize) -> Self {
+        Self::from_buffered(BufReader::new(reader), writer, config, window)
+    }
+
+    pub(crate) fn from_buffered(
+        reader: BufReader<R>,
+        writer: W,
+        config: ToraConfig,
+        window: usize,
+    ) -> Self {
+        assert!(window > 0, "The channel window must not be zero");
+
+        Self {
+            shared: Arc::new(Shared {
+                state: Mutex::new(State {
+                    channels: HashMap::new(),
+                    reading: false,
+                    error: None,
+                }),
+                changed: Condvar::new(),
+                reader: Mutex::new(reader),
+                writer: Mutex::new(writer),
+                config,
+                window,
+            }),
+        }
+    }
+
+    /// Returns the amount of frames that may be in flight on a channel.
+    pub fn window(&self) -> usize {
+        self.shared.window
+    }
+
+    /// Opens the channel with the given ID, carrying values of type [T].
+    ///
+    /// Frames that arrived for the channel before it was opened are kept.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the channel is already open.
+    pub fn channel<T>(&self, id: u16) -> Channel<T, R, W> {
+        let mut state = lock(&self.shared.state);
+        let channel = state.channel(id, self.shared.window);
+
+        assert!(!channel.open, "Channel {id} is already open");
+        channel.open = true;
+
+        Channel {
+            id,
+            shared: self.shared.clone(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl Multiplexer<TcpStream, TcpStream> {
+    /// Constructs a Multiplexer over a TCP connection.
+    pub fn from_tcp(stream: TcpStream) -> io::Result<Self> {
+        Ok(Self::new(stream.try_clone()?, stream))
+    }
+}
+
+impl<R, W> Clone for Multiplexer<R, W> {
+    fn clone(&self) -> Self {
+        Self {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<R, W> fmt::Debug for Multiplexer<R, W> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Multiplexer")
+            .field("window", &self.shared.window)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A channel of a [Multiplexer], sending and receiving values of type [T].
+///
+/// The channel can be opened again once this handle is dropped.
+pub struct Channel<T, R = TcpStream, W = TcpStream> {
+    id: u16,
+    shared: Arc<Shared<R, W>>,
+    _marker: PhantomData<fn(T) -> T>,
+}
+
+impl<T, R, W> Channel<T, R, W>
+where
+    R: Read,
+    W: Write,
+{
+    /// Returns the ID of this channel.
+    pub fn id(&self) -> u16 {
+        self.id
+    }
+
+    /// Sends a value on this channel.
+    ///
+    /// Blocks while the peer has not consumed the frames already in flight.
+    pub fn send(&self, value: &T) -> io::Result<()>
+    where
+        T: SerializeIo,
+    {
+        let config = &self.shared.config;
+
+        let mut payload = Vec::new();
+        value.serialize_with(&mut payload, config)?;
+
+        self.shared.wait_until(|state| {
+            let channel = state.channel(self.id, self.shared.window);
+
+            (channel.credit > 0).then(|| channel.credit -= 1)
+        })?;
+
+        self.shared.write_frame(DATA, self.id, |frame| {
+            config.write_length(frame, payload.len())?;
+            frame.extend_from_slice(&payload);
+            Ok(())
+        })
+    }
+
+    /// Waits for the next value on this channel.
+    ///
+    /// Returns [ErrorKind::InvalidData] if the value does not occupy the whole frame, or if the
+    /// peer breaks the flow control of the connection.
+    ///
+    /// ```
+    /// use std::io::{self, ErrorKind};
+    ///
+    /// use tora::mux::Multiplexer;
+    ///
+    /// // A credit frame for channel 0, granting more frames than the window holds.
+    /// let bytes = [1, 0, 0, 0xFF, 0xFF, 0xFF, 0xFF];
+    /// let mux = Multiplexer::new(bytes.as_slice(), io::sink());
+    ///
+    /// let e = mux.channel::<u8>(0).recv().unwrap_err();
+    /// assert_eq!(e.kind(), ErrorKind::InvalidData);
+    ///
+    /// // A data frame for each of 65 channels, none of which is opened.
+    /// let bytes: Vec<u8> = (0..65u16).flat_map(|id| [0, id as u8, 0, 1, 0, 0, 0, 7]).collect();
+    /// let mux = Multiplexer::new(bytes.as_slice(), io::sink());
+    ///
+    /// let e = mux.channel::<u8>(100).recv().unwrap_err();
+    /// assert_eq!(e.kind(), ErrorKind::InvalidData);
+    /// ```
+    pub fn recv(&self) -> io::Result<T>
+    where
+        T: FromReader,
+    {
+        let window = self.shared.window;
+        let payload = self
+            .shared
+            .wait_until(|state| state.channel(self.id, window).queue.pop_front())?;
+
+        // Grant credit in batches of half the window, rather than for every frame.
+        let grant = {
+            let mut state = lock(&self.shared.state);
+            let channel = state.channel(self.id, window);
+            channel.consumed += 1;
+
+            match channel.consumed >= window.div_ceil(2) {
+                true => std::mem::take(&mut channel.consumed),
+                false => 0,
+            }
+        };
+        if grant > 0 {
+            self.shared.write_frame(CREDIT, self.id, |frame| {
+                (grant as u32).serialize_with(frame, &self.shared.config)
+            })?;
+        }
+
+        decode_frame(&payload, &self.shared.config)
+    }
+}
+
+impl<T, R, W> Drop for Channel<T, R, W> {
+    fn drop(&mut self) {
+        if let Some(channel) = lock(&self.shared.state).channels.get_mut(&self.id) {
+            channel.open = false;
+        }
+    }
+}
+
+impl<T, R, W> fmt::Debug for Channel<T, R, W> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Channel").field("id", &self.id).finish()
+    }
+}
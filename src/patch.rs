@@ -0,0 +1,50 @@
+//! Partial updates holding only the changed fields of a struct.
+//!
+//! `#[derive(Patch)]` generates two companion types for a struct with named fields:
+//!
+//! * `{Struct}Patch`, with an `Option` per field, written as a mask of the fields present
+//!   followed by their values, so an update changing one field of a large struct only holds that
+//!   field.
+//! * `{Struct}Tracker`, wrapping the struct with a `set_{field}` and `{field}_mut` method per
+//!   field, which record the fields changed until `take_patch` collects them into a patch.
+//!
+//! The receiving side [applies](Patch::apply) the patches to its copy of the struct.
+//!
+//! ```
+//! use tora::patch::Patch;
+//! use tora::Patch;
+//!
+//! #[derive(Clone, Debug, Default, PartialEq, Patch)]
+//! struct Player {
+//!     name: String,
+//!     health: u32,
+//!     position: (i32, i32),
+//!     inventory: Vec<u16>,
+//! }
+//!
+//! let mut server = PlayerTracker::new(Player::default());
+//! let mut client = Player::default();
+//!
+//! server.set_health(90);
+//! server.inventory_mut().push(7);
+//!
+//! let patch = server.take_patch();
+//! assert!(!server.is_dirty());
+//!
+//! // The mask, then the health and the inventory.
+//! tora::assert_bytes_eq!(patch, "0a 5a 00 00 00 01 00 00 00 07 00");
+//!
+//! client.apply(tora::testing::roundtrip(&patch));
+//! assert_eq!(&client, server.get());
+//! ```
+
+/// A struct that can be updated by a patch holding some of its fields.
+///
+/// Derived with `#[derive(Patch)]`.
+pub trait Patch {
+    /// The patch type, holding the changed fields.
+    type Patch;
+
+    /// Sets the fields present in the patch.
+    fn apply(&mut self, patch: Self::Patch);
+}
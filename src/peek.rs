@@ -0,0 +1,180 @@
+//! Decoding values without consuming them.
+//!
+//! A [PeekReader] buffers the reader it wraps, keeping the bytes of peeked values until they are
+//! read, so any reader can be peeked. This lets a header be inspected to decide which subsystem
+//! handles the rest of the stream, while the subsystem still reads the message from its start.
+//! Readers that can seek are peeked with [ToraRead::peeks](crate::read::ToraRead::peeks)
+//! directly.
+//!
+//! ```
+//! use std::io;
+//! use std::io::Read;
+//!
+//! use tora::peek::PeekReader;
+//! use tora::read::ToraRead;
+//!
+//! fn handle<R>(reader: &mut PeekReader<R>) -> io::Result<String>
+//! where
+//!     R: Read,
+//! {
+//!     Ok(match reader.peeks::<u8>()? {
+//!         0 => format!("Chat: {:?}", reader.reads::<(u8, String)>()?),
+//!         1 => format!("Move: {:?}", reader.reads::<(u8, i16, i16)>()?),
+//!         kind => format!("Unknown: {kind}"),
+//!     })
+//! }
+//!
+//! fn main() -> io::Result<()> {
+//!     let mut reader = PeekReader::new([1, 2, 0, 3, 0].as_slice());
+//!
+//!     assert_eq!(handle(&mut reader)?, "Move: (1, 2, 3)");
+//!     Ok(())
+//! }
+//! ```
+
+use std::io;
+use std::io::{BufRead, ErrorKind, Read};
+
+use crate::config::ToraConfig;
+use crate::read::FromReader;
+
+/// The amount of bytes read from the inner reader at a time.
+const CAPACITY: usize = 8 * 1024;
+
+/// A buffered reader whose values can be decoded without being consumed.
+///
+/// Peeked bytes are kept in the buffer until they are read, so the buffer grows to hold the
+/// largest value peeked.
+#[derive(Debug)]
+pub struct PeekReader<R> {
+    inner: R,
+    buf: Vec<u8>,
+    /// The position of the first byte of the buffer not read yet.
+    pos: usize,
+}
+
+impl<R> PeekReader<R>
+where
+    R: Read,
+{
+    /// Constructs a PeekReader over the given reader.
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            buf: Vec::new(),
+            pos: 0,
+        }
+    }
+
+    /// Decodes the next value without consuming it.
+    ///
+    /// Bytes read past the value, or before an error, are kept for the next reads.
+    pub fn peeks<T>(&mut self) -> io::Result<T>
+    where
+        T: FromReader,
+    {
+        self.peeks_with(&ToraConfig::DEFAULT)
+    }
+
+    /// Decodes the next value without consuming it, honoring the given configuration.
+    pub fn peeks_with<T>(&mut self, config: &ToraConfig) -> io::Result<T>
+    where
+        T: FromReader,
+    {
+        self.buf.drain(..self.pos);
+        self.pos = 0;
+
+        T::from_reader_with(
+            &mut Peeking {
+                reader: self,
+                offset: 0,
+            },
+            config,
+        )
+    }
+
+    /// Returns the buffered bytes not read yet.
+    pub fn buffer(&self) -> &[u8] {
+        &self.buf[self.pos..]
+    }
+
+    /// Returns a reference to the underlying reader.
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Returns the underlying reader, discarding the buffered bytes.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R> Read for PeekReader<R>
+where
+    R: Read,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.pos == self.buf.len() && buf.len() >= CAPACITY {
+            return self.inner.read(buf);
+        }
+
+        let read = self.fill_buf()?.read(buf)?;
+        self.consume(read);
+        Ok(read)
+    }
+}
+
+impl<R> BufRead for PeekReader<R>
+where
+    R: Read,
+{
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        if self.pos == self.buf.len() {
+            self.buf.resize(CAPACITY, 0);
+            self.pos = 0;
+
+            let read = loop {
+                match self.inner.read(&mut self.buf) {
+                    Ok(read) => break read,
+                    Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                    Err(e) => {
+                        self.buf.clear();
+                        return Err(e);
+                    }
+                }
+            };
+            self.buf.truncate(read);
+        }
+        Ok(&self.buf[self.pos..])
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.pos = (self.pos + amt).min(self.buf.len());
+    }
+}
+
+/// Reads the buffered bytes of a [PeekReader] from an offset, then the inner reader, keeping the
+/// bytes read from the latter in the buffer.
+struct Peeking<'a, R> {
+    reader: &'a mut PeekReader<R>,
+    offset: usize,
+}
+
+impl<R> Read for Peeking<'_, R>
+where
+    R: Read,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let reader = &mut *self.reader;
+
+        let read = if self.offset < reader.buf.len() {
+            (&reader.buf[self.offset..]).read(buf)?
+        } else {
+            let read = reader.inner.read(buf)?;
+            reader.buf.extend_from_slice(&buf[..read]);
+            read
+        };
+        self.offset += read;
+        Ok(read)
+    }
+}
@@ -0,0 +1,191 @@
+//! Pipelined serialization of large record sets.
+//!
+//! A [PipelinedWriter] serializes batches of records on a pool of worker threads, each into a
+//! buffer reused across batches, while a dedicated thread writes the buffers to the underlying
+//! writer in the order the batches were sent. Serialization and IO overlap, and serialization is
+//! spread over as many cores as there are workers.
+//!
+//! Records are written back to back, exactly as writing each of them in order with
+//! [writes_with](crate::write::ToraWrite::writes_with) would.
+//!
+//! ```
+//! use std::io;
+//! use std::io::Cursor;
+//!
+//! use tora::pipeline::PipelinedWriter;
+//! use tora::read::ToraRead;
+//!
+//! fn main() -> io::Result<()> {
+//!     let mut writer = PipelinedWriter::new(Vec::new(), 4);
+//!
+//!     for batch in 0..100u32 {
+//!         writer.send((batch * 10..batch * 10 + 10).collect())?;
+//!     }
+//!     let bytes = writer.finish()?;
+//!
+//!     let mut reader = Cursor::new(bytes);
+//!     for expected in 0..1000u32 {
+//!         assert_eq!(reader.reads::<u32>()?, expected);
+//!     }
+//!     Ok(())
+//! }
+//! ```
+
+use std::collections::BTreeMap;
+use std::io;
+use std::io::{ErrorKind, Write};
+use std::sync::mpsc::{Receiver, SyncSender};
+use std::sync::{mpsc, Arc, Mutex, MutexGuard, PoisonError};
+use std::thread;
+use std::thread::JoinHandle;
+
+use crate::config::ToraConfig;
+use crate::write::SerializeIo;
+
+/// The amount of batches queued per worker before [PipelinedWriter::send] blocks.
+const QUEUE_PER_WORKER: usize = 2;
+
+/// Buffers returned by the IO thread once written, reused by the workers.
+type Pool = Arc<Mutex<Vec<Vec<u8>>>>;
+
+/// A batch of serialized records, along with its position in the order of batches sent.
+type Serialized = (u64, io::Result<Vec<u8>>);
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn stopped() -> io::Error {
+    io::Error::new(ErrorKind::BrokenPipe, "The pipeline stopped")
+}
+
+fn panicked() -> io::Error {
+    io::Error::other("A pipeline thread panicked")
+}
+
+/// A writer serializing batches of records on worker threads while writing them on another.
+///
+/// Errors, from serialization or from the underlying writer, stop the pipeline: later calls to
+/// [PipelinedWriter::send] fail, and [PipelinedWriter::finish] returns the first error.
+pub struct PipelinedWriter<T, W> {
+    jobs: Option<SyncSender<(u64, Vec<T>)>>,
+    workers: Vec<JoinHandle<()>>,
+    io: JoinHandle<io::Result<W>>,
+    sent: u64,
+}
+
+impl<T, W> PipelinedWriter<T, W>
+where
+    T: SerializeIo + Send + 'static,
+    W: Write + Send + 'static,
+{
+    /// Starts a pipeline writing to `inner` with the given amount of worker threads, using the
+    /// default configuration.
+    pub fn new(inner: W, workers: usize) -> Self {
+        Self::with_config(inner, workers, ToraConfig::DEFAULT)
+    }
+
+    /// Starts a pipeline writing to `inner` with the given amount of worker threads, using the
+    /// given configuration.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `workers` is zero.
+    pub fn with_config(inner: W, workers: usize, config: ToraConfig) -> Self {
+        assert!(workers > 0, "A pipeline needs at least one worker");
+
+        let (jobs, job_receiver) = mpsc::sync_channel(workers * QUEUE_PER_WORKER);
+        let (results, result_receiver) = mpsc::sync_channel(workers * QUEUE_PER_WORKER);
+        let job_receiver = Arc::new(Mutex::new(job_receiver));
+        let pool = Pool::default();
+
+        let workers = (0..workers)
+            .map(|_| {
+                let (jobs, results, pool) = (job_receiver.clone(), results.clone(), pool.clone());
+                let config = config.clone();
+                thread::spawn(move || serialize_batches(&jobs, &results, &pool, &config))
+            })
+            .collect();
+        let io = thread::spawn(move || write_batches(inner, &result_receiver, &pool));
+
+        Self {
+            jobs: Some(jobs),
+            workers,
+            io,
+            sent: 0,
+        }
+    }
+
+    /// Queues a batch of records, blocking while the queue is full.
+    ///
+    /// Returns [ErrorKind::BrokenPipe] if the pipeline stopped after an error, which
+    /// [PipelinedWriter::finish] returns.
+    pub fn send(&mut self, batch: Vec<T>) -> io::Result<()> {
+        let jobs = self.jobs.as_ref().ok_or_else(stopped)?;
+
+        jobs.send((self.sent, batch)).map_err(|_| stopped())?;
+        self.sent += 1;
+        Ok(())
+    }
+
+    /// Waits for every batch sent to be written, flushes the underlying writer and returns it.
+    pub fn finish(mut self) -> io::Result<W> {
+        drop(self.jobs.take());
+
+        for worker in self.workers {
+            worker.join().map_err(|_| panicked())?;
+        }
+        self.io.join().map_err(|_| panicked())?
+    }
+}
+
+/// Serializes the batches received on `jobs` until the channel closes or the IO thread stops.
+fn serialize_batches<T>(
+    jobs: &Mutex<Receiver<(u64, Vec<T>)>>,
+    results: &SyncSender<Serialized>,
+    pool: &Pool,
+    config: &ToraConfig,
+) where
+    T: SerializeIo,
+{
+    loop {
+        // The lock is released before serializing, so other workers can take the next batch.
+        let Ok((index, batch)) = lock(jobs).recv() else {
+            return;
+        };
+        let mut buf = lock(pool).pop().unwrap_or_default();
+
+        let result = batch
+            .iter()
+            .try_for_each(|record| record.serialize_with(&mut buf, config))
+            .map(|_| buf);
+
+        if results.send((index, result)).is_err() {
+            return;
+        }
+    }
+}
+
+/// Writes the serialized batches to `w` in order, returning buffers to the pool once written.
+fn write_batches<W>(mut w: W, results: &Receiver<Serialized>, pool: &Pool) -> io::Result<W>
+where
+    W: Write,
+{
+    let mut pending = BTreeMap::new();
+    let mut next = 0;
+
+    for (index, result) in results {
+        pending.insert(index, result);
+
+        while let Some(result) = pending.remove(&next) {
+            let mut buf = result?;
+            w.write_all(&buf)?;
+
+            buf.clear();
+            lock(pool).push(buf);
+            next += 1;
+        }
+    }
+    w.flush()?;
+    Ok(w)
+}
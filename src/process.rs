@@ -0,0 +1,210 @@
+//! Framed connections over pipes, such as the standard streams of a child process.
+//!
+//! A [PipeStream] pairs a [FrameReader] and a [FrameWriter] over separate pipes, sending values
+//! of type [S] and receiving values of type [R]. A supervisor spawns a worker as a [Worker], and
+//! the worker talks back over its own standard streams with [PipeStream::stdio].
+//!
+//! ```
+//! use std::io;
+//! use std::process::Command;
+//!
+//! use tora::process::Worker;
+//!
+//! # #[cfg(unix)]
+//! fn main() -> io::Result<()> {
+//!     // `cat` echoes every frame back, as a worker answering requests would.
+//!     let mut worker = Worker::<u32, u32>::spawn(&mut Command::new("cat"))?;
+//!
+//!     worker.send(&7)?;
+//!     assert_eq!(worker.recv()?, 7);
+//!
+//!     assert!(worker.shutdown()?.success());
+//!     Ok(())
+//! }
+//! # #[cfg(not(unix))]
+//! # fn main() {}
+//! ```
+
+use std::io;
+use std::io::{Read, Stdin, Stdout, Write};
+use std::process::{Child, ChildStdin, ChildStdout, Command, ExitStatus, Stdio};
+
+use crate::config::ToraConfig;
+use crate::read::FromReader;
+use crate::stream::{FrameReader, FrameWriter};
+use crate::write::SerializeIo;
+
+/// A framed connection over a pair of pipes, sending values of type [S] and receiving values of
+/// type [R].
+///
+/// Frames are written in the same format as a [ToraStream](crate::stream::ToraStream).
+#[derive(Debug)]
+pub struct PipeStream<S, R, W = ChildStdin, P = ChildStdout> {
+    reader: FrameReader<R, P>,
+    writer: FrameWriter<S, W>,
+}
+
+impl<S, R, W, P> PipeStream<S, R, W, P>
+where
+    W: Write,
+    P: Read,
+{
+    /// Constructs a PipeStream reading from and writing to the given pipes, using the default
+    /// configuration.
+    pub fn new(reader: P, writer: W) -> Self {
+        Self::with_config(reader, writer, ToraConfig::DEFAULT)
+    }
+
+    /// Constructs a PipeStream reading from and writing to the given pipes, using the given
+    /// configuration.
+    pub fn with_config(reader: P, writer: W, config: ToraConfig) -> Self {
+        Self {
+            reader: FrameReader::with_config(reader, config.clone()),
+            writer: FrameWriter::with_config(writer, config),
+        }
+    }
+
+    /// Serializes the value and writes it as a frame, then flushes the pipe.
+    pub fn send(&mut self, value: &S) -> io::Result<()>
+    where
+        S: SerializeIo,
+    {
+        self.writer.send(value)
+    }
+
+    /// Waits for the next frame and deserializes it.
+    ///
+    /// Returns [ErrorKind::UnexpectedEof](io::ErrorKind::UnexpectedEof) once the other end closes
+    /// its pipe. The frame buffer grows as the payload arrives, so a frame claiming more bytes
+    /// than were sent fails the same way, without allocating its claimed length.
+    ///
+    /// ```
+    /// use std::io::{self, ErrorKind};
+    ///
+    /// use tora::process::PipeStream;
+    ///
+    /// // A frame claiming 4 GiB, of which 2 bytes are sent before the pipe closes.
+    /// let pipe = [255, 255, 255, 255, 1, 2];
+    /// let mut stream = PipeStream::<u8, Vec<u8>, _, _>::new(pipe.as_slice(), io::sink());
+    ///
+    /// assert_eq!(stream.recv().unwrap_err().kind(), ErrorKind::UnexpectedEof);
+    /// ```
+    pub fn recv(&mut self) -> io::Result<R>
+    where
+        R: FromReader,
+    {
+        self.reader.recv()
+    }
+
+    /// Splits this stream into halves that can be moved to separate threads.
+    pub fn split(self) -> (FrameReader<R, P>, FrameWriter<S, W>) {
+        (self.reader, self.writer)
+    }
+
+    /// Returns the underlying pipes, discarding buffered bytes.
+    pub fn into_inner(self) -> (P, W) {
+        (self.reader.into_inner(), self.writer.into_inner())
+    }
+}
+
+impl<S, R> PipeStream<S, R, Stdout, Stdin> {
+    /// Constructs a PipeStream over the standard input and output of this process, as used by a
+    /// worker spawned by a [Worker].
+    ///
+    /// Nothing else may be printed to the standard output, as it would corrupt the frames.
+    pub fn stdio() -> Self {
+        Self::stdio_with_config(ToraConfig::DEFAULT)
+    }
+
+    /// Constructs a PipeStream over the standard input and output of this process, using the
+    /// given configuration.
+    pub fn stdio_with_config(config: ToraConfig) -> Self {
+        Self::with_config(io::stdin(), io::stdout(), config)
+    }
+}
+
+/// A child process exchanging framed values over its standard input and output.
+///
+/// Sends values of type [S] to the child's standard input, and receives values of type [R] from
+/// its standard output. Its standard error is inherited, so it remains available for logging.
+///
+/// Dropping a Worker closes its pipes without waiting for the child to exit.
+#[derive(Debug)]
+pub struct Worker<S, R> {
+    child: Child,
+    stream: PipeStream<S, R>,
+}
+
+impl<S, R> Worker<S, R> {
+    /// Spawns the command with piped standard input and output, using the default configuration.
+    pub fn spawn(command: &mut Command) -> io::Result<Self> {
+        Self::spawn_with_config(command, ToraConfig::DEFAULT)
+    }
+
+    /// Spawns the command with piped standard input and output, using the given configuration.
+    pub fn spawn_with_config(command: &mut Command, config: ToraConfig) -> io::Result<Self> {
+        let mut child = command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+
+        let stdin = child.stdin.take().expect("The standard input is piped");
+        let stdout = child.stdout.take().expect("The standard output is piped");
+
+        Ok(Self {
+            child,
+            stream: PipeStream::with_config(stdout, stdin, config),
+        })
+    }
+
+    /// Serializes the value and sends it to the child.
+    ///
+    /// Returns [ErrorKind::BrokenPipe](io::ErrorKind::BrokenPipe) if the child exited.
+    pub fn send(&mut self, value: &S) -> io::Result<()>
+    where
+        S: SerializeIo,
+    {
+        self.stream.send(value)
+    }
+
+    /// Waits for the next value sent by the child.
+    ///
+    /// Returns [ErrorKind::UnexpectedEof](io::ErrorKind::UnexpectedEof) if the child exited.
+    pub fn recv(&mut self) -> io::Result<R>
+    where
+        R: FromReader,
+    {
+        self.stream.recv()
+    }
+
+    /// Returns the OS-assigned process identifier of the child.
+    pub fn id(&self) -> u32 {
+        self.child.id()
+    }
+
+    /// Returns a reference to the child process.
+    pub fn child(&self) -> &Child {
+        &self.child
+    }
+
+    /// Closes the child's standard input, signalling that nothing more will be sent, then waits
+    /// for it to exit.
+    ///
+    /// Values the child sends until it exits are discarded.
+    pub fn shutdown(self) -> io::Result<ExitStatus> {
+        let Self { mut child, stream } = self;
+        let (mut stdout, stdin) = stream.into_inner();
+
+        drop(stdin);
+        io::copy(&mut stdout, &mut io::sink())?;
+        child.wait()
+    }
+
+    /// Kills the child, then waits for it to exit.
+    pub fn kill(self) -> io::Result<ExitStatus> {
+        let Self { mut child, stream } = self;
+        drop(stream);
+        child.kill()?;
+        child.wait()
+    }
+}
@@ -0,0 +1,270 @@
+//! Connection phases and the messages allowed in each.
+//!
+//! A [Protocol] declares the phases of a connection, such as a handshake, authentication, then
+//! play, along with the messages allowed in each phase and the phase each message moves the
+//! connection to. A [PhasedStream] enforces it on both directions of a connection, rejecting
+//! out-of-phase messages with an [UnexpectedMessage] error, so handlers no longer have to check
+//! the phase of the connection themselves.
+//!
+//! ```
+//! use std::io;
+//! use std::io::Cursor;
+//!
+//! use tora::protocol::{PhasedStream, Protocol, UnexpectedMessage};
+//! use tora::stream::ToraStream;
+//! use tora::{ReadEnum, WriteEnum};
+//!
+//! #[derive(Clone, Copy, Debug, Eq, PartialEq)]
+//! enum Phase {
+//!     Handshake,
+//!     Auth,
+//!     Play,
+//! }
+//!
+//! #[derive(Debug, PartialEq, ReadEnum, WriteEnum)]
+//! enum Packet {
+//!     Hello { version: u8 },
+//!     Login { token: String },
+//!     Move { x: i32, y: i32 },
+//!     Chat { text: String },
+//! }
+//!
+//! fn protocol() -> Protocol<Phase, Packet> {
+//!     Protocol::new(Phase::Handshake)
+//!         .transition(Phase::Handshake, |p| matches!(p, Packet::Hello { .. }), Phase::Auth)
+//!         .transition(Phase::Auth, |p| matches!(p, Packet::Login { .. }), Phase::Play)
+//!         .allow(Phase::Play, |p| matches!(p, Packet::Move { .. } | Packet::Chat { .. }))
+//! }
+//!
+//! fn main() -> io::Result<()> {
+//!     // A client skipping the login.
+//!     let mut client = ToraStream::<Packet, _>::new(Cursor::new(Vec::new()));
+//!     client.send(&Packet::Hello { version: 1 })?;
+//!     client.send(&Packet::Move { x: 1, y: 2 })?;
+//!     let bytes = client.into_inner().into_inner();
+//!
+//!     let mut server = PhasedStream::new(ToraStream::new(Cursor::new(bytes)), protocol());
+//!     server.recv()?;
+//!     assert_eq!(server.phase(), Phase::Auth);
+//!
+//!     let e = server.recv().unwrap_err();
+//!     let unexpected = UnexpectedMessage::<Phase>::of(&e).unwrap();
+//!     assert_eq!(unexpected.phase, Phase::Auth);
+//!     Ok(())
+//! }
+//! ```
+
+use std::error::Error;
+use std::fmt;
+use std::fmt::{Debug, Display, Formatter};
+use std::io;
+use std::io::{ErrorKind, Read, Write};
+use std::net::TcpStream;
+
+use crate::capture::Direction;
+use crate::read::FromReader;
+use crate::stream::ToraStream;
+use crate::write::SerializeIo;
+
+/// A message allowed in a phase.
+struct Rule<P, T> {
+    phase: P,
+    matches: fn(&T) -> bool,
+    /// The phase the message moves the connection to, if it does.
+    next: Option<P>,
+}
+
+/// The phases of a connection, and the messages of type [T] allowed in each.
+///
+/// Phases are usually a fieldless enum. A message is allowed if a rule declared for the current
+/// phase matches it; rules are tried in declaration order.
+pub struct Protocol<P, T> {
+    initial: P,
+    rules: Vec<Rule<P, T>>,
+}
+
+impl<P, T> Protocol<P, T>
+where
+    P: Copy + Eq,
+{
+    /// Constructs a Protocol starting in the `initial` phase, which allows no messages yet.
+    pub fn new(initial: P) -> Self {
+        Self {
+            initial,
+            rules: Vec::new(),
+        }
+    }
+
+    /// Allows the messages `matches` accepts in `phase`, without changing phase.
+    pub fn allow(mut self, phase: P, matches: fn(&T) -> bool) -> Self {
+        self.rules.push(Rule {
+            phase,
+            matches,
+            next: None,
+        });
+        self
+    }
+
+    /// Allows the messages `matches` accepts in `phase`, moving the connection to `next`.
+    pub fn transition(mut self, phase: P, matches: fn(&T) -> bool, next: P) -> Self {
+        self.rules.push(Rule {
+            phase,
+            matches,
+            next: Some(next),
+        });
+        self
+    }
+
+    /// Returns the phase connections start in.
+    pub fn initial(&self) -> P {
+        self.initial
+    }
+
+    /// Returns the phase the connection is in after `message`, or [None] if `message` is not
+    /// allowed in `phase`.
+    pub fn next(&self, phase: P, message: &T) -> Option<P> {
+        self.rules
+            .iter()
+            .find(|rule| rule.phase == phase && (rule.matches)(message))
+            .map(|rule| rule.next.unwrap_or(phase))
+    }
+}
+
+impl<P, T> Debug for Protocol<P, T>
+where
+    P: Debug,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Protocol")
+            .field("initial", &self.initial)
+            .field("rules", &self.rules.len())
+            .finish()
+    }
+}
+
+/// Returned when a message is sent or received in a phase that does not allow it.
+///
+/// Carried by the [io::Error] returned from [PhasedStream], of kind [ErrorKind::InvalidData] for
+/// received messages and [ErrorKind::InvalidInput] for sent ones.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct UnexpectedMessage<P> {
+    /// The phase the connection was in.
+    pub phase: P,
+    pub direction: Direction,
+}
+
+impl<P> UnexpectedMessage<P>
+where
+    P: Debug + Send + Sync + 'static,
+{
+    /// Returns the UnexpectedMessage carried by the error, if any.
+    pub fn of(e: &io::Error) -> Option<&Self> {
+        e.get_ref().and_then(|e| e.downcast_ref())
+    }
+}
+
+impl<P> Display for UnexpectedMessage<P>
+where
+    P: Debug,
+{
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self.direction {
+            Direction::Inbound => write!(f, "Received a message not allowed in {:?}", self.phase),
+            Direction::Outbound => write!(f, "Sent a message not allowed in {:?}", self.phase),
+        }
+    }
+}
+
+impl<P> Error for UnexpectedMessage<P> where P: Debug {}
+
+impl<P> From<UnexpectedMessage<P>> for io::Error
+where
+    P: Debug + Send + Sync + 'static,
+{
+    fn from(value: UnexpectedMessage<P>) -> Self {
+        let kind = match value.direction {
+            Direction::Inbound => ErrorKind::InvalidData,
+            Direction::Outbound => ErrorKind::InvalidInput,
+        };
+        io::Error::new(kind, value)
+    }
+}
+
+/// A [ToraStream] tracking the phase of its connection, and rejecting the messages its
+/// [Protocol] does not allow in that phase.
+///
+/// Messages sent and received both move the connection through the protocol. A rejected message
+/// leaves the phase unchanged; a rejected message is not sent.
+#[derive(Debug)]
+pub struct PhasedStream<P, T, S = TcpStream> {
+    stream: ToraStream<T, S>,
+    protocol: Protocol<P, T>,
+    phase: P,
+}
+
+impl<P, T, S> PhasedStream<P, T, S>
+where
+    P: Copy + Eq + Debug + Send + Sync + 'static,
+    S: Read + Write,
+{
+    /// Constructs a PhasedStream in the initial phase of the protocol.
+    pub fn new(stream: ToraStream<T, S>, protocol: Protocol<P, T>) -> Self {
+        let phase = protocol.initial();
+        Self {
+            stream,
+            protocol,
+            phase,
+        }
+    }
+
+    /// Serializes the value and writes it as a frame, then moves to the next phase.
+    ///
+    /// Returns an [UnexpectedMessage] error without sending anything if the current phase does
+    /// not allow the value.
+    pub fn send(&mut self, value: &T) -> io::Result<()>
+    where
+        T: SerializeIo,
+    {
+        let next = self.check(value, Direction::Outbound)?;
+        self.stream.send(value)?;
+        self.phase = next;
+        Ok(())
+    }
+
+    /// Waits for the next frame and deserializes it, then moves to the next phase.
+    ///
+    /// Returns an [UnexpectedMessage] error if the current phase does not allow the value.
+    pub fn recv(&mut self) -> io::Result<T>
+    where
+        T: FromReader,
+    {
+        let value = self.stream.recv()?;
+        self.phase = self.check(&value, Direction::Inbound)?;
+        Ok(value)
+    }
+
+    fn check(&self, value: &T, direction: Direction) -> io::Result<P> {
+        self.protocol.next(self.phase, value).ok_or_else(|| {
+            UnexpectedMessage {
+                phase: self.phase,
+                direction,
+            }
+            .into()
+        })
+    }
+
+    /// Returns the phase the connection is in.
+    pub fn phase(&self) -> P {
+        self.phase
+    }
+
+    /// Returns a reference to the underlying stream.
+    pub fn get_ref(&self) -> &ToraStream<T, S> {
+        &self.stream
+    }
+
+    /// Returns the underlying stream.
+    pub fn into_inner(self) -> ToraStream<T, S> {
+        self.stream
+    }
+}
@@ -0,0 +1,409 @@
+//! A logging proxy for inspecting the traffic of framed connections.
+//!
+//! A [Proxy] sits between a client and a server speaking through
+//! [ToraStream](crate::stream::ToraStream)s. It forwards every frame unchanged, and logs a
+//! human-readable description of it, decoded with a [Schema] or a concrete type.
+//!
+//! ```no_run
+//! use std::io;
+//!
+//! use tora::proxy::{Proxy, Typed};
+//! use tora::ReadEnum;
+//!
+//! #[derive(Debug, ReadEnum)]
+//! enum Packet {
+//!     Ping,
+//!     Chat(String),
+//! }
+//!
+//! fn main() -> io::Result<()> {
+//!     // Point the client at port 4000 instead of the server on port 5000.
+//!     Proxy::new(Typed::<Packet>::new())
+//!         .with_logger(|tapped| eprintln!("{tapped}"))
+//!         .listen("127.0.0.1:4000", "127.0.0.1:5000")
+//! }
+//! ```
+//!
+//! Logs lines such as:
+//!
+//! ```text
+//! 127.0.0.1:51234 -> server  6 bytes  Chat("Hi")
+//! ```
+
+use std::fmt::Debug;
+use std::io::{BufRead, BufReader, ErrorKind, Read, Write};
+use std::marker::PhantomData;
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::Arc;
+use std::{fmt, io, thread};
+
+use crate::config::{LengthPrefix, ToraConfig};
+use crate::read::{read_bytes, FromReader};
+use crate::schema::{format_path, walk, Schema, Visit};
+use crate::testing::to_hex;
+
+/// Describes the payload of a frame for logging.
+///
+/// Implemented for [Schema], [Typed] and closures.
+pub trait Describe: Send + Sync {
+    /// Returns a human-readable description of the payload.
+    fn describe(&self, payload: &[u8], config: &ToraConfig) -> io::Result<String>;
+}
+
+/// Lists the primitives of the payload with their paths, such as `{id: 5, name: "John"}`.
+impl Describe for Schema {
+    fn describe(&self, payload: &[u8], config: &ToraConfig) -> io::Result<String> {
+        let mut fields = Vec::new();
+
+        walk(payload, self, config, &mut |visit: Visit| {
+            if let Some(value) = visit.value {
+                fields.push(match visit.path.is_empty() {
+                    true => value.to_string(),
+                    false => format!("{}: {value}", format_path(visit.path)),
+                });
+            }
+        })?;
+
+        Ok(format!("{{{}}}", fields.join(", ")))
+    }
+}
+
+impl<F> Describe for F
+where
+    F: Fn(&[u8], &ToraConfig) -> io::Result<String> + Send + Sync,
+{
+    fn describe(&self, payload: &[u8], config: &ToraConfig) -> io::Result<String> {
+        self(payload, config)
+    }
+}
+
+/// Describes payloads by decoding them as [T] and formatting them with [Debug].
+pub struct Typed<T> {
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T> Typed<T> {
+    /// Constructs a Typed describing payloads as [T].
+    pub const fn new() -> Self {
+        Self {
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T> Default for Typed<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> fmt::Debug for Typed<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Typed<{}>", std::any::type_name::<T>())
+    }
+}
+
+impl<T> Describe for Typed<T>
+where
+    T: FromReader + Debug,
+{
+    fn describe(&self, mut payload: &[u8], config: &ToraConfig) -> io::Result<String> {
+        T::from_reader_with(&mut payload, config).map(|value| format!("{value:?}"))
+    }
+}
+
+/// The way a frame travelled through a [Proxy].
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Route {
+    ToServer,
+    ToClient,
+}
+
+/// A frame forwarded by a [Proxy].
+#[derive(Debug)]
+pub struct Tapped<'a> {
+    /// The address of the client the frame was sent from or to.
+    pub client: SocketAddr,
+    pub route: Route,
+    /// The payload, without the frame's length prefix.
+    pub payload: &'a [u8],
+    /// The description of the payload, or the error describing it.
+    pub description: io::Result<String>,
+}
+
+/// Formats the frame as a single line.
+impl fmt::Display for Tapped<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.route {
+            Route::ToServer => write!(f, "{} -> server", self.client)?,
+            Route::ToClient => write!(f, "{} <- server", self.client)?,
+        }
+        write!(f, "  {} bytes  ", self.payload.len())?;
+
+        match &self.description {
+            Ok(description) => f.write_str(description),
+            Err(e) => {
+                let shown = &self.payload[..self.payload.len().min(32)];
+                write!(f, "<{e}> {}", to_hex(shown))?;
+
+                match shown.len() < self.payload.len() {
+                    true => f.write_str(" ..."),
+                    false => Ok(()),
+                }
+            }
+        }
+    }
+}
+
+type Logger = dyn Fn(&Tapped) + Send + Sync;
+
+/// Forwards framed traffic between clients and a server, logging every frame.
+///
+/// Cloning a Proxy is cheap.
+#[derive(Clone)]
+pub struct Proxy {
+    to_server: Arc<dyn Describe>,
+    to_client: Arc<dyn Describe>,
+    config: ToraConfig,
+    logger: Arc<Logger>,
+}
+
+impl Proxy {
+    /// Constructs a Proxy describing the frames of both routes the same way.
+    ///
+    /// Frames are forwarded silently until [with_logger](Self::with_logger) is called.
+    pub fn new<D>(describe: D) -> Self
+    where
+        D: Describe + 'static,
+    {
+        let describe = Arc::new(describe);
+        Self {
+            to_server: describe.clone(),
+            to_client: describe,
+            config: ToraConfig::DEFAULT,
+            logger: Arc::new(|_: &Tapped| {}),
+        }
+    }
+
+    /// Constructs a Proxy describing the frames sent to the server and to clients differently.
+    pub fn with_routes<S, C>(to_server: S, to_client: C) -> Self
+    where
+        S: Describe + 'static,
+        C: Describe + 'static,
+    {
+        Self {
+            to_server: Arc::new(to_server),
+            ..Self::new(to_client)
+        }
+    }
+
+    /// Sets the configuration the frames are encoded with.
+    pub fn with_config(mut self, config: ToraConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Passes every forwarded frame to the given function.
+    pub fn with_logger<F>(mut self, logger: F) -> Self
+    where
+        F: Fn(&Tapped) + Send + Sync + 'static,
+    {
+        self.logger = Arc::new(logger);
+        self
+    }
+
+    /// Accepts clients on the listen address, connecting each to the upstream server.
+    ///
+    /// Every connection is handled on its own thread, which connects to the upstream server. A
+    /// client whose upstream connection fails is disconnected without affecting the others. Only
+    /// returns if accepting a client fails.
+    pub fn listen<L, U>(&self, listen: L, upstream: U) -> io::Result<()>
+    where
+        L: ToSocketAddrs,
+        U: ToSocketAddrs,
+    {
+        let listener = TcpListener::bind(listen)?;
+        let upstream = upstream.to_socket_addrs()?.collect::<Arc<[_]>>();
+
+        loop {
+            let (client, _) = listener.accept()?;
+
+            let (proxy, upstream) = (self.clone(), upstream.clone());
+            thread::spawn(move || proxy.run(client, TcpStream::connect(&upstream[..])?));
+        }
+    }
+
+    /// Forwards frames between a connected client and server until either disconnects.
+    ///
+    /// ```
+    /// use std::io::{self, ErrorKind, Write};
+    /// use std::net::{TcpListener, TcpStream};
+    /// use std::sync::{Arc, Mutex};
+    /// use std::thread;
+    ///
+    /// use tora::proxy::{Proxy, Route};
+    /// use tora::schema::Schema;
+    /// use tora::stream::ToraStream;
+    ///
+    /// fn main() -> io::Result<()> {
+    ///     let server = TcpListener::bind("127.0.0.1:0")?;
+    ///     let listener = TcpListener::bind("127.0.0.1:0")?;
+    ///     let (upstream, address) = (server.local_addr()?, listener.local_addr()?);
+    ///
+    ///     let log = Arc::new(Mutex::new(Vec::new()));
+    ///     let proxy = Proxy::new(Schema::String).with_logger({
+    ///         let log = log.clone();
+    ///         move |tapped| log.lock().unwrap().push((tapped.route, tapped.payload.to_vec()))
+    ///     });
+    ///
+    ///     let forwarding = thread::spawn(move || {
+    ///         let (client, _) = listener.accept()?;
+    ///         proxy.run(client, TcpStream::connect(upstream)?)
+    ///     });
+    ///
+    ///     let mut client = ToraStream::<String>::connect(address)?;
+    ///     let mut server = ToraStream::<String>::new(server.accept()?.0);
+    ///
+    ///     client.send(&"Hello".to_string())?;
+    ///     assert_eq!(server.recv()?, "Hello");
+    ///     server.send(&"Hi".to_string())?;
+    ///     assert_eq!(client.recv()?, "Hi");
+    ///
+    ///     // A frame claiming more bytes than the client ever sends.
+    ///     let mut client = client.into_inner();
+    ///     client.write_all(&[0xFF, 0xFF, 0xFF, 0xFF, 1])?;
+    ///     drop(client);
+    ///
+    ///     let result = forwarding.join().unwrap();
+    ///     assert_eq!(result.unwrap_err().kind(), ErrorKind::UnexpectedEof);
+    ///
+    ///     let log = log.lock().unwrap();
+    ///     assert_eq!(log.len(), 2);
+    ///     assert!(log.contains(&(Route::ToServer, b"Hello\0".to_vec())));
+    ///     assert!(log.contains(&(Route::ToClient, b"Hi\0".to_vec())));
+    ///     Ok(())
+    /// }
+    /// ```
+    ///
+    /// A frame that cannot be forwarded ends the connection in both directions:
+    ///
+    /// ```
+    /// use std::io::{self, ErrorKind, Write};
+    /// use std::net::{TcpListener, TcpStream};
+    /// use std::thread;
+    ///
+    /// use tora::config::ToraConfig;
+    /// use tora::proxy::Proxy;
+    /// use tora::schema::Schema;
+    ///
+    /// fn main() -> io::Result<()> {
+    ///     let server = TcpListener::bind("127.0.0.1:0")?;
+    ///     let listener = TcpListener::bind("127.0.0.1:0")?;
+    ///     let (upstream, address) = (server.local_addr()?, listener.local_addr()?);
+    ///
+    ///     let proxy = Proxy::new(Schema::String).with_config(ToraConfig::DEFAULT.max_length(4));
+    ///     let forwarding = thread::spawn(move || {
+    ///         let (client, _) = listener.accept()?;
+    ///         proxy.run(client, TcpStream::connect(upstream)?)
+    ///     });
+    ///
+    ///     let mut client = TcpStream::connect(address)?;
+    ///     let _server = server.accept()?;
+    ///     client.write_all(&[5, 0, 0, 0])?;
+    ///
+    ///     let result = forwarding.join().unwrap();
+    ///     assert_eq!(result.unwrap_err().kind(), ErrorKind::InvalidData);
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn run(&self, client: TcpStream, server: TcpStream) -> io::Result<()> {
+        let address = client.peer_addr()?;
+
+        let to_client = {
+            let proxy = self.clone();
+            let (server, client) = (server.try_clone()?, client.try_clone()?);
+            thread::spawn(move || proxy.forward(address, Route::ToClient, server, client))
+        };
+        let to_server = self.forward(address, Route::ToServer, client, server);
+
+        let to_client = to_client
+            .join()
+            .unwrap_or_else(|_| Err(io::Error::other("The forwarding thread panicked")));
+        to_server.and(to_client)
+    }
+
+    /// Forwards frames from `src` to `dst`, shutting both down once `src` ends or either fails.
+    fn forward(
+        &self,
+        client: SocketAddr,
+        route: Route,
+        src: TcpStream,
+        dst: TcpStream,
+    ) -> io::Result<()> {
+        let result = self.relay(client, route, &src, &dst);
+
+        // Unblock the other route, which may be waiting for a frame.
+        let _ = src.shutdown(Shutdown::Both);
+        let _ = dst.shutdown(Shutdown::Both);
+        result
+    }
+
+    /// Forwards frames from `src` to `dst` until `src` ends between two frames.
+    fn relay(
+        &self,
+        client: SocketAddr,
+        route: Route,
+        src: &TcpStream,
+        mut dst: &TcpStream,
+    ) -> io::Result<()> {
+        let describe = match route {
+            Route::ToServer => &self.to_server,
+            Route::ToClient => &self.to_client,
+        };
+        let mut reader = BufReader::new(src);
+
+        loop {
+            if reader.fill_buf()?.is_empty() {
+                return Ok(());
+            }
+            let mut frame = vec![0; prefix_width(self.config.length_prefix)];
+            reader.read_exact(&mut frame)?;
+
+            let len = self.config.read_length(&mut frame.as_slice())?;
+            let start = frame.len();
+
+            if read_bytes(&mut reader, &mut frame, len, &self.config)? != len {
+                return Err(io::Error::new(ErrorKind::UnexpectedEof, "Truncated frame"));
+            }
+
+            dst.write_all(&frame)?;
+            dst.flush()?;
+
+            let payload = &frame[start..];
+            (self.logger)(&Tapped {
+                client,
+                route,
+                payload,
+                description: describe.describe(payload, &self.config),
+            });
+        }
+    }
+}
+
+impl fmt::Debug for Proxy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Proxy")
+            .field("config", &self.config)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Returns the amount of bytes of a length prefix.
+fn prefix_width(prefix: LengthPrefix) -> usize {
+    match prefix {
+        LengthPrefix::U8 => 1,
+        LengthPrefix::U16 => 2,
+        LengthPrefix::U32 => 4,
+        LengthPrefix::U64 => 8,
+    }
+}
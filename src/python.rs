@@ -0,0 +1,447 @@
+//! Python bindings decoding and encoding values described by a [Schema], through `pyo3`.
+//!
+//! [register] adds a `Schema` class along with `decode(schema, bytes)` and
+//! `encode(schema, value)` functions to a Python extension module. The Rust side exports the
+//! schemas of its types, usually derived with `Reflect`, and Python reads and writes their values
+//! without reimplementing the format.
+//!
+//! Values are converted as follows:
+//!
+//! - Integers, floats, bools and strings are converted to their Python counterparts, and chars to
+//!   strings of one character.
+//! - Units are `None`, and options are either `None` or their value.
+//! - Vecs and arrays are lists, and tuples are tuples.
+//! - Structs are dicts of their field names to their values.
+//! - Enums are dicts holding a single item, the name of their variant to the dict of its fields.
+//!   Results are dicts holding a single item, `"Ok"` or `"Err"` to the value.
+//!
+//! ```
+//! use pyo3::prelude::*;
+//! use tora::python::PySchema;
+//! use tora::schema::Reflect;
+//! use tora::Reflect;
+//!
+//! #[derive(Reflect)]
+//! struct Login {
+//!     user: String,
+//!     attempts: u8,
+//! }
+//!
+//! #[pymodule]
+//! fn records(module: &Bound<'_, PyModule>) -> PyResult<()> {
+//!     tora::python::register(module)?;
+//!     module.add("Login", PySchema::new(Login::schema()))
+//! }
+//!
+//! fn main() -> PyResult<()> {
+//!     pyo3::append_to_inittab!(records);
+//!     Python::initialize();
+//!
+//!     Python::attach(|py| {
+//!         py.run(
+//!             cr#"
+//! import records
+//!
+//! login = records.decode(records.Login, b"john\x00\x03")
+//! assert login == {"user": "john", "attempts": 3}
+//! assert records.encode(records.Login, login) == b"john\x00\x03"
+//! "#,
+//!             None,
+//!             None,
+//!         )
+//!     })
+//! }
+//! ```
+
+use std::io;
+use std::io::{Cursor, ErrorKind};
+
+use pyo3::exceptions::{PyEOFError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{PyBytes, PyDict, PyList, PyTuple};
+use pyo3::IntoPyObjectExt;
+
+use crate::config::ToraConfig;
+use crate::read::ToraRead;
+use crate::schema::{read_primitive, EnumSchema, FieldSchema, Schema, Value};
+use crate::write::SerializeIo;
+
+/// Converts an IO error, raising [ErrorKind::UnexpectedEof] as `EOFError` and invalid data as
+/// `ValueError`.
+fn to_py_err(e: io::Error) -> PyErr {
+    match e.kind() {
+        ErrorKind::UnexpectedEof => PyEOFError::new_err(e.to_string()),
+        ErrorKind::InvalidData | ErrorKind::InvalidInput => PyValueError::new_err(e.to_string()),
+        _ => e.into(),
+    }
+}
+
+/// A [Schema] exposed to Python as the `Schema` class, along with the configuration values are
+/// read and written with.
+#[pyclass(name = "Schema", frozen, skip_from_py_object)]
+#[derive(Clone, Debug)]
+pub struct PySchema {
+    schema: Schema,
+    config: ToraConfig,
+}
+
+impl PySchema {
+    /// Constructs a PySchema using the default configuration.
+    pub fn new(schema: Schema) -> Self {
+        Self::with_config(schema, ToraConfig::DEFAULT)
+    }
+
+    /// Constructs a PySchema using the given configuration.
+    pub fn with_config(schema: Schema, config: ToraConfig) -> Self {
+        Self { schema, config }
+    }
+
+    /// Returns the described schema.
+    pub fn schema(&self) -> &Schema {
+        &self.schema
+    }
+}
+
+impl From<Schema> for PySchema {
+    fn from(schema: Schema) -> Self {
+        Self::new(schema)
+    }
+}
+
+#[pymethods]
+impl PySchema {
+    /// The amount of bytes values of this schema always serialize to, or None if it varies.
+    #[getter]
+    fn fixed_size(&self) -> Option<usize> {
+        self.schema.fixed_size()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("Schema({:?})", self.schema)
+    }
+}
+
+/// Adds the `Schema` class and the `decode` and `encode` functions to the module.
+pub fn register(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_class::<PySchema>()?;
+    module.add_function(wrap_pyfunction!(decode, module)?)?;
+    module.add_function(wrap_pyfunction!(encode, module)?)?;
+    Ok(())
+}
+
+/// Decodes the value described by the schema, which must occupy every byte.
+#[pyfunction]
+fn decode<'py>(py: Python<'py>, schema: &PySchema, bytes: &[u8]) -> PyResult<Bound<'py, PyAny>> {
+    let mut cursor = Cursor::new(bytes);
+    let value = decode_value(py, &mut cursor, &schema.schema, &schema.config)?;
+
+    if cursor.position() != bytes.len() as u64 {
+        return Err(PyValueError::new_err("Bytes contain trailing data"));
+    }
+    Ok(value)
+}
+
+/// Encodes the value described by the schema.
+#[pyfunction]
+fn encode<'py>(
+    py: Python<'py>,
+    schema: &PySchema,
+    value: &Bound<'py, PyAny>,
+) -> PyResult<Bound<'py, PyBytes>> {
+    let mut buf = Vec::new();
+    encode_value(value, &schema.schema, &mut buf, &schema.config)?;
+    Ok(PyBytes::new(py, &buf))
+}
+
+fn value_to_py(py: Python<'_>, value: Value) -> PyResult<Bound<'_, PyAny>> {
+    match value {
+        Value::Unit => Ok(py.None().into_bound(py)),
+        Value::Bool(b) => b.into_bound_py_any(py),
+        Value::U8(n) => n.into_bound_py_any(py),
+        Value::U16(n) => n.into_bound_py_any(py),
+        Value::U32(n) => n.into_bound_py_any(py),
+        Value::U64(n) => n.into_bound_py_any(py),
+        Value::U128(n) => n.into_bound_py_any(py),
+        Value::I8(n) => n.into_bound_py_any(py),
+        Value::I16(n) => n.into_bound_py_any(py),
+        Value::I32(n) => n.into_bound_py_any(py),
+        Value::I64(n) => n.into_bound_py_any(py),
+        Value::I128(n) => n.into_bound_py_any(py),
+        Value::F32(n) => n.into_bound_py_any(py),
+        Value::F64(n) => n.into_bound_py_any(py),
+        Value::Usize(n) => n.into_bound_py_any(py),
+        Value::Char(c) => c.into_bound_py_any(py),
+        Value::String(s) => s.into_bound_py_any(py),
+    }
+}
+
+fn decode_value<'py>(
+    py: Python<'py>,
+    r: &mut Cursor<&[u8]>,
+    schema: &Schema,
+    config: &ToraConfig,
+) -> PyResult<Bound<'py, PyAny>> {
+    if let Some(value) = read_primitive(r, schema, config).map_err(to_py_err)? {
+        return value_to_py(py, value);
+    }
+    let read_bool = |r: &mut Cursor<&[u8]>| r.reads_with::<bool>(config).map_err(to_py_err);
+
+    match schema {
+        Schema::Option(inner) => match read_bool(r)? {
+            true => decode_value(py, r, inner, config),
+            false => Ok(py.None().into_bound(py)),
+        },
+        Schema::Result(ok, err) => {
+            let (name, inner) = match read_bool(r)? {
+                true => ("Err", err),
+                false => ("Ok", ok),
+            };
+            let dict = PyDict::new(py);
+            dict.set_item(name, decode_value(py, r, inner, config)?)?;
+            Ok(dict.into_any())
+        }
+        Schema::Vec(inner) => {
+            let len = config.read_length(r).map_err(to_py_err)?;
+            decode_list(py, r, inner, len, config)
+        }
+        Schema::Array(inner, len) => decode_list(py, r, inner, *len, config),
+        Schema::Tuple(items) => {
+            let items = items
+                .iter()
+                .map(|item| decode_value(py, r, item, config))
+                .collect::<PyResult<Vec<_>>>()?;
+            Ok(PyTuple::new(py, items)?.into_any())
+        }
+        Schema::Struct(s) => decode_fields(py, r, &s.fields, config),
+        Schema::Enum(e) => decode_enum(py, r, e, config),
+        _ => unreachable!("Primitives are decoded by read_primitive"),
+    }
+}
+
+fn decode_list<'py>(
+    py: Python<'py>,
+    r: &mut Cursor<&[u8]>,
+    schema: &Schema,
+    len: usize,
+    config: &ToraConfig,
+) -> PyResult<Bound<'py, PyAny>> {
+    let list = PyList::empty(py);
+
+    for _ in 0..len {
+        list.append(decode_value(py, r, schema, config)?)?;
+    }
+    Ok(list.into_any())
+}
+
+fn decode_fields<'py>(
+    py: Python<'py>,
+    r: &mut Cursor<&[u8]>,
+    fields: &[FieldSchema],
+    config: &ToraConfig,
+) -> PyResult<Bound<'py, PyAny>> {
+    let dict = PyDict::new(py);
+
+    for field in fields {
+        r.skip(field.pad_before).map_err(to_py_err)?;
+        dict.set_item(&field.name, decode_value(py, r, &field.schema, config)?)?;
+        r.skip(field.pad_after).map_err(to_py_err)?;
+    }
+    Ok(dict.into_any())
+}
+
+fn decode_enum<'py>(
+    py: Python<'py>,
+    r: &mut Cursor<&[u8]>,
+    schema: &EnumSchema,
+    config: &ToraConfig,
+) -> PyResult<Bound<'py, PyAny>> {
+    let id = read_primitive(r, &schema.id, config)
+        .map_err(to_py_err)?
+        .and_then(|id| id.as_u64());
+    let variant = schema
+        .variants
+        .iter()
+        .find(|v| Some(v.id) == id)
+        .ok_or_else(|| PyValueError::new_err("Unknown variant id"))?;
+
+    let fields = match schema.length_prefixed {
+        true => {
+            let end = config.read_length(r).map_err(to_py_err)? as u64 + r.position();
+            let fields = decode_fields(py, r, &variant.fields, config)?;
+
+            if r.position() > end {
+                return Err(PyValueError::new_err("Variant exceeds its length prefix"));
+            }
+            r.set_position(end);
+            fields
+        }
+        false => decode_fields(py, r, &variant.fields, config)?,
+    };
+
+    let dict = PyDict::new(py);
+    dict.set_item(&variant.name, fields)?;
+    Ok(dict.into_any())
+}
+
+/// Extracts the value if the schema is a primitive, returning None otherwise.
+fn extract_primitive(value: &Bound<'_, PyAny>, schema: &Schema) -> PyResult<Option<Value>> {
+    Ok(Some(match schema {
+        Schema::Unit => Value::Unit,
+        Schema::Bool => Value::Bool(value.extract()?),
+        Schema::U8 => Value::U8(value.extract()?),
+        Schema::U16 => Value::U16(value.extract()?),
+        Schema::U32 => Value::U32(value.extract()?),
+        Schema::U64 => Value::U64(value.extract()?),
+        Schema::U128 => Value::U128(value.extract()?),
+        Schema::I8 => Value::I8(value.extract()?),
+        Schema::I16 => Value::I16(value.extract()?),
+        Schema::I32 => Value::I32(value.extract()?),
+        Schema::I64 => Value::I64(value.extract()?),
+        Schema::I128 => Value::I128(value.extract()?),
+        Schema::F32 => Value::F32(value.extract()?),
+        Schema::F64 => Value::F64(value.extract()?),
+        Schema::Usize => Value::Usize(value.extract()?),
+        Schema::Char => Value::Char(value.extract()?),
+        Schema::String => Value::String(value.extract()?),
+        _ => return Ok(None),
+    }))
+}
+
+fn encode_value(
+    value: &Bound<'_, PyAny>,
+    schema: &Schema,
+    w: &mut Vec<u8>,
+    config: &ToraConfig,
+) -> PyResult<()> {
+    if let Some(primitive) = extract_primitive(value, schema)? {
+        return primitive.serialize_with(w, config).map_err(to_py_err);
+    }
+    let write_bool = |w: &mut Vec<u8>, b: bool| b.serialize_with(w, config).map_err(to_py_err);
+
+    match schema {
+        Schema::Option(inner) => match value.is_none() {
+            true => write_bool(w, false),
+            false => {
+                write_bool(w, true)?;
+                encode_value(value, inner, w, config)
+            }
+        },
+        Schema::Result(ok, err) => {
+            let (name, item) = single_item(value)?;
+
+            match name.as_str() {
+                "Ok" => {
+                    write_bool(w, false)?;
+                    encode_value(&item, ok, w, config)
+                }
+                "Err" => {
+                    write_bool(w, true)?;
+                    encode_value(&item, err, w, config)
+                }
+                _ => Err(PyValueError::new_err("Expected a result, Ok or Err")),
+            }
+        }
+        Schema::Vec(inner) => {
+            let items = to_items(value)?;
+            config.write_length(w, items.len()).map_err(to_py_err)?;
+            encode_items(&items, |_| inner, w, config)
+        }
+        Schema::Array(inner, len) => {
+            let items = to_items(value)?;
+            check_len(&items, *len)?;
+            encode_items(&items, |_| inner, w, config)
+        }
+        Schema::Tuple(schemas) => {
+            let items = to_items(value)?;
+            check_len(&items, schemas.len())?;
+            encode_items(&items, |i| &schemas[i], w, config)
+        }
+        Schema::Struct(s) => encode_fields(value, &s.fields, w, config),
+        Schema::Enum(e) => encode_enum(value, e, w, config),
+        _ => unreachable!("Primitives are encoded by extract_primitive"),
+    }
+}
+
+fn to_items<'py>(value: &Bound<'py, PyAny>) -> PyResult<Vec<Bound<'py, PyAny>>> {
+    value.try_iter()?.collect()
+}
+
+fn check_len(items: &[Bound<'_, PyAny>], len: usize) -> PyResult<()> {
+    match items.len() == len {
+        true => Ok(()),
+        false => Err(PyValueError::new_err(format!(
+            "Expected {len} elements, found {}",
+            items.len()
+        ))),
+    }
+}
+
+fn encode_items<'s, F>(
+    items: &[Bound<'_, PyAny>],
+    schema: F,
+    w: &mut Vec<u8>,
+    config: &ToraConfig,
+) -> PyResult<()>
+where
+    F: Fn(usize) -> &'s Schema,
+{
+    for (i, item) in items.iter().enumerate() {
+        encode_value(item, schema(i), w, config)?;
+    }
+    Ok(())
+}
+
+/// Returns the only item of a dict, as used by enums and results.
+fn single_item<'py>(value: &Bound<'py, PyAny>) -> PyResult<(String, Bound<'py, PyAny>)> {
+    let dict = value.cast::<PyDict>()?;
+
+    match dict.len() {
+        1 => {
+            let (key, item) = dict.iter().next().expect("The dict holds an item");
+            Ok((key.extract()?, item))
+        }
+        _ => Err(PyValueError::new_err(
+            "Expected a dict holding a single item",
+        )),
+    }
+}
+
+fn encode_fields(
+    value: &Bound<'_, PyAny>,
+    fields: &[FieldSchema],
+    w: &mut Vec<u8>,
+    config: &ToraConfig,
+) -> PyResult<()> {
+    for field in fields {
+        w.resize(w.len() + field.pad_before, 0);
+        encode_value(&value.get_item(&field.name)?, &field.schema, w, config)?;
+        w.resize(w.len() + field.pad_after, 0);
+    }
+    Ok(())
+}
+
+fn encode_enum(
+    value: &Bound<'_, PyAny>,
+    schema: &EnumSchema,
+    w: &mut Vec<u8>,
+    config: &ToraConfig,
+) -> PyResult<()> {
+    let (name, fields) = single_item(value)?;
+    let variant = schema
+        .variants
+        .iter()
+        .find(|v| v.name == name)
+        .ok_or_else(|| PyValueError::new_err(format!("Unknown variant {name}")))?;
+
+    let id = variant.id.into_bound_py_any(value.py())?;
+    encode_value(&id, &schema.id, w, config)?;
+
+    if !schema.length_prefixed {
+        return encode_fields(&fields, &variant.fields, w, config);
+    }
+    let mut buf = Vec::new();
+    encode_fields(&fields, &variant.fields, &mut buf, config)?;
+    config.write_length(w, buf.len()).map_err(to_py_err)?;
+    w.extend_from_slice(&buf);
+    Ok(())
+}
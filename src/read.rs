@@ -1,7 +1,110 @@
+use std::cell::{Cell, RefCell};
+#[cfg(feature = "dyn_impl")]
+use std::collections::{HashMap, HashSet};
+#[cfg(feature = "dyn_impl")]
+use std::hash::{BuildHasher, Hash};
 use std::io;
-use std::io::{ErrorKind, Read};
+use std::io::{BufRead, ErrorKind, Read, Seek, SeekFrom};
+use std::marker::PhantomData;
+use std::ops::ControlFlow;
+use std::sync::{Arc, Mutex, RwLock};
+use std::task::Poll;
+use std::time::Duration;
+
+use crate::cancel::{CHECK_BYTES, CHECK_ELEMENTS};
+use crate::compact::read_packed;
+use crate::config::{Endian, FloatFormat, IntSequenceFormat, StringFormat, ToraConfig};
+use crate::skip::{Skip, SkipRead};
 
 macro_rules! from_reader_impl {
+    (@impl $t:ty { $($hooks:tt)* }) => {
+        impl FromReader for $t {
+            fn from_reader<R>(r: &mut R) -> io::Result<Self>
+            where
+                R: Read,
+            {
+                let mut buf = [0; std::mem::size_of::<$t>()];
+                r.read_exact(&mut buf).map(|_| <$t>::from_le_bytes(buf))
+            }
+
+            fn from_reader_with<R>(r: &mut R, config: &ToraConfig) -> io::Result<Self>
+            where
+                R: Read,
+            {
+                if let Some(result) = config.read_codec::<$t, _>(r) {
+                    return result;
+                }
+                let mut buf = [0; std::mem::size_of::<$t>()];
+                r.read_exact(&mut buf)?;
+
+                Ok(match config.endian {
+                    Endian::Little => <$t>::from_le_bytes(buf),
+                    Endian::Big => <$t>::from_be_bytes(buf),
+                })
+            }
+
+            $($hooks)*
+        }
+    };
+    (packed $($t:ty),*) => {
+        $(
+        from_reader_impl!(@impl $t {
+            /// Reads bit-packed deltas between the integers if the configured integer sequence
+            /// format is [IntSequenceFormat::Packed].
+            fn from_reader_slice<R>(
+                r: &mut R,
+                slice: &mut [Self],
+                config: &ToraConfig,
+            ) -> io::Result<()>
+            where
+                R: Read,
+            {
+                if config.int_sequence_format == IntSequenceFormat::Packed {
+                    let mut values = slice.iter_mut();
+                    return read_packed(r, values.len(), <$t>::BITS, config, false, |value| {
+                        *values.next().unwrap() = value as $t
+                    });
+                }
+                for value in slice {
+                    *value = Self::from_reader_with(r, config)?;
+                }
+                Ok(())
+            }
+
+            /// Reads bit-packed deltas between the integers if the configured integer sequence
+            /// format is [IntSequenceFormat::Packed].
+            fn from_reader_vec<R>(
+                r: &mut R,
+                len: usize,
+                config: &ToraConfig,
+            ) -> io::Result<Vec<Self>>
+            where
+                R: Read,
+            {
+                let mut buf = Vec::new();
+                try_reserve(&mut buf, preallocation::<$t>(len))?;
+
+                if config.int_sequence_format == IntSequenceFormat::Packed {
+                    read_packed(r, len, <$t>::BITS, config, true, |value| buf.push(value as $t))?;
+                    return Ok(buf);
+                }
+                for i in 0..len {
+                    if i % CHECK_ELEMENTS == 0 {
+                        config.check_cancelled()?;
+                    }
+                    buf.push(Self::from_reader_with(r, config)?);
+                }
+                Ok(buf)
+            }
+        });
+        )*
+    };
+    ($($t:ty),*) => {
+        $(from_reader_impl!(@impl $t {});)*
+    };
+}
+
+macro_rules! from_reader_float {
     ($($t:ty),*) => {
         $(
         impl FromReader for $t {
@@ -12,12 +115,34 @@ macro_rules! from_reader_impl {
                 let mut buf = [0; std::mem::size_of::<$t>()];
                 r.read_exact(&mut buf).map(|_| <$t>::from_le_bytes(buf))
             }
+
+            /// Reads the float, rejecting non-finite values if the float format is
+            /// [FloatFormat::Finite].
+            fn from_reader_with<R>(r: &mut R, config: &ToraConfig) -> io::Result<Self>
+            where
+                R: Read,
+            {
+                if let Some(result) = config.read_codec::<$t, _>(r) {
+                    return result;
+                }
+                let mut buf = [0; std::mem::size_of::<$t>()];
+                r.read_exact(&mut buf)?;
+
+                let value = match config.endian {
+                    Endian::Little => <$t>::from_le_bytes(buf),
+                    Endian::Big => <$t>::from_be_bytes(buf),
+                };
+                if config.float_format == FloatFormat::Finite && !value.is_finite() {
+                    return Err(io::Error::new(ErrorKind::InvalidData, "Float is not finite"));
+                }
+                Ok(value)
+            }
         }
         )*
     };
 }
 
-/// A reader that reads and discards 
+/// A reader that reads and discards a fixed amount of padding before each value.
 #[derive(Default)]
 pub struct PaddedReader {
     padding: usize,
@@ -42,8 +167,25 @@ impl PaddedReader {
         T: FromReader,
         R: Read,
     {
-        let mut temp = vec![0; self.padding];
-        r.read_exact(&mut temp)?;
+        r.skip(self.padding)?;
+        r.reads()
+    }
+
+    /// Seeks past the amount of padding, then reads [T].
+    ///
+    /// Prefer this over [PaddedReader::reads] when the reader supports seeking, as the padding
+    /// is never read.
+    ///
+    /// Returns [ErrorKind::InvalidInput] if the padding exceeds [i64::MAX].
+    pub fn seek_reads<T, R>(&self, r: &mut R) -> io::Result<T>
+    where
+        T: FromReader,
+        R: Read + Seek,
+    {
+        let padding = i64::try_from(self.padding)
+            .map_err(|_| io::Error::new(ErrorKind::InvalidInput, "Padding exceeds i64::MAX"))?;
+
+        r.seek(SeekFrom::Current(padding))?;
         r.reads()
     }
 
@@ -64,6 +206,194 @@ impl PaddedReader {
     }
 }
 
+/// A reader that counts the bytes read from the inner reader.
+///
+/// ```
+/// use std::io;
+/// use std::io::Cursor;
+///
+/// use tora::read::{ByteCountReader, ToraRead};
+///
+/// fn main() -> io::Result<()> {
+///     let mut reader = ByteCountReader::new(Cursor::new([1, 0, 0, 0, 2]));
+///     reader.reads::<u32>()?;
+///
+///     assert_eq!(reader.count(), 4);
+///     Ok(())
+/// }
+/// ```
+pub struct ByteCountReader<R> {
+    inner: R,
+    count: u64,
+}
+
+impl<R> ByteCountReader<R> {
+    /// Constructs a ByteCountReader wrapping the given reader.
+    pub const fn new(inner: R) -> Self {
+        Self { inner, count: 0 }
+    }
+
+    /// Returns the amount of bytes read since construction or the last reset.
+    pub const fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Resets the byte count to zero, returning the previous count.
+    pub fn reset(&mut self) -> u64 {
+        std::mem::take(&mut self.count)
+    }
+
+    /// Returns a reference to the inner reader.
+    pub const fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Returns a mutable reference to the inner reader.
+    ///
+    /// Bytes read directly from the inner reader are not counted.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    /// Consumes this reader, returning the inner reader.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R> Read for ByteCountReader<R>
+where
+    R: Read,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.count += read as u64;
+        Ok(read)
+    }
+}
+
+impl<R> BufRead for ByteCountReader<R>
+where
+    R: BufRead,
+{
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        self.inner.fill_buf()
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.inner.consume(amt);
+        self.count += amt as u64;
+    }
+}
+
+/// An iterator over consecutive values of [T] read from a reader.
+///
+/// Returned by [iter].
+pub struct ReadIter<T, R> {
+    reader: R,
+    done: bool,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T, R> ReadIter<T, R> {
+    /// Consumes this iterator, returning the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+impl<T, R> Iterator for ReadIter<T, R>
+where
+    T: FromReader,
+    R: Read,
+{
+    type Item = io::Result<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        match self.reader.reads_opt() {
+            Ok(Some(value)) => Some(Ok(value)),
+            Ok(None) => {
+                self.done = true;
+                None
+            }
+            Err(e) => {
+                self.done = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+/// Returns an iterator reading consecutive values of [T] until the reader is exhausted.
+///
+/// The iterator ends when the reader reaches EOF exactly at a value boundary. If the reader ends
+/// partway through a value, the iterator yields an [ErrorKind::UnexpectedEof] error instead.
+///
+/// The iterator is fused after yielding any error.
+///
+/// Zero-sized types are rejected at compile time, as reading them consumes nothing and the
+/// iterator would never end.
+///
+/// ```
+/// use std::io;
+/// use std::io::Cursor;
+///
+/// use tora::read;
+///
+/// fn main() -> io::Result<()> {
+///     let records = read::iter::<u16, _>(Cursor::new([1, 0, 2, 0]));
+///     assert_eq!(records.collect::<io::Result<Vec<_>>>()?, [1, 2]);
+///
+///     let mut truncated = read::iter::<u16, _>(Cursor::new([1, 0, 2]));
+///     assert_eq!(truncated.next().unwrap()?, 1);
+///     assert!(truncated.next().unwrap().is_err());
+///     Ok(())
+/// }
+/// ```
+///
+/// ```compile_fail
+/// let units = tora::read::iter::<(), _>(std::io::empty());
+/// ```
+pub fn iter<T, R>(reader: R) -> ReadIter<T, R>
+where
+    T: FromReader,
+    R: Read,
+{
+    const {
+        assert!(
+            std::mem::size_of::<T>() != 0,
+            "Cannot iterate over zero-sized values"
+        )
+    };
+
+    ReadIter {
+        reader,
+        done: false,
+        _marker: PhantomData,
+    }
+}
+
+/// Tracks whether any byte was read from the inner reader.
+struct EofGuard<'a, R> {
+    inner: &'a mut R,
+    read_any: bool,
+}
+
+impl<R> Read for EofGuard<'_, R>
+where
+    R: Read,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.read_any |= read != 0;
+        Ok(read)
+    }
+}
+
 /// Marks a type as able to be deserialized from a reader.
 ///
 /// If you are implementing this trait, make sure tora's derive macros are inapplicable to your use
@@ -98,184 +428,829 @@ pub trait FromReader: Sized {
     fn from_reader<R>(r: &mut R) -> io::Result<Self>
     where
         R: Read;
-}
-
-from_reader_impl!(u8, u16, u32, u64, u128, i8, i16, i32, i64, i128, f32, f64, usize);
 
-impl FromReader for bool {
-    /// Reads a bool from this reader.
+    /// Deserialize this type, honoring the given configuration.
     ///
-    /// Returns true if the read [u8] is **not** zero.
-    fn from_reader<R>(r: &mut R) -> io::Result<Self>
+    /// The default implementation ignores the configuration and calls
+    /// [FromReader::from_reader]. Implementations containing other values should override this
+    /// method and forward the configuration to them.
+    fn from_reader_with<R>(r: &mut R, config: &ToraConfig) -> io::Result<Self>
     where
         R: Read,
     {
-        r.reads::<u8>().map(|x| x != 0)
+        let _ = config;
+        Self::from_reader(r)
     }
-}
 
-impl FromReader for char {
-    /// Reads a character from this reader.
+    /// Deserialize a value into every element of the slice, in order.
     ///
-    /// Returns [ErrorKind::InvalidData] if the read [u32] cannot be converted to a [char].
-    fn from_reader<R>(r: &mut R) -> io::Result<Self>
+    /// Used by fixed-size arrays. The default implementation deserializes the elements one at a
+    /// time, and [u8] overrides it to read all of them at once.
+    fn from_reader_slice<R>(r: &mut R, slice: &mut [Self], config: &ToraConfig) -> io::Result<()>
     where
         R: Read,
     {
-        r.reads::<u32>().and_then(|c| {
-            char::from_u32(c)
-                .ok_or_else(|| io::Error::new(ErrorKind::InvalidData, "Not a character"))
-        })
+        for value in slice {
+            *value = Self::from_reader_with(r, config)?;
+        }
+        Ok(())
     }
-}
 
-impl FromReader for String {
-    /// Read a UTF-8 string from this reader.
+    /// Deserialize `len` values into a Vec.
     ///
-    /// Reads until a NUL `0x00` byte is encountered. Does not include the terminating byte.
+    /// Used by [Vec]. The default implementation deserializes the values one at a time, and [u8]
+    /// overrides it to read all of them at once.
     ///
-    /// Returns [ErrorKind::InvalidData] if the received message is not valid UTF-8.
-    fn from_reader<R>(r: &mut R) -> io::Result<Self>
+    /// Since `len` comes from the input, implementations should only reserve a bounded capacity
+    /// up front and grow the Vec as values arrive, returning [ErrorKind::OutOfMemory] rather than
+    /// aborting when it cannot grow.
+    fn from_reader_vec<R>(r: &mut R, len: usize, config: &ToraConfig) -> io::Result<Vec<Self>>
     where
         R: Read,
     {
         let mut buf = Vec::new();
+        try_reserve(&mut buf, preallocation::<Self>(len))?;
 
-        loop {
-            let b = r.reads::<u8>()?;
-            if b == 0 {
-                break String::from_utf8(buf)
-                    .map_err(|_| io::Error::new(ErrorKind::InvalidData, "Invalid UTF-8"));
+        for i in 0..len {
+            if i % CHECK_ELEMENTS == 0 {
+                config.check_cancelled()?;
             }
-            buf.push(b);
+            buf.push(Self::from_reader_with(r, config)?);
         }
+        Ok(buf)
     }
 }
 
-impl<T> FromReader for Option<T>
+/// Marks a type as able to be deserialized from a reader with the help of external state.
+///
+/// The seed is passed by the caller and can hold anything needed to decode the value, such as an
+/// arena, a string table or a registry. Seeds are forwarded to fields by the `ReadStruct` and
+/// `ReadEnum` derive macros through the `#[tora(seed)]` attribute.
+///
+/// # Examples
+///
+/// ```
+/// use std::io;
+/// use std::io::{Cursor, Read};
+///
+/// use tora::read::{FromReaderSeed, ToraRead};
+///
+/// struct Interned(String);
+///
+/// impl FromReaderSeed<Vec<String>> for Interned {
+///     fn from_reader_seed<R>(r: &mut R, seed: &mut Vec<String>) -> io::Result<Self>
+///     where
+///         R: Read,
+///     {
+///         let s: String = r.reads()?;
+///
+///         if !seed.contains(&s) {
+///             seed.push(s.clone());
+///         }
+///         Ok(Self(s))
+///     }
+/// }
+///
+/// fn main() -> io::Result<()> {
+///     let mut seen = Vec::new();
+///     let mut cursor = Cursor::new(b"\x03\0\0\0a\0b\0a\0");
+///
+///     let values: Vec<Interned> = cursor.reads_seed(&mut seen)?;
+///
+///     assert_eq!(values.len(), 3);
+///     assert_eq!(seen, ["a", "b"]);
+///     Ok(())
+/// }
+/// ```
+pub trait FromReaderSeed<S>: Sized
 where
-    T: FromReader,
+    S: ?Sized,
 {
-    /// Reads a bool and if true, reads and returns Some([T]).
-    fn from_reader<R>(r: &mut R) -> io::Result<Self>
+    fn from_reader_seed<R>(r: &mut R, seed: &mut S) -> io::Result<Self>
+    where
+        R: Read;
+}
+
+impl<T, S> FromReaderSeed<S> for Option<T>
+where
+    T: FromReaderSeed<S>,
+    S: ?Sized,
+{
+    /// Reads a bool and if true, reads and returns Some([T]) using the seed.
+    fn from_reader_seed<R>(r: &mut R, seed: &mut S) -> io::Result<Self>
     where
         R: Read,
     {
         if r.reads::<bool>()? {
-            return Ok(Some(r.reads()?));
+            return Ok(Some(r.reads_seed(seed)?));
         }
         Ok(None)
     }
 }
 
 #[cfg(feature = "dyn_impl")]
-impl<T> FromReader for Vec<T>
+impl<T, S> FromReaderSeed<S> for Vec<T>
 where
-    T: FromReader,
+    T: FromReaderSeed<S>,
+    S: ?Sized,
 {
-    /// Reads a [u32], then reads N amount of [T] into a Vec and returns it.
-    fn from_reader<R>(r: &mut R) -> io::Result<Self>
+    /// Reads a [u32], then reads N amount of [T] using the seed into a Vec and returns it.
+    fn from_reader_seed<R>(r: &mut R, seed: &mut S) -> io::Result<Self>
     where
         R: Read,
     {
         let len = r.reads::<u32>()? as usize;
-        let mut buf = Vec::with_capacity(len);
+        let mut buf = Vec::new();
+        try_reserve(&mut buf, preallocation::<T>(len))?;
 
         for _ in 0..len {
-            buf.push(r.reads()?);
+            buf.push(r.reads_seed(seed)?);
         }
         Ok(buf)
     }
 }
 
-impl<T, const N: usize> FromReader for [T; N]
+impl<T, S> FromReaderSeed<S> for Box<T>
 where
-    T: FromReader + Copy + Default,
+    T: FromReaderSeed<S>,
+    S: ?Sized,
 {
-    /// Reads and deserializes [N] amount of [T].
-    fn from_reader<R>(r: &mut R) -> io::Result<Self>
+    fn from_reader_seed<R>(r: &mut R, seed: &mut S) -> io::Result<Self>
     where
         R: Read,
     {
-        let mut arr = [T::default(); N];
-
-        for value in arr.iter_mut() {
-            *value = r.reads()?;
-        }
-        Ok(arr)
+        Ok(Box::new(r.reads_seed(seed)?))
     }
 }
 
-impl<T, E> FromReader for Result<T, E>
-where
-    T: FromReader,
-    E: FromReader,
-{
-    /// Reads a boolean and if true, tries to deserialize the [E] type, else [T].
-    fn from_reader<R>(r: &mut R) -> io::Result<Result<T, E>>
+from_reader_impl!(u16, u128, i8, i16, i32, i64, i128);
+from_reader_impl!(packed u32, u64);
+from_reader_float!(f32, f64);
+
+/// Reads a [u64], so values are portable between 32 and 64-bit targets.
+///
+/// Returns [ErrorKind::InvalidData] if the value does not fit in a [usize] on this target.
+impl FromReader for usize {
+    fn from_reader<R>(r: &mut R) -> io::Result<Self>
     where
         R: Read,
     {
-        if r.reads()? {
-            return Ok(Err(r.reads()?));
-        }
-        Ok(Ok(r.reads()?))
+        Self::from_reader_with(r, &ToraConfig::DEFAULT)
     }
-}
 
-impl FromReader for () {
-    /// Immediately returns [Ok] of unit value.
-    fn from_reader<R>(_r: &mut R) -> io::Result<Self>
+    fn from_reader_with<R>(r: &mut R, config: &ToraConfig) -> io::Result<Self>
     where
         R: Read,
     {
-        Ok(())
+        if let Some(result) = config.read_codec(r) {
+            return result;
+        }
+        usize::try_from(u64::from_reader_with(r, config)?)
+            .map_err(|_| io::Error::new(ErrorKind::InvalidData, "Value exceeds usize::MAX"))
     }
 }
 
-impl<T, Z> FromReader for (T, Z)
-where
-    T: FromReader,
-    Z: FromReader,
-{
-    /// Reads a tuple of [T] and [Z], respectively.
+impl FromReader for u8 {
     fn from_reader<R>(r: &mut R) -> io::Result<Self>
     where
         R: Read,
     {
-        Ok((r.reads()?, r.reads()?))
+        let mut buf = [0];
+        r.read_exact(&mut buf).map(|_| buf[0])
     }
-}
 
-impl<T, Z, H> FromReader for (T, Z, H)
-where
-    T: FromReader,
-    Z: FromReader,
-    H: FromReader,
-{
-    /// Reads a tuple of [T], [Z], and [H], respectively.
-    fn from_reader<R>(r: &mut R) -> io::Result<Self>
+    fn from_reader_with<R>(r: &mut R, config: &ToraConfig) -> io::Result<Self>
     where
         R: Read,
     {
-        Ok((r.reads()?, r.reads()?, r.reads()?))
+        if let Some(result) = config.read_codec(r) {
+            return result;
+        }
+        Self::from_reader(r)
     }
-}
 
-impl<T> FromReader for Box<T>
-where T: FromReader
-{
-    fn from_reader<R>(r: &mut R) -> io::Result<Self>
+    /// Reads the bytes of the whole slice with a single `read_exact`, unless a codec is
+    /// registered for [u8].
+    fn from_reader_slice<R>(r: &mut R, slice: &mut [Self], config: &ToraConfig) -> io::Result<()>
     where
         R: Read,
     {
-        Ok(Box::new(r.reads()?))
+        if config.codec::<u8>().is_some() {
+            for value in slice {
+                *value = Self::from_reader_with(r, config)?;
+            }
+            return Ok(());
+        }
+        r.read_exact(slice)
     }
-}
 
-/// An extension upon the standard [Read] implementation.
-///
-/// ```
+    /// Reads all bytes at once, growing the Vec only as bytes arrive, unless a codec is
+    /// registered for [u8].
+    fn from_reader_vec<R>(r: &mut R, len: usize, config: &ToraConfig) -> io::Result<Vec<Self>>
+    where
+        R: Read,
+    {
+        if config.codec::<u8>().is_some() {
+            return (0..len)
+                .map(|_| Self::from_reader_with(r, config))
+                .collect();
+        }
+        let mut buf = Vec::new();
+
+        if read_bytes(r, &mut buf, len, config)? != len {
+            return Err(io::Error::new(
+                ErrorKind::UnexpectedEof,
+                "Truncated byte sequence",
+            ));
+        }
+        Ok(buf)
+    }
+}
+
+impl FromReader for bool {
+    /// Reads a bool from this reader.
+    ///
+    /// Returns true if the read [u8] is **not** zero.
+    fn from_reader<R>(r: &mut R) -> io::Result<Self>
+    where
+        R: Read,
+    {
+        r.reads::<u8>().map(|x| x != 0)
+    }
+
+    fn from_reader_with<R>(r: &mut R, config: &ToraConfig) -> io::Result<Self>
+    where
+        R: Read,
+    {
+        if let Some(result) = config.read_codec(r) {
+            return result;
+        }
+        Self::from_reader(r)
+    }
+}
+
+impl FromReader for char {
+    /// Reads a character from this reader.
+    ///
+    /// Returns [ErrorKind::InvalidData] if the read [u32] cannot be converted to a [char].
+    fn from_reader<R>(r: &mut R) -> io::Result<Self>
+    where
+        R: Read,
+    {
+        Self::from_reader_with(r, &ToraConfig::DEFAULT)
+    }
+
+    fn from_reader_with<R>(r: &mut R, config: &ToraConfig) -> io::Result<Self>
+    where
+        R: Read,
+    {
+        if let Some(result) = config.read_codec(r) {
+            return result;
+        }
+        r.reads_with::<u32>(config).and_then(|c| {
+            char::from_u32(c)
+                .ok_or_else(|| io::Error::new(ErrorKind::InvalidData, "Not a character"))
+        })
+    }
+}
+
+impl FromReader for Duration {
+    /// Reads the seconds as a [u64], then the nanoseconds as a [u32].
+    ///
+    /// Returns [ErrorKind::InvalidData] if the nanoseconds exceed a second.
+    fn from_reader<R>(r: &mut R) -> io::Result<Self>
+    where
+        R: Read,
+    {
+        Self::from_reader_with(r, &ToraConfig::DEFAULT)
+    }
+
+    fn from_reader_with<R>(r: &mut R, config: &ToraConfig) -> io::Result<Self>
+    where
+        R: Read,
+    {
+        if let Some(result) = config.read_codec(r) {
+            return result;
+        }
+        let secs = r.reads_with::<u64>(config)?;
+        let nanos = r.reads_with::<u32>(config)?;
+
+        if nanos >= 1_000_000_000 {
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                "Duration nanoseconds exceed a second",
+            ));
+        }
+        Ok(Duration::new(secs, nanos))
+    }
+}
+
+impl FromReader for String {
+    /// Read a UTF-8 string from this reader.
+    ///
+    /// Reads until a NUL `0x00` byte is encountered. Does not include the terminating byte.
+    ///
+    /// Returns [ErrorKind::InvalidData] if the received message is not valid UTF-8.
+    fn from_reader<R>(r: &mut R) -> io::Result<Self>
+    where
+        R: Read,
+    {
+        Self::from_reader_with(r, &ToraConfig::DEFAULT)
+    }
+
+    /// Read a UTF-8 string in the configured string format from this reader.
+    ///
+    /// Returns [ErrorKind::InvalidData] if the received message is not valid UTF-8, or if it is
+    /// longer than the configured maximum length.
+    fn from_reader_with<R>(r: &mut R, config: &ToraConfig) -> io::Result<Self>
+    where
+        R: Read,
+    {
+        if let Some(result) = config.read_codec(r) {
+            return result;
+        }
+        let buf = match config.string_format {
+            StringFormat::NulTerminated => {
+                let mut buf = Vec::new();
+
+                loop {
+                    let b = r.reads::<u8>()?;
+                    if b == 0 {
+                        break buf;
+                    }
+                    buf.push(b);
+                    config.check_length(buf.len())?;
+                }
+            }
+            StringFormat::LengthPrefixed => {
+                let len = config.read_length(r)?;
+                let mut buf = Vec::new();
+
+                if read_bytes(r, &mut buf, len, config)? != len {
+                    return Err(io::Error::new(ErrorKind::UnexpectedEof, "Truncated string"));
+                }
+                buf
+            }
+        };
+        String::from_utf8(buf).map_err(|_| io::Error::new(ErrorKind::InvalidData, "Invalid UTF-8"))
+    }
+}
+
+impl<T> FromReader for Option<T>
+where
+    T: FromReader,
+{
+    /// Reads a bool and if true, reads and returns Some([T]).
+    fn from_reader<R>(r: &mut R) -> io::Result<Self>
+    where
+        R: Read,
+    {
+        Self::from_reader_with(r, &ToraConfig::DEFAULT)
+    }
+
+    fn from_reader_with<R>(r: &mut R, config: &ToraConfig) -> io::Result<Self>
+    where
+        R: Read,
+    {
+        if r.reads::<bool>()? {
+            return Ok(Some(r.reads_with(config)?));
+        }
+        Ok(None)
+    }
+}
+
+#[cfg(feature = "dyn_impl")]
+impl<T> FromReader for Vec<T>
+where
+    T: FromReader,
+{
+    /// Reads the length prefix of the default configuration, then reads N amount of [T] into a Vec
+    /// and returns it.
+    fn from_reader<R>(r: &mut R) -> io::Result<Self>
+    where
+        R: Read,
+    {
+        Self::from_reader_with(r, &ToraConfig::DEFAULT)
+    }
+
+    /// Reads the configured length prefix, then reads N amount of [T] into a Vec and returns it.
+    ///
+    /// Returns [ErrorKind::InvalidData] if N exceeds the configured maximum length, and
+    /// [ErrorKind::OutOfMemory] if the Vec cannot grow. Only a bounded capacity is reserved up
+    /// front, so a length exceeding the input fails with [ErrorKind::UnexpectedEof] on the
+    /// missing values, without allocating the memory it claims.
+    ///
+    /// ```
+    /// use std::io::{Cursor, ErrorKind};
+    ///
+    /// use tora::config::{LengthPrefix, ToraConfig};
+    /// use tora::read::FromReader;
+    ///
+    /// let config = ToraConfig::DEFAULT.length_prefix(LengthPrefix::U64);
+    /// let bytes = (u64::MAX / 2).to_le_bytes();
+    ///
+    /// let e = Vec::<u64>::from_reader_with(&mut Cursor::new(bytes), &config).unwrap_err();
+    /// assert_eq!(e.kind(), ErrorKind::UnexpectedEof);
+    /// ```
+    fn from_reader_with<R>(r: &mut R, config: &ToraConfig) -> io::Result<Self>
+    where
+        R: Read,
+    {
+        let len = config.read_length(r)?;
+        T::from_reader_vec(r, len, config)
+    }
+}
+
+#[cfg(feature = "dyn_impl")]
+impl<K, V, S> FromReader for HashMap<K, V, S>
+where
+    K: FromReader + Eq + Hash,
+    V: FromReader,
+    S: BuildHasher + Default,
+{
+    /// Reads the length prefix of the default configuration, then reads N amount of keys and
+    /// values into a HashMap and returns it.
+    fn from_reader<R>(r: &mut R) -> io::Result<Self>
+    where
+        R: Read,
+    {
+        Self::from_reader_with(r, &ToraConfig::DEFAULT)
+    }
+
+    /// Reads the configured length prefix, then reads N amount of keys and values into a HashMap
+    /// with the default hasher of [S], and returns it.
+    ///
+    /// Only a bounded capacity is reserved up front, the map growing as its entries arrive, so a
+    /// length prefix exceeding the input fails on the missing entries rather than allocating for
+    /// them.
+    ///
+    /// Returns [ErrorKind::InvalidData] if N exceeds the configured maximum length, or if a key
+    /// appears twice.
+    ///
+    /// ```
+    /// use std::collections::HashMap;
+    /// use std::hash::{BuildHasherDefault, DefaultHasher};
+    /// use std::io::{Cursor, ErrorKind};
+    ///
+    /// use tora::read::ToraRead;
+    /// use tora::{ReadStruct, WriteStruct};
+    ///
+    /// type FastMap<K, V> = HashMap<K, V, BuildHasherDefault<DefaultHasher>>;
+    ///
+    /// #[derive(ReadStruct, WriteStruct)]
+    /// struct Scores {
+    ///     by_player: FastMap<u32, u16>,
+    /// }
+    ///
+    /// let mut by_player = FastMap::default();
+    /// by_player.insert(7, 120);
+    ///
+    /// let bytes = tora::testing::to_bytes(&Scores { by_player });
+    /// let scores: Scores = Cursor::new(bytes).reads().unwrap();
+    /// assert_eq!(scores.by_player[&7], 120);
+    ///
+    /// let e = Cursor::new(u32::MAX.to_le_bytes()).reads::<FastMap<u32, u16>>().unwrap_err();
+    /// assert_eq!(e.kind(), ErrorKind::UnexpectedEof);
+    /// ```
+    fn from_reader_with<R>(r: &mut R, config: &ToraConfig) -> io::Result<Self>
+    where
+        R: Read,
+    {
+        let len = config.read_length(r)?;
+        let mut map = HashMap::with_hasher(S::default());
+        map.try_reserve(preallocation::<(K, V)>(len)).map_err(|_| {
+            io::Error::new(ErrorKind::OutOfMemory, "Could not allocate read buffer")
+        })?;
+
+        for i in 0..len {
+            if i % CHECK_ELEMENTS == 0 {
+                config.check_cancelled()?;
+            }
+            let key = r.reads_with(config)?;
+
+            if map.insert(key, r.reads_with(config)?).is_some() {
+                return Err(io::Error::new(ErrorKind::InvalidData, "Duplicate map key"));
+            }
+        }
+        Ok(map)
+    }
+}
+
+#[cfg(feature = "dyn_impl")]
+impl<T, S> FromReader for HashSet<T, S>
+where
+    T: FromReader + Eq + Hash,
+    S: BuildHasher + Default,
+{
+    /// Reads the length prefix of the default configuration, then reads N amount of [T] into a
+    /// HashSet and returns it.
+    fn from_reader<R>(r: &mut R) -> io::Result<Self>
+    where
+        R: Read,
+    {
+        Self::from_reader_with(r, &ToraConfig::DEFAULT)
+    }
+
+    /// Reads the configured length prefix, then reads N amount of [T] into a HashSet with the
+    /// default hasher of [S], and returns it.
+    ///
+    /// Only a bounded capacity is reserved up front, as for [HashMap].
+    ///
+    /// Returns [ErrorKind::InvalidData] if N exceeds the configured maximum length, or if a value
+    /// appears twice.
+    fn from_reader_with<R>(r: &mut R, config: &ToraConfig) -> io::Result<Self>
+    where
+        R: Read,
+    {
+        let len = config.read_length(r)?;
+        let mut set = HashSet::with_hasher(S::default());
+        set.try_reserve(preallocation::<T>(len)).map_err(|_| {
+            io::Error::new(ErrorKind::OutOfMemory, "Could not allocate read buffer")
+        })?;
+
+        for i in 0..len {
+            if i % CHECK_ELEMENTS == 0 {
+                config.check_cancelled()?;
+            }
+            if !set.insert(r.reads_with(config)?) {
+                return Err(io::Error::new(
+                    ErrorKind::InvalidData,
+                    "Duplicate set value",
+                ));
+            }
+        }
+        Ok(set)
+    }
+}
+
+impl<T, const N: usize> FromReader for [T; N]
+where
+    T: FromReader + Copy + Default,
+{
+    /// Reads and deserializes [N] amount of [T].
+    fn from_reader<R>(r: &mut R) -> io::Result<Self>
+    where
+        R: Read,
+    {
+        Self::from_reader_with(r, &ToraConfig::DEFAULT)
+    }
+
+    fn from_reader_with<R>(r: &mut R, config: &ToraConfig) -> io::Result<Self>
+    where
+        R: Read,
+    {
+        let mut arr = [T::default(); N];
+        T::from_reader_slice(r, &mut arr, config)?;
+        Ok(arr)
+    }
+}
+
+impl<T, E> FromReader for Result<T, E>
+where
+    T: FromReader,
+    E: FromReader,
+{
+    /// Reads a boolean and if true, tries to deserialize the [E] type, else [T].
+    fn from_reader<R>(r: &mut R) -> io::Result<Result<T, E>>
+    where
+        R: Read,
+    {
+        Self::from_reader_with(r, &ToraConfig::DEFAULT)
+    }
+
+    fn from_reader_with<R>(r: &mut R, config: &ToraConfig) -> io::Result<Result<T, E>>
+    where
+        R: Read,
+    {
+        if r.reads()? {
+            return Ok(Err(r.reads_with(config)?));
+        }
+        Ok(Ok(r.reads_with(config)?))
+    }
+}
+
+impl<B, C> FromReader for ControlFlow<B, C>
+where
+    B: FromReader,
+    C: FromReader,
+{
+    /// Reads a boolean and if true, tries to deserialize a Break of [B], else a Continue of [C].
+    fn from_reader<R>(r: &mut R) -> io::Result<Self>
+    where
+        R: Read,
+    {
+        Self::from_reader_with(r, &ToraConfig::DEFAULT)
+    }
+
+    fn from_reader_with<R>(r: &mut R, config: &ToraConfig) -> io::Result<Self>
+    where
+        R: Read,
+    {
+        if r.reads()? {
+            return Ok(ControlFlow::Break(r.reads_with(config)?));
+        }
+        Ok(ControlFlow::Continue(r.reads_with(config)?))
+    }
+}
+
+impl<T> FromReader for Poll<T>
+where
+    T: FromReader,
+{
+    /// Reads a boolean and if true, tries to deserialize a Ready of [T], else returns Pending.
+    fn from_reader<R>(r: &mut R) -> io::Result<Self>
+    where
+        R: Read,
+    {
+        Self::from_reader_with(r, &ToraConfig::DEFAULT)
+    }
+
+    fn from_reader_with<R>(r: &mut R, config: &ToraConfig) -> io::Result<Self>
+    where
+        R: Read,
+    {
+        if r.reads()? {
+            return Ok(Poll::Ready(r.reads_with(config)?));
+        }
+        Ok(Poll::Pending)
+    }
+}
+
+impl FromReader for () {
+    /// Immediately returns [Ok] of unit value.
+    fn from_reader<R>(_r: &mut R) -> io::Result<Self>
+    where
+        R: Read,
+    {
+        Ok(())
+    }
+}
+
+impl<T, Z> FromReader for (T, Z)
+where
+    T: FromReader,
+    Z: FromReader,
+{
+    /// Reads a tuple of [T] and [Z], respectively.
+    fn from_reader<R>(r: &mut R) -> io::Result<Self>
+    where
+        R: Read,
+    {
+        Self::from_reader_with(r, &ToraConfig::DEFAULT)
+    }
+
+    fn from_reader_with<R>(r: &mut R, config: &ToraConfig) -> io::Result<Self>
+    where
+        R: Read,
+    {
+        Ok((r.reads_with(config)?, r.reads_with(config)?))
+    }
+}
+
+impl<T, Z, H> FromReader for (T, Z, H)
+where
+    T: FromReader,
+    Z: FromReader,
+    H: FromReader,
+{
+    /// Reads a tuple of [T], [Z], and [H], respectively.
+    fn from_reader<R>(r: &mut R) -> io::Result<Self>
+    where
+        R: Read,
+    {
+        Self::from_reader_with(r, &ToraConfig::DEFAULT)
+    }
+
+    fn from_reader_with<R>(r: &mut R, config: &ToraConfig) -> io::Result<Self>
+    where
+        R: Read,
+    {
+        Ok((
+            r.reads_with(config)?,
+            r.reads_with(config)?,
+            r.reads_with(config)?,
+        ))
+    }
+}
+
+impl<T> FromReader for Box<T>
+where
+    T: FromReader,
+{
+    fn from_reader<R>(r: &mut R) -> io::Result<Self>
+    where
+        R: Read,
+    {
+        Self::from_reader_with(r, &ToraConfig::DEFAULT)
+    }
+
+    fn from_reader_with<R>(r: &mut R, config: &ToraConfig) -> io::Result<Self>
+    where
+        R: Read,
+    {
+        Ok(Box::new(r.reads_with(config)?))
+    }
+}
+
+/// Reads a [String] into a new allocation. Use an [Interner](crate::intern::Interner) seed to
+/// share the allocations of identical strings.
+impl FromReader for Arc<str> {
+    fn from_reader<R>(r: &mut R) -> io::Result<Self>
+    where
+        R: Read,
+    {
+        Self::from_reader_with(r, &ToraConfig::DEFAULT)
+    }
+
+    fn from_reader_with<R>(r: &mut R, config: &ToraConfig) -> io::Result<Self>
+    where
+        R: Read,
+    {
+        Ok(r.reads_with::<String>(config)?.into())
+    }
+}
+
+/// Reads the value into a new, unlocked Mutex.
+impl<T> FromReader for Mutex<T>
+where
+    T: FromReader,
+{
+    fn from_reader<R>(r: &mut R) -> io::Result<Self>
+    where
+        R: Read,
+    {
+        Self::from_reader_with(r, &ToraConfig::DEFAULT)
+    }
+
+    fn from_reader_with<R>(r: &mut R, config: &ToraConfig) -> io::Result<Self>
+    where
+        R: Read,
+    {
+        Ok(Mutex::new(r.reads_with(config)?))
+    }
+}
+
+/// Reads the value into a new, unlocked RwLock.
+impl<T> FromReader for RwLock<T>
+where
+    T: FromReader,
+{
+    fn from_reader<R>(r: &mut R) -> io::Result<Self>
+    where
+        R: Read,
+    {
+        Self::from_reader_with(r, &ToraConfig::DEFAULT)
+    }
+
+    fn from_reader_with<R>(r: &mut R, config: &ToraConfig) -> io::Result<Self>
+    where
+        R: Read,
+    {
+        Ok(RwLock::new(r.reads_with(config)?))
+    }
+}
+
+impl<T> FromReader for Cell<T>
+where
+    T: FromReader,
+{
+    fn from_reader<R>(r: &mut R) -> io::Result<Self>
+    where
+        R: Read,
+    {
+        Self::from_reader_with(r, &ToraConfig::DEFAULT)
+    }
+
+    fn from_reader_with<R>(r: &mut R, config: &ToraConfig) -> io::Result<Self>
+    where
+        R: Read,
+    {
+        Ok(Cell::new(r.reads_with(config)?))
+    }
+}
+
+impl<T> FromReader for RefCell<T>
+where
+    T: FromReader,
+{
+    fn from_reader<R>(r: &mut R) -> io::Result<Self>
+    where
+        R: Read,
+    {
+        Self::from_reader_with(r, &ToraConfig::DEFAULT)
+    }
+
+    fn from_reader_with<R>(r: &mut R, config: &ToraConfig) -> io::Result<Self>
+    where
+        R: Read,
+    {
+        Ok(RefCell::new(r.reads_with(config)?))
+    }
+}
+
+/// An extension upon the standard [Read] implementation.
+///
+/// ```no_run
 /// use std::io;
 /// use std::net::TcpStream;
 /// use tora::read::ToraRead;
@@ -291,7 +1266,7 @@ where T: FromReader
 pub trait ToraRead {
     /// Try to read and deserialize a type from this reader.
     ///
-    /// ```
+    /// ```no_run
     /// use std::io;
     /// use std::net::TcpStream;
     /// use tora::read::ToraRead;
@@ -309,6 +1284,193 @@ pub trait ToraRead {
     fn reads<T>(&mut self) -> io::Result<T>
     where
         T: FromReader;
+
+    /// Try to read and deserialize a type from this reader, honoring the given configuration.
+    ///
+    /// See [ToraConfig].
+    fn reads_with<T>(&mut self, config: &ToraConfig) -> io::Result<T>
+    where
+        T: FromReader,
+        Self: Read + Sized,
+    {
+        T::from_reader_with(self, config)
+    }
+
+    /// Try to read and deserialize a type from this reader, passing the given seed to it.
+    ///
+    /// See [FromReaderSeed].
+    fn reads_seed<T, S>(&mut self, seed: &mut S) -> io::Result<T>
+    where
+        T: FromReaderSeed<S>,
+        S: ?Sized,
+        Self: Read + Sized,
+    {
+        T::from_reader_seed(self, seed)
+    }
+
+    /// Try to read and deserialize `n` consecutive values of [T] from this reader.
+    ///
+    /// Unlike reading a [Vec], no length prefix is read; the element count must be known by the
+    /// caller, such as from a previously read header field.
+    ///
+    /// ```
+    /// use std::io;
+    /// use std::io::Cursor;
+    /// use tora::read::ToraRead;
+    ///
+    /// fn main() -> io::Result<()> {
+    ///     let mut cursor = Cursor::new([2, 5, 0, 6, 0]);
+    ///
+    ///     let count = cursor.reads::<u8>()?;
+    ///     let values = cursor.reads_n::<u16>(count as usize)?;
+    ///
+    ///     assert_eq!(values, [5, 6]);
+    ///     Ok(())
+    /// }
+    /// ```
+    fn reads_n<T>(&mut self, n: usize) -> io::Result<Vec<T>>
+    where
+        T: FromReader,
+    {
+        let mut buf = Vec::new();
+        try_reserve(&mut buf, preallocation::<T>(n))?;
+
+        for _ in 0..n {
+            buf.push(self.reads()?);
+        }
+        Ok(buf)
+    }
+
+    /// Try to read and deserialize a type from this reader, returning [None] if the reader is
+    /// already exhausted.
+    ///
+    /// EOF is only treated as the end of the stream if it occurs before the first byte of the
+    /// value. If the reader ends partway through the value, [ErrorKind::UnexpectedEof] is returned.
+    ///
+    /// ```
+    /// use std::io;
+    /// use std::io::Cursor;
+    /// use tora::read::ToraRead;
+    ///
+    /// fn main() -> io::Result<()> {
+    ///     let mut cursor = Cursor::new([1, 0, 2]);
+    ///
+    ///     assert_eq!(cursor.reads_opt::<u16>()?, Some(1));
+    ///     assert!(cursor.reads_opt::<u16>().is_err());
+    ///     assert_eq!(cursor.reads_opt::<u16>()?, None);
+    ///     Ok(())
+    /// }
+    /// ```
+    fn reads_opt<T>(&mut self) -> io::Result<Option<T>>
+    where
+        T: FromReader,
+        Self: Read + Sized,
+    {
+        let mut guard = EofGuard {
+            inner: self,
+            read_any: false,
+        };
+
+        match T::from_reader(&mut guard) {
+            Ok(value) => Ok(Some(value)),
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof && !guard.read_any => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Try to read and deserialize a type from this reader without consuming it, seeking back to
+    /// where the value started.
+    ///
+    /// The reader is restored even if the value could not be read. Readers that cannot seek can
+    /// be wrapped in a [PeekReader](crate::peek::PeekReader) instead.
+    ///
+    /// ```
+    /// use std::io;
+    /// use std::io::Cursor;
+    /// use tora::read::ToraRead;
+    ///
+    /// fn main() -> io::Result<()> {
+    ///     let mut cursor = Cursor::new([3, 0, 4, 0]);
+    ///
+    ///     assert_eq!(cursor.peeks::<u16>()?, 3);
+    ///     assert_eq!(cursor.reads::<(u16, u16)>()?, (3, 4));
+    ///     Ok(())
+    /// }
+    /// ```
+    fn peeks<T>(&mut self) -> io::Result<T>
+    where
+        T: FromReader,
+        Self: Read + Seek + Sized,
+    {
+        self.peeks_with(&ToraConfig::DEFAULT)
+    }
+
+    /// Try to read and deserialize a type from this reader without consuming it, honoring the
+    /// given configuration.
+    ///
+    /// See [ToraRead::peeks].
+    fn peeks_with<T>(&mut self, config: &ToraConfig) -> io::Result<T>
+    where
+        T: FromReader,
+        Self: Read + Seek + Sized,
+    {
+        let start = self.stream_position()?;
+        let value = T::from_reader_with(self, config);
+
+        self.seek(SeekFrom::Start(start))?;
+        value
+    }
+
+    /// Advance past a value of [T] without deserializing it.
+    ///
+    /// See [Skip].
+    fn skips<T>(&mut self) -> io::Result<()>
+    where
+        T: Skip,
+        Self: SkipRead + Sized,
+    {
+        T::skip(self)
+    }
+
+    /// Read and discard exactly `n` bytes from this reader.
+    ///
+    /// The bytes are consumed through a fixed-size stack buffer, so no allocation is made
+    /// regardless of `n`.
+    ///
+    /// Returns [ErrorKind::UnexpectedEof] if the reader ends before `n` bytes were skipped.
+    ///
+    /// ```
+    /// use std::io;
+    /// use std::io::Cursor;
+    /// use tora::read::ToraRead;
+    ///
+    /// fn main() -> io::Result<()> {
+    ///     let mut cursor = Cursor::new([0xFF, 0xFF, 0xFF, 7]);
+    ///     cursor.skip(3)?;
+    ///
+    ///     assert_eq!(cursor.reads::<u8>()?, 7);
+    ///     Ok(())
+    /// }
+    /// ```
+    fn skip(&mut self, n: usize) -> io::Result<()>
+    where
+        Self: Read,
+    {
+        let mut buf = [0; 256];
+        let mut remaining = n;
+
+        while remaining > 0 {
+            let len = remaining.min(buf.len());
+
+            match self.read(&mut buf[..len]) {
+                Ok(0) => return Err(io::Error::new(ErrorKind::UnexpectedEof, "Could not skip")),
+                Ok(read) => remaining -= read,
+                Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(())
+    }
 }
 
 #[cfg(feature = "read_impl")]
@@ -323,3 +1485,52 @@ where
         T::from_reader(self)
     }
 }
+
+/// Reserves capacity for `additional` more elements, returning [ErrorKind::OutOfMemory] instead of
+/// aborting if the allocation fails.
+///
+/// Lengths are read from untrusted input, so a corrupt or hostile length must not take down the
+/// process.
+pub(crate) fn try_reserve<T>(buf: &mut Vec<T>, additional: usize) -> io::Result<()> {
+    buf.try_reserve(additional)
+        .map_err(|_| io::Error::new(ErrorKind::OutOfMemory, "Could not allocate read buffer"))
+}
+
+/// The most memory reserved up front for a collection whose length is read from the input.
+const MAX_PREALLOCATION: usize = 64 * 1024;
+
+/// Returns the capacity to reserve up front for `len` elements of [T] whose length is read from
+/// the input.
+///
+/// Larger collections grow as their elements arrive, so a length exceeding the input fails on the
+/// missing elements before the memory it claims is allocated.
+pub(crate) fn preallocation<T>(len: usize) -> usize {
+    len.min(MAX_PREALLOCATION / std::mem::size_of::<T>().max(1))
+}
+
+/// Appends up to `len` bytes to `buf`, growing it only as bytes arrive and checking for
+/// cancellation between chunks.
+///
+/// Returns the amount of bytes appended, fewer than `len` if the reader ends first.
+pub(crate) fn read_bytes<R>(
+    r: &mut R,
+    buf: &mut Vec<u8>,
+    len: usize,
+    config: &ToraConfig,
+) -> io::Result<usize>
+where
+    R: Read,
+{
+    let mut read = 0;
+
+    while read < len {
+        config.check_cancelled()?;
+        let chunk = (len - read).min(CHECK_BYTES) as u64;
+
+        match r.by_ref().take(chunk).read_to_end(buf)? {
+            0 => break,
+            n => read += n,
+        }
+    }
+    Ok(read)
+}
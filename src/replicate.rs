@@ -0,0 +1,475 @@
+//! Replicating a state to clients as a snapshot, then diffs.
+//!
+//! A [Replicator] runs on the server, one per client. Every tick, it turns the current state into
+//! an [Update]: a full snapshot for a client that just joined, and otherwise a [Delta] against
+//! the last state the client acknowledged, unless the diff would be larger. A [Replica] runs on
+//! the client, applying updates and returning the sequence number to acknowledge.
+//!
+//! Diffs are computed on the serialized bytes of the state, so any [SerializeIo] type can be
+//! replicated. As a diff is always based on an acknowledged state, lost updates do not break the
+//! replication; the next diff is simply larger. Once a client acknowledged none of the last
+//! [max_lag](Replicator::with_max_lag) updates, it receives a snapshot again.
+//!
+//! ```
+//! use std::io;
+//!
+//! use tora::replicate::{Replica, Replicator, Update};
+//!
+//! fn main() -> io::Result<()> {
+//!     let mut world = vec![0u32; 256];
+//!
+//!     let mut server = Replicator::new();
+//!     let mut client = Replica::<Vec<u32>>::new();
+//!
+//!     // The first update is a snapshot, as the client acknowledged nothing yet.
+//!     let update = server.update(&world)?;
+//!     assert!(matches!(update, Update::Snapshot { .. }));
+//!
+//!     let (seq, state) = client.apply(&update)?;
+//!     server.ack(seq);
+//!     assert_eq!(state, world);
+//!
+//!     // Later updates only hold the bytes that changed, instead of the 1 KiB state.
+//!     world[100] = 7;
+//!     let update = server.update(&world)?;
+//!     assert!(matches!(update, Update::Diff { .. }));
+//!     assert!(tora::testing::to_bytes(&update).len() < 64);
+//!
+//!     let (seq, state) = client.apply(&update)?;
+//!     server.ack(seq);
+//!     assert_eq!(state, world);
+//!     Ok(())
+//! }
+//! ```
+
+use std::collections::VecDeque;
+use std::io;
+use std::io::{ErrorKind, Read, Write};
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+use crate::config::ToraConfig;
+use crate::read::{preallocation, try_reserve, FromReader, ToraRead};
+use crate::stream::decode_frame;
+use crate::write::{SerializeIo, ToraWrite};
+
+const SNAPSHOT: u8 = 0;
+const DIFF: u8 = 1;
+
+/// The default amount of updates a client may lag behind before receiving a snapshot.
+pub const DEFAULT_MAX_LAG: u64 = 32;
+
+/// Changed runs separated by at most this many equal bytes are merged, as a run costs more.
+const MERGE_GAP: usize = 8;
+
+/// The changes turning a base sequence of bytes into a target.
+///
+/// Holds the length of the target, and the runs of bytes that differ from the base, along with
+/// their offset.
+#[derive(Clone, Debug, Default, Eq, Hash, PartialEq)]
+pub struct Delta {
+    pub len: usize,
+    pub runs: Vec<(usize, Vec<u8>)>,
+}
+
+impl Delta {
+    /// Computes the changes turning `base` into `target`.
+    pub fn between(base: &[u8], target: &[u8]) -> Self {
+        let mut runs: Vec<(usize, Vec<u8>)> = Vec::new();
+        let mut i = 0;
+
+        while i < target.len() {
+            if base.get(i) == Some(&target[i]) {
+                i += 1;
+                continue;
+            }
+            let start = i;
+            while i < target.len() && base.get(i) != Some(&target[i]) {
+                i += 1;
+            }
+
+            match runs.last_mut() {
+                Some((offset, bytes)) if start - (*offset + bytes.len()) <= MERGE_GAP => {
+                    bytes.extend_from_slice(&target[*offset + bytes.len()..i]);
+                }
+                _ => runs.push((start, target[start..i].to_vec())),
+            }
+        }
+
+        Self {
+            len: target.len(),
+            runs,
+        }
+    }
+
+    /// Returns an estimate of the amount of bytes this delta is written in.
+    fn cost(&self) -> usize {
+        self.runs.iter().map(|(_, bytes)| bytes.len() + 8).sum()
+    }
+
+    /// Applies the changes to `base`, returning the target.
+    ///
+    /// Returns [ErrorKind::InvalidData] if a run lies outside of the target, or if the target is
+    /// longer than the base by more bytes than the runs hold, as every byte past the end of the
+    /// base is part of a run.
+    ///
+    /// ```
+    /// use std::io::ErrorKind;
+    ///
+    /// use tora::replicate::Delta;
+    ///
+    /// let delta = Delta::between(&[1, 2, 3], &[1, 5, 3, 4]);
+    /// assert_eq!(delta.apply(&[1, 2, 3]).unwrap(), [1, 5, 3, 4]);
+    ///
+    /// // A forged length, which would allocate far more than the delta holds.
+    /// let forged = Delta {
+    ///     len: usize::MAX,
+    ///     runs: Vec::new(),
+    /// };
+    /// assert_eq!(forged.apply(&[1, 2, 3]).unwrap_err().kind(), ErrorKind::InvalidData);
+    /// ```
+    pub fn apply(&self, base: &[u8]) -> io::Result<Vec<u8>> {
+        let carried = self
+            .runs
+            .iter()
+            .map(|(_, bytes)| bytes.len())
+            .sum::<usize>();
+
+        if self.len.saturating_sub(base.len()) > carried {
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                "Delta grows the base by more bytes than its runs hold",
+            ));
+        }
+
+        let mut target = base.to_vec();
+        target.resize(self.len, 0);
+
+        for (offset, bytes) in &self.runs {
+            let range = offset
+                .checked_add(bytes.len())
+                .filter(|end| *end <= self.len)
+                .map(|end| *offset..end)
+                .ok_or_else(|| {
+                    io::Error::new(ErrorKind::InvalidData, "Delta run outside of the target")
+                })?;
+            target[range].copy_from_slice(bytes);
+        }
+        Ok(target)
+    }
+}
+
+impl FromReader for Delta {
+    fn from_reader<R>(r: &mut R) -> io::Result<Self>
+    where
+        R: Read,
+    {
+        Self::from_reader_with(r, &ToraConfig::DEFAULT)
+    }
+
+    fn from_reader_with<R>(r: &mut R, config: &ToraConfig) -> io::Result<Self>
+    where
+        R: Read,
+    {
+        let len = config.read_length(r)?;
+        let count = config.read_length(r)?;
+
+        let mut runs = Vec::new();
+        try_reserve(&mut runs, preallocation::<(usize, Vec<u8>)>(count))?;
+
+        for _ in 0..count {
+            runs.push((config.read_length(r)?, r.reads_with(config)?));
+        }
+        Ok(Self { len, runs })
+    }
+}
+
+impl SerializeIo for Delta {
+    fn serialize<W>(&self, w: &mut W) -> io::Result<()>
+    where
+        W: Write,
+    {
+        self.serialize_with(w, &ToraConfig::DEFAULT)
+    }
+
+    fn serialize_with<W>(&self, w: &mut W, config: &ToraConfig) -> io::Result<()>
+    where
+        W: Write,
+    {
+        config.write_length(w, self.len)?;
+        config.write_length(w, self.runs.len())?;
+
+        for (offset, bytes) in &self.runs {
+            config.write_length(w, *offset)?;
+            w.writes_with(bytes, config)?;
+        }
+        Ok(())
+    }
+}
+
+/// A replicated state, as sent by a [Replicator].
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum Update {
+    /// The full serialized state.
+    Snapshot { seq: u64, state: Vec<u8> },
+    /// The changes since the state numbered `base`.
+    Diff { seq: u64, base: u64, delta: Delta },
+}
+
+impl Update {
+    /// Returns the sequence number of the state, which the client acknowledges.
+    pub fn seq(&self) -> u64 {
+        match *self {
+            Self::Snapshot { seq, .. } | Self::Diff { seq, .. } => seq,
+        }
+    }
+}
+
+impl FromReader for Update {
+    fn from_reader<R>(r: &mut R) -> io::Result<Self>
+    where
+        R: Read,
+    {
+        Self::from_reader_with(r, &ToraConfig::DEFAULT)
+    }
+
+    fn from_reader_with<R>(r: &mut R, config: &ToraConfig) -> io::Result<Self>
+    where
+        R: Read,
+    {
+        match r.reads_with::<u8>(config)? {
+            SNAPSHOT => Ok(Self::Snapshot {
+                seq: r.reads_with(config)?,
+                state: r.reads_with(config)?,
+            }),
+            DIFF => Ok(Self::Diff {
+                seq: r.reads_with(config)?,
+                base: r.reads_with(config)?,
+                delta: r.reads_with(config)?,
+            }),
+            _ => Err(io::Error::new(
+                ErrorKind::InvalidData,
+                "Invalid Update variant id",
+            )),
+        }
+    }
+}
+
+impl SerializeIo for Update {
+    fn serialize<W>(&self, w: &mut W) -> io::Result<()>
+    where
+        W: Write,
+    {
+        self.serialize_with(w, &ToraConfig::DEFAULT)
+    }
+
+    fn serialize_with<W>(&self, w: &mut W, config: &ToraConfig) -> io::Result<()>
+    where
+        W: Write,
+    {
+        match self {
+            Self::Snapshot { seq, state } => {
+                w.writes_with(&SNAPSHOT, config)?;
+                w.writes_with(seq, config)?;
+                w.writes_with(state, config)
+            }
+            Self::Diff { seq, base, delta } => {
+                w.writes_with(&DIFF, config)?;
+                w.writes_with(seq, config)?;
+                w.writes_with(base, config)?;
+                w.writes_with(delta, config)
+            }
+        }
+    }
+}
+
+/// The server side of the replication of a state of type [T] to one client.
+///
+/// Keeps the states sent within the last [max_lag](Self::with_max_lag) updates, and the last
+/// state the client acknowledged, which diffs are based on.
+#[derive(Debug)]
+pub struct Replicator<T> {
+    config: ToraConfig,
+    max_lag: u64,
+    next_seq: u64,
+    /// The states sent but not acknowledged yet, in order.
+    sent: VecDeque<(u64, Arc<[u8]>)>,
+    acked: Option<(u64, Arc<[u8]>)>,
+    _marker: PhantomData<fn(&T)>,
+}
+
+impl<T> Default for Replicator<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Replicator<T> {
+    /// Constructs a Replicator using the default maximum lag and configuration.
+    pub fn new() -> Self {
+        Self::with_config(DEFAULT_MAX_LAG, ToraConfig::DEFAULT)
+    }
+
+    /// Constructs a Replicator sending a snapshot once the client lags `max_lag` updates behind,
+    /// using the default configuration.
+    pub fn with_max_lag(max_lag: u64) -> Self {
+        Self::with_config(max_lag, ToraConfig::DEFAULT)
+    }
+
+    /// Constructs a Replicator sending a snapshot once the client lags `max_lag` updates behind,
+    /// using the given configuration.
+    pub fn with_config(max_lag: u64, config: ToraConfig) -> Self {
+        Self {
+            config,
+            max_lag,
+            next_seq: 0,
+            sent: VecDeque::new(),
+            acked: None,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Serializes the state and returns the update bringing the client to it.
+    pub fn update(&mut self, state: &T) -> io::Result<Update>
+    where
+        T: SerializeIo,
+    {
+        let mut bytes = Vec::new();
+        state.serialize_with(&mut bytes, &self.config)?;
+        Ok(self.update_serialized(bytes.into()))
+    }
+
+    /// Returns the update bringing the client to a state already serialized.
+    ///
+    /// A server replicating one state to many clients serializes it once, and shares the bytes
+    /// between the replicators of every client.
+    pub fn update_serialized(&mut self, state: Arc<[u8]>) -> Update {
+        let seq = self.next_seq;
+        self.next_seq += 1;
+
+        let delta = match self.acked {
+            Some((base, ref bytes)) if seq - base <= self.max_lag => {
+                Some((base, Delta::between(bytes, &state)))
+            }
+            _ => None,
+        };
+        let update = match delta {
+            // A diff changing most of the state is larger than a snapshot.
+            Some((base, delta)) if delta.cost() < state.len() => Update::Diff { seq, base, delta },
+            _ => Update::Snapshot {
+                seq,
+                state: state.to_vec(),
+            },
+        };
+
+        // States older than the maximum lag are never diffed against, even if acknowledged.
+        while self
+            .sent
+            .front()
+            .is_some_and(|(sent, _)| sent.saturating_add(self.max_lag) < seq)
+        {
+            self.sent.pop_front();
+        }
+        self.sent.push_back((seq, state));
+        update
+    }
+
+    /// Records that the client acknowledged the state numbered `seq`, so later diffs are based
+    /// on it.
+    ///
+    /// Acknowledgements older than the last one, or of states no longer kept, are ignored.
+    pub fn ack(&mut self, seq: u64) {
+        if let Some(i) = self.sent.iter().position(|(sent, _)| *sent == seq) {
+            self.acked = self.sent.drain(..=i).next_back();
+        }
+    }
+
+    /// Forgets the acknowledged state, so the next update is a snapshot.
+    ///
+    /// Called when the client reports it could not apply a diff.
+    pub fn resync(&mut self) {
+        self.acked = None;
+    }
+
+    /// Returns the sequence number of the last state the client acknowledged.
+    pub fn acked(&self) -> Option<u64> {
+        self.acked.as_ref().map(|(seq, _)| *seq)
+    }
+}
+
+/// The client side of the replication of a state of type [T].
+///
+/// Keeps the states received within the last [max_lag](Self::with_max_lag) updates, as diffs may
+/// be based on any state acknowledged since.
+#[derive(Debug)]
+pub struct Replica<T> {
+    config: ToraConfig,
+    max_lag: u64,
+    states: VecDeque<(u64, Vec<u8>)>,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T> Default for Replica<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Replica<T> {
+    /// Constructs a Replica using the default maximum lag and configuration.
+    pub fn new() -> Self {
+        Self::with_config(DEFAULT_MAX_LAG, ToraConfig::DEFAULT)
+    }
+
+    /// Constructs a Replica keeping the states of the last `max_lag` updates, using the default
+    /// configuration. Must match the maximum lag of the [Replicator].
+    pub fn with_max_lag(max_lag: u64) -> Self {
+        Self::with_config(max_lag, ToraConfig::DEFAULT)
+    }
+
+    /// Constructs a Replica keeping the states of the last `max_lag` updates, using the given
+    /// configuration. Must match the maximum lag of the [Replicator].
+    pub fn with_config(max_lag: u64, config: ToraConfig) -> Self {
+        Self {
+            config,
+            max_lag,
+            states: VecDeque::new(),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Applies the update, returning its sequence number, to acknowledge, and the new state.
+    ///
+    /// Returns [ErrorKind::InvalidData] if a diff is based on a state this replica does not have,
+    /// in which case the server should [resync](Replicator::resync).
+    pub fn apply(&mut self, update: &Update) -> io::Result<(u64, T)>
+    where
+        T: FromReader,
+    {
+        let (seq, bytes) = match update {
+            Update::Snapshot { seq, state } => (*seq, state.clone()),
+            Update::Diff { seq, base, delta } => {
+                let Some(i) = self.states.iter().position(|(s, _)| s == base) else {
+                    return Err(io::Error::new(
+                        ErrorKind::InvalidData,
+                        format!("Diff based on unknown state {base}"),
+                    ));
+                };
+                // The server acknowledged the base, so it never diffs against older states.
+                self.states.drain(..i);
+                (*seq, delta.apply(&self.states[0].1)?)
+            }
+        };
+        let state = decode_frame(&bytes, &self.config)?;
+
+        while self
+            .states
+            .front()
+            .is_some_and(|(kept, _)| kept.saturating_add(self.max_lag) < seq)
+        {
+            self.states.pop_front();
+        }
+        self.states.push_back((seq, bytes));
+        Ok((seq, state))
+    }
+}
@@ -0,0 +1,472 @@
+//! Resumable parsing of large values.
+//!
+//! Reading a value from a [Read] blocks until all of it arrived. The parsers of this module are
+//! fed bytes as they arrive instead, and suspend wherever the input runs out, at any depth of
+//! the value, to continue from that exact point once more bytes are fed. No byte is parsed twice,
+//! and the fed bytes are dropped as soon as they are parsed.
+//!
+//! A [SeqParser] parses a length-prefixed sequence, such as a `Vec` or a map, handing out its
+//! elements as they are parsed, so memory is bounded by what the caller keeps rather than by the
+//! length of the sequence. A [ValueParser] parses any [FromReader] value, such as a struct
+//! holding a huge `Vec`.
+//!
+//! ```
+//! use std::io;
+//!
+//! use tora::resume::SeqParser;
+//!
+//! fn main() -> io::Result<()> {
+//!     let bytes = tora::testing::to_bytes(&vec![1u32, 2, 3, 4]);
+//!     let mut parser = SeqParser::<u32>::new();
+//!     let mut sum = 0;
+//!
+//!     for chunk in bytes.chunks(3) {
+//!         parser.feed(chunk)?;
+//!         sum += parser.take_items().iter().sum::<u32>();
+//!     }
+//!
+//!     assert!(parser.is_complete());
+//!     assert_eq!(sum, 10);
+//!     Ok(())
+//! }
+//! ```
+//!
+//! Every parser reads on a thread of its own, started by the first [feed](SeqParser::feed) and
+//! parked while it waits for input. A feed returns once the thread consumed all the bytes fed,
+//! so the parsers behave as if parsing happened within the call. Dropping a parser ends its
+//! thread.
+
+use std::collections::VecDeque;
+use std::io::{ErrorKind, Read};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError};
+use std::thread::{self, JoinHandle};
+use std::{fmt, io};
+
+use crate::cancel::CHECK_ELEMENTS;
+use crate::config::ToraConfig;
+use crate::read::FromReader;
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+#[derive(Default)]
+struct InputState {
+    /// The bytes fed and not yet read by the parsing thread.
+    buf: VecDeque<u8>,
+    /// Whether the parsing thread read every byte fed, and waits for more.
+    waiting: bool,
+    /// Whether no more bytes will be fed.
+    closed: bool,
+    /// Whether the parsing thread ended.
+    done: bool,
+}
+
+#[derive(Default)]
+struct Input {
+    state: Mutex<InputState>,
+    /// Notified whenever bytes were fed or read, or the parsing thread ended.
+    changed: Condvar,
+}
+
+impl Input {
+    fn wait<'a>(&self, state: MutexGuard<'a, InputState>) -> MutexGuard<'a, InputState> {
+        self.changed
+            .wait(state)
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn close(&self) {
+        lock(&self.state).closed = true;
+        self.changed.notify_all();
+    }
+}
+
+/// Reads the bytes fed to a parser on its parsing thread, blocking until they arrive.
+///
+/// Reaches the end of its input once the parser is finished or dropped.
+struct InputReader(Arc<Input>);
+
+impl Read for InputReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut state = lock(&self.0.state);
+
+        loop {
+            if !state.buf.is_empty() || state.closed || buf.is_empty() {
+                return state.buf.read(buf);
+            }
+            state.waiting = true;
+            self.0.changed.notify_all();
+            state = self.0.wait(state);
+        }
+    }
+}
+
+/// Marks the input as done when the parsing thread ends, even by panicking.
+struct Done(Arc<Input>);
+
+impl Drop for Done {
+    fn drop(&mut self) {
+        lock(&self.0.state).done = true;
+        self.0.changed.notify_all();
+    }
+}
+
+type Parse<V> = Box<dyn FnOnce(&mut InputReader) -> io::Result<V> + Send>;
+
+/// A parse running on a thread of its own, reading the bytes fed to it.
+struct Resumable<V> {
+    input: Arc<Input>,
+    /// The parse, until the first feed starts its thread.
+    parse: Option<Parse<V>>,
+    thread: Option<JoinHandle<io::Result<V>>>,
+    /// The parsed value, once the parsing thread ended.
+    value: Option<V>,
+    /// The error the parsing thread ended with.
+    error: Option<(ErrorKind, String)>,
+    /// The bytes fed after the end of the value.
+    remainder: Vec<u8>,
+}
+
+impl<V> Resumable<V>
+where
+    V: Send + 'static,
+{
+    fn new<F>(parse: F) -> Self
+    where
+        F: FnOnce(&mut InputReader) -> io::Result<V> + Send + 'static,
+    {
+        Self {
+            input: Arc::default(),
+            parse: Some(Box::new(parse)),
+            thread: None,
+            value: None,
+            error: None,
+            remainder: Vec::new(),
+        }
+    }
+
+    fn is_done(&self) -> bool {
+        self.value.is_some() || self.error.is_some()
+    }
+
+    fn feed(&mut self, bytes: &[u8]) -> io::Result<()> {
+        if let Some((kind, ref message)) = self.error {
+            return Err(io::Error::new(kind, message.clone()));
+        }
+        if self.value.is_some() {
+            self.remainder.extend_from_slice(bytes);
+            return Ok(());
+        }
+        if let Some(parse) = self.parse.take() {
+            let input = self.input.clone();
+            self.thread = Some(
+                thread::Builder::new()
+                    .name("tora-resume".to_string())
+                    .spawn(move || {
+                        let _done = Done(input.clone());
+                        parse(&mut InputReader(input))
+                    })?,
+            );
+        }
+
+        let mut state = lock(&self.input.state);
+        state.buf.extend(bytes);
+        state.waiting = false;
+        self.input.changed.notify_all();
+
+        while !state.waiting && !state.done {
+            state = self.input.wait(state);
+        }
+        if !state.done {
+            return Ok(());
+        }
+
+        self.remainder.extend(state.buf.drain(..));
+        drop(state);
+        self.join()
+    }
+
+    /// Collects the result of the ended parsing thread.
+    fn join(&mut self) -> io::Result<()> {
+        let result = match self.thread.take() {
+            Some(thread) => thread
+                .join()
+                .unwrap_or_else(|_| Err(io::Error::other("The parsing thread panicked"))),
+            None => return Ok(()),
+        };
+
+        match result {
+            Ok(value) => {
+                self.value = Some(value);
+                Ok(())
+            }
+            Err(e) => {
+                self.error = Some((e.kind(), e.to_string()));
+                Err(e)
+            }
+        }
+    }
+
+    /// Ends the input, returning the value if it was complete.
+    fn finish(mut self) -> io::Result<V> {
+        if !self.is_done() {
+            self.feed(&[])?;
+            self.input.close();
+            self.join()?;
+        }
+        match (self.value.take(), self.error.take()) {
+            (Some(value), _) => Ok(value),
+            (None, Some((kind, message))) => Err(io::Error::new(kind, message)),
+            (None, None) => Err(io::Error::new(ErrorKind::UnexpectedEof, "Truncated value")),
+        }
+    }
+}
+
+impl<V> Drop for Resumable<V> {
+    fn drop(&mut self) {
+        self.input.close();
+    }
+}
+
+impl<V> fmt::Debug for Resumable<V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Resumable")
+            .field("started", &self.parse.is_none())
+            .field("error", &self.error)
+            .field("remainder", &self.remainder)
+            .finish_non_exhaustive()
+    }
+}
+
+#[derive(Debug)]
+struct Progress<T> {
+    len: Option<usize>,
+    /// The elements parsed since the last feed returned.
+    items: Vec<T>,
+}
+
+/// A parser of a length-prefixed sequence of [T], as written by a `Vec<T>`, a slice or a map of
+/// `(K, V)` entries, which suspends when its input runs out.
+///
+/// After an error, the parser only returns that error again and should be discarded.
+#[derive(Debug)]
+pub struct SeqParser<T> {
+    parser: Resumable<()>,
+    progress: Arc<Mutex<Progress<T>>>,
+    len: Option<usize>,
+    parsed: usize,
+    items: Vec<T>,
+}
+
+impl<T> SeqParser<T>
+where
+    T: FromReader + Send + 'static,
+{
+    /// Constructs a parser using the default configuration.
+    pub fn new() -> Self {
+        Self::with_config(ToraConfig::DEFAULT)
+    }
+
+    /// Constructs a parser using the given configuration.
+    pub fn with_config(config: ToraConfig) -> Self {
+        let progress = Arc::new(Mutex::new(Progress {
+            len: None,
+            items: Vec::new(),
+        }));
+
+        let parser = Resumable::new({
+            let progress = progress.clone();
+
+            move |r| {
+                let len = config.read_length(r)?;
+                lock(&progress).len = Some(len);
+
+                for i in 0..len {
+                    if i.is_multiple_of(CHECK_ELEMENTS) {
+                        config.check_cancelled()?;
+                    }
+                    let item = T::from_reader_with(r, &config)?;
+                    lock(&progress).items.push(item);
+                }
+                Ok(())
+            }
+        });
+
+        Self {
+            parser,
+            progress,
+            len: None,
+            parsed: 0,
+            items: Vec::new(),
+        }
+    }
+
+    /// Parses as many elements as the bytes received so far hold, keeping the progress made
+    /// into the element cut off at the end.
+    ///
+    /// Bytes following the sequence are kept, see [SeqParser::remainder].
+    pub fn feed(&mut self, bytes: &[u8]) -> io::Result<()> {
+        let result = self.parser.feed(bytes);
+
+        let mut progress = lock(&self.progress);
+        self.len = progress.len;
+        self.parsed += progress.items.len();
+        self.items.append(&mut progress.items);
+        result
+    }
+
+    /// Returns the amount of elements in the sequence, once its length prefix was parsed.
+    pub fn total(&self) -> Option<usize> {
+        self.len
+    }
+
+    /// Returns the amount of elements parsed so far, including those taken out.
+    pub fn parsed(&self) -> usize {
+        self.parsed
+    }
+
+    /// Returns true if every element of the sequence was parsed.
+    pub fn is_complete(&self) -> bool {
+        self.parser.value.is_some()
+    }
+
+    /// Returns the elements parsed and not yet taken out.
+    pub fn items(&self) -> &[T] {
+        &self.items
+    }
+
+    /// Takes out the elements parsed so far.
+    pub fn take_items(&mut self) -> Vec<T> {
+        std::mem::take(&mut self.items)
+    }
+
+    /// Returns the bytes received after the end of the sequence, once it is complete.
+    ///
+    /// ```
+    /// use std::io;
+    ///
+    /// use tora::resume::SeqParser;
+    ///
+    /// fn main() -> io::Result<()> {
+    ///     let mut parser = SeqParser::<u16>::new();
+    ///
+    ///     parser.feed(&[2, 0, 0, 0, 7, 0, 8])?;
+    ///     assert_eq!(parser.items(), [7]);
+    ///     assert!(parser.remainder().is_empty());
+    ///
+    ///     parser.feed(&[0, 0xFF])?;
+    ///     assert_eq!(parser.remainder(), [0xFF]);
+    ///     assert_eq!(parser.finish()?, [7, 8]);
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn remainder(&self) -> &[u8] {
+        &self.parser.remainder
+    }
+
+    /// Returns the elements not yet taken out.
+    ///
+    /// Returns [ErrorKind::UnexpectedEof] if the sequence is incomplete.
+    pub fn finish(self) -> io::Result<Vec<T>> {
+        self.parser.finish().map(|()| self.items)
+    }
+}
+
+impl<T> Default for SeqParser<T>
+where
+    T: FromReader + Send + 'static,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A parser of a single [T], which suspends when its input runs out.
+///
+/// After an error, the parser only returns that error again and should be discarded.
+///
+/// ```
+/// use std::io;
+///
+/// use tora::resume::ValueParser;
+/// use tora::{ReadStruct, WriteStruct};
+///
+/// #[derive(Debug, PartialEq, ReadStruct, WriteStruct)]
+/// struct Snapshot {
+///     tick: u32,
+///     positions: Vec<(f32, f32)>,
+/// }
+///
+/// fn main() -> io::Result<()> {
+///     let snapshot = Snapshot {
+///         tick: 7,
+///         positions: vec![(1.0, 2.0); 10_000],
+///     };
+///     let bytes = tora::testing::to_bytes(&snapshot);
+///     let mut parser = ValueParser::<Snapshot>::new();
+///
+///     for chunk in bytes.chunks(1000) {
+///         assert!(!parser.is_complete());
+///         parser.feed(chunk)?;
+///     }
+///
+///     assert!(parser.is_complete());
+///     assert_eq!(parser.finish()?, snapshot);
+///     Ok(())
+/// }
+/// ```
+#[derive(Debug)]
+pub struct ValueParser<T> {
+    parser: Resumable<T>,
+}
+
+impl<T> ValueParser<T>
+where
+    T: FromReader + Send + 'static,
+{
+    /// Constructs a parser using the default configuration.
+    pub fn new() -> Self {
+        Self::with_config(ToraConfig::DEFAULT)
+    }
+
+    /// Constructs a parser using the given configuration.
+    pub fn with_config(config: ToraConfig) -> Self {
+        Self {
+            parser: Resumable::new(move |r| T::from_reader_with(r, &config)),
+        }
+    }
+
+    /// Parses as much of the value as the bytes received so far hold.
+    ///
+    /// Bytes following the value are kept, see [ValueParser::remainder].
+    pub fn feed(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.parser.feed(bytes)
+    }
+
+    /// Returns true if the whole value was parsed.
+    pub fn is_complete(&self) -> bool {
+        self.parser.value.is_some()
+    }
+
+    /// Returns the bytes received after the end of the value, once it is complete.
+    pub fn remainder(&self) -> &[u8] {
+        &self.parser.remainder
+    }
+
+    /// Returns the value.
+    ///
+    /// Returns [ErrorKind::UnexpectedEof] if the value is incomplete.
+    pub fn finish(self) -> io::Result<T> {
+        self.parser.finish()
+    }
+}
+
+impl<T> Default for ValueParser<T>
+where
+    T: FromReader + Send + 'static,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
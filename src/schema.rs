@@ -0,0 +1,845 @@
+//! Runtime descriptions of the wire format, and inspection of encoded data.
+//!
+//! A [Schema] describes how a type is laid out on the wire. [walk] uses one to step through an
+//! encoded buffer, calling a [Visitor] for every value with its path, schema, byte range and, for
+//! primitives, its decoded [Value]. This allows generic tooling without decoding into the type.
+//!
+//! ```
+//! use std::io;
+//!
+//! use tora::config::ToraConfig;
+//! use tora::schema::{walk, FieldSchema, Schema, Segment, StructSchema, Visit};
+//! use tora::write::ToraWrite;
+//!
+//! fn main() -> io::Result<()> {
+//!     let schema = Schema::Struct(StructSchema::new(
+//!         "Login",
+//!         vec![
+//!             FieldSchema::new("user", Schema::String),
+//!             FieldSchema::new("password", Schema::String),
+//!         ],
+//!     ));
+//!
+//!     let mut bytes = Vec::new();
+//!     bytes.writes(&("john", "hunter2"))?;
+//!
+//!     // Redact the password in place, without decoding the login.
+//!     let mut ranges = Vec::new();
+//!     walk(&bytes, &schema, &ToraConfig::DEFAULT, &mut |visit: Visit| {
+//!         if visit.path == [Segment::Field("password")] {
+//!             ranges.push(visit.range);
+//!         }
+//!     })?;
+//!
+//!     for range in ranges {
+//!         bytes[range.start..range.end - 1].fill(b'*');
+//!     }
+//!     assert_eq!(bytes, b"john\0*******\0");
+//!     Ok(())
+//! }
+//! ```
+
+use std::cell::{Cell, RefCell};
+use std::fmt::Write as _;
+use std::io::{Cursor, ErrorKind, Read, Write};
+use std::ops::{ControlFlow, Range};
+use std::sync::{Mutex, RwLock};
+use std::task::Poll;
+use std::time::Duration;
+use std::{fmt, io};
+
+use crate::config::ToraConfig;
+use crate::read::{FromReader, ToraRead};
+use crate::write::SerializeIo;
+
+/// Describes the wire format of a type.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Schema {
+    Unit,
+    Bool,
+    U8,
+    U16,
+    U32,
+    U64,
+    U128,
+    I8,
+    I16,
+    I32,
+    I64,
+    I128,
+    F32,
+    F64,
+    /// Written as a [u64] on every target.
+    Usize,
+    Char,
+    String,
+    /// A bool, followed by the value if true.
+    Option(Box<Schema>),
+    /// A bool, followed by the error if true, or the value if false.
+    Result(Box<Schema>, Box<Schema>),
+    /// A length prefix, followed by the elements.
+    Vec(Box<Schema>),
+    /// A fixed amount of elements.
+    Array(Box<Schema>, usize),
+    Tuple(Vec<Schema>),
+    Struct(StructSchema),
+    Enum(EnumSchema),
+}
+
+impl Schema {
+    /// Returns the amount of bytes values of this schema always serialize to, or None if it
+    /// varies.
+    ///
+    /// Enums have a fixed size only if the fields of all their variants do.
+    pub fn fixed_size(&self) -> Option<usize> {
+        fn fields_size(fields: &[FieldSchema]) -> Option<usize> {
+            fields.iter().try_fold(0, |size, field| {
+                Some(size + field.pad_before + field.schema.fixed_size()? + field.pad_after)
+            })
+        }
+
+        Some(match self {
+            Schema::Unit => 0,
+            Schema::Bool | Schema::U8 | Schema::I8 => 1,
+            Schema::U16 | Schema::I16 => 2,
+            Schema::U32 | Schema::I32 | Schema::F32 | Schema::Char => 4,
+            Schema::U64 | Schema::I64 | Schema::F64 | Schema::Usize => 8,
+            Schema::U128 | Schema::I128 => 16,
+            Schema::String | Schema::Option(_) | Schema::Result(..) | Schema::Vec(_) => {
+                return None;
+            }
+            Schema::Array(inner, len) => inner.fixed_size()? * len,
+            Schema::Tuple(items) => items
+                .iter()
+                .map(Schema::fixed_size)
+                .sum::<Option<usize>>()?,
+            Schema::Struct(s) => fields_size(&s.fields)?,
+            Schema::Enum(e) if e.length_prefixed => return None,
+            Schema::Enum(e) => {
+                let mut sizes = e.variants.iter().map(|v| fields_size(&v.fields));
+                let size = sizes.next().unwrap_or(Some(0))?;
+
+                if !sizes.all(|s| s == Some(size)) {
+                    return None;
+                }
+                e.id.fixed_size()? + size
+            }
+        })
+    }
+
+    /// Returns the byte offset and the schema of the value at the path, relative to the start of
+    /// a value of this schema.
+    ///
+    /// Struct fields are found by [Segment::Field], including the fields of tuple structs named by
+    /// their index, and the elements of arrays and tuples by [Segment::Index]. Returns None if
+    /// there is no such value, or if a value before it does not have a fixed size.
+    ///
+    /// ```
+    /// use tora::schema::{FieldSchema, Schema, Segment, StructSchema};
+    ///
+    /// let schema = Schema::Struct(StructSchema::new(
+    ///     "Row",
+    ///     vec![
+    ///         FieldSchema::new("id", Schema::U32),
+    ///         FieldSchema::new("position", Schema::Array(Box::new(Schema::F32), 3)),
+    ///     ],
+    /// ));
+    ///
+    /// let (offset, field) = schema
+    ///     .offset_of(&[Segment::Field("position"), Segment::Index(2)])
+    ///     .unwrap();
+    /// assert_eq!((offset, field), (12, &Schema::F32));
+    /// ```
+    pub fn offset_of(&self, path: &[Segment]) -> Option<(usize, &Schema)> {
+        let Some((segment, rest)) = path.split_first() else {
+            return Some((0, self));
+        };
+
+        let (offset, schema) = match (self, segment) {
+            (Schema::Array(inner, len), Segment::Index(i)) if i < len => {
+                (inner.fixed_size()? * i, &**inner)
+            }
+            (Schema::Tuple(items), Segment::Index(i)) => {
+                let offset = items[..(*i).min(items.len())]
+                    .iter()
+                    .map(Schema::fixed_size)
+                    .sum::<Option<usize>>()?;
+                (offset, items.get(*i)?)
+            }
+            (Schema::Struct(s), Segment::Field(name)) => {
+                let mut offset = 0;
+
+                for field in &s.fields {
+                    offset += field.pad_before;
+
+                    if field.name == *name {
+                        let (nested, schema) = field.schema.offset_of(rest)?;
+                        return Some((offset + nested, schema));
+                    }
+                    offset += field.schema.fixed_size()? + field.pad_after;
+                }
+                return None;
+            }
+            _ => return None,
+        };
+
+        let (nested, schema) = schema.offset_of(rest)?;
+        Some((offset + nested, schema))
+    }
+}
+
+/// Describes the fields of a struct, in wire order.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StructSchema {
+    pub name: String,
+    pub fields: Vec<FieldSchema>,
+}
+
+impl StructSchema {
+    /// Constructs a StructSchema with the given name and fields.
+    pub fn new<S>(name: S, fields: Vec<FieldSchema>) -> Self
+    where
+        S: Into<String>,
+    {
+        Self {
+            name: name.into(),
+            fields,
+        }
+    }
+}
+
+/// Describes a field of a struct or enum variant.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FieldSchema {
+    /// The name of the field, or its index in a tuple.
+    pub name: String,
+    pub schema: Schema,
+    /// The amount of padding bytes before the field.
+    pub pad_before: usize,
+    /// The amount of padding bytes after the field.
+    pub pad_after: usize,
+}
+
+impl FieldSchema {
+    /// Constructs a FieldSchema without padding.
+    pub fn new<S>(name: S, schema: Schema) -> Self
+    where
+        S: Into<String>,
+    {
+        Self {
+            name: name.into(),
+            schema,
+            pad_before: 0,
+            pad_after: 0,
+        }
+    }
+
+    /// Sets the amount of padding bytes before and after the field.
+    pub fn with_padding(mut self, before: usize, after: usize) -> Self {
+        self.pad_before = before;
+        self.pad_after = after;
+        self
+    }
+}
+
+/// Describes the variants of an enum.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EnumSchema {
+    pub name: String,
+    /// The schema of the variant ID, an integer.
+    pub id: Box<Schema>,
+    pub variants: Vec<VariantSchema>,
+    /// Whether each variant's fields are prefixed with their length in bytes.
+    pub length_prefixed: bool,
+}
+
+impl EnumSchema {
+    /// Reads a variant ID, returning it with the variant it identifies, or the fallback variant if
+    /// the ID is unknown.
+    ///
+    /// Returns [ErrorKind::InvalidData] if the ID is unknown and there is no fallback variant.
+    pub(crate) fn read_variant<R>(
+        &self,
+        r: &mut R,
+        config: &ToraConfig,
+    ) -> io::Result<(u64, &VariantSchema)>
+    where
+        R: Read,
+    {
+        let invalid = || io::Error::new(ErrorKind::InvalidData, "Unknown variant id");
+
+        let id = match read_primitive(r, &self.id, config)? {
+            Some(id) => id.as_u64().ok_or_else(invalid)?,
+            None => return Err(invalid()),
+        };
+        let variant = self
+            .variants
+            .iter()
+            .find(|v| v.id == id)
+            .or_else(|| self.variants.iter().find(|v| v.fallback))
+            .ok_or_else(invalid)?;
+        Ok((id, variant))
+    }
+
+    /// Constructs an EnumSchema with the given name, variant ID schema and variants.
+    pub fn new<S>(name: S, id: Schema, variants: Vec<VariantSchema>) -> Self
+    where
+        S: Into<String>,
+    {
+        Self {
+            name: name.into(),
+            id: Box::new(id),
+            variants,
+            length_prefixed: false,
+        }
+    }
+}
+
+/// Describes a variant of an enum.
+#[derive(Clone, Debug, PartialEq)]
+pub struct VariantSchema {
+    pub name: String,
+    /// The variant ID written before the fields.
+    pub id: u64,
+    pub fields: Vec<FieldSchema>,
+    /// Whether variant IDs not described by the enum are read as this variant.
+    pub fallback: bool,
+}
+
+impl VariantSchema {
+    /// Constructs a VariantSchema with the given name, ID and fields.
+    pub fn new<S>(name: S, id: u64, fields: Vec<FieldSchema>) -> Self
+    where
+        S: Into<String>,
+    {
+        Self {
+            name: name.into(),
+            id,
+            fields,
+            fallback: false,
+        }
+    }
+
+    /// Marks this variant as read in place of unknown variant IDs, as `#[tora(fallback)]` does.
+    pub fn as_fallback(mut self) -> Self {
+        self.fallback = true;
+        self
+    }
+}
+
+/// A type whose wire format is described by a [Schema].
+///
+/// Can be derived with the `Reflect` derive macro, exposing the field names, types and variants
+/// of packet types at runtime.
+///
+/// ```
+/// use tora::schema::{Reflect, Schema};
+///
+/// assert_eq!(<Vec<u8>>::schema(), Schema::Vec(Box::new(Schema::U8)));
+/// ```
+pub trait Reflect {
+    /// Returns the schema of this type.
+    fn schema() -> Schema;
+}
+
+macro_rules! reflect_impl {
+    ($($t:ty => $schema:ident),*) => {
+        $(
+        impl Reflect for $t {
+            fn schema() -> Schema {
+                Schema::$schema
+            }
+        }
+        )*
+    };
+}
+
+reflect_impl!(
+    () => Unit, bool => Bool, u8 => U8, u16 => U16, u32 => U32, u64 => U64, u128 => U128,
+    i8 => I8, i16 => I16, i32 => I32, i64 => I64, i128 => I128, f32 => F32, f64 => F64,
+    usize => Usize, char => Char, String => String, str => String
+);
+
+/// Described as a tuple of the seconds and the nanoseconds.
+impl Reflect for Duration {
+    fn schema() -> Schema {
+        Schema::Tuple(vec![Schema::U64, Schema::U32])
+    }
+}
+
+impl<T> Reflect for Option<T>
+where
+    T: Reflect,
+{
+    fn schema() -> Schema {
+        Schema::Option(Box::new(T::schema()))
+    }
+}
+
+impl<T, E> Reflect for Result<T, E>
+where
+    T: Reflect,
+    E: Reflect,
+{
+    fn schema() -> Schema {
+        Schema::Result(Box::new(T::schema()), Box::new(E::schema()))
+    }
+}
+
+/// Described as a result, with Continue as Ok and Break as Err.
+impl<B, C> Reflect for ControlFlow<B, C>
+where
+    B: Reflect,
+    C: Reflect,
+{
+    fn schema() -> Schema {
+        Schema::Result(Box::new(C::schema()), Box::new(B::schema()))
+    }
+}
+
+/// Described as an option, with Ready as Some.
+impl<T> Reflect for Poll<T>
+where
+    T: Reflect,
+{
+    fn schema() -> Schema {
+        Schema::Option(Box::new(T::schema()))
+    }
+}
+
+impl<T> Reflect for Vec<T>
+where
+    T: Reflect,
+{
+    fn schema() -> Schema {
+        Schema::Vec(Box::new(T::schema()))
+    }
+}
+
+impl<T, const N: usize> Reflect for [T; N]
+where
+    T: Reflect,
+{
+    fn schema() -> Schema {
+        Schema::Array(Box::new(T::schema()), N)
+    }
+}
+
+impl<T, Z> Reflect for (T, Z)
+where
+    T: Reflect,
+    Z: Reflect,
+{
+    fn schema() -> Schema {
+        Schema::Tuple(vec![T::schema(), Z::schema()])
+    }
+}
+
+impl<T, Z, H> Reflect for (T, Z, H)
+where
+    T: Reflect,
+    Z: Reflect,
+    H: Reflect,
+{
+    fn schema() -> Schema {
+        Schema::Tuple(vec![T::schema(), Z::schema(), H::schema()])
+    }
+}
+
+impl<T> Reflect for Box<T>
+where
+    T: Reflect + ?Sized,
+{
+    fn schema() -> Schema {
+        T::schema()
+    }
+}
+
+impl<T> Reflect for &T
+where
+    T: Reflect + ?Sized,
+{
+    fn schema() -> Schema {
+        T::schema()
+    }
+}
+
+impl<T> Reflect for Mutex<T>
+where
+    T: Reflect + ?Sized,
+{
+    fn schema() -> Schema {
+        T::schema()
+    }
+}
+
+impl<T> Reflect for RwLock<T>
+where
+    T: Reflect + ?Sized,
+{
+    fn schema() -> Schema {
+        T::schema()
+    }
+}
+
+impl<T> Reflect for Cell<T>
+where
+    T: Reflect + ?Sized,
+{
+    fn schema() -> Schema {
+        T::schema()
+    }
+}
+
+impl<T> Reflect for RefCell<T>
+where
+    T: Reflect + ?Sized,
+{
+    fn schema() -> Schema {
+        T::schema()
+    }
+}
+
+/// A decoded primitive value.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Unit,
+    Bool(bool),
+    U8(u8),
+    U16(u16),
+    U32(u32),
+    U64(u64),
+    U128(u128),
+    I8(i8),
+    I16(i16),
+    I32(i32),
+    I64(i64),
+    I128(i128),
+    F32(f32),
+    F64(f64),
+    Usize(usize),
+    Char(char),
+    String(String),
+}
+
+impl Value {
+    /// Returns this value as a u64, if it is an integer in range.
+    pub fn as_u64(&self) -> Option<u64> {
+        match *self {
+            Self::U8(n) => Some(n as u64),
+            Self::U16(n) => Some(n as u64),
+            Self::U32(n) => Some(n as u64),
+            Self::U64(n) => Some(n),
+            Self::U128(n) => n.try_into().ok(),
+            Self::I8(n) => n.try_into().ok(),
+            Self::I16(n) => n.try_into().ok(),
+            Self::I32(n) => n.try_into().ok(),
+            Self::I64(n) => n.try_into().ok(),
+            Self::I128(n) => n.try_into().ok(),
+            Self::Usize(n) => n.try_into().ok(),
+            _ => None,
+        }
+    }
+}
+
+/// Formats the primitive as it would be written in Rust, quoting strings and chars.
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unit => f.write_str("()"),
+            Self::Bool(b) => b.fmt(f),
+            Self::U8(n) => n.fmt(f),
+            Self::U16(n) => n.fmt(f),
+            Self::U32(n) => n.fmt(f),
+            Self::U64(n) => n.fmt(f),
+            Self::U128(n) => n.fmt(f),
+            Self::I8(n) => n.fmt(f),
+            Self::I16(n) => n.fmt(f),
+            Self::I32(n) => n.fmt(f),
+            Self::I64(n) => n.fmt(f),
+            Self::I128(n) => n.fmt(f),
+            Self::F32(n) => n.fmt(f),
+            Self::F64(n) => n.fmt(f),
+            Self::Usize(n) => n.fmt(f),
+            Self::Char(c) => write!(f, "{c:?}"),
+            Self::String(s) => write!(f, "{s:?}"),
+        }
+    }
+}
+
+/// Writes the primitive as its type would be written.
+impl SerializeIo for Value {
+    fn serialize<W>(&self, w: &mut W) -> io::Result<()>
+    where
+        W: Write,
+    {
+        self.serialize_with(w, &ToraConfig::DEFAULT)
+    }
+
+    fn serialize_with<W>(&self, w: &mut W, config: &ToraConfig) -> io::Result<()>
+    where
+        W: Write,
+    {
+        match self {
+            Self::Unit => Ok(()),
+            Self::Bool(b) => b.serialize_with(w, config),
+            Self::U8(n) => n.serialize_with(w, config),
+            Self::U16(n) => n.serialize_with(w, config),
+            Self::U32(n) => n.serialize_with(w, config),
+            Self::U64(n) => n.serialize_with(w, config),
+            Self::U128(n) => n.serialize_with(w, config),
+            Self::I8(n) => n.serialize_with(w, config),
+            Self::I16(n) => n.serialize_with(w, config),
+            Self::I32(n) => n.serialize_with(w, config),
+            Self::I64(n) => n.serialize_with(w, config),
+            Self::I128(n) => n.serialize_with(w, config),
+            Self::F32(n) => n.serialize_with(w, config),
+            Self::F64(n) => n.serialize_with(w, config),
+            Self::Usize(n) => n.serialize_with(w, config),
+            Self::Char(c) => c.serialize_with(w, config),
+            Self::String(s) => s.serialize_with(w, config),
+        }
+    }
+}
+
+/// Reads the value if the schema is a primitive, returning None otherwise.
+pub(crate) fn read_primitive<R>(
+    r: &mut R,
+    schema: &Schema,
+    config: &ToraConfig,
+) -> io::Result<Option<Value>>
+where
+    R: Read,
+{
+    Ok(Some(match schema {
+        Schema::Unit => Value::Unit,
+        Schema::Bool => Value::Bool(r.reads_with(config)?),
+        Schema::U8 => Value::U8(r.reads_with(config)?),
+        Schema::U16 => Value::U16(r.reads_with(config)?),
+        Schema::U32 => Value::U32(r.reads_with(config)?),
+        Schema::U64 => Value::U64(r.reads_with(config)?),
+        Schema::U128 => Value::U128(r.reads_with(config)?),
+        Schema::I8 => Value::I8(r.reads_with(config)?),
+        Schema::I16 => Value::I16(r.reads_with(config)?),
+        Schema::I32 => Value::I32(r.reads_with(config)?),
+        Schema::I64 => Value::I64(r.reads_with(config)?),
+        Schema::I128 => Value::I128(r.reads_with(config)?),
+        Schema::F32 => Value::F32(r.reads_with(config)?),
+        Schema::F64 => Value::F64(r.reads_with(config)?),
+        Schema::Usize => Value::Usize(r.reads_with(config)?),
+        Schema::Char => Value::Char(r.reads_with(config)?),
+        Schema::String => Value::String(r.reads_with(config)?),
+        _ => return Ok(None),
+    }))
+}
+
+/// A step in the path from the walked value to a nested value.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Segment<'a> {
+    /// A named field of a struct or variant.
+    Field(&'a str),
+    /// An element of a collection or tuple.
+    Index(usize),
+    /// The variant of an enum, or `Ok`/`Err` of a result.
+    Variant(&'a str),
+}
+
+/// Formats a path as it would be written in Rust, such as `players[2].name` or `::Move.x`.
+///
+/// ```
+/// use tora::schema::{format_path, Segment};
+///
+/// let path = [Segment::Field("players"), Segment::Index(2), Segment::Field("name")];
+/// assert_eq!(format_path(&path), "players[2].name");
+/// ```
+pub fn format_path(path: &[Segment]) -> String {
+    let mut formatted = String::new();
+
+    for segment in path {
+        let _ = match segment {
+            Segment::Field(name) if formatted.is_empty() => write!(formatted, "{name}"),
+            Segment::Field(name) => write!(formatted, ".{name}"),
+            Segment::Index(i) => write!(formatted, "[{i}]"),
+            Segment::Variant(name) => write!(formatted, "::{name}"),
+        };
+    }
+    formatted
+}
+
+/// A value encountered by [walk].
+#[derive(Debug)]
+pub struct Visit<'a> {
+    /// The path from the walked value to this value, empty for the walked value itself.
+    pub path: &'a [Segment<'a>],
+    pub schema: &'a Schema,
+    /// The bytes of this value in the walked buffer.
+    pub range: Range<usize>,
+    /// The decoded value, if it is a primitive.
+    pub value: Option<Value>,
+}
+
+/// Receives the values encountered by [walk].
+///
+/// Implemented for closures taking a [Visit].
+pub trait Visitor {
+    /// Called for every value, after the values nested in it.
+    fn visit(&mut self, visit: Visit<'_>);
+}
+
+impl<F> Visitor for F
+where
+    F: FnMut(Visit<'_>),
+{
+    fn visit(&mut self, visit: Visit<'_>) {
+        self(visit)
+    }
+}
+
+/// Walks the value described by `schema` at the start of `bytes`, calling `visitor` for it and
+/// every value nested in it.
+///
+/// Returns the amount of bytes the value occupies. Returns [ErrorKind::InvalidData] if an enum
+/// variant ID is not described by the schema, and the enum has no fallback variant.
+pub fn walk<V>(
+    bytes: &[u8],
+    schema: &Schema,
+    config: &ToraConfig,
+    visitor: &mut V,
+) -> io::Result<usize>
+where
+    V: Visitor + ?Sized,
+{
+    let mut walker = Walker {
+        cursor: Cursor::new(bytes),
+        config,
+        visitor,
+        path: Vec::new(),
+    };
+    walker.walk(schema)?;
+
+    Ok(walker.cursor.position() as usize)
+}
+
+struct Walker<'a, 's, V>
+where
+    V: ?Sized,
+{
+    cursor: Cursor<&'a [u8]>,
+    config: &'a ToraConfig,
+    visitor: &'a mut V,
+    path: Vec<Segment<'s>>,
+}
+
+impl<'s, V> Walker<'_, 's, V>
+where
+    V: Visitor + ?Sized,
+{
+    fn position(&self) -> usize {
+        self.cursor.position() as usize
+    }
+
+    fn read<T>(&mut self) -> io::Result<T>
+    where
+        T: FromReader,
+    {
+        self.cursor.reads_with(self.config)
+    }
+
+    fn walk_nested(&mut self, segment: Segment<'s>, schema: &'s Schema) -> io::Result<()> {
+        self.path.push(segment);
+        self.walk(schema)?;
+        self.path.pop();
+        Ok(())
+    }
+
+    fn walk_fields(&mut self, fields: &'s [FieldSchema]) -> io::Result<()> {
+        for field in fields {
+            self.cursor.skip(field.pad_before)?;
+            self.walk_nested(Segment::Field(&field.name), &field.schema)?;
+            self.cursor.skip(field.pad_after)?;
+        }
+        Ok(())
+    }
+
+    fn walk_enum(&mut self, schema: &'s EnumSchema) -> io::Result<()> {
+        let (_, variant) = schema.read_variant(&mut self.cursor, self.config)?;
+
+        self.path.push(Segment::Variant(&variant.name));
+
+        if schema.length_prefixed {
+            let len = self.config.read_length(&mut self.cursor)?;
+            let end = self
+                .position()
+                .checked_add(len)
+                .filter(|end| *end <= self.cursor.get_ref().len())
+                .ok_or_else(|| {
+                    io::Error::new(ErrorKind::UnexpectedEof, "Variant exceeds the input")
+                })?;
+            self.walk_fields(&variant.fields)?;
+
+            if self.position() > end {
+                return Err(io::Error::new(
+                    ErrorKind::InvalidData,
+                    "Variant exceeds its length prefix",
+                ));
+            }
+            self.cursor.set_position(end as u64);
+        } else {
+            self.walk_fields(&variant.fields)?;
+        }
+
+        self.path.pop();
+        Ok(())
+    }
+
+    fn walk(&mut self, schema: &'s Schema) -> io::Result<()> {
+        let start = self.position();
+        let value = read_primitive(&mut self.cursor, schema, self.config)?;
+
+        match schema {
+            Schema::Option(inner) => {
+                let some = self.read::<bool>()?;
+
+                if some {
+                    self.walk(inner)?;
+                }
+            }
+            Schema::Result(ok, err) => {
+                if self.read::<bool>()? {
+                    self.walk_nested(Segment::Variant("Err"), err)?;
+                } else {
+                    self.walk_nested(Segment::Variant("Ok"), ok)?;
+                }
+            }
+            Schema::Vec(inner) => {
+                let len = self.config.read_length(&mut self.cursor)?;
+
+                for i in 0..len {
+                    self.walk_nested(Segment::Index(i), inner)?;
+                }
+            }
+            Schema::Array(inner, len) => {
+                for i in 0..*len {
+                    self.walk_nested(Segment::Index(i), inner)?;
+                }
+            }
+            Schema::Tuple(items) => {
+                for (i, item) in items.iter().enumerate() {
+                    self.walk_nested(Segment::Index(i), item)?;
+                }
+            }
+            Schema::Struct(s) => self.walk_fields(&s.fields)?,
+            Schema::Enum(e) => self.walk_enum(e)?,
+            _ => {}
+        }
+
+        let range = start..self.position();
+        self.visitor.visit(Visit {
+            path: &self.path,
+            schema,
+            range,
+            value,
+        });
+        Ok(())
+    }
+}
@@ -0,0 +1,370 @@
+//! A single-producer, single-consumer ring buffer in shared memory.
+//!
+//! Processes on the same host exchange bytes through a file mapped into both of their address
+//! spaces, such as one in `/dev/shm` on Linux, without a system call per message. One process
+//! creates the ring and writes to it through a [ShmWriter], and another opens it and reads from
+//! it through a [ShmReader]. Both implement the standard IO traits, so the framed
+//! [FrameWriter](crate::stream::FrameWriter) and [FrameReader](crate::stream::FrameReader) carry
+//! typed values over them.
+//!
+//! Waiting for space or bytes spins briefly, then yields the thread, trading CPU time for
+//! latency.
+//!
+//! ```
+//! use std::io;
+//! use std::thread;
+//!
+//! use tora::shm::ShmRing;
+//! use tora::stream::{FrameReader, FrameWriter};
+//! use tora::{ReadStruct, WriteStruct};
+//!
+//! #[derive(Debug, PartialEq, ReadStruct, WriteStruct)]
+//! struct Frame {
+//!     tick: u64,
+//!     positions: Vec<[f32; 2]>,
+//! }
+//!
+//! fn main() -> io::Result<()> {
+//!     let path = std::env::temp_dir().join("tora_shm_example");
+//!     let ring = ShmRing::create(&path, 4096)?;
+//!
+//!     let simulation = thread::spawn(move || -> io::Result<()> {
+//!         let mut frames = FrameWriter::<Frame, _>::new(ring.writer());
+//!
+//!         for tick in 0..100 {
+//!             frames.send(&Frame { tick, positions: vec![[1.0, 2.0]; 8] })?;
+//!         }
+//!         Ok(())
+//!     });
+//!
+//!     let mut frames = FrameReader::<Frame, _>::new(ShmRing::open(&path)?.reader());
+//!
+//!     for tick in 0..100 {
+//!         assert_eq!(frames.recv()?.tick, tick);
+//!     }
+//!     simulation.join().unwrap()
+//! }
+//! ```
+
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::io::{ErrorKind, Read, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::{hint, ptr, thread};
+
+use memmap2::MmapMut;
+
+/// Identifies a file holding a ring.
+const MAGIC: u64 = u64::from_le_bytes(*b"TORASHM1");
+
+/// The size of the header preceding the ring's bytes. The counters are on separate cache lines,
+/// so the writer and reader do not contend on them.
+const HEADER: usize = 256;
+const CAPACITY_OFFSET: usize = 8;
+/// The total amount of bytes written.
+const HEAD_OFFSET: usize = 64;
+/// The total amount of bytes read.
+const TAIL_OFFSET: usize = 128;
+/// The [WRITER_CLOSED] and [READER_CLOSED] flags.
+const CLOSED_OFFSET: usize = 192;
+
+const WRITER_CLOSED: u32 = 1;
+const READER_CLOSED: u32 = 2;
+
+/// The amount of times a waiting side spins before yielding its thread.
+const SPINS: u32 = 128;
+
+/// A ring buffer in a shared memory file, before it is used for writing or reading.
+pub struct ShmRing {
+    map: MmapMut,
+    capacity: usize,
+}
+
+impl ShmRing {
+    /// Creates the file at the given path, truncating it, and maps a ring of `capacity` bytes.
+    ///
+    /// Returns [ErrorKind::InvalidInput] if the capacity is zero.
+    pub fn create<P>(path: P, capacity: usize) -> io::Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        if capacity == 0 {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                "The ring capacity must not be zero",
+            ));
+        }
+
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)?;
+        file.set_len((HEADER + capacity) as u64)?;
+
+        let mut ring = Self::map(&file, capacity)?;
+        ring.map[..8].copy_from_slice(&MAGIC.to_le_bytes());
+        ring.map[CAPACITY_OFFSET..CAPACITY_OFFSET + 8]
+            .copy_from_slice(&(capacity as u64).to_le_bytes());
+        Ok(ring)
+    }
+
+    /// Opens the ring created at the given path.
+    ///
+    /// Returns [ErrorKind::InvalidData] if the file does not hold a ring.
+    pub fn open<P>(path: P) -> io::Result<Self>
+    where
+        P: AsRef<Path>,
+    {
+        let file = OpenOptions::new().read(true).write(true).open(path)?;
+        let len = file.metadata()?.len();
+
+        let invalid = || io::Error::new(ErrorKind::InvalidData, "Not a shared memory ring");
+
+        if len < HEADER as u64 {
+            return Err(invalid());
+        }
+        let ring = Self::map(&file, len as usize - HEADER)?;
+
+        let read_u64 =
+            |offset: usize| u64::from_le_bytes(ring.map[offset..offset + 8].try_into().unwrap());
+        if read_u64(0) != MAGIC || read_u64(CAPACITY_OFFSET) != ring.capacity as u64 {
+            return Err(invalid());
+        }
+        Ok(ring)
+    }
+
+    fn map(file: &File, capacity: usize) -> io::Result<Self> {
+        // The ring is only accessed through atomics and the bytes between its counters.
+        let map = unsafe { MmapMut::map_mut(file) }?;
+        Ok(Self { map, capacity })
+    }
+
+    /// Returns the amount of bytes the ring holds.
+    pub const fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Uses this ring for writing. Only one process may write to a ring.
+    pub fn writer(self) -> ShmWriter {
+        ShmWriter { ring: self }
+    }
+
+    /// Uses this ring for reading. Only one process may read from a ring.
+    pub fn reader(self) -> ShmReader {
+        ShmReader { ring: self }
+    }
+
+    fn counter(&self, offset: usize) -> &AtomicU64 {
+        // The map is page aligned, and the offset is a multiple of 8 within the header.
+        unsafe { AtomicU64::from_ptr(self.map.as_ptr().add(offset).cast_mut().cast()) }
+    }
+
+    fn closed(&self) -> &AtomicU32 {
+        unsafe { AtomicU32::from_ptr(self.map.as_ptr().add(CLOSED_OFFSET).cast_mut().cast()) }
+    }
+
+    /// Returns the amount of bytes written but not read yet.
+    ///
+    /// Returns [ErrorKind::InvalidData] if the counters are inconsistent, which a correct writer
+    /// and reader never leave them in, but another process mapping the file might.
+    fn pending(&self, head: u64, tail: u64) -> io::Result<usize> {
+        match head.checked_sub(tail) {
+            Some(pending) if pending <= self.capacity as u64 => Ok(pending as usize),
+            _ => Err(io::Error::new(
+                ErrorKind::InvalidData,
+                "The ring's counters are inconsistent",
+            )),
+        }
+    }
+
+    /// Copies bytes between `buf` and the ring, starting at the given total position and wrapping
+    /// around its end.
+    ///
+    /// # Safety
+    ///
+    /// The ring's bytes in the range must not be accessed by the other side.
+    unsafe fn copy(&self, position: u64, buf: *mut u8, len: usize, into_ring: bool) {
+        let index = (position % self.capacity as u64) as usize;
+        let first = len.min(self.capacity - index);
+
+        // The bytes up to the end of the ring, then the rest from its start.
+        for (index, offset, count) in [(index, 0, first), (0, first, len - first)] {
+            let ring = unsafe { self.map.as_ptr().add(HEADER + index).cast_mut() };
+            let buf = unsafe { buf.add(offset) };
+
+            match into_ring {
+                true => unsafe { ptr::copy_nonoverlapping(buf, ring, count) },
+                false => unsafe { ptr::copy_nonoverlapping(ring, buf, count) },
+            }
+        }
+    }
+}
+
+/// Waits for the other side of a ring, spinning at first, then yielding the thread.
+fn wait(spins: &mut u32) {
+    if *spins < SPINS {
+        *spins += 1;
+        hint::spin_loop();
+    } else {
+        thread::yield_now();
+    }
+}
+
+/// The writing side of a [ShmRing].
+///
+/// Writes block while the ring is full, and return [ErrorKind::BrokenPipe] once the reader is
+/// dropped, or [ErrorKind::InvalidData] if the counters of the ring were corrupted. Dropping the
+/// writer lets the reader reach the end of the stream.
+///
+/// ```
+/// use std::io::{ErrorKind, Read, Write};
+/// use std::thread;
+///
+/// use tora::shm::ShmRing;
+///
+/// let path = std::env::temp_dir().join("tora_shm_writer");
+/// let mut writer = ShmRing::create(&path, 4)?.writer();
+/// let mut reader = ShmRing::open(&path)?.reader();
+///
+/// // Ten bytes pass through a ring of four, wrapping around its end.
+/// let sending = thread::spawn(move || writer.write_all(b"0123456789").map(|_| writer));
+///
+/// let mut received = [0; 10];
+/// reader.read_exact(&mut received)?;
+/// assert_eq!(&received, b"0123456789");
+///
+/// let mut writer = sending.join().unwrap()?;
+/// drop(reader);
+/// assert_eq!(writer.write(b"!").unwrap_err().kind(), ErrorKind::BrokenPipe);
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub struct ShmWriter {
+    ring: ShmRing,
+}
+
+impl Write for ShmWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let ring = &self.ring;
+        let head = ring.counter(HEAD_OFFSET).load(Ordering::Relaxed);
+        let mut spins = 0;
+
+        loop {
+            if ring.closed().load(Ordering::Acquire) & READER_CLOSED != 0 {
+                return Err(io::Error::new(
+                    ErrorKind::BrokenPipe,
+                    "The reader was dropped",
+                ));
+            }
+
+            let tail = ring.counter(TAIL_OFFSET).load(Ordering::Acquire);
+            let free = ring.capacity - ring.pending(head, tail)?;
+
+            if free == 0 {
+                wait(&mut spins);
+                continue;
+            }
+
+            let len = free.min(buf.len());
+            // The free bytes are not read until the head moves past them.
+            unsafe { ring.copy(head, buf.as_ptr().cast_mut(), len, true) };
+            ring.counter(HEAD_OFFSET)
+                .store(head + len as u64, Ordering::Release);
+            return Ok(len);
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for ShmWriter {
+    fn drop(&mut self) {
+        self.ring
+            .closed()
+            .fetch_or(WRITER_CLOSED, Ordering::Release);
+    }
+}
+
+/// The reading side of a [ShmRing].
+///
+/// Reads block until bytes are available, and return zero bytes once the writer is dropped and
+/// every byte it wrote was read. Returns [ErrorKind::InvalidData] if the counters of the ring
+/// were corrupted, rather than reading outside of it.
+///
+/// ```
+/// use std::fs::OpenOptions;
+/// use std::io::{ErrorKind, Read, Seek, SeekFrom, Write};
+///
+/// use tora::shm::ShmRing;
+///
+/// let path = std::env::temp_dir().join("tora_shm_reader");
+/// let mut writer = ShmRing::create(&path, 16)?.writer();
+/// let mut reader = ShmRing::open(&path)?.reader();
+///
+/// writer.write_all(b"Hi")?;
+/// drop(writer);
+///
+/// let mut received = Vec::new();
+/// reader.read_to_end(&mut received)?;
+/// assert_eq!(received, b"Hi");
+///
+/// // Another process claims far more bytes were written than the ring holds.
+/// let mut file = OpenOptions::new().write(true).open(&path)?;
+/// file.seek(SeekFrom::Start(64))?;
+/// file.write_all(&u64::MAX.to_le_bytes())?;
+///
+/// let mut buf = [0; 16];
+/// assert_eq!(reader.read(&mut buf).unwrap_err().kind(), ErrorKind::InvalidData);
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub struct ShmReader {
+    ring: ShmRing,
+}
+
+impl Read for ShmReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let ring = &self.ring;
+        let tail = ring.counter(TAIL_OFFSET).load(Ordering::Relaxed);
+        let mut spins = 0;
+
+        loop {
+            // Checked before the head, so bytes written before closing are never missed.
+            let closed = ring.closed().load(Ordering::Acquire) & WRITER_CLOSED != 0;
+            let head = ring.counter(HEAD_OFFSET).load(Ordering::Acquire);
+            let available = ring.pending(head, tail)?;
+
+            if available == 0 {
+                if closed {
+                    return Ok(0);
+                }
+                wait(&mut spins);
+                continue;
+            }
+
+            let len = available.min(buf.len());
+            // The available bytes are not written until the tail moves past them.
+            unsafe { ring.copy(tail, buf.as_mut_ptr(), len, false) };
+            ring.counter(TAIL_OFFSET)
+                .store(tail + len as u64, Ordering::Release);
+            return Ok(len);
+        }
+    }
+}
+
+impl Drop for ShmReader {
+    fn drop(&mut self) {
+        self.ring
+            .closed()
+            .fetch_or(READER_CLOSED, Ordering::Release);
+    }
+}
@@ -0,0 +1,513 @@
+//! Advancing past values without deserializing them.
+//!
+//! [Skip] reads only the framing of a value, such as its length prefixes, option flags and variant
+//! IDs, and advances past the rest. Routers and partial readers can then jump over large payloads
+//! they do not care about without allocating them. Fixed-size values, and collections of them,
+//! are skipped at once.
+//!
+//! Skipping reads from a [SkipRead], which seeks over skipped bytes where it can: slices,
+//! [Cursor]s, [File]s and [BufReader]s over seekable readers. Other readers, such as sockets, are
+//! wrapped in a [Discarding] reader, or a [PeekReader], which read and discard the skipped bytes.
+//!
+//! Skipped values are not validated, so a value that could not be read may still be skipped.
+//!
+//! ```
+//! use std::io;
+//! use std::io::Cursor;
+//!
+//! use tora::read::ToraRead;
+//! use tora::write::ToraWrite;
+//! use tora::{ReadStruct, Skip, WriteStruct};
+//!
+//! #[derive(ReadStruct, Skip, WriteStruct)]
+//! struct Upload {
+//!     name: String,
+//!     data: Vec<u8>,
+//! }
+//!
+//! fn main() -> io::Result<()> {
+//!     let upload = Upload { name: "map.bin".to_string(), data: vec![0; 1 << 20] };
+//!
+//!     let mut cursor = Cursor::new(Vec::new());
+//!     cursor.writes(&upload)?;
+//!     cursor.writes(&7u8)?;
+//!     cursor.set_position(0);
+//!
+//!     // Seeks over the megabyte of data.
+//!     cursor.skips::<Upload>()?;
+//!     assert_eq!(cursor.reads::<u8>()?, 7);
+//!     Ok(())
+//! }
+//! ```
+
+use std::cell::{Cell, RefCell};
+#[cfg(feature = "dyn_impl")]
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io;
+use std::io::{BufRead, BufReader, Cursor, ErrorKind, Read, Seek};
+use std::ops::ControlFlow;
+use std::sync::{Arc, Mutex, RwLock};
+use std::task::Poll;
+use std::time::Duration;
+
+use crate::cancel::CHECK_ELEMENTS;
+use crate::compact::read_packed;
+use crate::config::{IntSequenceFormat, StringFormat, ToraConfig};
+use crate::layout::ConstSize;
+use crate::peek::PeekReader;
+use crate::read::{FromReader, ToraRead};
+
+/// A reader able to advance past bytes without reading them.
+pub trait SkipRead: Read {
+    /// Advances past the next `n` bytes.
+    ///
+    /// Returns [ErrorKind::UnexpectedEof] if the reader ends before `n` bytes, where this can be
+    /// detected. Seekable readers of unknown length, such as files, may be advanced past their
+    /// end, failing the next read instead.
+    fn skip_bytes(&mut self, n: usize) -> io::Result<()>;
+}
+
+impl SkipRead for &[u8] {
+    fn skip_bytes(&mut self, n: usize) -> io::Result<()> {
+        if n > self.len() {
+            *self = &self[self.len()..];
+            return Err(io::Error::new(ErrorKind::UnexpectedEof, "Could not skip"));
+        }
+        *self = &self[n..];
+        Ok(())
+    }
+}
+
+impl<T> SkipRead for Cursor<T>
+where
+    T: AsRef<[u8]>,
+{
+    fn skip_bytes(&mut self, n: usize) -> io::Result<()> {
+        let len = self.get_ref().as_ref().len() as u64;
+        let remaining = len.saturating_sub(self.position());
+
+        if n as u64 > remaining {
+            self.set_position(len);
+            return Err(io::Error::new(ErrorKind::UnexpectedEof, "Could not skip"));
+        }
+        self.set_position(self.position() + n as u64);
+        Ok(())
+    }
+}
+
+/// Returns the offset of `n` bytes, to seek relatively to the current position.
+fn relative_offset(n: usize) -> io::Result<i64> {
+    i64::try_from(n)
+        .map_err(|_| io::Error::new(ErrorKind::InvalidInput, "Skipped length exceeds i64::MAX"))
+}
+
+impl SkipRead for File {
+    fn skip_bytes(&mut self, n: usize) -> io::Result<()> {
+        self.seek_relative(relative_offset(n)?)
+    }
+}
+
+impl<R> SkipRead for BufReader<R>
+where
+    R: Read + Seek,
+{
+    /// Consumes the buffered bytes first, seeking the underlying reader only past the buffer.
+    fn skip_bytes(&mut self, n: usize) -> io::Result<()> {
+        self.seek_relative(relative_offset(n)?)
+    }
+}
+
+impl<R> SkipRead for PeekReader<R>
+where
+    R: Read,
+{
+    /// Consumes the buffered bytes first, reading and discarding the rest.
+    fn skip_bytes(&mut self, n: usize) -> io::Result<()> {
+        let buffered = self.buffer().len().min(n);
+        self.consume(buffered);
+        ToraRead::skip(self, n - buffered)
+    }
+}
+
+impl<R> SkipRead for &mut R
+where
+    R: SkipRead + ?Sized,
+{
+    fn skip_bytes(&mut self, n: usize) -> io::Result<()> {
+        (**self).skip_bytes(n)
+    }
+}
+
+/// Wraps a reader that cannot seek, skipping bytes by reading and discarding them.
+///
+/// ```
+/// use std::io;
+/// use std::io::Read;
+///
+/// use tora::read::ToraRead;
+/// use tora::skip::Discarding;
+///
+/// fn main() -> io::Result<()> {
+///     let mut reader = Discarding(io::repeat(1).take(6));
+///
+///     reader.skips::<(u32, u8)>()?;
+///     assert_eq!(reader.reads::<u8>()?, 1);
+///     Ok(())
+/// }
+/// ```
+#[derive(Debug)]
+pub struct Discarding<R>(pub R);
+
+impl<R> Read for Discarding<R>
+where
+    R: Read,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.0.read(buf)
+    }
+}
+
+impl<R> SkipRead for Discarding<R>
+where
+    R: Read,
+{
+    fn skip_bytes(&mut self, n: usize) -> io::Result<()> {
+        ToraRead::skip(&mut self.0, n)
+    }
+}
+
+/// Marks a type as able to be skipped over in a reader, without deserializing it.
+///
+/// Can be derived with the `Skip` derive macro, which honors the same attributes as `ReadStruct`
+/// and `ReadEnum`. `#[tora(length_prefixed)]` enums are skipped past their length prefix, without
+/// reading their fields.
+pub trait Skip {
+    /// Advances the reader past a value.
+    fn skip<R>(r: &mut R) -> io::Result<()>
+    where
+        R: SkipRead,
+    {
+        Self::skip_with(r, &ToraConfig::DEFAULT)
+    }
+
+    /// Advances the reader past a value written with the given configuration.
+    fn skip_with<R>(r: &mut R, config: &ToraConfig) -> io::Result<()>
+    where
+        R: SkipRead;
+
+    /// Advances the reader past `n` consecutive values, as written in collections.
+    ///
+    /// The default implementation skips the values one at a time.
+    fn skip_n<R>(r: &mut R, n: usize, config: &ToraConfig) -> io::Result<()>
+    where
+        R: SkipRead,
+    {
+        for i in 0..n {
+            if i % CHECK_ELEMENTS == 0 {
+                config.check_cancelled()?;
+            }
+            Self::skip_with(r, config)?;
+        }
+        Ok(())
+    }
+}
+
+/// Skips `n` values of [T] in a single call, unless codecs may change their size.
+fn skip_const<T, R>(r: &mut R, n: usize, config: &ToraConfig) -> io::Result<()>
+where
+    T: ConstSize + FromReader,
+    R: SkipRead,
+{
+    if config.codecs.is_some() {
+        for _ in 0..n {
+            T::from_reader_with(r, config)?;
+        }
+        return Ok(());
+    }
+
+    let len = T::SIZE
+        .checked_mul(n)
+        .ok_or_else(|| io::Error::new(ErrorKind::InvalidData, "Skipped length overflows usize"))?;
+    r.skip_bytes(len)
+}
+
+macro_rules! skip_impl {
+    (@impl $t:ty { $($hooks:tt)* }) => {
+        /// Skipped in a single call, unless codecs are configured.
+        impl Skip for $t {
+            fn skip_with<R>(r: &mut R, config: &ToraConfig) -> io::Result<()>
+            where
+                R: SkipRead,
+            {
+                skip_const::<$t, R>(r, 1, config)
+            }
+
+            $($hooks)*
+        }
+    };
+    (packed $($t:ty),*) => {
+        $(
+        skip_impl!(@impl $t {
+            /// Reads the bit-packed deltas if the configured integer sequence format is
+            /// [IntSequenceFormat::Packed], as their size depends on the values.
+            fn skip_n<R>(r: &mut R, n: usize, config: &ToraConfig) -> io::Result<()>
+            where
+                R: SkipRead,
+            {
+                if config.int_sequence_format == IntSequenceFormat::Packed {
+                    return read_packed(r, n, <$t>::BITS, config, false, |_| {});
+                }
+                skip_const::<$t, R>(r, n, config)
+            }
+        });
+        )*
+    };
+    ($($t:ty),*) => {
+        $(
+        skip_impl!(@impl $t {
+            fn skip_n<R>(r: &mut R, n: usize, config: &ToraConfig) -> io::Result<()>
+            where
+                R: SkipRead,
+            {
+                skip_const::<$t, R>(r, n, config)
+            }
+        });
+        )*
+    };
+}
+
+skip_impl!(
+    u8,
+    u16,
+    u128,
+    i8,
+    i16,
+    i32,
+    i64,
+    i128,
+    f32,
+    f64,
+    usize,
+    bool,
+    char,
+    (),
+    Duration
+);
+skip_impl!(packed u32, u64);
+
+impl Skip for String {
+    /// Reads up to the NUL terminator, or seeks past the bytes after the length prefix.
+    ///
+    /// Returns [ErrorKind::InvalidData] if the string is longer than the configured maximum
+    /// length.
+    fn skip_with<R>(r: &mut R, config: &ToraConfig) -> io::Result<()>
+    where
+        R: SkipRead,
+    {
+        if config.codecs.is_some() {
+            return String::from_reader_with(r, config).map(drop);
+        }
+
+        match config.string_format {
+            StringFormat::NulTerminated => {
+                let mut len = 0;
+
+                while r.reads::<u8>()? != 0 {
+                    len += 1;
+                    config.check_length(len)?;
+                }
+                Ok(())
+            }
+            StringFormat::LengthPrefixed => {
+                let len = config.read_length(r)?;
+                r.skip_bytes(len)
+            }
+        }
+    }
+}
+
+impl Skip for Arc<str> {
+    fn skip_with<R>(r: &mut R, config: &ToraConfig) -> io::Result<()>
+    where
+        R: SkipRead,
+    {
+        String::skip_with(r, config)
+    }
+}
+
+impl<T> Skip for Option<T>
+where
+    T: Skip,
+{
+    fn skip_with<R>(r: &mut R, config: &ToraConfig) -> io::Result<()>
+    where
+        R: SkipRead,
+    {
+        if r.reads::<bool>()? {
+            T::skip_with(r, config)?;
+        }
+        Ok(())
+    }
+}
+
+impl<T, E> Skip for Result<T, E>
+where
+    T: Skip,
+    E: Skip,
+{
+    fn skip_with<R>(r: &mut R, config: &ToraConfig) -> io::Result<()>
+    where
+        R: SkipRead,
+    {
+        match r.reads::<bool>()? {
+            true => E::skip_with(r, config),
+            false => T::skip_with(r, config),
+        }
+    }
+}
+
+impl<B, C> Skip for ControlFlow<B, C>
+where
+    B: Skip,
+    C: Skip,
+{
+    fn skip_with<R>(r: &mut R, config: &ToraConfig) -> io::Result<()>
+    where
+        R: SkipRead,
+    {
+        match r.reads::<bool>()? {
+            true => B::skip_with(r, config),
+            false => C::skip_with(r, config),
+        }
+    }
+}
+
+impl<T> Skip for Poll<T>
+where
+    T: Skip,
+{
+    fn skip_with<R>(r: &mut R, config: &ToraConfig) -> io::Result<()>
+    where
+        R: SkipRead,
+    {
+        Option::<T>::skip_with(r, config)
+    }
+}
+
+#[cfg(feature = "dyn_impl")]
+impl<T> Skip for Vec<T>
+where
+    T: Skip,
+{
+    /// Reads the configured length prefix, then skips that many values of [T].
+    ///
+    /// Returns [ErrorKind::InvalidData] if the length exceeds the configured maximum length.
+    fn skip_with<R>(r: &mut R, config: &ToraConfig) -> io::Result<()>
+    where
+        R: SkipRead,
+    {
+        let len = config.read_length(r)?;
+        T::skip_n(r, len, config)
+    }
+}
+
+#[cfg(feature = "dyn_impl")]
+impl<K, V, S> Skip for HashMap<K, V, S>
+where
+    K: Skip,
+    V: Skip,
+{
+    /// Reads the configured length prefix, then skips that many keys and values.
+    fn skip_with<R>(r: &mut R, config: &ToraConfig) -> io::Result<()>
+    where
+        R: SkipRead,
+    {
+        let len = config.read_length(r)?;
+        <(K, V)>::skip_n(r, len, config)
+    }
+}
+
+#[cfg(feature = "dyn_impl")]
+impl<T, S> Skip for HashSet<T, S>
+where
+    T: Skip,
+{
+    /// Reads the configured length prefix, then skips that many values of [T].
+    fn skip_with<R>(r: &mut R, config: &ToraConfig) -> io::Result<()>
+    where
+        R: SkipRead,
+    {
+        let len = config.read_length(r)?;
+        T::skip_n(r, len, config)
+    }
+}
+
+impl<T, const N: usize> Skip for [T; N]
+where
+    T: Skip,
+{
+    fn skip_with<R>(r: &mut R, config: &ToraConfig) -> io::Result<()>
+    where
+        R: SkipRead,
+    {
+        T::skip_n(r, N, config)
+    }
+}
+
+impl<T, Z> Skip for (T, Z)
+where
+    T: Skip,
+    Z: Skip,
+{
+    fn skip_with<R>(r: &mut R, config: &ToraConfig) -> io::Result<()>
+    where
+        R: SkipRead,
+    {
+        T::skip_with(r, config)?;
+        Z::skip_with(r, config)
+    }
+}
+
+impl<T, Z, H> Skip for (T, Z, H)
+where
+    T: Skip,
+    Z: Skip,
+    H: Skip,
+{
+    fn skip_with<R>(r: &mut R, config: &ToraConfig) -> io::Result<()>
+    where
+        R: SkipRead,
+    {
+        T::skip_with(r, config)?;
+        Z::skip_with(r, config)?;
+        H::skip_with(r, config)
+    }
+}
+
+macro_rules! skip_wrapper {
+    ($($wrapper:ident),*) => {
+        $(
+        impl<T> Skip for $wrapper<T>
+        where
+            T: Skip,
+        {
+            fn skip_with<R>(r: &mut R, config: &ToraConfig) -> io::Result<()>
+            where
+                R: SkipRead,
+            {
+                T::skip_with(r, config)
+            }
+
+            fn skip_n<R>(r: &mut R, n: usize, config: &ToraConfig) -> io::Result<()>
+            where
+                R: SkipRead,
+            {
+                T::skip_n(r, n, config)
+            }
+        }
+        )*
+    };
+}
+
+skip_wrapper!(Box, Mutex, RwLock, Cell, RefCell);
@@ -0,0 +1,163 @@
+//! Zero-copy deserialization of values borrowing from a byte slice.
+//!
+//! [FromReader] copies every string and byte sequence it reads into an owned allocation.
+//! [FromSlice] instead reads from a slice held in memory, such as a memory-mapped file or a pooled
+//! receive buffer, and returns `&str` and `&[u8]` values pointing into it, allocating nothing.
+//!
+//! The wire format is the same: a `&str` reads what a [String] writes, and a `&[u8]` what a
+//! `Vec<u8>` writes. Structs with a lifetime parameter derive the trait with
+//! `#[derive(FromSlice)]`. Primitives are read through [FromReader], so registered codecs apply
+//! to them, but not to `&str` and `&[u8]`, as codecs produce owned values.
+//!
+//! ```
+//! use tora::slice::FromSlice;
+//! use tora::{FromSlice, WriteStruct};
+//!
+//! #[derive(FromSlice)]
+//! struct Chat<'a> {
+//!     sender: u32,
+//!     message: &'a str,
+//! }
+//!
+//! #[derive(WriteStruct)]
+//! struct OwnedChat {
+//!     sender: u32,
+//!     message: String,
+//! }
+//!
+//! let bytes = tora::testing::to_bytes(&OwnedChat { sender: 7, message: "Hi".to_string() });
+//! let (chat, rest) = Chat::from_slice(&bytes).unwrap();
+//!
+//! assert_eq!(chat.sender, 7);
+//! assert_eq!(chat.message, "Hi");
+//! assert!(rest.is_empty());
+//! ```
+
+use std::io;
+use std::io::ErrorKind;
+use std::time::Duration;
+
+use crate::config::{StringFormat, ToraConfig};
+use crate::read::FromReader;
+
+/// Marks a type as able to be deserialized from a byte slice, borrowing from it.
+///
+/// Derived for structs with `#[derive(FromSlice)]`, which reads the fields in their wire order,
+/// honoring the `order`, `pad_before` and `pad_after` attributes.
+pub trait FromSlice<'a>: Sized {
+    /// Deserializes a value from the start of the slice, returning it along with the bytes left
+    /// after it.
+    fn from_slice(bytes: &'a [u8]) -> io::Result<(Self, &'a [u8])> {
+        Self::from_slice_with(bytes, &ToraConfig::DEFAULT)
+    }
+
+    /// Deserializes a value from the start of the slice using the given configuration.
+    fn from_slice_with(bytes: &'a [u8], config: &ToraConfig) -> io::Result<(Self, &'a [u8])>;
+}
+
+/// Splits off the first `n` bytes of the slice.
+///
+/// Returns [ErrorKind::UnexpectedEof] if the slice is shorter than `n`.
+pub fn take(bytes: &[u8], n: usize) -> io::Result<(&[u8], &[u8])> {
+    match bytes.len() >= n {
+        true => Ok(bytes.split_at(n)),
+        false => Err(ErrorKind::UnexpectedEof.into()),
+    }
+}
+
+/// Skips the first `n` bytes of the slice, returning the rest.
+///
+/// Returns [ErrorKind::UnexpectedEof] if the slice is shorter than `n`.
+pub fn skip(bytes: &[u8], n: usize) -> io::Result<&[u8]> {
+    take(bytes, n).map(|(_, rest)| rest)
+}
+
+/// Reads an owned value from the slice, advancing past it.
+fn read_owned<'a, T>(mut bytes: &'a [u8], config: &ToraConfig) -> io::Result<(T, &'a [u8])>
+where
+    T: FromReader,
+{
+    let value = T::from_reader_with(&mut bytes, config)?;
+    Ok((value, bytes))
+}
+
+macro_rules! from_slice_impl {
+    ($($t:ty),*) => {
+        $(
+        impl<'a> FromSlice<'a> for $t {
+            fn from_slice_with(
+                bytes: &'a [u8],
+                config: &ToraConfig,
+            ) -> io::Result<(Self, &'a [u8])> {
+                read_owned(bytes, config)
+            }
+        }
+        )*
+    };
+}
+
+from_slice_impl!(
+    u8,
+    u16,
+    u32,
+    u64,
+    u128,
+    usize,
+    i8,
+    i16,
+    i32,
+    i64,
+    i128,
+    f32,
+    f64,
+    bool,
+    char,
+    Duration,
+    ()
+);
+
+impl<'a> FromSlice<'a> for &'a [u8] {
+    /// Reads the configured length prefix, then borrows that many bytes.
+    fn from_slice_with(bytes: &'a [u8], config: &ToraConfig) -> io::Result<(Self, &'a [u8])> {
+        let mut rest = bytes;
+        let len = config.read_length(&mut rest)?;
+        take(rest, len)
+    }
+}
+
+impl<'a> FromSlice<'a> for &'a str {
+    /// Borrows a UTF-8 string in the configured string format.
+    ///
+    /// Returns [ErrorKind::InvalidData] if the string is not valid UTF-8, or if it is longer than
+    /// the configured maximum length.
+    fn from_slice_with(bytes: &'a [u8], config: &ToraConfig) -> io::Result<(Self, &'a [u8])> {
+        let (string, rest) = match config.string_format {
+            StringFormat::NulTerminated => {
+                let len = bytes
+                    .iter()
+                    .position(|&b| b == 0)
+                    .ok_or(ErrorKind::UnexpectedEof)?;
+                config.check_length(len)?;
+                (&bytes[..len], &bytes[len + 1..])
+            }
+            StringFormat::LengthPrefixed => <&[u8]>::from_slice_with(bytes, config)?,
+        };
+
+        let string = std::str::from_utf8(string)
+            .map_err(|_| io::Error::new(ErrorKind::InvalidData, "Invalid UTF-8"))?;
+        Ok((string, rest))
+    }
+}
+
+impl<'a, T> FromSlice<'a> for Option<T>
+where
+    T: FromSlice<'a>,
+{
+    /// Reads a bool and if true, reads and returns Some([T]).
+    fn from_slice_with(bytes: &'a [u8], config: &ToraConfig) -> io::Result<(Self, &'a [u8])> {
+        match bool::from_slice_with(bytes, config)? {
+            (true, rest) => T::from_slice_with(rest, config).map(|(v, rest)| (Some(v), rest)),
+            (false, rest) => Ok((None, rest)),
+        }
+    }
+}
@@ -0,0 +1,424 @@
+//! Typed, framed connections.
+//!
+//! A [ToraStream] sends and receives values of one type over a connection, such as a
+//! [TcpStream]. Each value is written as a frame: a length prefix holding the amount of bytes,
+//! followed by the serialized value. Reads are buffered, and a frame is only decoded once all of
+//! its bytes arrived, so partial reads never leave the connection in an inconsistent state.
+//!
+//! ```
+//! use std::io;
+//! use std::net::{TcpListener, TcpStream};
+//! use std::thread;
+//!
+//! use tora::stream::ToraStream;
+//! use tora::{ReadStruct, WriteStruct};
+//!
+//! #[derive(Debug, PartialEq, ReadStruct, WriteStruct)]
+//! struct Message {
+//!     id: u32,
+//!     text: String,
+//! }
+//!
+//! fn main() -> io::Result<()> {
+//!     let listener = TcpListener::bind("127.0.0.1:0")?;
+//!     let address = listener.local_addr()?;
+//!
+//!     let client = thread::spawn(move || -> io::Result<()> {
+//!         let mut stream = ToraStream::<Message>::connect(address)?;
+//!         stream.send(&Message { id: 1, text: "Hello".to_string() })
+//!     });
+//!
+//!     let (socket, _) = listener.accept()?;
+//!     let mut stream = ToraStream::<Message>::new(socket);
+//!
+//!     let message = stream.recv()?;
+//!     assert_eq!(message, Message { id: 1, text: "Hello".to_string() });
+//!
+//!     client.join().unwrap()
+//! }
+//! ```
+
+use std::io;
+use std::io::{BufReader, ErrorKind, Read, Write};
+use std::marker::PhantomData;
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::Arc;
+
+use crate::capture::{Capture, Direction};
+use crate::config::ToraConfig;
+use crate::mux;
+use crate::mux::Multiplexer;
+use crate::read::{read_bytes, FromReader};
+use crate::write::SerializeIo;
+
+/// A capture shared by the halves of a connection.
+type SharedCapture = Arc<Capture<dyn Write + Send>>;
+
+/// Reads a frame into `buf` and deserializes it.
+fn read_frame<T, R>(
+    r: &mut R,
+    buf: &mut Vec<u8>,
+    config: &ToraConfig,
+    capture: Option<&SharedCapture>,
+) -> io::Result<T>
+where
+    T: FromReader,
+    R: Read,
+{
+    config.check_cancelled()?;
+    let len = config.read_length(r)?;
+
+    #[cfg(feature = "tracing")]
+    let _span = tracing::debug_span!("recv", bytes = len).entered();
+
+    // The buffer grows as the payload arrives, so a forged length cannot allocate more than
+    // the peer sends.
+    buf.clear();
+    if read_bytes(r, buf, len, config)? != len {
+        return Err(io::Error::new(ErrorKind::UnexpectedEof, "Truncated frame"));
+    }
+
+    if let Some(capture) = capture {
+        capture.record(Direction::Inbound, buf)?;
+    }
+
+    decode_frame(buf, config)
+}
+
+/// Deserializes the payload of a frame, which the value must occupy entirely.
+pub(crate) fn decode_frame<T>(mut frame: &[u8], config: &ToraConfig) -> io::Result<T>
+where
+    T: FromReader,
+{
+    let value = T::from_reader_with(&mut frame, config)?;
+
+    if !frame.is_empty() {
+        return Err(io::Error::new(
+            ErrorKind::InvalidData,
+            "Frame contains trailing bytes",
+        ));
+    }
+    Ok(value)
+}
+
+/// Serializes the value into `buf` and writes it as a single frame.
+pub(crate) fn write_frame<T, W>(
+    w: &mut W,
+    value: &T,
+    buf: &mut Vec<u8>,
+    config: &ToraConfig,
+    capture: Option<&SharedCapture>,
+) -> io::Result<()>
+where
+    T: SerializeIo + ?Sized,
+    W: Write,
+{
+    buf.clear();
+    value.serialize_with(buf, config)?;
+
+    #[cfg(feature = "tracing")]
+    let _span = tracing::debug_span!("send", bytes = buf.len()).entered();
+
+    if let Some(capture) = capture {
+        capture.record(Direction::Outbound, buf)?;
+    }
+
+    // Prefix the payload in the same buffer so the frame is written at once.
+    let mut prefix = Vec::new();
+    config.write_length(&mut prefix, buf.len())?;
+    buf.splice(..0, prefix);
+
+    w.write_all(buf)?;
+    w.flush()
+}
+
+/// The receiving half of a framed connection.
+///
+/// Returned by [ToraStream::split], or constructed around any reader.
+#[derive(Debug)]
+pub struct FrameReader<T, R> {
+    reader: BufReader<R>,
+    config: ToraConfig,
+    buf: Vec<u8>,
+    capture: Option<SharedCapture>,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T, R> FrameReader<T, R>
+where
+    R: Read,
+{
+    /// Constructs a FrameReader using the default configuration.
+    pub fn new(reader: R) -> Self {
+        Self::with_config(reader, ToraConfig::DEFAULT)
+    }
+
+    /// Constructs a FrameReader using the given configuration.
+    pub fn with_config(reader: R, config: ToraConfig) -> Self {
+        Self::from_buffered(BufReader::new(reader), config)
+    }
+
+    fn from_buffered(reader: BufReader<R>, config: ToraConfig) -> Self {
+        Self {
+            reader,
+            config,
+            buf: Vec::new(),
+            capture: None,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Waits for the next frame and deserializes it.
+    ///
+    /// Returns [ErrorKind::InvalidData] if the value does not occupy the whole frame. The frame
+    /// buffer grows as the payload arrives, so a length prefix larger than the frame sent fails
+    /// with [ErrorKind::UnexpectedEof] once the connection ends, without allocating that length.
+    ///
+    /// ```
+    /// use std::io::ErrorKind;
+    ///
+    /// use tora::stream::FrameReader;
+    ///
+    /// // A frame claiming 4 GiB, of which 2 bytes are sent.
+    /// let mut reader = FrameReader::<Vec<u8>, _>::new([255, 255, 255, 255, 1, 2].as_slice());
+    ///
+    /// assert_eq!(reader.recv().unwrap_err().kind(), ErrorKind::UnexpectedEof);
+    /// ```
+    pub fn recv(&mut self) -> io::Result<T>
+    where
+        T: FromReader,
+    {
+        read_frame(
+            &mut self.reader,
+            &mut self.buf,
+            &self.config,
+            self.capture.as_ref(),
+        )
+    }
+
+    /// Records every frame received to the capture.
+    pub fn with_capture<W>(mut self, capture: Arc<Capture<W>>) -> Self
+    where
+        W: Write + Send + 'static,
+    {
+        self.capture = Some(capture);
+        self
+    }
+
+    /// Returns a reference to the underlying reader.
+    pub fn get_ref(&self) -> &R {
+        self.reader.get_ref()
+    }
+
+    /// Returns the underlying reader, discarding buffered bytes.
+    pub fn into_inner(self) -> R {
+        self.reader.into_inner()
+    }
+}
+
+/// The sending half of a framed connection.
+///
+/// Returned by [ToraStream::split], or constructed around any writer.
+#[derive(Debug)]
+pub struct FrameWriter<T, W> {
+    writer: W,
+    config: ToraConfig,
+    buf: Vec<u8>,
+    capture: Option<SharedCapture>,
+    _marker: PhantomData<fn(&T)>,
+}
+
+impl<T, W> FrameWriter<T, W>
+where
+    W: Write,
+{
+    /// Constructs a FrameWriter using the default configuration.
+    pub fn new(writer: W) -> Self {
+        Self::with_config(writer, ToraConfig::DEFAULT)
+    }
+
+    /// Constructs a FrameWriter using the given configuration.
+    pub fn with_config(writer: W, config: ToraConfig) -> Self {
+        Self {
+            writer,
+            config,
+            buf: Vec::new(),
+            capture: None,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Serializes the value and writes it as a frame, then flushes the writer.
+    pub fn send(&mut self, value: &T) -> io::Result<()>
+    where
+        T: SerializeIo,
+    {
+        write_frame(
+            &mut self.writer,
+            value,
+            &mut self.buf,
+            &self.config,
+            self.capture.as_ref(),
+        )
+    }
+
+    /// Records every frame sent to the capture.
+    pub fn with_capture<C>(mut self, capture: Arc<Capture<C>>) -> Self
+    where
+        C: Write + Send + 'static,
+    {
+        self.capture = Some(capture);
+        self
+    }
+
+    /// Returns a reference to the underlying writer.
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    /// Returns the underlying writer.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+/// A connection sending and receiving framed values of type [T].
+///
+/// Usually wraps a [TcpStream], but accepts any transport that is both [Read] and [Write].
+#[derive(Debug)]
+pub struct ToraStream<T, S = TcpStream> {
+    reader: FrameReader<T, S>,
+    buf: Vec<u8>,
+}
+
+impl<T, S> ToraStream<T, S>
+where
+    S: Read + Write,
+{
+    /// Constructs a ToraStream using the default configuration.
+    pub fn new(stream: S) -> Self {
+        Self::with_config(stream, ToraConfig::DEFAULT)
+    }
+
+    /// Constructs a ToraStream using the given configuration.
+    pub fn with_config(stream: S, config: ToraConfig) -> Self {
+        Self {
+            reader: FrameReader::with_config(stream, config),
+            buf: Vec::new(),
+        }
+    }
+
+    /// Serializes the value and writes it as a frame, then flushes the stream.
+    pub fn send(&mut self, value: &T) -> io::Result<()>
+    where
+        T: SerializeIo,
+    {
+        let FrameReader {
+            reader,
+            config,
+            capture,
+            ..
+        } = &mut self.reader;
+
+        write_frame(
+            reader.get_mut(),
+            value,
+            &mut self.buf,
+            config,
+            capture.as_ref(),
+        )
+    }
+
+    /// Records every frame sent and received to the capture.
+    pub fn with_capture<W>(mut self, capture: Arc<Capture<W>>) -> Self
+    where
+        W: Write + Send + 'static,
+    {
+        self.reader.capture = Some(capture);
+        self
+    }
+
+    /// Waits for the next frame and deserializes it.
+    ///
+    /// Returns [ErrorKind::InvalidData] if the value does not occupy the whole frame. The frame
+    /// buffer grows as the payload arrives, so a length prefix larger than the frame sent fails
+    /// with [ErrorKind::UnexpectedEof] once the connection ends, without allocating that length.
+    ///
+    /// ```
+    /// use std::io::ErrorKind;
+    ///
+    /// use tora::stream::FrameReader;
+    ///
+    /// // A frame claiming 4 GiB, of which 2 bytes are sent.
+    /// let mut reader = FrameReader::<Vec<u8>, _>::new([255, 255, 255, 255, 1, 2].as_slice());
+    ///
+    /// assert_eq!(reader.recv().unwrap_err().kind(), ErrorKind::UnexpectedEof);
+    /// ```
+    pub fn recv(&mut self) -> io::Result<T>
+    where
+        T: FromReader,
+    {
+        self.reader.recv()
+    }
+
+    /// Returns the configuration of this stream.
+    pub fn config(&self) -> &ToraConfig {
+        &self.reader.config
+    }
+
+    /// Returns a reference to the underlying stream.
+    pub fn get_ref(&self) -> &S {
+        self.reader.get_ref()
+    }
+
+    /// Returns the underlying stream, discarding buffered bytes.
+    pub fn into_inner(self) -> S {
+        self.reader.into_inner()
+    }
+}
+
+impl<T> ToraStream<T, TcpStream> {
+    /// Opens a TCP connection to the given address.
+    pub fn connect<A>(address: A) -> io::Result<Self>
+    where
+        A: ToSocketAddrs,
+    {
+        TcpStream::connect(address).map(Self::new)
+    }
+
+    /// Splits this stream into halves that can be moved to separate threads.
+    ///
+    /// Bytes already buffered are kept by the reading half. Both halves keep recording to the
+    /// capture, if any.
+    pub fn split(self) -> io::Result<(FrameReader<T, TcpStream>, FrameWriter<T, TcpStream>)> {
+        let FrameReader {
+            reader,
+            config,
+            capture,
+            ..
+        } = self.reader;
+        let writer = reader.get_ref().try_clone()?;
+
+        let mut reader = FrameReader::from_buffered(reader, config.clone());
+        let mut writer = FrameWriter::with_config(writer, config);
+        reader.capture.clone_from(&capture);
+        writer.capture = capture;
+
+        Ok((reader, writer))
+    }
+
+    /// Converts this stream into a [Multiplexer] carrying several typed channels.
+    ///
+    /// Useful once a handshake over the stream has completed. Bytes already buffered are kept.
+    pub fn multiplex(self) -> io::Result<Multiplexer> {
+        let FrameReader { reader, config, .. } = self.reader;
+        let writer = reader.get_ref().try_clone()?;
+
+        Ok(Multiplexer::from_buffered(
+            reader,
+            writer,
+            config,
+            mux::DEFAULT_WINDOW,
+        ))
+    }
+}
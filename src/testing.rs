@@ -0,0 +1,1073 @@
+//! Utilities for testing types implementing [SerializeIo] and [FromReader].
+//!
+//! [assert_roundtrip!](crate::assert_roundtrip) checks that a value survives serialization,
+//! [assert_bytes_eq!](crate::assert_bytes_eq) pins down its exact encoding, and
+//! [assert_decode_error] and [assert_truncation_fails] check how malformed input is rejected.
+//! Mismatched bytes are reported as a [ByteDiff], showing where the encodings diverge.
+//! [assert_snapshot!](crate::assert_snapshot) compares an encoding against a checked-in file.
+//! [FaultyReader] and [FaultyWriter] inject IO failures to exercise error paths, and [duplex]
+//! connects a client and server in memory. [block_on] runs asynchronous code without a runtime.
+//!
+//! ```
+//! use std::io::ErrorKind;
+//!
+//! use tora::testing::assert_decode_error;
+//! use tora::{assert_bytes_eq, assert_roundtrip};
+//!
+//! assert_roundtrip!(Some("Hello".to_string()));
+//! assert_bytes_eq!((1u16, true), "01 00 01");
+//!
+//! // 0xD800 is not a valid char.
+//! assert_decode_error::<char>(&[0x00, 0xD8, 0x00, 0x00], ErrorKind::InvalidData);
+//! ```
+
+use std::collections::VecDeque;
+use std::fmt::Write as _;
+use std::future::Future;
+use std::io::{Cursor, ErrorKind, Read, Write};
+use std::path::Path;
+use std::pin::pin;
+#[cfg(feature = "websocket_tokio")]
+use std::pin::Pin;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError};
+use std::task::{Context, Poll, Wake, Waker};
+use std::thread::Thread;
+use std::time::{Duration, Instant};
+use std::{env, fmt, fs, io, thread};
+
+use crate::config::ToraConfig;
+use crate::read::FromReader;
+use crate::schema::{format_path, walk, Schema, Visit};
+use crate::write::SerializeIo;
+
+/// Asserts that a value is equal to itself after being serialized and deserialized.
+///
+/// The value must implement [SerializeIo], [FromReader], [PartialEq] and [Debug](std::fmt::Debug).
+/// An optional second argument is the [ToraConfig] to use.
+///
+/// Panics if serialization fails, deserialization fails, bytes are left over, or the values differ.
+///
+/// ```
+/// use tora::assert_roundtrip;
+/// use tora::config::{LengthPrefix, ToraConfig};
+///
+/// assert_roundtrip!(vec![1u32, 2, 3]);
+///
+/// let config = ToraConfig::DEFAULT.length_prefix(LengthPrefix::U8);
+/// assert_roundtrip!(vec![1u32, 2, 3], &config);
+/// ```
+#[macro_export]
+macro_rules! assert_roundtrip {
+    ($value:expr $(,)?) => {
+        $crate::assert_roundtrip!($value, &$crate::config::ToraConfig::DEFAULT)
+    };
+    ($value:expr, $config:expr $(,)?) => {{
+        let value = &$value;
+        let decoded = $crate::testing::roundtrip_with(value, $config);
+
+        assert_eq!(*value, decoded, "The value changed after a round trip");
+    }};
+}
+
+/// Asserts that a value serializes to the given bytes, written in hexadecimal.
+///
+/// Whitespace in the hexadecimal string is ignored. An optional third argument is the
+/// [ToraConfig] to use. On failure, the panic message is a [ByteDiff].
+///
+/// ```
+/// use tora::assert_bytes_eq;
+///
+/// assert_bytes_eq!("Hi", "48 69 00");
+/// assert_bytes_eq!(0xABCDu16, "cdab");
+/// ```
+#[macro_export]
+macro_rules! assert_bytes_eq {
+    ($value:expr, $hex:expr $(,)?) => {
+        $crate::assert_bytes_eq!($value, $hex, &$crate::config::ToraConfig::DEFAULT)
+    };
+    ($value:expr, $hex:expr, $config:expr $(,)?) => {{
+        let bytes = $crate::testing::to_bytes_with(&$value, $config);
+        let expected = $crate::testing::parse_hex($hex);
+
+        if let Some(diff) = $crate::testing::ByteDiff::new(&bytes, &expected) {
+            panic!("The value did not serialize to the expected bytes\n{diff}");
+        }
+    }};
+}
+
+/// Asserts that two values serialize to the same bytes.
+///
+/// An optional `schema = ` argument describes the expected value, so a mismatch also names the
+/// field it falls in. On failure, the panic message is a [ByteDiff].
+///
+/// ```
+/// use tora::assert_same_bytes;
+/// use tora::schema::Reflect;
+///
+/// assert_same_bytes!(5u32, [5u8, 0, 0, 0]);
+/// assert_same_bytes!((1u8, "Hi"), (1u8, "Hi".to_string()), schema = <(u8, String)>::schema());
+/// ```
+#[macro_export]
+macro_rules! assert_same_bytes {
+    ($left:expr, $right:expr $(,)?) => {{
+        let left = $crate::testing::to_bytes(&$left);
+        let right = $crate::testing::to_bytes(&$right);
+
+        if let Some(diff) = $crate::testing::ByteDiff::new(&left, &right) {
+            panic!("The values did not serialize to the same bytes\n{diff}");
+        }
+    }};
+    ($left:expr, $right:expr, schema = $schema:expr $(,)?) => {{
+        let left = $crate::testing::to_bytes(&$left);
+        let right = $crate::testing::to_bytes(&$right);
+        let schema = $schema;
+
+        if let Some(diff) = $crate::testing::ByteDiff::new(&left, &right) {
+            let diff = diff.with_schema(&schema, &$crate::config::ToraConfig::DEFAULT);
+            panic!("The values did not serialize to the same bytes\n{diff}");
+        }
+    }};
+}
+
+/// Asserts that a value serializes to the bytes in a snapshot file.
+///
+/// The file is `tests/snapshots/{name}.hex` in the calling crate, holding the bytes in hexadecimal.
+/// When the `TORA_BLESS` environment variable is set, the file is written instead, so intended
+/// wire format changes are accepted with `TORA_BLESS=1 cargo test`.
+///
+/// ```no_run
+/// use tora::assert_snapshot;
+///
+/// assert_snapshot!("greeting", "Hello");
+/// ```
+#[macro_export]
+macro_rules! assert_snapshot {
+    ($name:expr, $value:expr $(,)?) => {
+        $crate::testing::assert_snapshot_file(
+            ::std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+                .join("tests")
+                .join("snapshots")
+                .join(format!("{}.hex", $name)),
+            &$value,
+        )
+    };
+}
+
+/// Asserts that a value serializes to the bytes in the snapshot file at the given path.
+///
+/// When the `TORA_BLESS` environment variable is set, the file and its parent directories are
+/// written instead. Panics if the file does not exist otherwise.
+#[track_caller]
+pub fn assert_snapshot_file<P, T>(path: P, value: &T)
+where
+    P: AsRef<Path>,
+    T: SerializeIo + ?Sized,
+{
+    let path = path.as_ref();
+    let bytes = to_bytes(value);
+
+    if env::var_os("TORA_BLESS").is_some() {
+        let mut hex = String::new();
+
+        for row in bytes.chunks(ROW) {
+            hex.push_str(&to_hex(row));
+            hex.push('\n');
+        }
+
+        let written = path
+            .parent()
+            .map_or(Ok(()), fs::create_dir_all)
+            .and_then(|_| fs::write(path, hex));
+
+        if let Err(e) = written {
+            panic!("Failed to write snapshot {}: {e}", path.display());
+        }
+        return;
+    }
+
+    let expected = match fs::read_to_string(path) {
+        Ok(hex) => parse_hex(&hex),
+        Err(e) => panic!(
+            "Failed to read snapshot {}: {e}\nRun with TORA_BLESS=1 to create it",
+            path.display()
+        ),
+    };
+
+    if let Some(diff) = ByteDiff::new(&bytes, &expected) {
+        panic!(
+            "The value does not match snapshot {}\n{diff}\nRun with TORA_BLESS=1 to update it",
+            path.display()
+        );
+    }
+}
+
+/// Serializes the value using the default configuration.
+///
+/// Panics if serialization fails.
+#[track_caller]
+pub fn to_bytes<T>(value: &T) -> Vec<u8>
+where
+    T: SerializeIo + ?Sized,
+{
+    to_bytes_with(value, &ToraConfig::DEFAULT)
+}
+
+/// Serializes the value using the given configuration.
+///
+/// Panics if serialization fails.
+#[track_caller]
+pub fn to_bytes_with<T>(value: &T, config: &ToraConfig) -> Vec<u8>
+where
+    T: SerializeIo + ?Sized,
+{
+    let mut bytes = Vec::new();
+
+    if let Err(e) = value.serialize_with(&mut bytes, config) {
+        panic!("Failed to serialize: {e}");
+    }
+    bytes
+}
+
+/// Serializes and deserializes the value using the default configuration.
+///
+/// Panics if either fails, or the deserialized value does not occupy every serialized byte.
+#[track_caller]
+pub fn roundtrip<T>(value: &T) -> T
+where
+    T: SerializeIo + FromReader,
+{
+    roundtrip_with(value, &ToraConfig::DEFAULT)
+}
+
+/// Serializes and deserializes the value using the given configuration.
+///
+/// Panics if either fails, or the deserialized value does not occupy every serialized byte.
+#[track_caller]
+pub fn roundtrip_with<T>(value: &T, config: &ToraConfig) -> T
+where
+    T: SerializeIo + FromReader,
+{
+    let bytes = to_bytes_with(value, config);
+    let mut cursor = Cursor::new(bytes.as_slice());
+
+    let decoded = match T::from_reader_with(&mut cursor, config) {
+        Ok(decoded) => decoded,
+        Err(e) => panic!("Failed to deserialize {}: {e}", to_hex(&bytes)),
+    };
+
+    let read = cursor.position() as usize;
+    assert_eq!(
+        read,
+        bytes.len(),
+        "Deserialization read {read} of {} bytes",
+        bytes.len()
+    );
+    decoded
+}
+
+/// Attempts to deserialize [T] from the bytes, returning the error.
+///
+/// Panics if deserialization succeeds.
+#[track_caller]
+pub fn decode_error<T>(bytes: &[u8]) -> io::Error
+where
+    T: FromReader,
+{
+    match T::from_reader(&mut Cursor::new(bytes)) {
+        Ok(_) => panic!(
+            "Expected deserializing {} to fail, but it succeeded",
+            to_hex(bytes)
+        ),
+        Err(e) => e,
+    }
+}
+
+/// Asserts that deserializing [T] from the bytes fails with the given error kind.
+#[track_caller]
+pub fn assert_decode_error<T>(bytes: &[u8], kind: ErrorKind)
+where
+    T: FromReader,
+{
+    let e = decode_error::<T>(bytes);
+    assert_eq!(e.kind(), kind, "Unexpected error: {e}");
+}
+
+/// Asserts that deserializing [T] from every truncation of the value's bytes fails with
+/// [ErrorKind::UnexpectedEof].
+///
+/// ```
+/// use tora::testing::assert_truncation_fails;
+///
+/// assert_truncation_fails(&("Hello".to_string(), 5u64));
+/// ```
+#[track_caller]
+pub fn assert_truncation_fails<T>(value: &T)
+where
+    T: SerializeIo + FromReader,
+{
+    let bytes = to_bytes(value);
+
+    for len in 0..bytes.len() {
+        let e = decode_error::<T>(&bytes[..len]);
+
+        assert_eq!(
+            e.kind(),
+            ErrorKind::UnexpectedEof,
+            "Unexpected error after {len} of {} bytes: {e}",
+            bytes.len()
+        );
+    }
+}
+
+/// Parses bytes written in hexadecimal, ignoring whitespace.
+///
+/// Panics if the string is not valid hexadecimal.
+#[track_caller]
+pub fn parse_hex(hex: &str) -> Vec<u8> {
+    let digits = hex
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .map(|c| match c.to_digit(16) {
+            Some(digit) => digit as u8,
+            None => panic!("Invalid hexadecimal digit {c:?}"),
+        })
+        .collect::<Vec<_>>();
+
+    assert!(
+        digits.len() % 2 == 0,
+        "Hexadecimal bytes must have an even amount of digits"
+    );
+    digits
+        .chunks(2)
+        .map(|pair| pair[0] << 4 | pair[1])
+        .collect()
+}
+
+/// Formats the bytes as lowercase hexadecimal, separated by spaces.
+///
+/// ```
+/// assert_eq!(tora::testing::to_hex(&[0x01, 0xAB]), "01 ab");
+/// ```
+pub fn to_hex(bytes: &[u8]) -> String {
+    let mut hex = String::with_capacity(bytes.len() * 3);
+
+    for (i, byte) in bytes.iter().enumerate() {
+        if i > 0 {
+            hex.push(' ');
+        }
+        let _ = write!(hex, "{byte:02x}");
+    }
+    hex
+}
+
+/// The amount of bytes shown per row of a [ByteDiff].
+const ROW: usize = 16;
+
+/// The first difference between two byte strings, displayed as an annotated hex dump.
+///
+/// The dump shows the rows around the first differing byte, with differing bytes in brackets.
+///
+/// ```
+/// use tora::schema::{FieldSchema, Schema, StructSchema};
+/// use tora::testing::ByteDiff;
+/// use tora::config::ToraConfig;
+///
+/// let schema = Schema::Struct(StructSchema::new(
+///     "Player",
+///     vec![FieldSchema::new("id", Schema::U8), FieldSchema::new("name", Schema::String)],
+/// ));
+///
+/// let diff = ByteDiff::new(b"\x01John\0", b"\x01Joan\0")
+///     .unwrap()
+///     .with_schema(&schema, &ToraConfig::DEFAULT);
+///
+/// assert_eq!(diff.offset(), 3);
+/// assert_eq!(diff.field(), Some("name"));
+/// ```
+#[derive(Clone, Debug)]
+pub struct ByteDiff<'a> {
+    actual: &'a [u8],
+    expected: &'a [u8],
+    offset: usize,
+    field: Option<String>,
+}
+
+impl<'a> ByteDiff<'a> {
+    /// Compares the byte strings, returning None if they are equal.
+    pub fn new(actual: &'a [u8], expected: &'a [u8]) -> Option<Self> {
+        let offset = actual
+            .iter()
+            .zip(expected)
+            .position(|(a, e)| a != e)
+            .or_else(|| {
+                (actual.len() != expected.len()).then(|| actual.len().min(expected.len()))
+            })?;
+
+        Some(Self {
+            actual,
+            expected,
+            offset,
+            field: None,
+        })
+    }
+
+    /// Names the field the first difference falls in, using the schema of the expected bytes.
+    ///
+    /// Falls back to the actual bytes if the expected bytes end before the difference.
+    pub fn with_schema(mut self, schema: &Schema, config: &ToraConfig) -> Self {
+        self.field = [self.expected, self.actual]
+            .into_iter()
+            .find_map(|bytes| field_at(bytes, schema, config, self.offset));
+        self
+    }
+
+    /// Returns the offset of the first differing byte.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Returns the path of the field the first difference falls in, if known.
+    pub fn field(&self) -> Option<&str> {
+        self.field.as_deref()
+    }
+
+    fn write_rows(&self, f: &mut fmt::Formatter<'_>, bytes: &[u8], other: &[u8]) -> fmt::Result {
+        let first = (self.offset / ROW).saturating_sub(1) * ROW;
+        let last = (self.offset / ROW + 2) * ROW;
+
+        for start in (first..last.min(bytes.len())).step_by(ROW) {
+            let mut row = String::new();
+
+            for (i, byte) in bytes.iter().enumerate().skip(start).take(ROW) {
+                let _ = match other.get(i) == Some(byte) {
+                    true => write!(row, " {byte:02x} "),
+                    false => write!(row, "[{byte:02x}]"),
+                };
+            }
+            writeln!(f, "    {start:08x} {}", row.trim_end())?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for ByteDiff<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "First difference at offset {} ({} actual bytes, {} expected)",
+            self.offset,
+            self.actual.len(),
+            self.expected.len()
+        )?;
+        if let Some(field) = &self.field {
+            write!(f, ", in `{field}`")?;
+        }
+
+        writeln!(f, "\n  actual:")?;
+        self.write_rows(f, self.actual, self.expected)?;
+        writeln!(f, "  expected:")?;
+        self.write_rows(f, self.expected, self.actual)
+    }
+}
+
+/// Returns the path of the innermost field or element containing the offset.
+fn field_at(bytes: &[u8], schema: &Schema, config: &ToraConfig, offset: usize) -> Option<String> {
+    let mut field = None;
+
+    // Nested values are visited before the values containing them. A malformed buffer ends the
+    // walk early, but the values visited until then are still useful.
+    let _ = walk(bytes, schema, config, &mut |visit: Visit| {
+        if field.is_none() && !visit.path.is_empty() && visit.range.contains(&offset) {
+            field = Some(format_path(visit.path));
+        }
+    });
+    field
+}
+
+/// Failures injected into the IO of a [FaultyReader] or [FaultyWriter].
+#[derive(Clone, Debug, Default)]
+struct Faults {
+    /// The position from which every call fails, and the kind of the error.
+    fail_after: Option<(usize, ErrorKind)>,
+    /// The maximum amount of bytes per call.
+    max_chunk: Option<usize>,
+    /// Errors returned once, when their position is reached.
+    injected: Vec<(usize, ErrorKind)>,
+}
+
+impl Faults {
+    /// Returns the amount of bytes the next call at the given position may transfer.
+    fn limit(&mut self, position: usize, mut len: usize) -> io::Result<usize> {
+        if let Some(i) = self.injected.iter().position(|&(at, _)| at <= position) {
+            let (_, kind) = self.injected.remove(i);
+            return Err(io::Error::new(kind, "Injected fault"));
+        }
+
+        if let Some((at, kind)) = self.fail_after {
+            if position >= at {
+                return Err(io::Error::new(kind, "Injected fault"));
+            }
+            len = len.min(at - position);
+        }
+        if let Some(next) = self.injected.iter().map(|&(at, _)| at).min() {
+            len = len.min(next - position);
+        }
+        if let Some(max) = self.max_chunk {
+            len = len.min(max);
+        }
+        Ok(len)
+    }
+}
+
+/// A reader injecting failures into the reads of another reader.
+///
+/// ```
+/// use std::io::{Cursor, ErrorKind, Read};
+///
+/// use tora::read::ToraRead;
+/// use tora::testing::FaultyReader;
+///
+/// let mut reader = FaultyReader::new(Cursor::new([1, 0, 0, 0, 2, 0, 0, 0]))
+///     .short_reads(1)
+///     .inject(2, ErrorKind::Interrupted)
+///     .fail_after(6, ErrorKind::ConnectionReset);
+///
+/// // Short and interrupted reads are retried.
+/// assert_eq!(reader.reads::<u32>().unwrap(), 1);
+///
+/// let e = reader.reads::<u32>().unwrap_err();
+/// assert_eq!(e.kind(), ErrorKind::ConnectionReset);
+/// ```
+#[derive(Clone, Debug)]
+pub struct FaultyReader<R> {
+    inner: R,
+    faults: Faults,
+    position: usize,
+}
+
+impl<R> FaultyReader<R> {
+    /// Constructs a FaultyReader passing reads through until faults are added.
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            faults: Faults::default(),
+            position: 0,
+        }
+    }
+
+    /// Fails every read once `position` bytes were read, with an error of the given kind.
+    pub fn fail_after(mut self, position: usize, kind: ErrorKind) -> Self {
+        self.faults.fail_after = Some((position, kind));
+        self
+    }
+
+    /// Limits every read to at most `max` bytes.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max` is zero.
+    pub fn short_reads(mut self, max: usize) -> Self {
+        assert!(max > 0, "Reads must be allowed at least one byte");
+        self.faults.max_chunk = Some(max);
+        self
+    }
+
+    /// Fails the first read once `position` bytes were read, with an error of the given kind.
+    ///
+    /// Useful for [ErrorKind::Interrupted] and [ErrorKind::WouldBlock], which callers may retry.
+    pub fn inject(mut self, position: usize, kind: ErrorKind) -> Self {
+        self.faults.injected.push((position, kind));
+        self
+    }
+
+    /// Returns the amount of bytes read so far.
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// Returns the underlying reader.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R> Read for FaultyReader<R>
+where
+    R: Read,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let len = self.faults.limit(self.position, buf.len())?;
+        let read = self.inner.read(&mut buf[..len])?;
+
+        self.position += read;
+        Ok(read)
+    }
+}
+
+/// A writer injecting failures into the writes of another writer.
+///
+/// ```
+/// use std::io::ErrorKind;
+///
+/// use tora::testing::FaultyWriter;
+/// use tora::write::ToraWrite;
+///
+/// let mut writer = FaultyWriter::new(Vec::new()).fail_after(6, ErrorKind::BrokenPipe);
+///
+/// assert_eq!(writer.writes(&"Hello world").unwrap_err().kind(), ErrorKind::BrokenPipe);
+/// assert_eq!(writer.into_inner(), b"Hello ");
+/// ```
+#[derive(Clone, Debug)]
+pub struct FaultyWriter<W> {
+    inner: W,
+    faults: Faults,
+    position: usize,
+}
+
+impl<W> FaultyWriter<W> {
+    /// Constructs a FaultyWriter passing writes through until faults are added.
+    pub fn new(inner: W) -> Self {
+        Self {
+            inner,
+            faults: Faults::default(),
+            position: 0,
+        }
+    }
+
+    /// Fails every write once `position` bytes were written, with an error of the given kind.
+    pub fn fail_after(mut self, position: usize, kind: ErrorKind) -> Self {
+        self.faults.fail_after = Some((position, kind));
+        self
+    }
+
+    /// Limits every write to at most `max` bytes.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `max` is zero.
+    pub fn short_writes(mut self, max: usize) -> Self {
+        assert!(max > 0, "Writes must be allowed at least one byte");
+        self.faults.max_chunk = Some(max);
+        self
+    }
+
+    /// Fails the first write once `position` bytes were written, with an error of the given kind.
+    ///
+    /// Useful for [ErrorKind::Interrupted] and [ErrorKind::WouldBlock], which callers may retry.
+    pub fn inject(mut self, position: usize, kind: ErrorKind) -> Self {
+        self.faults.injected.push((position, kind));
+        self
+    }
+
+    /// Returns the amount of bytes written so far.
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// Returns the underlying writer.
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W> Write for FaultyWriter<W>
+where
+    W: Write,
+{
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let len = self.faults.limit(self.position, buf.len())?;
+        let written = self.inner.write(&buf[..len])?;
+
+        self.position += written;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Configuration of the streams returned by [duplex_with].
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct DuplexConfig {
+    /// The maximum amount of bytes per read or write, simulating fragmented delivery.
+    pub max_chunk: Option<usize>,
+    /// The time between writing bytes and them becoming readable.
+    pub latency: Duration,
+}
+
+#[derive(Default)]
+struct PipeState {
+    /// Written chunks, and when they become readable.
+    chunks: VecDeque<(Instant, Vec<u8>)>,
+    /// The amount of bytes already read from the front chunk.
+    offset: usize,
+    /// Whether the writing end was dropped.
+    writer_closed: bool,
+    /// Whether the reading end was dropped.
+    reader_closed: bool,
+    /// The task waiting for bytes to read, woken when they are written.
+    waker: Option<Waker>,
+}
+
+/// Why a read from a pipe cannot complete yet.
+enum Wait {
+    /// No bytes were written.
+    Empty,
+    /// The first bytes become readable at the given time.
+    Until(Instant),
+}
+
+impl PipeState {
+    /// Reads the bytes readable now, returning 0 once the writing end was dropped.
+    fn take(&mut self, buf: &mut [u8], max_chunk: usize) -> Result<usize, Wait> {
+        let Some(&(ready, _)) = self.chunks.front() else {
+            return match self.writer_closed {
+                true => Ok(0),
+                false => Err(Wait::Empty),
+            };
+        };
+        if ready > Instant::now() {
+            return Err(Wait::Until(ready));
+        }
+
+        let chunk = &self.chunks[0].1[self.offset..];
+        let len = chunk.len().min(buf.len()).min(max_chunk);
+        buf[..len].copy_from_slice(&chunk[..len]);
+
+        if len == chunk.len() {
+            self.chunks.pop_front();
+            self.offset = 0;
+        } else {
+            self.offset += len;
+        }
+        Ok(len)
+    }
+}
+
+/// One direction of a duplex connection.
+#[derive(Default)]
+struct Pipe {
+    state: Mutex<PipeState>,
+    changed: Condvar,
+}
+
+impl Pipe {
+    fn lock(&self) -> MutexGuard<'_, PipeState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn read(&self, buf: &mut [u8], max_chunk: usize) -> io::Result<usize> {
+        let mut state = self.lock();
+
+        loop {
+            state = match state.take(buf, max_chunk) {
+                Ok(len) => return Ok(len),
+                Err(Wait::Empty) => self
+                    .changed
+                    .wait(state)
+                    .unwrap_or_else(PoisonError::into_inner),
+                Err(Wait::Until(ready)) => {
+                    self.changed
+                        .wait_timeout(state, ready.saturating_duration_since(Instant::now()))
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .0
+                }
+            };
+        }
+    }
+
+    /// Notifies the reading end, blocking or asynchronous, that the pipe changed.
+    fn notify(&self, state: &mut PipeState) {
+        self.changed.notify_all();
+
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+    }
+
+    fn close_writer(&self) {
+        let mut state = self.lock();
+        state.writer_closed = true;
+        self.notify(&mut state);
+    }
+
+    fn write(&self, buf: &[u8], latency: Duration) -> io::Result<()> {
+        let mut state = self.lock();
+
+        if state.reader_closed {
+            return Err(io::Error::new(
+                ErrorKind::BrokenPipe,
+                "The other end of the duplex was dropped",
+            ));
+        }
+        state
+            .chunks
+            .push_back((Instant::now() + latency, buf.to_vec()));
+        self.notify(&mut state);
+        Ok(())
+    }
+}
+
+/// One end of an in-memory connection, returned by [duplex].
+///
+/// Bytes written to one end are read from the other. Reads block until bytes are available, and
+/// return end of file once the other end is dropped. With the `websocket_tokio` feature, the
+/// stream also implements tokio's `AsyncRead` and `AsyncWrite`, whose reads are pending instead
+/// of blocking.
+///
+/// ```
+/// use std::io::{Read, Write};
+///
+/// use tora::testing::duplex;
+///
+/// let (mut a, mut b) = duplex();
+///
+/// // Empty writes send nothing, so they are never mistaken for end of file.
+/// assert_eq!(a.write(&[]).unwrap(), 0);
+/// a.write_all(b"Hi").unwrap();
+///
+/// let mut buf = [0; 8];
+/// assert_eq!(b.read(&mut buf).unwrap(), 2);
+/// ```
+pub struct DuplexStream {
+    incoming: Arc<Pipe>,
+    outgoing: Arc<Pipe>,
+    config: DuplexConfig,
+}
+
+/// Returns both ends of an in-memory connection, for testing clients and servers without sockets.
+///
+/// ```
+/// use std::io;
+/// use std::thread;
+///
+/// use tora::stream::ToraStream;
+/// use tora::testing::duplex;
+///
+/// fn main() -> io::Result<()> {
+///     let (client, server) = duplex();
+///
+///     let server = thread::spawn(move || -> io::Result<()> {
+///         let mut server = ToraStream::<u32, _>::new(server);
+///         let n = server.recv()?;
+///         server.send(&(n * 2))
+///     });
+///
+///     let mut client = ToraStream::<u32, _>::new(client);
+///     client.send(&21)?;
+///     assert_eq!(client.recv()?, 42);
+///
+///     server.join().unwrap()
+/// }
+/// ```
+pub fn duplex() -> (DuplexStream, DuplexStream) {
+    duplex_with(DuplexConfig::default())
+}
+
+/// Returns both ends of an in-memory connection using the given configuration.
+///
+/// ```
+/// use std::io::{Read, Write};
+/// use std::time::Duration;
+///
+/// use tora::testing::{duplex_with, DuplexConfig};
+///
+/// let (mut a, mut b) = duplex_with(DuplexConfig {
+///     max_chunk: Some(2),
+///     latency: Duration::from_millis(10),
+/// });
+///
+/// a.write_all(b"Hello").unwrap();
+///
+/// let mut buf = [0; 8];
+/// assert_eq!(b.read(&mut buf).unwrap(), 2);
+/// ```
+///
+/// # Panics
+///
+/// Panics if the maximum chunk size is zero.
+pub fn duplex_with(config: DuplexConfig) -> (DuplexStream, DuplexStream) {
+    assert!(
+        config.max_chunk != Some(0),
+        "The maximum chunk size must not be zero"
+    );
+
+    let (a, b) = (Arc::new(Pipe::default()), Arc::new(Pipe::default()));
+
+    let first = DuplexStream {
+        incoming: a.clone(),
+        outgoing: b.clone(),
+        config,
+    };
+    let second = DuplexStream {
+        incoming: b,
+        outgoing: a,
+        config,
+    };
+    (first, second)
+}
+
+impl DuplexStream {
+    fn max_chunk(&self) -> usize {
+        self.config.max_chunk.unwrap_or(usize::MAX)
+    }
+}
+
+impl Read for DuplexStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        self.incoming.read(buf, self.max_chunk())
+    }
+}
+
+impl Write for DuplexStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let len = buf.len().min(self.max_chunk());
+
+        self.outgoing.write(&buf[..len], self.config.latency)?;
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// ```
+/// use std::io;
+/// use std::time::Duration;
+///
+/// use tokio::io::{AsyncReadExt, AsyncWriteExt};
+/// use tora::testing::{block_on, duplex_with, DuplexConfig};
+///
+/// fn main() -> io::Result<()> {
+///     let (mut a, mut b) = duplex_with(DuplexConfig {
+///         max_chunk: Some(1),
+///         latency: Duration::from_millis(10),
+///     });
+///
+///     block_on(async {
+///         a.write_all(b"Hi").await?;
+///         a.shutdown().await?;
+///
+///         let mut buf = Vec::new();
+///         b.read_to_end(&mut buf).await?;
+///         assert_eq!(buf, b"Hi");
+///         Ok(())
+///     })
+/// }
+/// ```
+#[cfg(feature = "websocket_tokio")]
+impl tokio::io::AsyncRead for DuplexStream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut tokio::io::ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        if buf.remaining() == 0 {
+            return Poll::Ready(Ok(()));
+        }
+        let max_chunk = self.max_chunk();
+        let mut state = self.incoming.lock();
+
+        match state.take(buf.initialize_unfilled(), max_chunk) {
+            Ok(len) => {
+                buf.advance(len);
+                return Poll::Ready(Ok(()));
+            }
+            Err(Wait::Empty) => {}
+            Err(Wait::Until(ready)) => {
+                // Without a runtime timer, a thread wakes the task once the bytes are readable.
+                let waker = cx.waker().clone();
+                thread::spawn(move || {
+                    thread::sleep(ready.saturating_duration_since(Instant::now()));
+                    waker.wake();
+                });
+            }
+        }
+        state.waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+#[cfg(feature = "websocket_tokio")]
+impl tokio::io::AsyncWrite for DuplexStream {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Poll::Ready(self.write(buf))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    /// Ends the bytes the other end reads, as dropping this end does.
+    fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.outgoing.close_writer();
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl Drop for DuplexStream {
+    fn drop(&mut self) {
+        self.outgoing.close_writer();
+        self.incoming.lock().reader_closed = true;
+    }
+}
+
+impl fmt::Debug for DuplexStream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DuplexStream")
+            .field("config", &self.config)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Runs a future to completion on the current thread, parking it while the future is pending.
+///
+/// Enough to test asynchronous code over in-memory transports without an async runtime; futures
+/// relying on a runtime's timers or IO driver never complete.
+///
+/// ```
+/// use tora::testing::block_on;
+///
+/// assert_eq!(block_on(async { 20 + 22 }), 42);
+/// ```
+pub fn block_on<F>(future: F) -> F::Output
+where
+    F: Future,
+{
+    struct Unpark(Thread);
+
+    impl Wake for Unpark {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    let waker = Waker::from(Arc::new(Unpark(thread::current())));
+    let mut cx = Context::from_waker(&waker);
+    let mut future = pin!(future);
+
+    loop {
+        match future.as_mut().poll(&mut cx) {
+            Poll::Ready(output) => return output,
+            Poll::Pending => thread::park(),
+        }
+    }
+}
@@ -0,0 +1,340 @@
+//! Typed, framed connections over TLS, through `rustls`.
+//!
+//! A [TlsBuilder] collects the certificates and ALPN protocols of an endpoint, then builds a
+//! [TlsConnector] for clients or a [TlsAcceptor] for servers. Both complete the handshake before
+//! returning a [ToraStream] over the encrypted connection, so frames are written exactly as over
+//! plain TCP.
+//!
+//! ```
+//! use std::io;
+//! use std::net::TcpListener;
+//! use std::thread;
+//!
+//! use tora::tls::TlsBuilder;
+//!
+//! fn main() -> io::Result<()> {
+//!     let certified = rcgen::generate_simple_self_signed(["localhost".to_string()]).unwrap();
+//!     let cert = certified.cert.pem();
+//!     let key = certified.signing_key.serialize_pem();
+//!
+//!     let acceptor = TlsBuilder::new()
+//!         .certificate_pem(cert.as_bytes(), key.as_bytes())?
+//!         .alpn_protocols(["tora/1"])
+//!         .build_acceptor()?;
+//!     let connector = TlsBuilder::new()
+//!         .root_certificates_pem(cert.as_bytes())?
+//!         .alpn_protocols(["tora/1"])
+//!         .build_connector()?;
+//!
+//!     let listener = TcpListener::bind("127.0.0.1:0")?;
+//!     let address = listener.local_addr()?;
+//!
+//!     let server = thread::spawn(move || -> io::Result<()> {
+//!         let mut stream = acceptor.accept::<String, _>(listener.accept()?.0)?;
+//!         assert_eq!(stream.get_ref().conn.alpn_protocol(), Some(&b"tora/1"[..]));
+//!         assert_eq!(stream.recv()?, "Hello");
+//!         Ok(())
+//!     });
+//!
+//!     let mut stream = connector.connect::<String, _>(address, "localhost")?;
+//!     stream.send(&"Hello".to_string())?;
+//!     server.join().unwrap()
+//! }
+//! ```
+
+use std::io;
+use std::io::{ErrorKind, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::ops::DerefMut;
+use std::sync::Arc;
+
+use rustls::crypto::{ring, CryptoProvider};
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName};
+use rustls::server::WebPkiClientVerifier;
+use rustls::{
+    ClientConfig, ClientConnection, ConnectionCommon, RootCertStore, ServerConfig,
+    ServerConnection, SideData, StreamOwned,
+};
+
+use crate::config::ToraConfig;
+use crate::stream::ToraStream;
+
+/// A framed connection to a TLS server, returned by [TlsConnector].
+pub type TlsClientStream<T, S = TcpStream> = ToraStream<T, StreamOwned<ClientConnection, S>>;
+
+/// A framed connection to a TLS client, returned by [TlsAcceptor].
+pub type TlsServerStream<T, S = TcpStream> = ToraStream<T, StreamOwned<ServerConnection, S>>;
+
+/// Converts a rustls error, which is raised by invalid certificates, keys or handshakes.
+fn invalid_data<E>(e: E) -> io::Error
+where
+    E: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    io::Error::new(ErrorKind::InvalidData, e)
+}
+
+/// Completes the handshake of a connection, so its errors are returned before any frame is sent.
+fn handshake<C, D, S>(mut conn: C, mut stream: S) -> io::Result<StreamOwned<C, S>>
+where
+    C: DerefMut<Target = ConnectionCommon<D>>,
+    D: SideData,
+    S: Read + Write,
+{
+    while conn.is_handshaking() {
+        conn.complete_io(&mut stream)?;
+    }
+    Ok(StreamOwned::new(conn, stream))
+}
+
+/// Configures the certificates and protocols of a TLS endpoint.
+///
+/// Clients verify servers against the root certificates, and present the certificate, if any, to
+/// servers requiring client authentication. Servers present the certificate, and require clients
+/// to present one signed by the root certificates, if any were added.
+///
+/// Connections use the `ring` cryptography provider and the protocol versions rustls deems safe.
+#[derive(Debug)]
+pub struct TlsBuilder {
+    roots: RootCertStore,
+    certificate: Option<(Vec<CertificateDer<'static>>, PrivateKeyDer<'static>)>,
+    alpn_protocols: Vec<Vec<u8>>,
+    config: ToraConfig,
+}
+
+impl TlsBuilder {
+    /// Constructs a builder without certificates or protocols, using the default configuration.
+    pub fn new() -> Self {
+        Self {
+            roots: RootCertStore::empty(),
+            certificate: None,
+            alpn_protocols: Vec::new(),
+            config: ToraConfig::DEFAULT,
+        }
+    }
+
+    /// Trusts the Mozilla root certificates bundled by `webpki-roots`, as browsers do.
+    pub fn webpki_roots(mut self) -> Self {
+        self.roots
+            .extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+        self
+    }
+
+    /// Trusts the DER-encoded root certificate.
+    ///
+    /// Returns [ErrorKind::InvalidData] if the certificate cannot be parsed.
+    pub fn root_certificate(mut self, cert: CertificateDer<'_>) -> io::Result<Self> {
+        self.roots.add(cert).map_err(invalid_data)?;
+        Ok(self)
+    }
+
+    /// Trusts every root certificate in the PEM file contents.
+    ///
+    /// Returns [ErrorKind::InvalidData] if the contents hold no certificate, or one cannot be
+    /// parsed.
+    pub fn root_certificates_pem(mut self, pem: &[u8]) -> io::Result<Self> {
+        let mut found = false;
+
+        for cert in CertificateDer::pem_slice_iter(pem) {
+            self = self.root_certificate(cert.map_err(invalid_data)?)?;
+            found = true;
+        }
+        match found {
+            true => Ok(self),
+            false => Err(invalid_data("No certificate found in PEM")),
+        }
+    }
+
+    /// Presents the DER-encoded certificate chain, starting with the endpoint's own certificate,
+    /// along with its private key.
+    pub fn certificate(
+        mut self,
+        chain: Vec<CertificateDer<'static>>,
+        key: PrivateKeyDer<'static>,
+    ) -> Self {
+        self.certificate = Some((chain, key));
+        self
+    }
+
+    /// Presents the certificate chain and private key in the PEM file contents.
+    ///
+    /// Returns [ErrorKind::InvalidData] if either cannot be parsed.
+    pub fn certificate_pem(self, chain: &[u8], key: &[u8]) -> io::Result<Self> {
+        let chain = CertificateDer::pem_slice_iter(chain)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(invalid_data)?;
+        let key = PrivateKeyDer::from_pem_slice(key).map_err(invalid_data)?;
+
+        Ok(self.certificate(chain, key))
+    }
+
+    /// Offers the application protocols, in order of preference, through ALPN.
+    ///
+    /// Servers reject clients offering none of their protocols. The protocol agreed upon is
+    /// returned by the `alpn_protocol` method of the connection.
+    pub fn alpn_protocols<I, P>(mut self, protocols: I) -> Self
+    where
+        I: IntoIterator<Item = P>,
+        P: Into<Vec<u8>>,
+    {
+        self.alpn_protocols = protocols.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Uses the given configuration for the frames sent over connections.
+    pub fn config(mut self, config: ToraConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    fn provider() -> Arc<CryptoProvider> {
+        Arc::new(ring::default_provider())
+    }
+
+    /// Builds a connector for clients.
+    ///
+    /// Returns [ErrorKind::InvalidInput] if no root certificate was added, and
+    /// [ErrorKind::InvalidData] if the certificate is invalid.
+    pub fn build_connector(self) -> io::Result<TlsConnector> {
+        if self.roots.is_empty() {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                "No root certificate was added",
+            ));
+        }
+
+        let builder = ClientConfig::builder_with_provider(Self::provider())
+            .with_safe_default_protocol_versions()
+            .map_err(invalid_data)?
+            .with_root_certificates(self.roots);
+
+        let mut tls = match self.certificate {
+            Some((chain, key)) => builder
+                .with_client_auth_cert(chain, key)
+                .map_err(invalid_data)?,
+            None => builder.with_no_client_auth(),
+        };
+        tls.alpn_protocols = self.alpn_protocols;
+
+        Ok(TlsConnector {
+            tls: Arc::new(tls),
+            config: self.config,
+        })
+    }
+
+    /// Builds an acceptor for servers.
+    ///
+    /// Returns [ErrorKind::InvalidInput] if no certificate was set, and [ErrorKind::InvalidData]
+    /// if it is invalid.
+    pub fn build_acceptor(self) -> io::Result<TlsAcceptor> {
+        let Some((chain, key)) = self.certificate else {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                "No certificate was set",
+            ));
+        };
+
+        let builder = ServerConfig::builder_with_provider(Self::provider())
+            .with_safe_default_protocol_versions()
+            .map_err(invalid_data)?;
+
+        let builder = match self.roots.is_empty() {
+            true => builder.with_no_client_auth(),
+            false => {
+                let verifier = WebPkiClientVerifier::builder_with_provider(
+                    Arc::new(self.roots),
+                    Self::provider(),
+                )
+                .build()
+                .map_err(invalid_data)?;
+                builder.with_client_cert_verifier(verifier)
+            }
+        };
+
+        let mut tls = builder.with_single_cert(chain, key).map_err(invalid_data)?;
+        tls.alpn_protocols = self.alpn_protocols;
+
+        Ok(TlsAcceptor {
+            tls: Arc::new(tls),
+            config: self.config,
+        })
+    }
+}
+
+impl Default for TlsBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Opens framed TLS connections to servers. Built by [TlsBuilder::build_connector].
+///
+/// Cloning a connector shares its configuration, so it is cheap.
+#[derive(Clone, Debug)]
+pub struct TlsConnector {
+    tls: Arc<ClientConfig>,
+    config: ToraConfig,
+}
+
+impl TlsConnector {
+    /// Opens a TCP connection to the given address, then performs the handshake with the server,
+    /// verifying it holds a certificate for `server_name`.
+    pub fn connect<T, A>(&self, address: A, server_name: &str) -> io::Result<TlsClientStream<T>>
+    where
+        A: ToSocketAddrs,
+    {
+        self.client(TcpStream::connect(address)?, server_name)
+    }
+
+    /// Performs the handshake with the server over an established connection.
+    ///
+    /// Returns [ErrorKind::InvalidInput] if the server name is neither a DNS name nor an IP
+    /// address.
+    pub fn client<T, S>(&self, stream: S, server_name: &str) -> io::Result<TlsClientStream<T, S>>
+    where
+        S: Read + Write,
+    {
+        let name = ServerName::try_from(server_name.to_string())
+            .map_err(|e| io::Error::new(ErrorKind::InvalidInput, e))?;
+        let conn = ClientConnection::new(self.tls.clone(), name).map_err(invalid_data)?;
+
+        Ok(ToraStream::with_config(
+            handshake(conn, stream)?,
+            self.config.clone(),
+        ))
+    }
+
+    /// Returns the rustls configuration of this connector.
+    pub fn tls_config(&self) -> &Arc<ClientConfig> {
+        &self.tls
+    }
+}
+
+/// Accepts framed TLS connections from clients. Built by [TlsBuilder::build_acceptor].
+///
+/// Cloning an acceptor shares its configuration, so it is cheap.
+#[derive(Clone, Debug)]
+pub struct TlsAcceptor {
+    tls: Arc<ServerConfig>,
+    config: ToraConfig,
+}
+
+impl TlsAcceptor {
+    /// Performs the handshake with the client over an accepted connection.
+    pub fn accept<T, S>(&self, stream: S) -> io::Result<TlsServerStream<T, S>>
+    where
+        S: Read + Write,
+    {
+        let conn = ServerConnection::new(self.tls.clone()).map_err(invalid_data)?;
+
+        Ok(ToraStream::with_config(
+            handshake(conn, stream)?,
+            self.config.clone(),
+        ))
+    }
+
+    /// Returns the rustls configuration of this acceptor.
+    pub fn tls_config(&self) -> &Arc<ServerConfig> {
+        &self.tls
+    }
+}
@@ -0,0 +1,692 @@
+//! File and socket operations backed by io_uring, on Linux.
+//!
+//! Each read or write of the standard helpers costs at least one system call. A [Ring] submits
+//! the operations of a whole batch at once, and waits for all of them with a single call: writing
+//! or reading many files costs about as many system calls as writing one. A [UringStream] queues
+//! frames in memory and sends them together, and receives as many frames as fit its buffer at
+//! once.
+//!
+//! With the `io_uring` feature, [write_to_file](crate::file::write_to_file) and
+//! [read_from_file](crate::file::read_from_file) go through a ring of the calling thread, and
+//! fall back to plain system calls where io_uring is unavailable. A [UringIo] lets a
+//! [ToraStream](crate::stream::ToraStream) run over io_uring.
+//!
+//! ```
+//! use std::io;
+//!
+//! use tora::uring::Ring;
+//! use tora::{ReadStruct, WriteStruct};
+//!
+//! #[derive(Debug, PartialEq, ReadStruct, WriteStruct)]
+//! struct Chunk {
+//!     x: i32,
+//!     blocks: Vec<u8>,
+//! }
+//!
+//! fn main() -> io::Result<()> {
+//!     let dir = std::env::temp_dir();
+//!     let paths = [dir.join("tora_uring_0.bin"), dir.join("tora_uring_1.bin")];
+//!     let chunks = [Chunk { x: 0, blocks: vec![1, 2] }, Chunk { x: 1, blocks: vec![3] }];
+//!
+//!     let mut ring = Ring::new(32)?;
+//!     ring.write_files(paths.iter().zip(&chunks))?;
+//!
+//!     let read: Vec<Chunk> = ring.read_files(&paths)?;
+//!     assert_eq!(read, chunks);
+//!     Ok(())
+//! }
+//! ```
+
+use std::cell::RefCell;
+use std::fs::File;
+use std::io;
+use std::io::{ErrorKind, Read, Write};
+use std::marker::PhantomData;
+use std::net::TcpStream;
+use std::os::fd::{AsRawFd, RawFd};
+use std::path::Path;
+
+use io_uring::{opcode, squeue, types, EnterFlags, IoUring};
+
+use crate::config::ToraConfig;
+use crate::read::FromReader;
+use crate::stream::{decode_frame, write_frame};
+use crate::write::SerializeIo;
+
+/// The initial size of the receive buffer of a [UringStream].
+const RECV_BUFFER: usize = 64 * 1024;
+
+/// An io_uring instance, submitting operations in batches.
+pub struct Ring {
+    ring: IoUring,
+    /// Whether a failed submission left entries in the queue.
+    abandoned: bool,
+}
+
+impl Ring {
+    /// Constructs a Ring whose submission queue holds the given amount of operations.
+    ///
+    /// Larger batches are submitted in several rounds. Returns an error if the kernel does not
+    /// support io_uring, or forbids it.
+    pub fn new(entries: u32) -> io::Result<Self> {
+        IoUring::new(entries).map(|ring| Self {
+            ring,
+            abandoned: false,
+        })
+    }
+
+    /// Submits the entries and waits for all of them to complete, returning their results in the
+    /// same order. The user data of the entries is overwritten.
+    ///
+    /// If waiting fails, the operations already submitted are still waited for before returning
+    /// the error. Entries the kernel never took are left in the queue, and the ring refuses any
+    /// further submission so that they are never submitted.
+    ///
+    /// # Safety
+    ///
+    /// The buffers the entries point to must be valid until this function returns.
+    unsafe fn submit(&mut self, entries: &[squeue::Entry]) -> io::Result<Vec<i32>> {
+        if self.abandoned {
+            return Err(io::Error::other(
+                "The ring was abandoned after a failed submission",
+            ));
+        }
+        let mut results = vec![0; entries.len()];
+        let capacity = self.ring.params().sq_entries() as usize;
+
+        for (round, chunk) in entries.chunks(capacity).enumerate() {
+            for (i, entry) in chunk.iter().enumerate() {
+                let entry = entry.clone().user_data((round * capacity + i) as u64);
+                // The queue was emptied by the previous round, and the chunk fits its capacity.
+                if unsafe { self.ring.submission().push(&entry) }.is_err() {
+                    self.abandoned = true;
+                    return Err(io::Error::other("The submission queue is full"));
+                }
+            }
+
+            let mut completed = 0;
+            while completed < chunk.len() {
+                match self.ring.submit_and_wait(chunk.len() - completed) {
+                    Err(e) if e.kind() == ErrorKind::Interrupted => {}
+                    Err(e) => {
+                        let queued = self.ring.submission().len();
+                        self.drain(chunk.len() - queued - completed);
+                        self.abandoned = queued > 0;
+                        return Err(e);
+                    }
+                    Ok(_) => {}
+                }
+                for cqe in self.ring.completion() {
+                    results[cqe.user_data() as usize] = cqe.result();
+                    completed += 1;
+                }
+            }
+        }
+        Ok(results)
+    }
+
+    /// Waits for the given amount of submitted operations to complete, discarding their results,
+    /// without submitting the queued entries.
+    fn drain(&mut self, mut in_flight: usize) {
+        while in_flight > 0 {
+            let flags = EnterFlags::GETEVENTS.bits();
+
+            // Only waits for completions, as nothing is submitted.
+            match unsafe {
+                self.ring
+                    .submitter()
+                    .enter::<()>(0, in_flight as u32, flags, None)
+            } {
+                Ok(_) => {}
+                Err(e)
+                    if matches!(
+                        e.kind(),
+                        ErrorKind::Interrupted | ErrorKind::WouldBlock | ErrorKind::ResourceBusy
+                    ) => {}
+                // The kernel may still write to the buffers, which neither returning nor
+                // unwinding would keep alive.
+                Err(_) => std::process::abort(),
+            }
+            in_flight = in_flight.saturating_sub(self.ring.completion().count());
+        }
+    }
+
+    /// Reads or writes every buffer entirely, resubmitting the operations that completed
+    /// partially.
+    ///
+    /// # Safety
+    ///
+    /// The buffers must be valid for the given lengths, and only written buffers may be shared.
+    unsafe fn transfer(&mut self, transfers: &mut [Transfer]) -> io::Result<()> {
+        loop {
+            let pending = (0..transfers.len())
+                .filter(|&i| transfers[i].done < transfers[i].len)
+                .collect::<Vec<_>>();
+
+            if pending.is_empty() {
+                return Ok(());
+            }
+
+            let entries = pending
+                .iter()
+                .map(|&i| transfers[i].entry())
+                .collect::<Vec<_>>();
+            let results = unsafe { self.submit(&entries) }?;
+
+            for (&i, result) in pending.iter().zip(results) {
+                transfers[i].complete(result)?;
+            }
+        }
+    }
+
+    /// Serializes each value and writes it to the file at its path, creating or truncating it.
+    ///
+    /// The writes of all files are submitted together.
+    pub fn write_files<'a, I, P, C>(&mut self, files: I) -> io::Result<()>
+    where
+        I: IntoIterator<Item = (P, &'a C)>,
+        P: AsRef<Path>,
+        C: SerializeIo + 'a,
+    {
+        let mut opened = Vec::new();
+        let mut buffers = Vec::new();
+
+        for (path, content) in files {
+            let mut buf = Vec::new();
+            content.serialize(&mut buf)?;
+
+            opened.push(File::create(path)?);
+            buffers.push(buf);
+        }
+
+        self.write_buffers(opened.iter().zip(buffers.iter().map(Vec::as_slice)))
+    }
+
+    /// Reads the files at the given paths entirely, then deserializes a [T] from each.
+    ///
+    /// The reads of all files are submitted together.
+    pub fn read_files<T, P>(&mut self, paths: &[P]) -> io::Result<Vec<T>>
+    where
+        T: FromReader,
+        P: AsRef<Path>,
+    {
+        let opened = paths
+            .iter()
+            .map(File::open)
+            .collect::<io::Result<Vec<_>>>()?;
+
+        self.read_buffers(&opened)?
+            .iter()
+            .map(|buf| T::from_reader(&mut buf.as_slice()))
+            .collect()
+    }
+
+    /// Writes each buffer to the start of its file.
+    fn write_buffers<'a, I>(&mut self, writes: I) -> io::Result<()>
+    where
+        I: IntoIterator<Item = (&'a File, &'a [u8])>,
+    {
+        let mut transfers = writes
+            .into_iter()
+            .map(|(file, buf)| Transfer {
+                fd: file.as_raw_fd(),
+                ptr: buf.as_ptr().cast_mut(),
+                len: buf.len(),
+                done: 0,
+                offset: Some(0),
+                write: true,
+            })
+            .collect::<Vec<_>>();
+
+        // The buffers are borrowed for longer than the transfer, and are only read from.
+        unsafe { self.transfer(&mut transfers) }
+    }
+
+    /// Reads every file entirely, from its start.
+    fn read_buffers(&mut self, files: &[File]) -> io::Result<Vec<Vec<u8>>> {
+        let mut buffers = Vec::with_capacity(files.len());
+
+        for file in files {
+            let len = usize::try_from(file.metadata()?.len())
+                .map_err(|_| io::Error::new(ErrorKind::InvalidData, "File exceeds usize::MAX"))?;
+            buffers.push(vec![0u8; len]);
+        }
+
+        let mut transfers = files
+            .iter()
+            .zip(&mut buffers)
+            .map(|(file, buf)| Transfer {
+                fd: file.as_raw_fd(),
+                ptr: buf.as_mut_ptr(),
+                len: buf.len(),
+                done: 0,
+                offset: Some(0),
+                write: false,
+            })
+            .collect::<Vec<_>>();
+
+        // Each buffer is owned by a single transfer, and outlives it.
+        unsafe { self.transfer(&mut transfers) }?;
+        Ok(buffers)
+    }
+}
+
+thread_local! {
+    /// The ring of the current thread used by the file helpers, or None if io_uring is
+    /// unavailable.
+    static THREAD_RING: Option<RefCell<Ring>> = Ring::new(8).ok().map(RefCell::new);
+}
+
+/// Writes the buffer to the start of the file through the ring of the current thread, falling
+/// back to a plain write if io_uring is unavailable.
+pub(crate) fn write_file(mut file: &File, buf: &[u8]) -> io::Result<()> {
+    THREAD_RING.with(
+        |ring| match ring.as_ref().and_then(|ring| ring.try_borrow_mut().ok()) {
+            Some(mut ring) => ring.write_buffers([(file, buf)]),
+            None => file.write_all(buf),
+        },
+    )
+}
+
+/// Reads the file entirely through the ring of the current thread, falling back to a plain read
+/// if io_uring is unavailable.
+pub(crate) fn read_file(mut file: &File) -> io::Result<Vec<u8>> {
+    THREAD_RING.with(
+        |ring| match ring.as_ref().and_then(|ring| ring.try_borrow_mut().ok()) {
+            Some(mut ring) => ring
+                .read_buffers(std::slice::from_ref(file))
+                .map(|mut buffers| buffers.remove(0)),
+            None => {
+                let mut buf = Vec::new();
+                file.read_to_end(&mut buf)?;
+                Ok(buf)
+            }
+        },
+    )
+}
+
+/// A read or write of a whole buffer, which may take several operations.
+struct Transfer {
+    fd: RawFd,
+    ptr: *mut u8,
+    len: usize,
+    /// The amount of bytes transferred so far.
+    done: usize,
+    /// The file offset of the buffer, or None to use the position of the stream.
+    offset: Option<u64>,
+    write: bool,
+}
+
+impl Transfer {
+    /// Returns the operation transferring the remaining bytes.
+    fn entry(&self) -> squeue::Entry {
+        let fd = types::Fd(self.fd);
+        let ptr = self.ptr.wrapping_add(self.done);
+        let len = u32::try_from(self.len - self.done).unwrap_or(u32::MAX);
+        let offset = match self.offset {
+            Some(offset) => offset + self.done as u64,
+            None => u64::MAX,
+        };
+
+        match self.write {
+            true => opcode::Write::new(fd, ptr, len).offset(offset).build(),
+            false => opcode::Read::new(fd, ptr, len).offset(offset).build(),
+        }
+    }
+
+    /// Records the result of an operation.
+    ///
+    /// Returns [ErrorKind::UnexpectedEof] if a read reached the end of the file, and
+    /// [ErrorKind::WriteZero] if a write made no progress.
+    fn complete(&mut self, result: i32) -> io::Result<()> {
+        match result {
+            0 if self.write => Err(ErrorKind::WriteZero.into()),
+            0 => Err(ErrorKind::UnexpectedEof.into()),
+            n => {
+                self.done += completed(n)?;
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Returns the amount of bytes an operation transferred, or its error.
+///
+/// Interrupted operations transferred no bytes, and are retried.
+fn completed(result: i32) -> io::Result<usize> {
+    match result {
+        n if n < 0 => match io::Error::from_raw_os_error(-n) {
+            e if e.kind() == ErrorKind::Interrupted => Ok(0),
+            e => Err(e),
+        },
+        n => Ok(n as usize),
+    }
+}
+
+/// A connection sending and receiving framed values of type [T] through io_uring.
+///
+/// Frames are written in the same format as a [ToraStream](crate::stream::ToraStream), so either
+/// end of a connection can use either type. Frames given to [queue](Self::queue) are held until
+/// [flush](Self::flush), which writes all of them at once. Received bytes are buffered, so a
+/// single read yields every frame that has arrived.
+///
+/// ```
+/// use std::io;
+/// use std::os::unix::net::UnixStream;
+///
+/// use tora::uring::UringStream;
+///
+/// fn main() -> io::Result<()> {
+///     let (a, b) = UnixStream::pair()?;
+///     let mut sender = UringStream::<u32, _>::new(a)?;
+///     let mut receiver = UringStream::<u32, _>::new(b)?;
+///
+///     sender.send_all(&[1, 2, 3])?;
+///
+///     let mut received = Vec::new();
+///     while received.len() < 3 {
+///         received.extend(receiver.recv_available()?);
+///     }
+///     assert_eq!(received, [1, 2, 3]);
+///     Ok(())
+/// }
+/// ```
+pub struct UringStream<T, S = TcpStream> {
+    stream: S,
+    ring: Ring,
+    config: ToraConfig,
+    /// The received bytes, of which `read_buf[start..end]` are not decoded yet.
+    read_buf: Vec<u8>,
+    start: usize,
+    end: usize,
+    /// The frames queued for sending.
+    write_buf: Vec<u8>,
+    frame_buf: Vec<u8>,
+    _marker: PhantomData<fn(&T) -> T>,
+}
+
+impl<T, S> UringStream<T, S>
+where
+    S: AsRawFd,
+{
+    /// Constructs a UringStream using the default configuration.
+    pub fn new(stream: S) -> io::Result<Self> {
+        Self::with_config(stream, ToraConfig::DEFAULT)
+    }
+
+    /// Constructs a UringStream using the given configuration.
+    pub fn with_config(stream: S, config: ToraConfig) -> io::Result<Self> {
+        Ok(Self {
+            stream,
+            ring: Ring::new(8)?,
+            config,
+            read_buf: vec![0; RECV_BUFFER],
+            start: 0,
+            end: 0,
+            write_buf: Vec::new(),
+            frame_buf: Vec::new(),
+            _marker: PhantomData,
+        })
+    }
+
+    /// Serializes the value and queues it as a frame, without sending it.
+    pub fn queue(&mut self, value: &T) -> io::Result<()>
+    where
+        T: SerializeIo,
+    {
+        write_frame(
+            &mut self.write_buf,
+            value,
+            &mut self.frame_buf,
+            &self.config,
+            None,
+        )
+    }
+
+    /// Sends every queued frame.
+    pub fn flush(&mut self) -> io::Result<()> {
+        let mut transfer = Transfer {
+            fd: self.stream.as_raw_fd(),
+            ptr: self.write_buf.as_mut_ptr(),
+            len: self.write_buf.len(),
+            done: 0,
+            offset: None,
+            write: true,
+        };
+
+        // The write buffer is not touched until the transfer completes.
+        let result = unsafe { self.ring.transfer(std::slice::from_mut(&mut transfer)) };
+        self.write_buf.drain(..transfer.done);
+        result
+    }
+
+    /// Serializes the value and sends it as a frame, along with every queued frame.
+    pub fn send(&mut self, value: &T) -> io::Result<()>
+    where
+        T: SerializeIo,
+    {
+        self.queue(value)?;
+        self.flush()
+    }
+
+    /// Serializes the values and sends them as frames, with a single write.
+    pub fn send_all<'a, I>(&mut self, values: I) -> io::Result<()>
+    where
+        I: IntoIterator<Item = &'a T>,
+        T: SerializeIo + 'a,
+    {
+        for value in values {
+            self.queue(value)?;
+        }
+        self.flush()
+    }
+
+    /// Waits for the next frame and deserializes it.
+    ///
+    /// Returns [ErrorKind::InvalidData] if the value does not occupy the whole frame, and
+    /// [ErrorKind::UnexpectedEof] if the connection closes.
+    pub fn recv(&mut self) -> io::Result<T>
+    where
+        T: FromReader,
+    {
+        loop {
+            if let Some(value) = self.next_frame()? {
+                return Ok(value);
+            }
+            self.fill()?;
+        }
+    }
+
+    /// Waits for at least one frame, then deserializes every frame received so far.
+    pub fn recv_available(&mut self) -> io::Result<Vec<T>>
+    where
+        T: FromReader,
+    {
+        let mut values = vec![self.recv()?];
+
+        while let Some(value) = self.next_frame()? {
+            values.push(value);
+        }
+        Ok(values)
+    }
+
+    /// Decodes the next frame, if all of its bytes were received.
+    fn next_frame(&mut self) -> io::Result<Option<T>>
+    where
+        T: FromReader,
+    {
+        let (prefix, len) = match self.next_len()? {
+            Some((prefix, len)) if self.end - self.start - prefix >= len => (prefix, len),
+            _ => return Ok(None),
+        };
+
+        let payload = self.start + prefix;
+        let value = decode_frame(&self.read_buf[payload..payload + len], &self.config);
+
+        self.start = payload + len;
+        value.map(Some)
+    }
+
+    /// Returns the widths of the prefix and payload of the next frame, if its prefix was
+    /// received.
+    fn next_len(&self) -> io::Result<Option<(usize, usize)>> {
+        let mut available = &self.read_buf[self.start..self.end];
+
+        match self.config.read_length(&mut available) {
+            Ok(len) => Ok(Some((self.end - self.start - available.len(), len))),
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Receives more bytes, growing the buffer if it is full.
+    ///
+    /// The buffer only fills up with the start of a frame larger than it, and grows no larger
+    /// than that frame, whose length the configuration bounds.
+    fn fill(&mut self) -> io::Result<()> {
+        self.read_buf.copy_within(self.start..self.end, 0);
+        self.end -= self.start;
+        self.start = 0;
+
+        if self.end == self.read_buf.len() {
+            let frame = match self.next_len()? {
+                Some((prefix, len)) => prefix.saturating_add(len),
+                None => usize::MAX,
+            };
+            let len = frame.min(self.read_buf.len().saturating_mul(2));
+            self.read_buf.resize(len, 0);
+        }
+
+        let entry = Transfer {
+            fd: self.stream.as_raw_fd(),
+            ptr: self.read_buf[self.end..].as_mut_ptr(),
+            len: self.read_buf.len() - self.end,
+            done: 0,
+            offset: None,
+            write: false,
+        }
+        .entry();
+
+        // The read buffer is not touched until the read completes.
+        let result = unsafe { self.ring.submit(&[entry]) }?[0];
+
+        match result {
+            0 => Err(io::Error::new(
+                ErrorKind::UnexpectedEof,
+                "The connection was closed",
+            )),
+            n => {
+                self.end += completed(n)?;
+                Ok(())
+            }
+        }
+    }
+
+    /// Returns the configuration of this stream.
+    pub fn config(&self) -> &ToraConfig {
+        &self.config
+    }
+
+    /// Returns a reference to the underlying stream.
+    pub fn get_ref(&self) -> &S {
+        &self.stream
+    }
+
+    /// Returns the underlying stream, discarding buffered bytes and queued frames.
+    pub fn into_inner(self) -> S {
+        self.stream
+    }
+}
+
+/// A reader and writer over a file or socket, performing every operation through io_uring.
+///
+/// Lets the framed connections of [stream](crate::stream) run over io_uring. A [UringStream]
+/// additionally batches frames, sending or receiving many with a single operation.
+///
+/// ```
+/// use std::io;
+/// use std::os::unix::net::UnixStream;
+///
+/// use tora::stream::ToraStream;
+/// use tora::uring::UringIo;
+///
+/// fn main() -> io::Result<()> {
+///     let (a, b) = UnixStream::pair()?;
+///     let mut sender = ToraStream::<String, _>::new(UringIo::new(a)?);
+///     let mut receiver = ToraStream::<String, _>::new(UringIo::new(b)?);
+///
+///     sender.send(&"Hello".to_string())?;
+///     assert_eq!(receiver.recv()?, "Hello");
+///     Ok(())
+/// }
+/// ```
+pub struct UringIo<S> {
+    stream: S,
+    ring: Ring,
+}
+
+impl<S> UringIo<S>
+where
+    S: AsRawFd,
+{
+    /// Constructs a UringIo around the stream.
+    pub fn new(stream: S) -> io::Result<Self> {
+        Ring::new(2).map(|ring| Self { stream, ring })
+    }
+
+    /// Returns a reference to the underlying stream.
+    pub fn get_ref(&self) -> &S {
+        &self.stream
+    }
+
+    /// Returns the underlying stream.
+    pub fn into_inner(self) -> S {
+        self.stream
+    }
+
+    /// Performs a single read or write of the buffer at the position of the stream.
+    ///
+    /// # Safety
+    ///
+    /// The buffer must be valid for the given length, and only written if `write` is false.
+    unsafe fn transfer_once(&mut self, ptr: *mut u8, len: usize, write: bool) -> io::Result<usize> {
+        let entry = Transfer {
+            fd: self.stream.as_raw_fd(),
+            ptr,
+            len,
+            done: 0,
+            offset: None,
+            write,
+        }
+        .entry();
+
+        match unsafe { self.ring.submit(&[entry]) }?[0] {
+            n if n < 0 => Err(io::Error::from_raw_os_error(-n)),
+            n => Ok(n as usize),
+        }
+    }
+}
+
+impl<S> Read for UringIo<S>
+where
+    S: AsRawFd,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        // The buffer is borrowed mutably for the whole read.
+        unsafe { self.transfer_once(buf.as_mut_ptr(), buf.len(), false) }
+    }
+}
+
+impl<S> Write for UringIo<S>
+where
+    S: AsRawFd,
+{
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // The buffer is borrowed for the whole write, and only read from.
+        unsafe { self.transfer_once(buf.as_ptr().cast_mut(), buf.len(), true) }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,212 @@
+//! Versions of the wire format.
+//!
+//! The wire format of tora is identified by a [WireVersion], so improvements to the format can
+//! ship as a new version without breaking the files and captures written by previous ones.
+//! [WireVersion::V1] is the format written by [SerializeIo::serialize] and
+//! [ToraConfig::DEFAULT].
+//!
+//! Data stored for long can start with a header naming its version, by being wrapped in a
+//! [Versioned]. Readers then detect the version from the header, and decode the value in the
+//! format of that version. Headerless data is read by selecting the version it was written in
+//! with [WireVersion::configure].
+//!
+//! ```
+//! use std::io;
+//! use std::io::Cursor;
+//!
+//! use tora::read::ToraRead;
+//! use tora::version::{Versioned, WireVersion};
+//! use tora::write::ToraWrite;
+//!
+//! fn main() -> io::Result<()> {
+//!     let mut bytes = Vec::new();
+//!     bytes.writes(&Versioned::new((7u16, "Save".to_string())))?;
+//!
+//!     assert_eq!(&bytes[..5], b"TORA\x01");
+//!
+//!     let save: Versioned<(u16, String)> = Cursor::new(bytes).reads()?;
+//!     assert_eq!(save.version, WireVersion::V1);
+//!     assert_eq!(save.value, (7, "Save".to_string()));
+//!     Ok(())
+//! }
+//! ```
+//!
+//! # Header format
+//!
+//! The header is the 4 bytes `TORA` followed by the version number as a [u8].
+
+use std::fmt;
+use std::io;
+use std::io::{ErrorKind, Read, Write};
+
+use crate::config::{
+    Endian, FloatFormat, IntSequenceFormat, LengthPrefix, StringFormat, ToraConfig,
+};
+use crate::read::{FromReader, ToraRead};
+use crate::write::{SerializeIo, ToraWrite};
+
+const MAGIC: &[u8; 4] = b"TORA";
+
+/// A version of the wire format.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[non_exhaustive]
+pub enum WireVersion {
+    /// Little endian numbers, [u32] length prefixes, NUL-terminated strings and raw floats.
+    V1,
+}
+
+impl WireVersion {
+    /// The latest version, written by [Versioned::new].
+    pub const LATEST: Self = Self::V1;
+
+    /// Returns the number identifying this version in headers.
+    pub const fn number(self) -> u8 {
+        match self {
+            Self::V1 => 1,
+        }
+    }
+
+    /// Returns the version identified by the number, if it is known.
+    pub const fn from_number(number: u8) -> Option<Self> {
+        match number {
+            1 => Some(Self::V1),
+            _ => None,
+        }
+    }
+
+    /// Returns the configuration of the format of this version, with the limits, codecs and
+    /// cancellation flag of the given configuration.
+    ///
+    /// ```
+    /// use tora::config::{LengthPrefix, ToraConfig};
+    /// use tora::version::WireVersion;
+    ///
+    /// let config = ToraConfig::DEFAULT.length_prefix(LengthPrefix::U8).max_length(64);
+    /// let v1 = WireVersion::V1.configure(&config);
+    ///
+    /// assert_eq!(v1.length_prefix, LengthPrefix::U32);
+    /// assert_eq!(v1.max_length, Some(64));
+    /// ```
+    pub fn configure(self, config: &ToraConfig) -> ToraConfig {
+        match self {
+            Self::V1 => ToraConfig {
+                endian: Endian::Little,
+                length_prefix: LengthPrefix::U32,
+                string_format: StringFormat::NulTerminated,
+                float_format: FloatFormat::Raw,
+                int_sequence_format: IntSequenceFormat::Plain,
+                ..config.clone()
+            },
+        }
+    }
+
+    /// Writes the header naming this version.
+    pub fn write_header<W>(self, w: &mut W) -> io::Result<()>
+    where
+        W: Write,
+    {
+        w.write_all(MAGIC)?;
+        w.writes(&self.number())
+    }
+
+    /// Reads a header, returning the version it names.
+    ///
+    /// Returns [ErrorKind::InvalidData] if the reader does not start with a header, or the
+    /// version is unknown to this release.
+    pub fn read_header<R>(r: &mut R) -> io::Result<Self>
+    where
+        R: Read,
+    {
+        if r.reads::<[u8; 4]>()? != *MAGIC {
+            return Err(io::Error::new(
+                ErrorKind::InvalidData,
+                "Missing wire format header",
+            ));
+        }
+
+        let number = r.reads::<u8>()?;
+        Self::from_number(number).ok_or_else(|| {
+            io::Error::new(
+                ErrorKind::InvalidData,
+                format!("Unsupported wire format version {number}"),
+            )
+        })
+    }
+}
+
+impl fmt::Display for WireVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "v{}", self.number())
+    }
+}
+
+/// A value preceded by a header naming the version of the format it is written in.
+///
+/// The value is written and read in the format of its version, keeping the limits, codecs and
+/// cancellation flag of the configuration it is used with.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct Versioned<T> {
+    /// The version the value is written in, or was read from.
+    pub version: WireVersion,
+    /// The value.
+    pub value: T,
+}
+
+impl<T> Versioned<T> {
+    /// Wraps the value, to be written in the [latest](WireVersion::LATEST) version.
+    pub fn new(value: T) -> Self {
+        Self::with_version(value, WireVersion::LATEST)
+    }
+
+    /// Wraps the value, to be written in the given version.
+    pub fn with_version(value: T, version: WireVersion) -> Self {
+        Self { version, value }
+    }
+
+    /// Returns the value.
+    pub fn into_inner(self) -> T {
+        self.value
+    }
+}
+
+impl<T> FromReader for Versioned<T>
+where
+    T: FromReader,
+{
+    fn from_reader<R>(r: &mut R) -> io::Result<Self>
+    where
+        R: Read,
+    {
+        Self::from_reader_with(r, &ToraConfig::DEFAULT)
+    }
+
+    fn from_reader_with<R>(r: &mut R, config: &ToraConfig) -> io::Result<Self>
+    where
+        R: Read,
+    {
+        let version = WireVersion::read_header(r)?;
+        let value = T::from_reader_with(r, &version.configure(config))?;
+        Ok(Self { version, value })
+    }
+}
+
+impl<T> SerializeIo for Versioned<T>
+where
+    T: SerializeIo,
+{
+    fn serialize<W>(&self, w: &mut W) -> io::Result<()>
+    where
+        W: Write,
+    {
+        self.serialize_with(w, &ToraConfig::DEFAULT)
+    }
+
+    fn serialize_with<W>(&self, w: &mut W, config: &ToraConfig) -> io::Result<()>
+    where
+        W: Write,
+    {
+        self.version.write_header(w)?;
+        self.value
+            .serialize_with(w, &self.version.configure(config))
+    }
+}
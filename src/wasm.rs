@@ -0,0 +1,70 @@
+//! Conversions between values and JavaScript byte arrays, for browser clients.
+//!
+//! A WebSocket with its `binaryType` set to `"arraybuffer"` receives binary messages as
+//! [ArrayBuffer]s, which [from_array_buffer] decodes. Values are sent back by passing the
+//! [Uint8Array] returned by [to_uint8_array] to `WebSocket.send`.
+//!
+//! As with the streams of the `websocket` module, each message holds a single value, without a
+//! length prefix.
+//!
+//! ```ignore
+//! use js_sys::ArrayBuffer;
+//! use tora::config::ToraConfig;
+//! use tora::wasm;
+//! use wasm_bindgen::prelude::*;
+//!
+//! #[wasm_bindgen]
+//! pub fn on_message(data: ArrayBuffer) -> Result<u32, JsError> {
+//!     let score: u32 = wasm::from_array_buffer(&data, &ToraConfig::DEFAULT)?;
+//!     Ok(score)
+//! }
+//! ```
+
+use std::io;
+
+use js_sys::{ArrayBuffer, Uint8Array};
+
+use crate::config::ToraConfig;
+use crate::read::FromReader;
+use crate::stream::decode_frame;
+use crate::write::SerializeIo;
+
+/// Serializes the value into a new [Uint8Array].
+pub fn to_uint8_array<T>(value: &T, config: &ToraConfig) -> io::Result<Uint8Array>
+where
+    T: SerializeIo + ?Sized,
+{
+    let mut buf = Vec::new();
+    value.serialize_with(&mut buf, config)?;
+    Ok(Uint8Array::from(&buf[..]))
+}
+
+/// Serializes the value into a new [ArrayBuffer].
+pub fn to_array_buffer<T>(value: &T, config: &ToraConfig) -> io::Result<ArrayBuffer>
+where
+    T: SerializeIo + ?Sized,
+{
+    to_uint8_array(value, config).map(|array| array.buffer())
+}
+
+/// Deserializes a value from the bytes of the [Uint8Array].
+///
+/// Returns [ErrorKind::InvalidData](io::ErrorKind::InvalidData) if the value does not occupy
+/// every byte.
+pub fn from_uint8_array<T>(array: &Uint8Array, config: &ToraConfig) -> io::Result<T>
+where
+    T: FromReader,
+{
+    decode_frame(&array.to_vec(), config)
+}
+
+/// Deserializes a value from the bytes of the [ArrayBuffer].
+///
+/// Returns [ErrorKind::InvalidData](io::ErrorKind::InvalidData) if the value does not occupy
+/// every byte.
+pub fn from_array_buffer<T>(buffer: &ArrayBuffer, config: &ToraConfig) -> io::Result<T>
+where
+    T: FromReader,
+{
+    from_uint8_array(&Uint8Array::new(buffer), config)
+}
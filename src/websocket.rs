@@ -0,0 +1,642 @@
+//! Typed WebSocket connections, through `tungstenite`.
+//!
+//! Each value is sent as a single binary message holding its serialized bytes, without a length
+//! prefix, as WebSocket messages are already delimited. Values are serialized straight into the
+//! buffer of the outgoing message, and deserialized straight from the payload of the incoming
+//! one.
+//!
+//! A [WsStream] wraps a blocking `tungstenite` socket. With the `websocket_tokio` feature, an
+//! [AsyncWsStream] wraps a `tokio-tungstenite` socket instead, and can send through a bounded
+//! [SendQueue] applying backpressure to its senders.
+//!
+//! ```
+//! use std::io;
+//! use std::net::{TcpListener, TcpStream};
+//! use std::thread;
+//!
+//! use tora::websocket::WsStream;
+//!
+//! fn main() -> io::Result<()> {
+//!     let listener = TcpListener::bind("127.0.0.1:0")?;
+//!     let address = listener.local_addr()?;
+//!
+//!     let client = thread::spawn(move || -> io::Result<()> {
+//!         let stream = TcpStream::connect(address)?;
+//!         let (socket, _) = tungstenite::client(format!("ws://{address}"), stream)
+//!             .map_err(io::Error::other)?;
+//!
+//!         WsStream::<(u8, String), _>::new(socket).send(&(1, "Hello".to_string()))
+//!     });
+//!
+//!     let socket = tungstenite::accept(listener.accept()?.0).map_err(io::Error::other)?;
+//!     let mut stream = WsStream::<(u8, String), _>::new(socket);
+//!
+//!     assert_eq!(stream.recv()?, (1, "Hello".to_string()));
+//!     client.join().unwrap()
+//! }
+//! ```
+
+use std::io;
+use std::io::{ErrorKind, Read, Write};
+use std::marker::PhantomData;
+
+use tungstenite::{Error, Message, WebSocket};
+
+use crate::config::ToraConfig;
+use crate::read::FromReader;
+use crate::stream::decode_frame;
+use crate::write::SerializeIo;
+
+/// Serializes the value into a binary message.
+pub fn to_message<T>(value: &T, config: &ToraConfig) -> io::Result<Message>
+where
+    T: SerializeIo + ?Sized,
+{
+    let mut buf = Vec::new();
+    value.serialize_with(&mut buf, config)?;
+    Ok(Message::Binary(buf.into()))
+}
+
+/// Deserializes a value from a message.
+///
+/// Returns None for ping, pong and raw frames, [ErrorKind::UnexpectedEof] for a close message,
+/// and [ErrorKind::InvalidData] for a text message or if the value does not occupy the whole
+/// message.
+pub fn from_message<T>(message: &Message, config: &ToraConfig) -> io::Result<Option<T>>
+where
+    T: FromReader,
+{
+    match message {
+        Message::Binary(payload) => decode_frame(payload, config).map(Some),
+        Message::Text(_) => Err(io::Error::new(
+            ErrorKind::InvalidData,
+            "Expected a binary message, received text",
+        )),
+        Message::Close(_) => Err(io::Error::new(
+            ErrorKind::UnexpectedEof,
+            "The WebSocket was closed",
+        )),
+        Message::Ping(_) | Message::Pong(_) | Message::Frame(_) => Ok(None),
+    }
+}
+
+/// Converts a WebSocket error, keeping IO errors as they are.
+fn to_io_error(e: Error) -> io::Error {
+    match e {
+        Error::Io(e) => e,
+        Error::ConnectionClosed | Error::AlreadyClosed => {
+            io::Error::new(ErrorKind::UnexpectedEof, "The WebSocket was closed")
+        }
+        e => io::Error::other(e),
+    }
+}
+
+/// A blocking WebSocket sending and receiving values of type [T] as binary messages.
+#[derive(Debug)]
+pub struct WsStream<T, S> {
+    socket: WebSocket<S>,
+    config: ToraConfig,
+    _marker: PhantomData<fn(&T) -> T>,
+}
+
+impl<T, S> WsStream<T, S>
+where
+    S: Read + Write,
+{
+    /// Constructs a WsStream over a socket whose handshake completed, using the default
+    /// configuration.
+    pub fn new(socket: WebSocket<S>) -> Self {
+        Self::with_config(socket, ToraConfig::DEFAULT)
+    }
+
+    /// Constructs a WsStream over a socket whose handshake completed, using the given
+    /// configuration.
+    pub fn with_config(socket: WebSocket<S>, config: ToraConfig) -> Self {
+        Self {
+            socket,
+            config,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Serializes the value and sends it as a binary message.
+    pub fn send(&mut self, value: &T) -> io::Result<()>
+    where
+        T: SerializeIo,
+    {
+        let message = to_message(value, &self.config)?;
+        self.socket.send(message).map_err(to_io_error)
+    }
+
+    /// Waits for the next binary message and deserializes it. Control messages are answered by
+    /// the socket and skipped.
+    ///
+    /// Returns [ErrorKind::UnexpectedEof] once the WebSocket is closed.
+    pub fn recv(&mut self) -> io::Result<T>
+    where
+        T: FromReader,
+    {
+        loop {
+            let message = self.socket.read().map_err(to_io_error)?;
+
+            if let Some(value) = from_message(&message, &self.config)? {
+                return Ok(value);
+            }
+        }
+    }
+
+    /// Starts the closing handshake, then flushes the socket.
+    pub fn close(&mut self) -> io::Result<()> {
+        self.socket.close(None).map_err(to_io_error)?;
+        self.socket.flush().map_err(to_io_error)
+    }
+
+    /// Returns the configuration of this stream.
+    pub fn config(&self) -> &ToraConfig {
+        &self.config
+    }
+
+    /// Returns a reference to the underlying socket.
+    pub fn get_ref(&self) -> &WebSocket<S> {
+        &self.socket
+    }
+
+    /// Returns the underlying socket.
+    pub fn into_inner(self) -> WebSocket<S> {
+        self.socket
+    }
+}
+
+#[cfg(feature = "websocket_tokio")]
+pub use self::async_stream::{AsyncWsReceiver, AsyncWsStream, Overflow, QueueWriter, SendQueue};
+
+#[cfg(feature = "websocket_tokio")]
+mod async_stream {
+    use std::collections::VecDeque;
+    use std::io;
+    use std::io::ErrorKind;
+    use std::marker::PhantomData;
+    use std::pin::pin;
+    use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+    use futures_util::stream::{SplitSink, SplitStream};
+    use futures_util::{SinkExt, Stream, StreamExt};
+    use tokio::io::{AsyncRead, AsyncWrite};
+    use tokio::sync::Notify;
+    use tokio_tungstenite::WebSocketStream;
+    use tungstenite::{Error, Message};
+
+    use super::{from_message, to_io_error, to_message};
+    use crate::config::ToraConfig;
+    use crate::read::FromReader;
+    use crate::write::SerializeIo;
+
+    /// An asynchronous WebSocket sending and receiving values of type [T] as binary messages.
+    ///
+    /// ```
+    /// use std::io;
+    ///
+    /// use futures_util::future::try_join;
+    /// use tokio::io::duplex;
+    /// use tora::testing::block_on;
+    /// use tora::websocket::AsyncWsStream;
+    ///
+    /// fn main() -> io::Result<()> {
+    ///     let (client, server) = duplex(4096);
+    ///
+    ///     let client = async {
+    ///         let (socket, _) = tokio_tungstenite::client_async("ws://localhost", client)
+    ///             .await
+    ///             .map_err(io::Error::other)?;
+    ///
+    ///         AsyncWsStream::<u32, _>::new(socket).send(&42).await
+    ///     };
+    ///     let server = async {
+    ///         let socket = tokio_tungstenite::accept_async(server)
+    ///             .await
+    ///             .map_err(io::Error::other)?;
+    ///
+    ///         AsyncWsStream::<u32, _>::new(socket).recv().await
+    ///     };
+    ///
+    ///     let ((), received) = block_on(try_join(client, server))?;
+    ///     assert_eq!(received, 42);
+    ///     Ok(())
+    /// }
+    /// ```
+    #[derive(Debug)]
+    pub struct AsyncWsStream<T, S> {
+        socket: WebSocketStream<S>,
+        config: ToraConfig,
+        _marker: PhantomData<fn(&T) -> T>,
+    }
+
+    impl<T, S> AsyncWsStream<T, S>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        /// Constructs an AsyncWsStream over a socket whose handshake completed, using the default
+        /// configuration.
+        pub fn new(socket: WebSocketStream<S>) -> Self {
+            Self::with_config(socket, ToraConfig::DEFAULT)
+        }
+
+        /// Constructs an AsyncWsStream over a socket whose handshake completed, using the given
+        /// configuration.
+        pub fn with_config(socket: WebSocketStream<S>, config: ToraConfig) -> Self {
+            Self {
+                socket,
+                config,
+                _marker: PhantomData,
+            }
+        }
+
+        /// Serializes the value and sends it as a binary message.
+        pub async fn send(&mut self, value: &T) -> io::Result<()>
+        where
+            T: SerializeIo,
+        {
+            let message = to_message(value, &self.config)?;
+            self.socket.send(message).await.map_err(to_io_error)
+        }
+
+        /// Waits for the next binary message and deserializes it. Control messages are answered
+        /// by the socket and skipped.
+        ///
+        /// Returns [ErrorKind::UnexpectedEof](io::ErrorKind::UnexpectedEof) once the WebSocket
+        /// is closed.
+        pub async fn recv(&mut self) -> io::Result<T>
+        where
+            T: FromReader,
+        {
+            recv_from(&mut self.socket, &self.config).await
+        }
+
+        /// Splits this stream into its receiving half and a bounded queue of outbound messages,
+        /// holding at most `capacity` messages.
+        ///
+        /// The queue is drained by the returned [QueueWriter], which must be polled, usually on
+        /// its own task. `overflow` chooses what happens to each message sent while the queue is
+        /// full.
+        ///
+        /// # Panics
+        ///
+        /// Panics if `capacity` is zero.
+        pub fn queued(
+            self,
+            capacity: usize,
+            overflow: fn(&T) -> Overflow,
+        ) -> (AsyncWsReceiver<T, S>, SendQueue<T>, QueueWriter<S>) {
+            assert!(
+                capacity > 0,
+                "A send queue needs a capacity of at least one"
+            );
+
+            let (sink, stream) = self.socket.split();
+            let shared = Arc::new(Shared {
+                queue: Mutex::new(Queue {
+                    messages: VecDeque::with_capacity(capacity),
+                    capacity,
+                    senders: 1,
+                    closed: false,
+                    dropped: 0,
+                }),
+                space: Notify::new(),
+                ready: Notify::new(),
+            });
+
+            let receiver = AsyncWsReceiver {
+                stream,
+                config: self.config.clone(),
+                _marker: PhantomData,
+            };
+            let queue = SendQueue {
+                shared: shared.clone(),
+                config: self.config,
+                overflow,
+            };
+            (receiver, queue, QueueWriter { sink, shared })
+        }
+
+        /// Starts the closing handshake.
+        pub async fn close(&mut self) -> io::Result<()> {
+            self.socket.close(None).await.map_err(to_io_error)
+        }
+
+        /// Returns the configuration of this stream.
+        pub fn config(&self) -> &ToraConfig {
+            &self.config
+        }
+
+        /// Returns a reference to the underlying socket.
+        pub fn get_ref(&self) -> &WebSocketStream<S> {
+            &self.socket
+        }
+
+        /// Returns the underlying socket.
+        pub fn into_inner(self) -> WebSocketStream<S> {
+            self.socket
+        }
+    }
+
+    /// Waits for the next binary message and deserializes it, skipping control messages.
+    async fn recv_from<T, St>(stream: &mut St, config: &ToraConfig) -> io::Result<T>
+    where
+        T: FromReader,
+        St: Stream<Item = Result<Message, Error>> + Unpin,
+    {
+        loop {
+            let message = match stream.next().await {
+                Some(message) => message.map_err(to_io_error)?,
+                None => return Err(to_io_error(Error::ConnectionClosed)),
+            };
+
+            if let Some(value) = from_message(&message, config)? {
+                return Ok(value);
+            }
+        }
+    }
+
+    /// The receiving half of an [AsyncWsStream], returned by [AsyncWsStream::queued].
+    #[derive(Debug)]
+    pub struct AsyncWsReceiver<T, S> {
+        stream: SplitStream<WebSocketStream<S>>,
+        config: ToraConfig,
+        _marker: PhantomData<fn() -> T>,
+    }
+
+    impl<T, S> AsyncWsReceiver<T, S>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        /// Waits for the next binary message and deserializes it. Control messages are answered
+        /// by the socket and skipped.
+        ///
+        /// Returns [ErrorKind::UnexpectedEof] once the WebSocket is closed.
+        pub async fn recv(&mut self) -> io::Result<T>
+        where
+            T: FromReader,
+        {
+            recv_from(&mut self.stream, &self.config).await
+        }
+    }
+
+    /// What happens to a message sent to a full [SendQueue].
+    #[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+    pub enum Overflow {
+        /// The sender waits for space in the queue, and [SendQueue::try_send] fails.
+        #[default]
+        Block,
+        /// The message is dropped.
+        DropNewest,
+        /// The oldest queued message sent with this policy is dropped to make room. If there is
+        /// none, the sender waits as with [Block](Self::Block).
+        DropOldest,
+    }
+
+    struct Queue {
+        messages: VecDeque<(Message, Overflow)>,
+        capacity: usize,
+        /// The amount of live [SendQueue] handles.
+        senders: usize,
+        /// Set once the [QueueWriter] stopped.
+        closed: bool,
+        dropped: u64,
+    }
+
+    impl Queue {
+        /// Queues the message, or returns it if the sender must wait for space.
+        fn push(&mut self, message: Message, overflow: Overflow) -> io::Result<Option<Message>> {
+            if self.closed {
+                return Err(io::Error::new(
+                    ErrorKind::BrokenPipe,
+                    "The queue writer stopped",
+                ));
+            }
+            if self.messages.len() < self.capacity {
+                self.messages.push_back((message, overflow));
+                return Ok(None);
+            }
+
+            match overflow {
+                Overflow::Block => return Ok(Some(message)),
+                Overflow::DropNewest => {}
+                Overflow::DropOldest => {
+                    let Some(i) = self
+                        .messages
+                        .iter()
+                        .position(|(_, o)| *o == Overflow::DropOldest)
+                    else {
+                        return Ok(Some(message));
+                    };
+                    self.messages.remove(i);
+                    self.messages.push_back((message, overflow));
+                }
+            }
+            self.dropped += 1;
+            Ok(None)
+        }
+    }
+
+    struct Shared {
+        queue: Mutex<Queue>,
+        /// Notified when a message leaves the queue, or the writer stops.
+        space: Notify,
+        /// Notified when a message enters the queue, or the last sender is dropped.
+        ready: Notify,
+    }
+
+    impl Shared {
+        fn lock(&self) -> MutexGuard<'_, Queue> {
+            self.queue.lock().unwrap_or_else(PoisonError::into_inner)
+        }
+    }
+
+    /// A bounded queue of messages to send, returned by [AsyncWsStream::queued].
+    ///
+    /// Values are serialized as they are queued. Clones share the same queue, so many tasks can
+    /// send to one client; a slow client fills its queue, applying backpressure to the senders
+    /// instead of buffering without bounds.
+    ///
+    /// ```
+    /// use std::io;
+    ///
+    /// use futures_util::future::try_join;
+    /// use tokio::io::duplex;
+    /// use tora::testing::block_on;
+    /// use tora::websocket::{AsyncWsStream, Overflow};
+    ///
+    /// fn main() -> io::Result<()> {
+    ///     let (client, server) = duplex(4096);
+    ///
+    ///     let client = async {
+    ///         let (socket, _) = tokio_tungstenite::client_async("ws://localhost", client)
+    ///             .await
+    ///             .map_err(io::Error::other)?;
+    ///         let mut stream = AsyncWsStream::<u32, _>::new(socket);
+    ///
+    ///         let mut received = Vec::new();
+    ///         for _ in 0..2 {
+    ///             received.push(stream.recv().await?);
+    ///         }
+    ///         io::Result::Ok(received)
+    ///     };
+    ///     let server = async {
+    ///         let socket = tokio_tungstenite::accept_async(server)
+    ///             .await
+    ///             .map_err(io::Error::other)?;
+    ///
+    ///         // Even values are state updates, superseded by later ones when the client lags
+    ///         // behind.
+    ///         let stream = AsyncWsStream::<u32, _>::new(socket);
+    ///         let (_receiver, queue, writer) = stream.queued(2, |v| match v % 2 {
+    ///             0 => Overflow::DropOldest,
+    ///             _ => Overflow::Block,
+    ///         });
+    ///
+    ///         queue.try_send(&0)?;
+    ///         queue.try_send(&1)?;
+    ///         queue.try_send(&2)?;
+    ///         assert_eq!(queue.try_send(&3).unwrap_err().kind(), io::ErrorKind::WouldBlock);
+    ///         assert_eq!(queue.dropped(), 1);
+    ///         drop(queue);
+    ///
+    ///         writer.run().await
+    ///     };
+    ///
+    ///     let (received, ()) = block_on(try_join(client, server))?;
+    ///     assert_eq!(received, [1, 2]);
+    ///     Ok(())
+    /// }
+    /// ```
+    pub struct SendQueue<T> {
+        shared: Arc<Shared>,
+        config: ToraConfig,
+        overflow: fn(&T) -> Overflow,
+    }
+
+    impl<T> SendQueue<T>
+    where
+        T: SerializeIo,
+    {
+        /// Queues the value without waiting.
+        ///
+        /// Returns [ErrorKind::WouldBlock] if the queue is full and the value must wait for
+        /// space, and [ErrorKind::BrokenPipe] if the [QueueWriter] stopped.
+        pub fn try_send(&self, value: &T) -> io::Result<()> {
+            let message = to_message(value, &self.config)?;
+
+            match self.shared.lock().push(message, (self.overflow)(value))? {
+                Some(_) => Err(io::Error::new(ErrorKind::WouldBlock, "The queue is full")),
+                None => {
+                    self.shared.ready.notify_one();
+                    Ok(())
+                }
+            }
+        }
+
+        /// Queues the value, waiting for space if the queue is full and the value must not be
+        /// dropped.
+        ///
+        /// Returns [ErrorKind::BrokenPipe] if the [QueueWriter] stopped.
+        pub async fn send(&self, value: &T) -> io::Result<()> {
+            let overflow = (self.overflow)(value);
+            let mut message = to_message(value, &self.config)?;
+
+            loop {
+                // Registered before checking, so space freed in between is not missed.
+                let mut space = pin!(self.shared.space.notified());
+                space.as_mut().enable();
+
+                match self.shared.lock().push(message, overflow)? {
+                    Some(returned) => message = returned,
+                    None => {
+                        self.shared.ready.notify_one();
+                        return Ok(());
+                    }
+                }
+                space.await;
+            }
+        }
+    }
+
+    impl<T> SendQueue<T> {
+        /// Returns the amount of messages queued.
+        pub fn len(&self) -> usize {
+            self.shared.lock().messages.len()
+        }
+
+        /// Returns true if no messages are queued.
+        pub fn is_empty(&self) -> bool {
+            self.len() == 0
+        }
+
+        /// Returns the amount of messages dropped by their [Overflow] policy.
+        pub fn dropped(&self) -> u64 {
+            self.shared.lock().dropped
+        }
+    }
+
+    impl<T> Clone for SendQueue<T> {
+        fn clone(&self) -> Self {
+            self.shared.lock().senders += 1;
+            Self {
+                shared: self.shared.clone(),
+                config: self.config.clone(),
+                overflow: self.overflow,
+            }
+        }
+    }
+
+    impl<T> Drop for SendQueue<T> {
+        fn drop(&mut self) {
+            self.shared.lock().senders -= 1;
+            self.shared.ready.notify_one();
+        }
+    }
+
+    /// The sending half of an [AsyncWsStream], writing the messages of its [SendQueue] in order.
+    pub struct QueueWriter<S> {
+        sink: SplitSink<WebSocketStream<S>, Message>,
+        shared: Arc<Shared>,
+    }
+
+    impl<S> QueueWriter<S>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        /// Sends the queued messages until every [SendQueue] is dropped and the queue is empty,
+        /// then starts the closing handshake.
+        ///
+        /// Once this returns, senders fail with [ErrorKind::BrokenPipe].
+        pub async fn run(mut self) -> io::Result<()> {
+            let result = self.send_all().await;
+
+            self.shared.lock().closed = true;
+            self.shared.space.notify_waiters();
+            result
+        }
+
+        async fn send_all(&mut self) -> io::Result<()> {
+            loop {
+                let next = {
+                    let mut queue = self.shared.lock();
+                    match queue.messages.pop_front() {
+                        Some((message, _)) => Some(message),
+                        None if queue.senders == 0 => break,
+                        None => None,
+                    }
+                };
+
+                match next {
+                    Some(message) => {
+                        self.shared.space.notify_one();
+                        self.sink.send(message).await.map_err(to_io_error)?;
+                    }
+                    None => self.shared.ready.notified().await,
+                }
+            }
+            self.sink.close().await.map_err(to_io_error)
+        }
+    }
+}
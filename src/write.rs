@@ -1,8 +1,64 @@
+use std::cell::{Cell, RefCell};
+#[cfg(feature = "dyn_impl")]
+use std::collections::{HashMap, HashSet};
 use std::io;
-use std::io::Write;
+use std::io::{ErrorKind, Write};
+use std::ops::ControlFlow;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex, RwLock};
+use std::task::Poll;
+use std::time::Duration;
+
+use crate::compact::write_packed;
+use crate::config::{Endian, FloatFormat, IntSequenceFormat, StringFormat, ToraConfig};
 
 macro_rules! serialize_io_num {
+    (@impl $t:ty { $($hooks:tt)* }) => {
+        impl SerializeIo for $t {
+            fn serialize<W>(&self, w: &mut W) -> io::Result<()>
+            where W: Write
+            {
+                w.write_all(&self.to_le_bytes())
+            }
+
+            fn serialize_with<W>(&self, w: &mut W, config: &ToraConfig) -> io::Result<()>
+            where W: Write
+            {
+                if let Some(result) = config.write_codec(self, w) {
+                    return result;
+                }
+                match config.endian {
+                    Endian::Little => w.write_all(&self.to_le_bytes()),
+                    Endian::Big => w.write_all(&self.to_be_bytes()),
+                }
+            }
+
+            $($hooks)*
+        }
+    };
+    (packed $($t:ty),*) => {
+        $(
+        serialize_io_num!(@impl $t {
+            /// Writes the bit-packed deltas between the integers if the configured integer
+            /// sequence format is [IntSequenceFormat::Packed].
+            fn serialize_slice<W>(slice: &[Self], w: &mut W, config: &ToraConfig) -> io::Result<()>
+            where W: Write
+            {
+                if config.int_sequence_format == IntSequenceFormat::Packed {
+                    return write_packed(w, slice.iter().map(|&value| value as u64), <$t>::BITS);
+                }
+                slice.iter().try_for_each(|value| value.serialize_with(w, config))
+            }
+        });
+        )*
+    };
     ($($t:ty),*) => {
+        $(serialize_io_num!(@impl $t {});)*
+    };
+}
+
+macro_rules! serialize_io_float {
+    ($($t:ty => $nan:expr),*) => {
         $(
         impl SerializeIo for $t {
             fn serialize<W>(&self, w: &mut W) -> io::Result<()>
@@ -10,16 +66,334 @@ macro_rules! serialize_io_num {
             {
                 w.write_all(&self.to_le_bytes())
             }
+
+            /// Writes the float, canonicalized as configured by [FloatFormat].
+            fn serialize_with<W>(&self, w: &mut W, config: &ToraConfig) -> io::Result<()>
+            where W: Write
+            {
+                if let Some(result) = config.write_codec(self, w) {
+                    return result;
+                }
+                let value = match config.float_format {
+                    FloatFormat::Raw => *self,
+                    FloatFormat::Finite if !self.is_finite() => {
+                        return Err(io::Error::new(ErrorKind::InvalidInput, "Float is not finite"));
+                    }
+                    _ if self.is_nan() => <$t>::from_bits($nan),
+                    _ if *self == 0.0 => 0.0,
+                    _ => *self,
+                };
+
+                match config.endian {
+                    Endian::Little => w.write_all(&value.to_le_bytes()),
+                    Endian::Big => w.write_all(&value.to_be_bytes()),
+                }
+            }
         })*
     }
 }
 
+/// A writer that writes a fixed amount of padding before each value.
+///
+/// Alternatively, the writer can align each value to a multiple of N bytes, counted from the
+/// first byte written through this writer.
+///
+/// ```
+/// use std::io;
+///
+/// use tora::write::PaddedWriter;
+///
+/// fn main() -> io::Result<()> {
+///     let mut bytes = Vec::new();
+///     let mut writer = PaddedWriter::aligned_to(4);
+///
+///     writer.writes(&mut bytes, &1u8)?;
+///     writer.writes(&mut bytes, &2u16)?;
+///
+///     assert_eq!(bytes, [1, 0, 0, 0, 2, 0]);
+///     Ok(())
+/// }
+/// ```
+#[derive(Default)]
+pub struct PaddedWriter {
+    padding: usize,
+    alignment: Option<usize>,
+    fill: u8,
+    written: usize,
+}
+
+impl PaddedWriter {
+    /// Writes the amount of padding, then writes [S], and applies the new padding to future
+    /// writes.
+    pub fn writes_then_set_padding<S, W>(
+        &mut self,
+        w: &mut W,
+        s: &S,
+        new_padding: usize,
+    ) -> io::Result<()>
+    where
+        S: SerializeIo + ?Sized,
+        W: Write,
+    {
+        self.writes(w, s)?;
+        self.padding = new_padding;
+        Ok(())
+    }
+
+    /// Writes the amount of padding, then writes [S].
+    ///
+    /// If this writer is aligned, writes as much padding as is needed to reach the next multiple
+    /// of the alignment instead.
+    pub fn writes<S, W>(&mut self, w: &mut W, s: &S) -> io::Result<()>
+    where
+        S: SerializeIo + ?Sized,
+        W: Write,
+    {
+        let padding = self.next_padding();
+        w.pad(padding, self.fill)?;
+
+        let mut counter = ByteCountWriter::new(w);
+        counter.writes(s)?;
+
+        self.written += padding + counter.count() as usize;
+        Ok(())
+    }
+
+    /// Sets the amount of padding written before each value.
+    pub fn set_padding(&mut self, padding: usize) -> &mut Self {
+        self.padding = padding;
+        self
+    }
+
+    /// Sets the byte used to fill padding.
+    pub fn set_fill(&mut self, fill: u8) -> &mut Self {
+        self.fill = fill;
+        self
+    }
+
+    /// Constructs a PaddedWriter with the given initial padding.
+    pub const fn with_padding(padding: usize) -> Self {
+        Self {
+            padding,
+            alignment: None,
+            fill: 0,
+            written: 0,
+        }
+    }
+
+    /// Constructs a PaddedWriter that aligns each value to a multiple of `alignment` bytes.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `alignment` is zero.
+    pub const fn aligned_to(alignment: usize) -> Self {
+        assert!(alignment != 0, "Alignment must be non-zero");
+
+        Self {
+            padding: 0,
+            alignment: Some(alignment),
+            fill: 0,
+            written: 0,
+        }
+    }
+
+    /// Returns the amount of padding that will be written before the next value.
+    pub const fn next_padding(&self) -> usize {
+        match self.alignment {
+            Some(alignment) => (alignment - self.written % alignment) % alignment,
+            None => self.padding,
+        }
+    }
+
+    /// Returns the current amount of padding this writer uses.
+    pub const fn padding(&self) -> usize {
+        self.padding
+    }
+
+    /// Returns the byte this writer fills padding with.
+    pub const fn fill(&self) -> u8 {
+        self.fill
+    }
+
+    /// Returns the amount of bytes written through this writer, padding included.
+    pub const fn written(&self) -> usize {
+        self.written
+    }
+}
+
+/// A writer that counts the bytes successfully written to the inner writer.
+///
+/// ```
+/// use std::io;
+///
+/// use tora::write::{ByteCountWriter, ToraWrite};
+///
+/// fn main() -> io::Result<()> {
+///     let mut writer = ByteCountWriter::new(io::sink());
+///     writer.writes(&"Hello")?;
+///
+///     assert_eq!(writer.count(), 6);
+///     Ok(())
+/// }
+/// ```
+pub struct ByteCountWriter<W> {
+    inner: W,
+    count: u64,
+}
+
+impl<W> ByteCountWriter<W> {
+    /// Constructs a ByteCountWriter wrapping the given writer.
+    pub const fn new(inner: W) -> Self {
+        Self { inner, count: 0 }
+    }
+
+    /// Returns the amount of bytes written since construction or the last reset.
+    pub const fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Resets the byte count to zero, returning the previous count.
+    pub fn reset(&mut self) -> u64 {
+        std::mem::take(&mut self.count)
+    }
+
+    /// Returns a reference to the inner writer.
+    pub const fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    /// Returns a mutable reference to the inner writer.
+    ///
+    /// Bytes written directly to the inner writer are not counted.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    /// Consumes this writer, returning the inner writer.
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W> Write for ByteCountWriter<W>
+where
+    W: Write,
+{
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.count += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
 /// An extension to the standard [Write] trait.
 pub trait ToraWrite {
     /// Serialize and write the given data.
     fn writes<S>(&mut self, s: &S) -> io::Result<()>
     where
-        S: SerializeIo;
+        S: SerializeIo + ?Sized;
+
+    /// Serialize and write the given data, honoring the given configuration.
+    ///
+    /// See [ToraConfig].
+    fn writes_with<S>(&mut self, s: &S, config: &ToraConfig) -> io::Result<()>
+    where
+        S: SerializeIo + ?Sized,
+        Self: Write + Sized,
+    {
+        s.serialize_with(self, config)
+    }
+
+    /// Write `n` copies of the `fill` byte.
+    ///
+    /// The bytes are written from a fixed-size stack buffer, so no allocation is made regardless
+    /// of `n`.
+    fn pad(&mut self, n: usize, fill: u8) -> io::Result<()>
+    where
+        Self: Write,
+    {
+        let buf = [fill; 256];
+        let mut remaining = n;
+
+        while remaining > 0 {
+            let len = remaining.min(buf.len());
+            self.write_all(&buf[..len])?;
+            remaining -= len;
+        }
+        Ok(())
+    }
+
+    /// Write a [u32] length prefix of `len`, then serialize each item yielded by the iterator.
+    ///
+    /// The output is read back as a [Vec], without ever collecting the items into one.
+    ///
+    /// Returns [ErrorKind::InvalidInput] if `len` does not fit in a [u32], or if the iterator
+    /// yields a different amount of items than `len`. In the latter case, the items yielded
+    /// before the mismatch was detected have already been written.
+    ///
+    /// ```
+    /// use std::io;
+    /// use std::io::Cursor;
+    ///
+    /// use tora::read::ToraRead;
+    /// use tora::write::ToraWrite;
+    ///
+    /// fn main() -> io::Result<()> {
+    ///     let mut bytes = Vec::new();
+    ///     bytes.writes_iter(3, (1..=3).map(|i: u16| i * 10))?;
+    ///
+    ///     let values: Vec<u16> = Cursor::new(bytes).reads()?;
+    ///     assert_eq!(values, [10, 20, 30]);
+    ///     Ok(())
+    /// }
+    /// ```
+    fn writes_iter<I>(&mut self, len: usize, iter: I) -> io::Result<()>
+    where
+        I: IntoIterator,
+        I::Item: SerializeIo,
+    {
+        let prefix = u32::try_from(len)
+            .map_err(|_| io::Error::new(ErrorKind::InvalidInput, "Length exceeds u32::MAX"))?;
+        self.writes(&prefix)?;
+
+        let mut written = 0;
+
+        for item in iter {
+            if written == len {
+                return Err(io::Error::new(
+                    ErrorKind::InvalidInput,
+                    "Iterator yielded more items than its length",
+                ));
+            }
+            self.writes(&item)?;
+            written += 1;
+        }
+
+        if written != len {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                "Iterator yielded fewer items than its length",
+            ));
+        }
+        Ok(())
+    }
+
+    /// Write the length prefix and the items of an iterator whose length is known ahead of time.
+    ///
+    /// See [ToraWrite::writes_iter].
+    fn writes_exact_iter<I>(&mut self, iter: I) -> io::Result<()>
+    where
+        I: IntoIterator,
+        I::IntoIter: ExactSizeIterator,
+        I::Item: SerializeIo,
+    {
+        let iter = iter.into_iter();
+        self.writes_iter(iter.len(), iter)
+    }
 }
 
 impl<W> ToraWrite for W
@@ -28,7 +402,7 @@ where
 {
     fn writes<S>(&mut self, s: &S) -> io::Result<()>
     where
-        S: SerializeIo,
+        S: SerializeIo + ?Sized,
     {
         s.serialize(self)
     }
@@ -61,9 +435,92 @@ pub trait SerializeIo {
     fn serialize<W>(&self, w: &mut W) -> io::Result<()>
     where
         W: Write;
+
+    /// Serialize this type into the given writer, honoring the given configuration.
+    ///
+    /// The default implementation ignores the configuration and calls [SerializeIo::serialize].
+    /// Implementations containing other values should override this method and forward the
+    /// configuration to them.
+    fn serialize_with<W>(&self, w: &mut W, config: &ToraConfig) -> io::Result<()>
+    where
+        W: Write,
+    {
+        let _ = config;
+        self.serialize(w)
+    }
+
+    /// Serialize every element of the slice, in order.
+    ///
+    /// Used by arrays, slices and [Vec]s. The default implementation serializes the elements one
+    /// at a time, and [u8] overrides it to write all of them at once.
+    fn serialize_slice<W>(slice: &[Self], w: &mut W, config: &ToraConfig) -> io::Result<()>
+    where
+        Self: Sized,
+        W: Write,
+    {
+        for value in slice {
+            value.serialize_with(w, config)?;
+        }
+        Ok(())
+    }
 }
 
-serialize_io_num!(u8, u16, u32, u64, u128, i8, i16, i32, i64, i128, f32, f64, usize);
+serialize_io_num!(u16, u128, i8, i16, i32, i64, i128);
+serialize_io_num!(packed u32, u64);
+serialize_io_float!(f32 => 0x7FC0_0000, f64 => 0x7FF8_0000_0000_0000);
+
+/// Writes a [u64], so values are portable between 32 and 64-bit targets.
+impl SerializeIo for usize {
+    fn serialize<W>(&self, w: &mut W) -> io::Result<()>
+    where
+        W: Write,
+    {
+        (*self as u64).serialize(w)
+    }
+
+    fn serialize_with<W>(&self, w: &mut W, config: &ToraConfig) -> io::Result<()>
+    where
+        W: Write,
+    {
+        if let Some(result) = config.write_codec(self, w) {
+            return result;
+        }
+        (*self as u64).serialize_with(w, config)
+    }
+}
+
+impl SerializeIo for u8 {
+    fn serialize<W>(&self, w: &mut W) -> io::Result<()>
+    where
+        W: Write,
+    {
+        w.write_all(&[*self])
+    }
+
+    fn serialize_with<W>(&self, w: &mut W, config: &ToraConfig) -> io::Result<()>
+    where
+        W: Write,
+    {
+        if let Some(result) = config.write_codec(self, w) {
+            return result;
+        }
+        self.serialize(w)
+    }
+
+    /// Writes the bytes of the whole slice with a single `write_all`, unless a codec is
+    /// registered for [u8].
+    fn serialize_slice<W>(slice: &[Self], w: &mut W, config: &ToraConfig) -> io::Result<()>
+    where
+        W: Write,
+    {
+        if config.codec::<u8>().is_some() {
+            return slice
+                .iter()
+                .try_for_each(|value| value.serialize_with(w, config));
+        }
+        w.write_all(slice)
+    }
+}
 
 impl SerializeIo for char {
     /// Serializes this char as a u32.
@@ -73,6 +530,37 @@ impl SerializeIo for char {
     {
         (*self as u32).serialize(w)
     }
+
+    fn serialize_with<W>(&self, w: &mut W, config: &ToraConfig) -> io::Result<()>
+    where
+        W: Write,
+    {
+        if let Some(result) = config.write_codec(self, w) {
+            return result;
+        }
+        (*self as u32).serialize_with(w, config)
+    }
+}
+
+impl SerializeIo for Duration {
+    /// Writes the seconds as a [u64], then the nanoseconds as a [u32].
+    fn serialize<W>(&self, w: &mut W) -> io::Result<()>
+    where
+        W: Write,
+    {
+        self.serialize_with(w, &ToraConfig::DEFAULT)
+    }
+
+    fn serialize_with<W>(&self, w: &mut W, config: &ToraConfig) -> io::Result<()>
+    where
+        W: Write,
+    {
+        if let Some(result) = config.write_codec(self, w) {
+            return result;
+        }
+        w.writes_with(&self.as_secs(), config)?;
+        w.writes_with(&self.subsec_nanos(), config)
+    }
 }
 
 impl SerializeIo for bool {
@@ -83,6 +571,16 @@ impl SerializeIo for bool {
     {
         (*self as u8).serialize(w)
     }
+
+    fn serialize_with<W>(&self, w: &mut W, config: &ToraConfig) -> io::Result<()>
+    where
+        W: Write,
+    {
+        if let Some(result) = config.write_codec(self, w) {
+            return result;
+        }
+        self.serialize(w)
+    }
 }
 
 impl SerializeIo for () {
@@ -105,8 +603,15 @@ where
     where
         W: Write,
     {
-        w.writes(&self.0)?;
-        w.writes(&self.1)
+        self.serialize_with(w, &ToraConfig::DEFAULT)
+    }
+
+    fn serialize_with<W>(&self, w: &mut W, config: &ToraConfig) -> io::Result<()>
+    where
+        W: Write,
+    {
+        w.writes_with(&self.0, config)?;
+        w.writes_with(&self.1, config)
     }
 }
 
@@ -121,9 +626,16 @@ where
     where
         W: Write,
     {
-        w.writes(&self.0)?;
-        w.writes(&self.1)?;
-        w.writes(&self.2)
+        self.serialize_with(w, &ToraConfig::DEFAULT)
+    }
+
+    fn serialize_with<W>(&self, w: &mut W, config: &ToraConfig) -> io::Result<()>
+    where
+        W: Write,
+    {
+        w.writes_with(&self.0, config)?;
+        w.writes_with(&self.1, config)?;
+        w.writes_with(&self.2, config)
     }
 }
 
@@ -134,9 +646,19 @@ impl SerializeIo for String {
     {
         self.as_str().serialize(w)
     }
+
+    fn serialize_with<W>(&self, w: &mut W, config: &ToraConfig) -> io::Result<()>
+    where
+        W: Write,
+    {
+        if let Some(result) = config.write_codec(self, w) {
+            return result;
+        }
+        self.as_str().serialize_with(w, config)
+    }
 }
 
-impl<'a> SerializeIo for &'a str {
+impl SerializeIo for str {
     /// Write the given string in UTF-8.
     ///
     /// If the given string does not end in a NUL `0x00` byte, one will be appended.
@@ -144,12 +666,35 @@ impl<'a> SerializeIo for &'a str {
     where
         W: Write,
     {
-        w.write_all(self.as_bytes())?;
+        self.serialize_with(w, &ToraConfig::DEFAULT)
+    }
 
-        if !self.ends_with(0u8 as char) {
-            w.write_all(&[0])?;
+    /// Write the given string in UTF-8, in the configured string format.
+    ///
+    /// If a codec is registered for [String], the string is copied and written with it.
+    fn serialize_with<W>(&self, w: &mut W, config: &ToraConfig) -> io::Result<()>
+    where
+        W: Write,
+    {
+        if config.codec::<String>().is_some() {
+            if let Some(result) = config.write_codec(&self.to_owned(), w) {
+                return result;
+            }
+        }
+        match config.string_format {
+            StringFormat::NulTerminated => {
+                w.write_all(self.as_bytes())?;
+
+                if !self.ends_with(0u8 as char) {
+                    w.write_all(&[0])?;
+                }
+                Ok(())
+            }
+            StringFormat::LengthPrefixed => {
+                config.write_length(w, self.len())?;
+                w.write_all(self.as_bytes())
+            }
         }
-        Ok(())
     }
 }
 
@@ -159,13 +704,20 @@ where
 {
     /// If this Option is Some, writes true and the inner value, else false.
     fn serialize<W>(&self, w: &mut W) -> io::Result<()>
+    where
+        W: Write,
+    {
+        self.serialize_with(w, &ToraConfig::DEFAULT)
+    }
+
+    fn serialize_with<W>(&self, w: &mut W, config: &ToraConfig) -> io::Result<()>
     where
         W: Write,
     {
         w.writes(&self.is_some())?;
 
         if let Some(ref v) = self {
-            w.writes(v)?;
+            w.writes_with(v, config)?;
         }
         Ok(())
     }
@@ -178,41 +730,282 @@ where
 {
     /// If this Result is an error, writes true and the inner error, else false and the inner value.
     fn serialize<W>(&self, w: &mut W) -> io::Result<()>
+    where
+        W: Write,
+    {
+        self.serialize_with(w, &ToraConfig::DEFAULT)
+    }
+
+    fn serialize_with<W>(&self, w: &mut W, config: &ToraConfig) -> io::Result<()>
     where
         W: Write,
     {
         w.writes(&self.is_err())?;
 
         match self {
-            Ok(v) => w.writes(v),
-            Err(v) => w.writes(v),
+            Ok(v) => w.writes_with(v, config),
+            Err(v) => w.writes_with(v, config),
         }
     }
 }
 
-impl<T, const N: usize> SerializeIo for [T; N]
+impl<B, C> SerializeIo for ControlFlow<B, C>
+where
+    B: SerializeIo,
+    C: SerializeIo,
+{
+    /// If this is Break, writes true and the break value, else false and the continue value.
+    fn serialize<W>(&self, w: &mut W) -> io::Result<()>
+    where
+        W: Write,
+    {
+        self.serialize_with(w, &ToraConfig::DEFAULT)
+    }
+
+    fn serialize_with<W>(&self, w: &mut W, config: &ToraConfig) -> io::Result<()>
+    where
+        W: Write,
+    {
+        w.writes(&self.is_break())?;
+
+        match self {
+            ControlFlow::Continue(v) => w.writes_with(v, config),
+            ControlFlow::Break(v) => w.writes_with(v, config),
+        }
+    }
+}
+
+impl<T> SerializeIo for Poll<T>
 where
     T: SerializeIo,
 {
+    /// If this Poll is Ready, writes true and the inner value, else false.
     fn serialize<W>(&self, w: &mut W) -> io::Result<()>
     where
         W: Write,
     {
-        for t in self {
-            w.writes(t)?;
+        self.serialize_with(w, &ToraConfig::DEFAULT)
+    }
+
+    fn serialize_with<W>(&self, w: &mut W, config: &ToraConfig) -> io::Result<()>
+    where
+        W: Write,
+    {
+        w.writes(&self.is_ready())?;
+
+        if let Poll::Ready(ref v) = self {
+            w.writes_with(v, config)?;
         }
         Ok(())
     }
 }
 
+impl<T, const N: usize> SerializeIo for [T; N]
+where
+    T: SerializeIo,
+{
+    fn serialize<W>(&self, w: &mut W) -> io::Result<()>
+    where
+        W: Write,
+    {
+        self.serialize_with(w, &ToraConfig::DEFAULT)
+    }
+
+    fn serialize_with<W>(&self, w: &mut W, config: &ToraConfig) -> io::Result<()>
+    where
+        W: Write,
+    {
+        T::serialize_slice(self, w, config)
+    }
+}
+
+/// Serializes the referenced value, so borrowed data can be written without cloning it.
+impl<T> SerializeIo for &T
+where
+    T: SerializeIo + ?Sized,
+{
+    fn serialize<W>(&self, w: &mut W) -> io::Result<()>
+    where
+        W: Write,
+    {
+        (**self).serialize(w)
+    }
+
+    fn serialize_with<W>(&self, w: &mut W, config: &ToraConfig) -> io::Result<()>
+    where
+        W: Write,
+    {
+        (**self).serialize_with(w, config)
+    }
+}
+
+impl<T> SerializeIo for &mut T
+where
+    T: SerializeIo + ?Sized,
+{
+    fn serialize<W>(&self, w: &mut W) -> io::Result<()>
+    where
+        W: Write,
+    {
+        (**self).serialize(w)
+    }
+
+    fn serialize_with<W>(&self, w: &mut W, config: &ToraConfig) -> io::Result<()>
+    where
+        W: Write,
+    {
+        (**self).serialize_with(w, config)
+    }
+}
+
 impl<T> SerializeIo for Box<T>
-where T: SerializeIo
+where
+    T: SerializeIo + ?Sized,
+{
+    fn serialize<W>(&self, w: &mut W) -> io::Result<()>
+    where
+        W: Write,
+    {
+        (**self).serialize(w)
+    }
+
+    fn serialize_with<W>(&self, w: &mut W, config: &ToraConfig) -> io::Result<()>
+    where
+        W: Write,
+    {
+        (**self).serialize_with(w, config)
+    }
+}
+
+impl<T> SerializeIo for Rc<T>
+where
+    T: SerializeIo + ?Sized,
+{
+    fn serialize<W>(&self, w: &mut W) -> io::Result<()>
+    where
+        W: Write,
+    {
+        (**self).serialize(w)
+    }
+
+    fn serialize_with<W>(&self, w: &mut W, config: &ToraConfig) -> io::Result<()>
+    where
+        W: Write,
+    {
+        (**self).serialize_with(w, config)
+    }
+}
+
+impl<T> SerializeIo for Arc<T>
+where
+    T: SerializeIo + ?Sized,
+{
+    fn serialize<W>(&self, w: &mut W) -> io::Result<()>
+    where
+        W: Write,
+    {
+        (**self).serialize(w)
+    }
+
+    fn serialize_with<W>(&self, w: &mut W, config: &ToraConfig) -> io::Result<()>
+    where
+        W: Write,
+    {
+        (**self).serialize_with(w, config)
+    }
+}
+
+/// Locks the mutex for the duration of the write.
+///
+/// Returns an error if the mutex is poisoned.
+impl<T> SerializeIo for Mutex<T>
+where
+    T: SerializeIo + ?Sized,
+{
+    fn serialize<W>(&self, w: &mut W) -> io::Result<()>
+    where
+        W: Write,
+    {
+        self.serialize_with(w, &ToraConfig::DEFAULT)
+    }
+
+    fn serialize_with<W>(&self, w: &mut W, config: &ToraConfig) -> io::Result<()>
+    where
+        W: Write,
+    {
+        let guard = self
+            .lock()
+            .map_err(|_| io::Error::other("The mutex is poisoned"))?;
+        w.writes_with(&*guard, config)
+    }
+}
+
+/// Acquires a read lock for the duration of the write.
+///
+/// Returns an error if the lock is poisoned.
+impl<T> SerializeIo for RwLock<T>
+where
+    T: SerializeIo + ?Sized,
 {
     fn serialize<W>(&self, w: &mut W) -> io::Result<()>
     where
         W: Write,
     {
-        w.writes(&**self)
+        self.serialize_with(w, &ToraConfig::DEFAULT)
+    }
+
+    fn serialize_with<W>(&self, w: &mut W, config: &ToraConfig) -> io::Result<()>
+    where
+        W: Write,
+    {
+        let guard = self
+            .read()
+            .map_err(|_| io::Error::other("The lock is poisoned"))?;
+        w.writes_with(&*guard, config)
+    }
+}
+
+impl<T> SerializeIo for Cell<T>
+where
+    T: SerializeIo + Copy,
+{
+    fn serialize<W>(&self, w: &mut W) -> io::Result<()>
+    where
+        W: Write,
+    {
+        self.get().serialize(w)
+    }
+
+    fn serialize_with<W>(&self, w: &mut W, config: &ToraConfig) -> io::Result<()>
+    where
+        W: Write,
+    {
+        self.get().serialize_with(w, config)
+    }
+}
+
+/// Borrows the value for the duration of the write.
+///
+/// Returns an error if the value is currently mutably borrowed.
+impl<T> SerializeIo for RefCell<T>
+where
+    T: SerializeIo + ?Sized,
+{
+    fn serialize<W>(&self, w: &mut W) -> io::Result<()>
+    where
+        W: Write,
+    {
+        self.serialize_with(w, &ToraConfig::DEFAULT)
+    }
+
+    fn serialize_with<W>(&self, w: &mut W, config: &ToraConfig) -> io::Result<()>
+    where
+        W: Write,
+    {
+        let value = self
+            .try_borrow()
+            .map_err(|_| io::Error::other("The value is already mutably borrowed"))?;
+        w.writes_with(&*value, config)
     }
 }
 
@@ -227,16 +1020,75 @@ macro_rules! dyn_impl {
             where
                 W: Write,
             {
-                w.writes(&(self.len() as u32))?;
+                self.serialize_with(w, &ToraConfig::DEFAULT)
+            }
 
-                for obj in self.iter() {
-                    w.writes(obj)?;
-                }
-                Ok(())
+            /// Writes the configured length prefix, then each element.
+            fn serialize_with<W>(&self, w: &mut W, config: &ToraConfig) -> io::Result<()>
+            where
+                W: Write,
+            {
+                config.write_length(w, self.len())?;
+                T::serialize_slice(self, w, config)
             }
         }
     };
 }
 
-dyn_impl!(&[T]);
+dyn_impl!([T]);
 dyn_impl!(Vec<T>);
+
+#[cfg(feature = "dyn_impl")]
+impl<K, V, S> SerializeIo for HashMap<K, V, S>
+where
+    K: SerializeIo,
+    V: SerializeIo,
+{
+    fn serialize<W>(&self, w: &mut W) -> io::Result<()>
+    where
+        W: Write,
+    {
+        self.serialize_with(w, &ToraConfig::DEFAULT)
+    }
+
+    /// Writes the configured length prefix, then each key and its value, in the iteration order
+    /// of the map.
+    fn serialize_with<W>(&self, w: &mut W, config: &ToraConfig) -> io::Result<()>
+    where
+        W: Write,
+    {
+        config.write_length(w, self.len())?;
+
+        for (key, value) in self {
+            w.writes_with(key, config)?;
+            w.writes_with(value, config)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "dyn_impl")]
+impl<T, S> SerializeIo for HashSet<T, S>
+where
+    T: SerializeIo,
+{
+    fn serialize<W>(&self, w: &mut W) -> io::Result<()>
+    where
+        W: Write,
+    {
+        self.serialize_with(w, &ToraConfig::DEFAULT)
+    }
+
+    /// Writes the configured length prefix, then each value, in the iteration order of the set.
+    fn serialize_with<W>(&self, w: &mut W, config: &ToraConfig) -> io::Result<()>
+    where
+        W: Write,
+    {
+        config.write_length(w, self.len())?;
+
+        for value in self {
+            w.writes_with(value, config)?;
+        }
+        Ok(())
+    }
+}
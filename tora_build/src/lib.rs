@@ -0,0 +1,565 @@
+//! # tora_build
+//!
+//! Generates [tora](https://crates.io/crates/tora) packet types from a protocol definition, so
+//! several crates can share one source of truth instead of copies of the same structs.
+//!
+//! # Protocol definitions
+//!
+//! ```text
+//! /// Sent by the client to log in.
+//! struct Login {
+//!     user: string,
+//!     token: option<u64>,
+//!     roles: list<string>,
+//! }
+//!
+//! enum Packet: u16 {
+//!     Ping,
+//!     Login(Login),
+//!     Move { x: i32, y: i32, position: [f32; 3] },
+//! }
+//! ```
+//!
+//! Fields and variants are separated by commas. `///` comments become doc comments, `//`
+//! comments are ignored.
+//!
+//! | Type                              | Rust type     |
+//! |-----------------------------------|---------------|
+//! | `u8` to `u128`, `i8` to `i128`    | The same      |
+//! | `f32`, `f64`, `bool`, `char`      | The same      |
+//! | `string`                          | `String`      |
+//! | `list<T>`                         | `Vec<T>`      |
+//! | `option<T>`                       | `Option<T>`   |
+//! | `[T; N]`                          | `[T; N]`      |
+//! | The name of a struct or enum      | The same      |
+//!
+//! Enum variant IDs are `u8` unless a type is given after the enum name.
+//!
+//! # Usage
+//!
+//! In `build.rs`:
+//!
+//! ```no_run
+//! tora_build::compile("protocol.tora").unwrap();
+//! ```
+//!
+//! Then include the generated types, which derive `Debug`, `Clone`, `PartialEq` and the tora
+//! read and write macros:
+//!
+//! ```ignore
+//! include!(concat!(env!("OUT_DIR"), "/protocol.rs"));
+//! ```
+
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+use std::{env, error, fmt, fs, io};
+
+/// An error in a protocol definition.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Error {
+    /// The line the error occurred on, starting at 1.
+    pub line: usize,
+    pub message: String,
+}
+
+impl Error {
+    fn new<S>(line: usize, message: S) -> Self
+    where
+        S: Into<String>,
+    {
+        Self {
+            line,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl error::Error for Error {}
+
+impl From<Error> for io::Error {
+    fn from(e: Error) -> Self {
+        io::Error::new(io::ErrorKind::InvalidData, e)
+    }
+}
+
+/// Generates the types defined in the given file into `$OUT_DIR/{file stem}.rs`.
+///
+/// Tells Cargo to rerun the build script when the file changes. Returns the path written to.
+pub fn compile<P>(path: P) -> io::Result<PathBuf>
+where
+    P: AsRef<Path>,
+{
+    let out_dir = env::var_os("OUT_DIR")
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "OUT_DIR is not set"))?;
+    compile_to(path, out_dir)
+}
+
+/// Generates the types defined in the given file into `{out_dir}/{file stem}.rs`.
+///
+/// Tells Cargo to rerun the build script when the file changes. Returns the path written to.
+pub fn compile_to<P, O>(path: P, out_dir: O) -> io::Result<PathBuf>
+where
+    P: AsRef<Path>,
+    O: AsRef<Path>,
+{
+    let path = path.as_ref();
+    println!("cargo:rerun-if-changed={}", path.display());
+
+    let source = fs::read_to_string(path)?;
+    let code = generate(&source)?;
+
+    let stem = path
+        .file_stem()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Not a file"))?;
+    let out = out_dir.as_ref().join(stem).with_extension("rs");
+
+    fs::write(&out, code)?;
+    Ok(out)
+}
+
+/// Generates the Rust source of the types defined in the given protocol definition.
+///
+/// ```
+/// let code = tora_build::generate("struct Ping { id: u32 }").unwrap();
+///
+/// assert!(code.contains("pub struct Ping {"));
+/// assert!(code.contains("pub id: u32,"));
+/// ```
+pub fn generate(source: &str) -> Result<String, Error> {
+    let items = Parser::new(source)?.parse()?;
+
+    for item in &items {
+        item.check_references(&items)?;
+    }
+
+    let mut code = String::from("// Generated by tora_build. Do not edit.\n");
+    for item in &items {
+        code.push('\n');
+        item.generate(&mut code);
+    }
+    Ok(code)
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Ident(String),
+    Number(usize),
+    Doc(String),
+    Punct(char),
+}
+
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    position: usize,
+}
+
+impl Parser {
+    fn new(source: &str) -> Result<Self, Error> {
+        let mut tokens = Vec::new();
+
+        for (i, line) in source.lines().enumerate() {
+            let line_number = i + 1;
+            let mut chars = line.char_indices().peekable();
+
+            while let Some((start, c)) = chars.next() {
+                match c {
+                    c if c.is_whitespace() => {}
+                    '/' if line[start..].starts_with("///") => {
+                        let doc = line[start + 3..]
+                            .strip_prefix(' ')
+                            .unwrap_or(&line[start + 3..]);
+                        tokens.push((line_number, Token::Doc(doc.to_string())));
+                        break;
+                    }
+                    '/' if line[start..].starts_with("//") => break,
+                    '{' | '}' | '(' | ')' | '<' | '>' | '[' | ']' | ':' | ';' | ',' => {
+                        tokens.push((line_number, Token::Punct(c)));
+                    }
+                    c if c.is_ascii_alphanumeric() || c == '_' => {
+                        let mut end = start + c.len_utf8();
+
+                        while let Some(&(i, c)) = chars.peek() {
+                            if !(c.is_ascii_alphanumeric() || c == '_') {
+                                break;
+                            }
+                            end = i + c.len_utf8();
+                            chars.next();
+                        }
+
+                        let word = &line[start..end];
+                        let token = match word.parse() {
+                            Ok(n) => Token::Number(n),
+                            Err(_) if c.is_ascii_digit() => {
+                                return Err(Error::new(
+                                    line_number,
+                                    format!("Invalid number `{word}`"),
+                                ))
+                            }
+                            Err(_) => Token::Ident(word.to_string()),
+                        };
+                        tokens.push((line_number, token));
+                    }
+                    c => {
+                        return Err(Error::new(
+                            line_number,
+                            format!("Unexpected character `{c}`"),
+                        ))
+                    }
+                }
+            }
+        }
+
+        Ok(Self {
+            tokens,
+            position: 0,
+        })
+    }
+
+    fn line(&self) -> usize {
+        self.tokens
+            .get(self.position)
+            .or(self.tokens.last())
+            .map_or(1, |(line, _)| *line)
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position).map(|(_, token)| token)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.peek().cloned();
+        self.position += 1;
+        token
+    }
+
+    fn error<T>(&self, expected: &str) -> Result<T, Error> {
+        let found = match self.peek() {
+            Some(Token::Ident(ident)) => format!("`{ident}`"),
+            Some(Token::Number(n)) => format!("`{n}`"),
+            Some(Token::Doc(_)) => "a doc comment".to_string(),
+            Some(Token::Punct(c)) => format!("`{c}`"),
+            None => "the end of the file".to_string(),
+        };
+        Err(Error::new(
+            self.line(),
+            format!("Expected {expected}, found {found}"),
+        ))
+    }
+
+    fn eat(&mut self, punct: char) -> bool {
+        if self.peek() == Some(&Token::Punct(punct)) {
+            self.position += 1;
+            return true;
+        }
+        false
+    }
+
+    fn expect(&mut self, punct: char) -> Result<(), Error> {
+        if self.eat(punct) {
+            return Ok(());
+        }
+        self.error(&format!("`{punct}`"))
+    }
+
+    fn ident(&mut self) -> Result<String, Error> {
+        match self.peek() {
+            Some(Token::Ident(ident)) => {
+                let ident = ident.clone();
+                self.position += 1;
+                Ok(ident)
+            }
+            _ => self.error("a name"),
+        }
+    }
+
+    fn docs(&mut self) -> Vec<String> {
+        let mut docs = Vec::new();
+
+        while let Some(Token::Doc(doc)) = self.peek() {
+            docs.push(doc.clone());
+            self.position += 1;
+        }
+        docs
+    }
+
+    fn parse(mut self) -> Result<Vec<Item>, Error> {
+        let mut items = Vec::new();
+
+        while self.peek().is_some() {
+            let docs = self.docs();
+            let line = self.line();
+
+            let kind = match self.ident()?.as_str() {
+                "struct" => {
+                    let name = self.ident()?;
+                    self.expect('{')?;
+                    ItemKind::Struct(self.fields('}')?, name)
+                }
+                "enum" => {
+                    let name = self.ident()?;
+                    let id = match self.eat(':') {
+                        true => self.ident()?,
+                        false => "u8".to_string(),
+                    };
+                    self.expect('{')?;
+                    ItemKind::Enum(self.variants()?, name, id)
+                }
+                _ => {
+                    self.position -= 1;
+                    return self.error("`struct` or `enum`");
+                }
+            };
+
+            items.push(Item { docs, line, kind });
+        }
+        Ok(items)
+    }
+
+    fn fields(&mut self, close: char) -> Result<Vec<Field>, Error> {
+        let mut fields = Vec::new();
+
+        while !self.eat(close) {
+            let docs = self.docs();
+            let name = self.ident()?;
+            self.expect(':')?;
+            let ty = self.ty()?;
+
+            fields.push(Field { docs, name, ty });
+
+            if !self.eat(',') && self.peek() != Some(&Token::Punct(close)) {
+                return self.error(&format!("`,` or `{close}`"));
+            }
+        }
+        Ok(fields)
+    }
+
+    fn variants(&mut self) -> Result<Vec<Variant>, Error> {
+        let mut variants = Vec::new();
+
+        while !self.eat('}') {
+            let docs = self.docs();
+            let name = self.ident()?;
+
+            let fields = if self.eat('(') {
+                let ty = self.ty()?;
+                self.expect(')')?;
+                VariantFields::Tuple(ty)
+            } else if self.eat('{') {
+                VariantFields::Named(self.fields('}')?)
+            } else {
+                VariantFields::Unit
+            };
+
+            variants.push(Variant { docs, name, fields });
+
+            if !self.eat(',') && self.peek() != Some(&Token::Punct('}')) {
+                return self.error("`,` or `}`");
+            }
+        }
+        Ok(variants)
+    }
+
+    fn ty(&mut self) -> Result<Ty, Error> {
+        if self.eat('[') {
+            let element = self.ty()?;
+            self.expect(';')?;
+
+            let len = match self.next() {
+                Some(Token::Number(n)) => n,
+                _ => {
+                    self.position -= 1;
+                    return self.error("an array length");
+                }
+            };
+            self.expect(']')?;
+            return Ok(Ty::Array(Box::new(element), len));
+        }
+
+        let line = self.line();
+        let name = self.ident()?;
+
+        Ok(match name.as_str() {
+            "list" | "option" => {
+                self.expect('<')?;
+                let inner = Box::new(self.ty()?);
+                self.expect('>')?;
+
+                match name.as_str() {
+                    "list" => Ty::List(inner),
+                    _ => Ty::Option(inner),
+                }
+            }
+            "string" => Ty::Primitive("String".to_string()),
+            "u8" | "u16" | "u32" | "u64" | "u128" | "i8" | "i16" | "i32" | "i64" | "i128"
+            | "f32" | "f64" | "bool" | "char" => Ty::Primitive(name),
+            _ => Ty::Named(name, line),
+        })
+    }
+}
+
+enum Ty {
+    Primitive(String),
+    List(Box<Ty>),
+    Option(Box<Ty>),
+    Array(Box<Ty>, usize),
+    /// A struct or enum, and the line it is referenced on.
+    Named(String, usize),
+}
+
+impl Ty {
+    fn check_references(&self, items: &[Item]) -> Result<(), Error> {
+        match self {
+            Ty::Primitive(_) => Ok(()),
+            Ty::List(inner) | Ty::Option(inner) | Ty::Array(inner, _) => {
+                inner.check_references(items)
+            }
+            Ty::Named(name, line) => match items.iter().any(|item| item.name() == name) {
+                true => Ok(()),
+                false => Err(Error::new(*line, format!("Unknown type `{name}`"))),
+            },
+        }
+    }
+}
+
+impl fmt::Display for Ty {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Ty::Primitive(name) => f.write_str(name),
+            Ty::List(inner) => write!(f, "Vec<{inner}>"),
+            Ty::Option(inner) => write!(f, "Option<{inner}>"),
+            Ty::Array(inner, len) => write!(f, "[{inner}; {len}]"),
+            Ty::Named(name, _) => f.write_str(name),
+        }
+    }
+}
+
+struct Field {
+    docs: Vec<String>,
+    name: String,
+    ty: Ty,
+}
+
+enum VariantFields {
+    Unit,
+    Tuple(Ty),
+    Named(Vec<Field>),
+}
+
+struct Variant {
+    docs: Vec<String>,
+    name: String,
+    fields: VariantFields,
+}
+
+enum ItemKind {
+    Struct(Vec<Field>, String),
+    /// The variants, name and variant ID type.
+    Enum(Vec<Variant>, String, String),
+}
+
+struct Item {
+    docs: Vec<String>,
+    /// The line the item starts on.
+    line: usize,
+    kind: ItemKind,
+}
+
+/// Writes each doc comment line at the given indentation.
+fn write_docs(code: &mut String, docs: &[String], indent: &str) {
+    for doc in docs {
+        let _ = writeln!(code, "{indent}/// {doc}");
+    }
+}
+
+fn write_fields(code: &mut String, fields: &[Field], indent: &str, public: bool) {
+    let vis = if public { "pub " } else { "" };
+
+    for field in fields {
+        write_docs(code, &field.docs, indent);
+        let _ = writeln!(code, "{indent}{vis}{}: {},", field.name, field.ty);
+    }
+}
+
+impl Item {
+    fn name(&self) -> &str {
+        match self.kind {
+            ItemKind::Struct(_, ref name) | ItemKind::Enum(_, ref name, _) => name,
+        }
+    }
+
+    fn check_references(&self, items: &[Item]) -> Result<(), Error> {
+        let check_fields = |fields: &[Field]| {
+            fields
+                .iter()
+                .try_for_each(|field| field.ty.check_references(items))
+        };
+
+        match self.kind {
+            ItemKind::Struct(ref fields, ref name) => {
+                if fields.is_empty() {
+                    return Err(Error::new(self.line, format!("`{name}` has no fields")));
+                }
+                check_fields(fields)
+            }
+            ItemKind::Enum(ref variants, ref name, _) => {
+                if variants.is_empty() {
+                    return Err(Error::new(self.line, format!("`{name}` has no variants")));
+                }
+                variants
+                    .iter()
+                    .try_for_each(|variant| match variant.fields {
+                        VariantFields::Unit => Ok(()),
+                        VariantFields::Tuple(ref ty) => ty.check_references(items),
+                        VariantFields::Named(ref fields) => check_fields(fields),
+                    })
+            }
+        }
+    }
+
+    fn generate(&self, code: &mut String) {
+        write_docs(code, &self.docs, "");
+
+        match self.kind {
+            ItemKind::Struct(ref fields, ref name) => {
+                code.push_str(
+                    "#[derive(Clone, Debug, PartialEq, tora::ReadStruct, tora::WriteStruct)]\n",
+                );
+                let _ = writeln!(code, "pub struct {name} {{");
+                write_fields(code, fields, "    ", true);
+                code.push_str("}\n");
+            }
+            ItemKind::Enum(ref variants, ref name, ref id) => {
+                code.push_str(
+                    "#[derive(Clone, Debug, PartialEq, tora::ReadEnum, tora::WriteEnum)]\n",
+                );
+                let _ = writeln!(code, "#[type_variant_id({id})]");
+                let _ = writeln!(code, "pub enum {name} {{");
+
+                for variant in variants {
+                    write_docs(code, &variant.docs, "    ");
+
+                    match variant.fields {
+                        VariantFields::Unit => {
+                            let _ = writeln!(code, "    {},", variant.name);
+                        }
+                        VariantFields::Tuple(ref ty) => {
+                            let _ = writeln!(code, "    {}({ty}),", variant.name);
+                        }
+                        VariantFields::Named(ref fields) => {
+                            let _ = writeln!(code, "    {} {{", variant.name);
+                            write_fields(code, fields, "        ", false);
+                            code.push_str("    },\n");
+                        }
+                    }
+                }
+                code.push_str("}\n");
+            }
+        }
+    }
+}
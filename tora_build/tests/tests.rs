@@ -0,0 +1,71 @@
+#[test]
+fn generates_items() {
+    let source = "
+        /// A login request.
+        struct Login {
+            user: string,
+            // Not yet used.
+            token: option<u64>,
+            roles: list<string>
+        }
+
+        enum Packet: u16 {
+            Ping,
+            /// Logs in.
+            Login(Login),
+            Move { x: i32, position: [f32; 3] },
+        }
+    ";
+
+    let expected = "// Generated by tora_build. Do not edit.
+
+/// A login request.
+#[derive(Clone, Debug, PartialEq, tora::ReadStruct, tora::WriteStruct)]
+pub struct Login {
+    pub user: String,
+    pub token: Option<u64>,
+    pub roles: Vec<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, tora::ReadEnum, tora::WriteEnum)]
+#[type_variant_id(u16)]
+pub enum Packet {
+    Ping,
+    /// Logs in.
+    Login(Login),
+    Move {
+        x: i32,
+        position: [f32; 3],
+    },
+}
+";
+
+    assert_eq!(tora_build::generate(source).unwrap(), expected);
+}
+
+#[test]
+fn reports_errors() {
+    let error = |source| tora_build::generate(source).unwrap_err().to_string();
+
+    assert_eq!(
+        error("struct A {\n    b: B,\n}"),
+        "line 2: Unknown type `B`"
+    );
+    assert_eq!(
+        error("struct A {\n    b: u8\n    c: u8\n}"),
+        "line 3: Expected `,` or `}`, found `c`"
+    );
+    assert_eq!(error("enum A {}"), "line 1: `A` has no variants");
+    assert_eq!(
+        error("union A {}"),
+        "line 1: Expected `struct` or `enum`, found `union`"
+    );
+    assert_eq!(
+        error("struct A { b: [u8; N] }"),
+        "line 1: Expected an array length, found `N`"
+    );
+    assert_eq!(
+        error("struct A {"),
+        "line 1: Expected a name, found the end of the file"
+    );
+}
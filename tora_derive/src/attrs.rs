@@ -0,0 +1,181 @@
+use syn::{Attribute, Expr, Field, Ident, LitInt, LitStr, Result, Token, Type, Variant};
+
+/// Returns an iterator over the `#[tora(...)]` attributes in the given list.
+fn tora_attributes(attributes: &[Attribute]) -> impl Iterator<Item = &Attribute> {
+    attributes
+        .iter()
+        .filter(|attribute| attribute.path().is_ident("tora"))
+}
+
+/// The `#[tora(...)]` attributes applied to a struct or enum.
+#[derive(Default)]
+pub struct ContainerAttrs {
+    /// `#[tora(seed = $ty)]`
+    pub seed: Option<Type>,
+    /// `#[tora(builder)]`
+    pub builder: bool,
+    /// `#[tora(repr_c)]`
+    pub repr_c: bool,
+    /// `#[tora(assert_size = N)]`
+    pub assert_size: Option<usize>,
+    /// `#[tora(test_roundtrip)]`
+    pub test_roundtrip: bool,
+    /// `#[tora(length_prefixed)]`
+    pub length_prefixed: bool,
+    /// `#[tora(frame)]`
+    pub frame: bool,
+    /// `#[tora(presence_bitmap)]`
+    pub presence_bitmap: bool,
+    /// `#[tora(inherent)]`
+    pub inherent: bool,
+}
+
+impl ContainerAttrs {
+    pub fn parse(attributes: &[Attribute]) -> Result<Self> {
+        let mut attrs = Self::default();
+
+        for attribute in tora_attributes(attributes) {
+            attribute.parse_nested_meta(|meta| {
+                if meta.path.is_ident("seed") {
+                    attrs.seed = Some(meta.value()?.parse()?);
+                    return Ok(());
+                }
+                if meta.path.is_ident("builder") {
+                    attrs.builder = true;
+                    return Ok(());
+                }
+                if meta.path.is_ident("length_prefixed") {
+                    attrs.length_prefixed = true;
+                    return Ok(());
+                }
+                if meta.path.is_ident("frame") {
+                    attrs.frame = true;
+                    return Ok(());
+                }
+                if meta.path.is_ident("presence_bitmap") {
+                    attrs.presence_bitmap = true;
+                    return Ok(());
+                }
+                if meta.path.is_ident("inherent") {
+                    attrs.inherent = true;
+                    return Ok(());
+                }
+                if meta.path.is_ident("test_roundtrip") {
+                    attrs.test_roundtrip = true;
+                    return Ok(());
+                }
+                if meta.path.is_ident("repr_c") {
+                    attrs.repr_c = true;
+                    return Ok(());
+                }
+                if meta.path.is_ident("assert_size") {
+                    attrs.assert_size = Some(meta.value()?.parse::<LitInt>()?.base10_parse()?);
+                    return Ok(());
+                }
+                Err(meta.error("Unknown tora container attribute"))
+            })?;
+        }
+        Ok(attrs)
+    }
+}
+
+/// The `#[tora(...)]` attributes applied to an enum variant.
+#[derive(Default)]
+pub struct VariantAttrs {
+    /// `#[tora(fallback)]`
+    pub fallback: bool,
+}
+
+impl VariantAttrs {
+    pub fn parse(variant: &Variant) -> Result<Self> {
+        let mut attrs = Self::default();
+
+        for attribute in tora_attributes(&variant.attrs) {
+            attribute.parse_nested_meta(|meta| {
+                if meta.path.is_ident("fallback") {
+                    attrs.fallback = true;
+                    return Ok(());
+                }
+                Err(meta.error("Unknown tora variant attribute"))
+            })?;
+        }
+        Ok(attrs)
+    }
+}
+
+/// The `#[tora(...)]` attributes applied to a field.
+#[derive(Default)]
+pub struct FieldAttrs {
+    /// `#[tora(seed)]`
+    pub seed: bool,
+    /// `#[tora(default)]` or `#[tora(default = $expr)]`
+    pub default: Option<Option<Expr>>,
+    /// `#[tora(order = N)]`
+    pub order: Option<usize>,
+    /// `#[tora(pad_before = N)]`
+    pub pad_before: usize,
+    /// `#[tora(pad_after = N)]`
+    pub pad_after: usize,
+    /// `#[tora(get = "fn")]`
+    pub get: Option<Ident>,
+    /// `#[tora(set = "fn")]`
+    pub set: Option<Ident>,
+    /// `#[tora(boxed)]`
+    pub boxed: bool,
+    /// `#[tora(extensions)]`
+    pub extensions: bool,
+}
+
+impl FieldAttrs {
+    pub fn parse(field: &Field) -> Result<Self> {
+        let mut attrs = Self::default();
+
+        for attribute in tora_attributes(&field.attrs) {
+            attribute.parse_nested_meta(|meta| {
+                if meta.path.is_ident("seed") {
+                    attrs.seed = true;
+                    return Ok(());
+                }
+                if meta.path.is_ident("default") {
+                    let expr = if meta.input.peek(Token![=]) {
+                        Some(meta.value()?.parse()?)
+                    } else {
+                        None
+                    };
+                    attrs.default = Some(expr);
+                    return Ok(());
+                }
+                if meta.path.is_ident("boxed") {
+                    attrs.boxed = true;
+                    return Ok(());
+                }
+                if meta.path.is_ident("extensions") {
+                    attrs.extensions = true;
+                    return Ok(());
+                }
+                if meta.path.is_ident("order") {
+                    attrs.order = Some(meta.value()?.parse::<LitInt>()?.base10_parse()?);
+                    return Ok(());
+                }
+                if meta.path.is_ident("pad_before") {
+                    attrs.pad_before = meta.value()?.parse::<LitInt>()?.base10_parse()?;
+                    return Ok(());
+                }
+                if meta.path.is_ident("pad_after") {
+                    attrs.pad_after = meta.value()?.parse::<LitInt>()?.base10_parse()?;
+                    return Ok(());
+                }
+                if meta.path.is_ident("get") {
+                    attrs.get = Some(meta.value()?.parse::<LitStr>()?.parse()?);
+                    return Ok(());
+                }
+                if meta.path.is_ident("set") {
+                    attrs.set = Some(meta.value()?.parse::<LitStr>()?.parse()?);
+                    return Ok(());
+                }
+                Err(meta.error("Unknown tora field attribute"))
+            })?;
+        }
+        Ok(attrs)
+    }
+}
@@ -0,0 +1,101 @@
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use syn::{Error, Fields, ItemStruct, Result, Type};
+
+use crate::attrs::FieldAttrs;
+
+/// Returns true if the type is spelled as `Option<...>`.
+fn is_option(ty: &Type) -> bool {
+    match ty {
+        Type::Path(path) => path
+            .path
+            .segments
+            .last()
+            .is_some_and(|segment| segment.ident == "Option"),
+        _ => false,
+    }
+}
+
+/// `#[tora(builder)]` implementation.
+///
+/// Generates a `{Ident}Builder` type with a setter per field, and a `builder` function on the
+/// struct.
+pub fn impl_builder(item: &ItemStruct) -> Result<TokenStream> {
+    let Fields::Named(ref fields) = item.fields else {
+        return Err(Error::new_spanned(
+            item,
+            "#[tora(builder)] can only be applied to structs with named fields",
+        ));
+    };
+
+    let vis = &item.vis;
+    let ident = &item.ident;
+    let builder = format_ident!("{ident}Builder");
+
+    let mut idents = Vec::new();
+    let mut types = Vec::new();
+    let mut defaults = Vec::new();
+
+    for field in &fields.named {
+        let attrs = FieldAttrs::parse(field)?;
+        let field_ident = field.ident.clone().unwrap();
+        let name = field_ident.to_string();
+
+        defaults.push(match attrs.default {
+            Some(Some(expr)) => quote! { #expr },
+            Some(None) => quote! { std::default::Default::default() },
+            None if is_option(&field.ty) => quote! { std::option::Option::None },
+            None => quote! {
+                return std::result::Result::Err(tora::builder::MissingField(#name))
+            },
+        });
+        idents.push(field_ident);
+        types.push(&field.ty);
+    }
+
+    let doc = format!("A builder for [{ident}].");
+    let builder_doc = format!("Returns a builder for [{ident}].");
+    let setter_docs = idents
+        .iter()
+        .map(|i| format!("Sets the `{i}` field."))
+        .collect::<Vec<_>>();
+
+    Ok(quote! {
+        #[doc = #doc]
+        #[derive(Default)]
+        #vis struct #builder {
+            #( #idents: std::option::Option<#types>, )*
+        }
+
+        impl #builder {
+            #(
+            #[doc = #setter_docs]
+            pub fn #idents(mut self, value: impl std::convert::Into<#types>) -> Self {
+                self.#idents = std::option::Option::Some(value.into());
+                self
+            }
+            )*
+
+            /// Builds the value, using the defaults of fields that were not set.
+            ///
+            /// Returns an error naming the first field that was not set and has no default.
+            pub fn build(self) -> std::result::Result<#ident, tora::builder::MissingField> {
+                std::result::Result::Ok(#ident {
+                    #(
+                    #idents: match self.#idents {
+                        std::option::Option::Some(value) => value,
+                        std::option::Option::None => #defaults,
+                    },
+                    )*
+                })
+            }
+        }
+
+        impl #ident {
+            #[doc = #builder_doc]
+            pub fn builder() -> #builder {
+                std::default::Default::default()
+            }
+        }
+    })
+}
@@ -1,153 +1,1647 @@
-use proc_macro2::{Ident, TokenStream};
-use quote::{quote, ToTokens};
-use syn::spanned::Spanned;
-use syn::{Fields, Type, Variant};
+use proc_macro2::{Ident, Span, TokenStream};
+use quote::{format_ident, quote};
+use syn::{
+    parse_quote, Error, Field, Fields, GenericArgument, GenericParam, Generics, Index, Lifetime,
+    LifetimeParam, Member, PathArguments, Result, Type, Variant,
+};
+
+use crate::attrs::{ContainerAttrs, FieldAttrs, VariantAttrs};
 
 /// Generates a `FromReader` implementation for the given `ident`.
-fn impl_from_reader(ident: &Ident, impl_tokens: TokenStream) -> TokenStream {
-    quote! {
-        impl tora::read::FromReader for #ident {
-            fn from_reader<R>(r: &mut R) -> std::io::Result<Self>
-            where R: std::io::Read
-            {
-                #impl_tokens
+///
+/// The `impl_tokens` read from `r` using the `config` in scope, unless a codec is registered for
+/// the type.
+///
+/// If the container has a seed type, generates a `FromReaderSeed` implementation instead, which
+/// reads using the default configuration.
+fn impl_from_reader(
+    ident: &Ident,
+    attrs: &ContainerAttrs,
+    impl_tokens: TokenStream,
+) -> TokenStream {
+    match attrs.seed {
+        Some(ref seed) => quote! {
+            impl tora::read::FromReaderSeed<#seed> for #ident {
+                fn from_reader_seed<R>(r: &mut R, seed: &mut #seed) -> std::io::Result<Self>
+                where R: std::io::Read
+                {
+                    let config = &tora::config::ToraConfig::DEFAULT;
+                    #impl_tokens
+                }
             }
-        }
+        },
+        None => quote! {
+            impl tora::read::FromReader for #ident {
+                fn from_reader<R>(r: &mut R) -> std::io::Result<Self>
+                where R: std::io::Read
+                {
+                    Self::from_reader_with(r, &tora::config::ToraConfig::DEFAULT)
+                }
+
+                fn from_reader_with<R>(
+                    r: &mut R,
+                    config: &tora::config::ToraConfig,
+                ) -> std::io::Result<Self>
+                where R: std::io::Read
+                {
+                    if let std::option::Option::Some(result) = config.read_codec(r) {
+                        return result;
+                    }
+                    #impl_tokens
+                }
+            }
+        },
     }
 }
 
 /// Generates a `SerializeIo` implementation for the given `ident`.
+///
+/// The `impl_tokens` write to `w` using the `config` in scope, unless a codec is registered for
+/// the type.
 fn impl_serialize_io(ident: &Ident, impl_tokens: TokenStream) -> TokenStream {
     quote! {
         impl tora::write::SerializeIo for #ident {
             fn serialize<W>(&self, w: &mut W) -> std::io::Result<()>
             where W: std::io::Write
             {
+                self.serialize_with(w, &tora::config::ToraConfig::DEFAULT)
+            }
+
+            fn serialize_with<W>(
+                &self,
+                w: &mut W,
+                config: &tora::config::ToraConfig,
+            ) -> std::io::Result<()>
+            where W: std::io::Write
+            {
+                if let std::option::Option::Some(result) = config.write_codec(self, w) {
+                    return result;
+                }
                 #impl_tokens
             }
         }
     }
 }
 
-fn to_reads_field(ident: Option<&Ident>) -> TokenStream {
-    match ident {
-        Some(ident) => quote! { #ident: tora::read::ToraRead::reads(r)? },
-        None => quote! { tora::read::ToraRead::reads(r)? },
+/// A field in the order it appears on the wire.
+struct WireField<'a> {
+    field: &'a Field,
+    attrs: FieldAttrs,
+    /// The type written on the wire, which is the marked type of virtual fields, and the optional
+    /// type of fields in the presence bitmap.
+    ty: Type,
+    /// Whether the field is a `PhantomData<T>` marker with `#[tora(get)]` or `#[tora(set)]`, whose
+    /// value is not stored in the struct.
+    is_virtual: bool,
+    /// The bit of the field in the presence bitmap, if it is in it.
+    presence_bit: Option<usize>,
+    /// The field's name, or index in a tuple.
+    member: Member,
+    /// The local variable the field is bound to.
+    binding: Ident,
+}
+
+/// Returns the fields sorted by their wire order.
+///
+/// A field's wire order is its `#[tora(order = N)]` attribute, or its declaration index.
+fn to_wire_fields<'a>(
+    fields: &'a Fields,
+    container: &ContainerAttrs,
+) -> Result<Vec<WireField<'a>>> {
+    let mut wire_fields = Vec::with_capacity(fields.len());
+    let mut orders = Vec::with_capacity(fields.len());
+
+    for (i, field) in fields.iter().enumerate() {
+        let attrs = FieldAttrs::parse(field)?;
+        let order = attrs.order.unwrap_or(i);
+
+        if orders.contains(&order) {
+            return Err(Error::new_spanned(
+                field,
+                format!("Duplicate wire order {order}"),
+            ));
+        }
+        orders.push(order);
+
+        if attrs.boxed && wrapped_type(&field.ty, "Box").is_none() {
+            return Err(Error::new_spanned(
+                &field.ty,
+                "#[tora(boxed)] fields must be of type Box<T>",
+            ));
+        }
+
+        let marked = match attrs.get.is_some() || attrs.set.is_some() {
+            true => wrapped_type(&field.ty, "PhantomData"),
+            false => None,
+        };
+        let is_virtual = marked.is_some();
+
+        let ty = marked.unwrap_or_else(|| field.ty.clone());
+
+        wire_fields.push(WireField {
+            field,
+            attrs,
+            ty,
+            is_virtual,
+            presence_bit: None,
+            member: match field.ident {
+                Some(ref ident) => Member::Named(ident.clone()),
+                None => Member::Unnamed(Index::from(i)),
+            },
+            binding: format_ident!("__field{i}"),
+        });
+    }
+
+    let mut ordered = orders.into_iter().zip(wire_fields).collect::<Vec<_>>();
+    ordered.sort_by_key(|(order, _)| *order);
+
+    let wire_fields = ordered.into_iter().map(|(_, f)| f).collect::<Vec<_>>();
+    check_extensions(&wire_fields, container)?;
+    Ok(wire_fields)
+}
+
+/// Errors if a `#[tora(extensions)]` field is not a `Vec<u8>` last on the wire, is combined with
+/// attributes changing how it is read, or its container does not bound the input it reads to the
+/// end of.
+fn check_extensions(wire_fields: &[WireField], container: &ContainerAttrs) -> Result<()> {
+    for (i, field) in wire_fields.iter().enumerate() {
+        let attrs = &field.attrs;
+
+        if !attrs.extensions {
+            continue;
+        }
+        if i != wire_fields.len() - 1 {
+            return Err(Error::new_spanned(
+                field.field,
+                "#[tora(extensions)] fields must be last in wire order",
+            ));
+        }
+        let is_bytes = wrapped_type(&field.field.ty, "Vec")
+            .is_some_and(|ty| matches!(ty, Type::Path(ref path) if path.path.is_ident("u8")));
+
+        if !is_bytes {
+            return Err(Error::new_spanned(
+                &field.field.ty,
+                "#[tora(extensions)] fields must be of type Vec<u8>",
+            ));
+        }
+        if attrs.seed || attrs.pad_before != 0 || attrs.pad_after != 0 {
+            return Err(Error::new_spanned(
+                field.field,
+                "#[tora(extensions)] cannot be combined with the seed or padding attributes",
+            ));
+        }
+        if !container.length_prefixed && !container.frame {
+            return Err(Error::new_spanned(
+                field.field,
+                "#[tora(extensions)] fields read to the end of the input, so the container must be \
+                 #[tora(length_prefixed)], or #[tora(frame)] if it is only read as a whole frame",
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Errors if a field is `#[tora(extensions)]`, which `derive` does not support.
+fn reject_extensions(fields: &Fields, derive: &str) -> Result<()> {
+    for field in fields {
+        if FieldAttrs::parse(field)?.extensions {
+            return Err(Error::new_spanned(
+                field,
+                format!("{derive} cannot be derived for types with #[tora(extensions)] fields"),
+            ));
+        }
     }
+    Ok(())
 }
 
-fn to_params<I, T>(it: I, fields: &Fields) -> TokenStream
-where
-    I: Iterator<Item = T>,
-    T: ToTokens,
-{
-    match fields {
-        Fields::Named(_) => quote!({ #( #it, )* }),
-        Fields::Unnamed(_) => quote!(( #( #it, )* )),
-        Fields::Unit => TokenStream::new(),
+/// Returns the `T` of a type `$wrapper<T>`, such as `Box<T>`.
+fn wrapped_type(ty: &Type, wrapper: &str) -> Option<Type> {
+    let Type::Path(ref path) = ty else {
+        return None;
+    };
+    let segment = path.path.segments.last().filter(|s| s.ident == wrapper)?;
+
+    match segment.arguments {
+        PathArguments::AngleBracketed(ref args) if args.args.len() == 1 => match args.args[0] {
+            GenericArgument::Type(ref ty) => Some(ty.clone()),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Errors if a virtual field lacks the `#[tora(get)]` or `#[tora(set)]` accessor, as `derive`
+/// cannot otherwise write or read its value.
+fn require_accessor(wire_fields: &[WireField], accessor: &str, derive: &str) -> Result<()> {
+    for field in wire_fields.iter().filter(|f| f.is_virtual) {
+        let present = match accessor {
+            "get" => field.attrs.get.is_some(),
+            _ => field.attrs.set.is_some(),
+        };
+        if !present {
+            return Err(Error::new_spanned(
+                field.field,
+                format!("{derive} requires #[tora({accessor})] on PhantomData fields"),
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Assigns a bit of the presence bitmap to each `Option` field, in wire order.
+///
+/// Returns the amount of bytes of the bitmap.
+fn assign_presence_bits(wire_fields: &mut [WireField]) -> Result<usize> {
+    let mut bits = 0;
+
+    for field in wire_fields {
+        let Some(ty) = wrapped_type(&field.ty, "Option") else {
+            continue;
+        };
+        field.ty = ty;
+        field.presence_bit = Some(bits);
+        bits += 1;
+    }
+    Ok(bits.div_ceil(8))
+}
+
+/// Returns true if the type refers to the container `ident`, by name or as `Self`.
+///
+/// Only unqualified paths are compared, as a qualified path of the same name usually refers to
+/// another type.
+///
+/// With `inline_only`, only types stored inline are searched: options, results, tuples and
+/// arrays. Other types, such as `Box` and `Vec`, hold their contents on the heap.
+fn refers_to(ty: &Type, ident: &Ident, inline_only: bool) -> bool {
+    match ty {
+        Type::Path(path) => {
+            let Some(segment) = path.path.segments.last() else {
+                return false;
+            };
+            if path.path.segments.len() == 1 && (segment.ident == *ident || segment.ident == "Self")
+            {
+                return true;
+            }
+            if inline_only && segment.ident != "Option" && segment.ident != "Result" {
+                return false;
+            }
+            match segment.arguments {
+                PathArguments::AngleBracketed(ref args) => args.args.iter().any(|arg| match arg {
+                    GenericArgument::Type(ty) => refers_to(ty, ident, inline_only),
+                    _ => false,
+                }),
+                _ => false,
+            }
+        }
+        Type::Tuple(tuple) => tuple
+            .elems
+            .iter()
+            .any(|ty| refers_to(ty, ident, inline_only)),
+        Type::Array(array) => refers_to(&array.elem, ident, inline_only),
+        Type::Paren(paren) => refers_to(&paren.elem, ident, inline_only),
+        Type::Group(group) => refers_to(&group.elem, ident, inline_only),
+        Type::Slice(slice) if !inline_only => refers_to(&slice.elem, ident, inline_only),
+        Type::Reference(reference) if !inline_only => {
+            refers_to(&reference.elem, ident, inline_only)
+        }
+        _ => false,
+    }
+}
+
+/// Errors if a field holds the container `ident` inline, which gives it an infinite size.
+fn reject_unboxed_recursion(ident: &Ident, fields: &Fields) -> Result<()> {
+    for field in fields {
+        if refers_to(&field.ty, ident, true) {
+            return Err(Error::new_spanned(
+                &field.ty,
+                format!(
+                    "Recursive fields have an infinite size; hold {ident} in a \
+                     Box<{ident}>, which is written the same as {ident}"
+                ),
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Errors if a field refers to the container `ident`, as the schema of a recursive type would be
+/// infinite.
+fn reject_reflected_recursion(ident: &Ident, fields: &Fields) -> Result<()> {
+    for field in fields {
+        if refers_to(&field.ty, ident, false) {
+            return Err(Error::new_spanned(
+                &field.ty,
+                "Reflect cannot describe recursive types",
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Generates the statements reading a single field into its binding.
+fn to_reads_field(field: &WireField, container: &ContainerAttrs) -> Result<TokenStream> {
+    let binding = &field.binding;
+
+    if field.attrs.extensions {
+        return Ok(quote! {
+            let mut #binding = std::vec::Vec::new();
+            std::io::Read::read_to_end(r, &mut #binding)?;
+        });
+    }
+
+    let ty = &field.ty;
+    let mut reads = if field.attrs.seed {
+        if container.seed.is_none() {
+            return Err(Error::new_spanned(
+                field.field,
+                "#[tora(seed)] requires the container to specify #[tora(seed = $ty)]",
+            ));
+        }
+        quote! { tora::read::ToraRead::reads_seed::<#ty, _>(r, seed)? }
+    } else {
+        quote! { tora::read::ToraRead::reads_with::<#ty>(r, config)? }
+    };
+    if let Some(bit) = field.presence_bit {
+        let (byte, mask) = (bit / 8, 1u8 << (bit % 8));
+        reads = quote! {
+            match presence[#byte] & #mask != 0 {
+                true => std::option::Option::Some(#reads),
+                false => std::option::Option::None,
+            }
+        };
+    }
+
+    let skip = |n: usize| match n {
+        0 => TokenStream::new(),
+        n => quote! { tora::read::ToraRead::skip(r, #n)?; },
+    };
+    let (before, after) = (skip(field.attrs.pad_before), skip(field.attrs.pad_after));
+
+    Ok(quote! {
+        #before
+        let #binding = #reads;
+        #after
+    })
+}
+
+/// Generates the statements writing a single field, given a reference to its `value`.
+fn to_writes_field(field: &WireField, value: TokenStream) -> TokenStream {
+    let pad = |n: usize| match n {
+        0 => TokenStream::new(),
+        n => quote! { tora::write::ToraWrite::pad(w, #n, 0)?; },
+    };
+    let (before, after) = (pad(field.attrs.pad_before), pad(field.attrs.pad_after));
+
+    let writes = match field.presence_bit {
+        _ if field.attrs.extensions => quote! { std::io::Write::write_all(w, #value)?; },
+        Some(_) => quote! {
+            if let std::option::Option::Some(value) = #value {
+                tora::write::ToraWrite::writes_with(w, value, config)?;
+            }
+        },
+        None => quote! { tora::write::ToraWrite::writes_with(w, #value, config)?; },
+    };
+
+    quote! {
+        #before
+        #writes
+        #after
     }
 }
 
-fn to_variant_match(variant_id: usize, ident: &Ident, fields: &Fields) -> TokenStream {
-    let field_iterator = fields.iter().map(|f| to_reads_field(f.ident.as_ref()));
-    let construction_method = to_params(field_iterator, fields);
+/// Generates the statements reading the presence bitmap of `len` bytes into `presence`.
+///
+/// Bits past the last optional field must be clear.
+fn to_read_presence(len: usize, wire_fields: &[WireField]) -> TokenStream {
+    if len == 0 {
+        return TokenStream::new();
+    }
+    let bits = wire_fields
+        .iter()
+        .filter(|f| f.presence_bit.is_some())
+        .count();
+    let unused = match bits % 8 {
+        0 => TokenStream::new(),
+        used => {
+            let last = len - 1;
+            quote! {
+                if presence[#last] >> #used != 0 {
+                    return std::result::Result::Err(std::io::Error::new(
+                        std::io::ErrorKind::InvalidData,
+                        "Unknown bits set in the presence bitmap",
+                    ));
+                }
+            }
+        }
+    };
 
     quote! {
-        #variant_id => Self::#ident #construction_method
+        let mut presence = [0u8; #len];
+        std::io::Read::read_exact(r, &mut presence)?;
+        #unused
     }
 }
 
-fn to_write_variant(variant_id: usize, id_ty: &Type, ident: Ident, fields: Fields) -> TokenStream {
-    let params = fields.iter().enumerate().map(|(i, f)| {
-        f.ident
-            .clone()
-            .unwrap_or_else(|| Ident::new(&format!("x{i}"), f.span()))
-    });
+/// Generates the statements writing the presence bitmap of `len` bytes, given a reference to the
+/// `value` of each field.
+fn to_write_presence(len: usize, wire_fields: &[WireField], values: &[TokenStream]) -> TokenStream {
+    if len == 0 {
+        return TokenStream::new();
+    }
+    let sets = wire_fields.iter().zip(values).filter_map(|(f, value)| {
+        let bit = f.presence_bit?;
+        let (byte, mask) = (bit / 8, 1u8 << (bit % 8));
 
-    let vars = params.clone();
-    let param_style = to_params(params, &fields);
+        Some(quote! {
+            if std::option::Option::is_some(#value) {
+                presence[#byte] |= #mask;
+            }
+        })
+    });
 
     quote! {
-        Self::#ident #param_style => {
-            tora::write::ToraWrite::writes(w, &(#variant_id as #id_ty))?;
-            #( tora::write::ToraWrite::writes(w, #vars)?; )*
+        let mut presence = [0u8; #len];
+        #( #sets )*
+        std::io::Write::write_all(w, &presence)?;
+    }
+}
+
+/// Generates a block reading the fields in wire order, then constructing `path` from them.
+fn to_construction(
+    path: TokenStream,
+    fields: &Fields,
+    container: &ContainerAttrs,
+) -> Result<TokenStream> {
+    let mut wire_fields = to_wire_fields(fields, container)?;
+    require_accessor(&wire_fields, "set", "ReadStruct")?;
+
+    let presence = match container.presence_bitmap {
+        true => to_read_presence(assign_presence_bits(&mut wire_fields)?, &wire_fields),
+        false => TokenStream::new(),
+    };
+    let mut reads = wire_fields
+        .iter()
+        .map(|f| to_reads_field(f, container))
+        .collect::<Result<Vec<_>>>()?;
+
+    if container.repr_c {
+        let skip = |padding| quote! { tora::read::ToraRead::skip(r, #padding)?; };
+        reads = to_repr_c_layout(&wire_fields, reads, skip)?;
+    }
+
+    let values = wire_fields.iter().map(|f| {
+        let (member, binding) = (&f.member, &f.binding);
+        match f.attrs.set.is_some() || f.is_virtual {
+            true => quote!(#member: std::default::Default::default()),
+            false => quote!(#member: #binding),
         }
+    });
+    let setters = wire_fields
+        .iter()
+        .filter_map(|f| {
+            let binding = &f.binding;
+            f.attrs
+                .set
+                .as_ref()
+                .map(|set| quote!(value.#set(#binding);))
+        })
+        .collect::<Vec<_>>();
+
+    if setters.is_empty() {
+        return Ok(quote! {{
+            #presence
+            #( #reads )*
+            #path { #( #values, )* }
+        }});
     }
+
+    Ok(quote! {{
+        #presence
+        #( #reads )*
+        let mut value = #path { #( #values, )* };
+        #( #setters )*
+        value
+    }})
 }
 
-/// `derive(ReadStruct)` implementation for named structs.
-pub fn impl_read_struct_named<I>(ident: Ident, field_idents: I) -> TokenStream
+/// Interleaves the statements of each field with the alignment padding of a `#[repr(C)]` struct.
+///
+/// Each field is asserted at compile time to serialize to its size in memory, so the padding
+/// matches the layout.
+///
+/// `pad` generates the statement handling an amount of padding, given as an expression.
+fn to_repr_c_layout<F>(
+    wire_fields: &[WireField],
+    statements: Vec<TokenStream>,
+    pad: F,
+) -> Result<Vec<TokenStream>>
 where
-    I: Iterator<Item = Ident>,
+    F: Fn(TokenStream) -> TokenStream,
 {
-    let construction_method =
-        quote! { Ok(Self { #( #field_idents: tora::read::ToraRead::reads(r)?, )* }) };
-    impl_from_reader(&ident, construction_method)
+    let mut layout = vec![quote! { let mut layout = tora::layout::ReprC::new(); }];
+
+    for (field, statement) in wire_fields.iter().zip(statements) {
+        let attrs = &field.attrs;
+
+        if attrs.order.is_some()
+            || attrs.pad_before != 0
+            || attrs.pad_after != 0
+            || attrs.extensions
+        {
+            return Err(Error::new_spanned(
+                field.field,
+                "#[tora(repr_c)] cannot be combined with the order, padding or extensions \
+                 attributes",
+            ));
+        }
+
+        let ty = &field.field.ty;
+        layout.push(quote! {
+            const {
+                assert!(
+                    <#ty as tora::layout::ConstSize>::SIZE == std::mem::size_of::<#ty>(),
+                    "#[tora(repr_c)] fields must serialize to their size in memory"
+                )
+            };
+        });
+        layout.push(pad(quote! { layout.field::<#ty>() }));
+        layout.push(statement);
+    }
+
+    layout.push(pad(quote! { layout.finish() }));
+    Ok(layout)
 }
 
-/// `derive(ReadStruct)` implementation for tuple structs.
-pub fn impl_read_struct_tuple<I>(ident: Ident, types: I) -> TokenStream
-where
-    I: Iterator<Item = Type>,
-{
-    let construction_method =
-        quote! { Ok(Self( #( tora::read::ToraRead::reads::<#types>(r)?, )*)) };
-    impl_from_reader(&ident, construction_method)
+/// Errors if the container is `#[tora(length_prefixed)]`, which only applies to enums.
+fn reject_length_prefixed(ident: &Ident, attrs: &ContainerAttrs) -> Result<()> {
+    if attrs.length_prefixed {
+        return Err(Error::new_spanned(
+            ident,
+            "#[tora(length_prefixed)] can only be applied to enums",
+        ));
+    }
+    Ok(())
+}
+
+/// Errors if the container is `#[tora(presence_bitmap)]`, which `derive` does not support.
+fn reject_presence_bitmap(ident: &Ident, attrs: &ContainerAttrs, derive: &str) -> Result<()> {
+    if attrs.presence_bitmap {
+        return Err(Error::new_spanned(
+            ident,
+            format!("{derive} cannot be derived for #[tora(presence_bitmap)] types"),
+        ));
+    }
+    Ok(())
+}
+
+/// Errors if the container is both `#[tora(repr_c)]` and `#[tora(presence_bitmap)]`.
+fn reject_repr_c_presence_bitmap(ident: &Ident, attrs: &ContainerAttrs) -> Result<()> {
+    if attrs.repr_c && attrs.presence_bitmap {
+        return Err(Error::new_spanned(
+            ident,
+            "#[tora(repr_c)] cannot be combined with #[tora(presence_bitmap)]",
+        ));
+    }
+    Ok(())
+}
+
+/// Generates the pattern binding each field to its local variable.
+fn to_pattern(fields: &Fields) -> TokenStream {
+    let bindings = (0..fields.len()).map(|i| format_ident!("__field{i}"));
+
+    match fields {
+        Fields::Named(named) => {
+            let idents = named.named.iter().map(|f| &f.ident);
+            quote!({ #( #idents: #bindings, )* })
+        }
+        Fields::Unnamed(_) => quote!(( #( #bindings, )* )),
+        Fields::Unit => TokenStream::new(),
+    }
+}
+
+/// The variant marked `#[tora(fallback)]`, read in place of unknown variant IDs.
+struct Fallback {
+    variant: Ident,
+    /// The field holding the unknown variant ID, if any.
+    member: Option<Member>,
+}
+
+/// Returns the fallback variant, if any.
+///
+/// Errors if more than one variant is marked, or if the fallback has more than one field.
+fn to_fallback(variants: &[Variant]) -> Result<Option<Fallback>> {
+    let mut fallback = None;
+
+    for variant in variants {
+        if !VariantAttrs::parse(variant)?.fallback {
+            continue;
+        }
+        if fallback.is_some() {
+            return Err(Error::new_spanned(
+                variant,
+                "Only one variant can be marked #[tora(fallback)]",
+            ));
+        }
+        if variant.fields.len() > 1 {
+            return Err(Error::new_spanned(
+                variant,
+                "#[tora(fallback)] variants can have at most one field, holding the variant ID",
+            ));
+        }
+        fallback = Some(Fallback {
+            variant: variant.ident.clone(),
+            member: variant.fields.members().next(),
+        });
+    }
+    Ok(fallback)
+}
+
+/// Errors if any of the fields use `#[tora(get)]` or `#[tora(set)]`, which only apply to structs.
+fn reject_accessors(fields: &Fields) -> Result<()> {
+    for field in fields {
+        let attrs = FieldAttrs::parse(field)?;
+
+        if attrs.get.is_some() || attrs.set.is_some() {
+            return Err(Error::new_spanned(
+                field,
+                "#[tora(get)] and #[tora(set)] can only be applied to struct fields",
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn to_variant_match(
+    variant_id: usize,
+    ident: &Ident,
+    fields: &Fields,
+    container: &ContainerAttrs,
+) -> Result<TokenStream> {
+    reject_accessors(fields)?;
+    let construction = to_construction(quote!(Self::#ident), fields, container)?;
+
+    Ok(quote! {
+        #variant_id => #construction
+    })
+}
+
+fn to_write_variant(
+    variant_id: usize,
+    id_ty: &Type,
+    ident: &Ident,
+    fields: &Fields,
+    container: &ContainerAttrs,
+) -> Result<TokenStream> {
+    reject_accessors(fields)?;
+    let pattern = to_pattern(fields);
+    let writes = to_wire_fields(fields, container)?
+        .iter()
+        .map(|f| {
+            let binding = &f.binding;
+            to_writes_field(f, quote!(#binding))
+        })
+        .collect::<Vec<_>>();
+    let payload = to_variant_payload(quote!(#( #writes )*), container);
+
+    Ok(quote! {
+        Self::#ident #pattern => {
+            tora::write::ToraWrite::writes_with(w, &(#variant_id as #id_ty), config)?;
+            #payload
+        }
+    })
+}
+
+/// Wraps the statements writing a variant's fields.
+///
+/// If the container is `#[tora(length_prefixed)]`, the fields are written to a buffer first, then
+/// written after its length.
+fn to_variant_payload(writes: TokenStream, container: &ContainerAttrs) -> TokenStream {
+    if !container.length_prefixed {
+        return writes;
+    }
+
+    quote! {
+        let mut payload = std::vec::Vec::new();
+        {
+            let w = &mut payload;
+            #writes
+        }
+        config.write_length(w, payload.len())?;
+        std::io::Write::write_all(w, &payload)?;
+    }
+}
+
+/// `derive(ReadStruct)` implementation.
+pub fn impl_read_struct(
+    ident: Ident,
+    attrs: ContainerAttrs,
+    fields: Fields,
+) -> Result<TokenStream> {
+    reject_length_prefixed(&ident, &attrs)?;
+    reject_unboxed_recursion(&ident, &fields)?;
+    reject_repr_c_presence_bitmap(&ident, &attrs)?;
+    let construction = to_construction(quote!(Self), &fields, &attrs)?;
+    Ok(impl_from_reader(
+        &ident,
+        &attrs,
+        quote! { std::result::Result::Ok(#construction) },
+    ))
 }
 
 /// `derive(ReadEnum)` implementation.
-pub fn impl_read_enum<I>(ident: Ident, ty: TokenStream, variants: I) -> TokenStream
+pub fn impl_read_enum<I>(
+    ident: Ident,
+    attrs: ContainerAttrs,
+    ty: Type,
+    variants: I,
+) -> Result<TokenStream>
 where
     I: Iterator<Item = Variant>,
 {
-    let variants = variants
+    reject_presence_bitmap(&ident, &attrs, "ReadEnum")?;
+    if attrs.repr_c {
+        return Err(Error::new_spanned(
+            ident,
+            "#[tora(repr_c)] cannot be applied to enums",
+        ));
+    }
+
+    let variants = variants.collect::<Vec<_>>();
+    for variant in &variants {
+        reject_unboxed_recursion(&ident, &variant.fields)?;
+    }
+    let fallback = to_fallback(&variants)?;
+
+    let arms = variants
+        .iter()
         .enumerate()
-        .map(|(i, v)| to_variant_match(i, &v.ident, &v.fields));
+        .filter(|(_, v)| fallback.as_ref().is_none_or(|f| f.variant != v.ident))
+        .map(|(i, v)| to_variant_match(i, &v.ident, &v.fields, &attrs))
+        .collect::<Result<Vec<_>>>()?;
 
-    impl_from_reader(
+    let fallback_arm = match fallback {
+        Some(Fallback {
+            variant,
+            member: Some(member),
+        }) => quote!(_ => Self::#variant { #member: id }),
+        Some(Fallback {
+            variant,
+            member: None,
+        }) => quote!(_ => Self::#variant),
+        None => quote! {
+            _ => return std::result::Result::Err(
+                std::io::Error::new(std::io::ErrorKind::InvalidInput,
+                format!("Invalid {} variant id", stringify!(#ident)))
+            )
+        },
+    };
+
+    let read_match = quote! {
+        match id as usize {
+            #( #arms, )*
+            #fallback_arm
+        }
+    };
+
+    if !attrs.length_prefixed {
+        return Ok(impl_from_reader(
+            &ident,
+            &attrs,
+            quote! {
+                let id = tora::read::ToraRead::reads_with::<#ty>(r, config)?;
+                std::result::Result::Ok(#read_match)
+            },
+        ));
+    }
+
+    // Fields are read from the payload only, and any bytes left unread are skipped.
+    Ok(impl_from_reader(
         &ident,
+        &attrs,
         quote! {
-            std::result::Result::Ok(match tora::read::ToraRead::reads::<#ty>(r)? as usize {
-                #( #variants, )*
-                _ => return std::result::Result::Err(
-                    std::io::Error::new(std::io::ErrorKind::InvalidInput,
-                    format!("Invalid {} variant id", stringify!(#ident)))
-                )
-            })
+            let id = tora::read::ToraRead::reads_with::<#ty>(r, config)?;
+            let len = config.read_length(r)?;
+            let mut payload = std::io::Read::take(&mut *r, len as u64);
+
+            let value = {
+                let r = &mut payload;
+                #read_match
+            };
+            std::io::copy(&mut payload, &mut std::io::sink())?;
+
+            if payload.limit() != 0 {
+                return std::result::Result::Err(std::io::ErrorKind::UnexpectedEof.into());
+            }
+            std::result::Result::Ok(value)
         },
-    )
+    ))
 }
 
 /// `derive(WriteStruct)` implementation.
-pub fn impl_write_struct<I>(ident: Ident, fields: I) -> TokenStream
-where
-    I: Iterator<Item = TokenStream>,
-{
-    impl_serialize_io(
+pub fn impl_write_struct(
+    ident: Ident,
+    attrs: ContainerAttrs,
+    fields: Fields,
+) -> Result<TokenStream> {
+    reject_length_prefixed(&ident, &attrs)?;
+    reject_unboxed_recursion(&ident, &fields)?;
+    reject_repr_c_presence_bitmap(&ident, &attrs)?;
+    let mut wire_fields = to_wire_fields(&fields, &attrs)?;
+    require_accessor(&wire_fields, "get", "WriteStruct")?;
+
+    let presence_len = match attrs.presence_bitmap {
+        true => Some(assign_presence_bits(&mut wire_fields)?),
+        false => None,
+    };
+
+    let values = wire_fields
+        .iter()
+        .map(|f| match f.attrs.get {
+            Some(ref get) => quote!(&self.#get()),
+            None => {
+                let member = &f.member;
+                quote!(&self.#member)
+            }
+        })
+        .collect::<Vec<_>>();
+    let mut writes = wire_fields
+        .iter()
+        .zip(&values)
+        .map(|(f, value)| to_writes_field(f, value.clone()))
+        .collect::<Vec<_>>();
+
+    if attrs.repr_c {
+        let pad = |padding| quote! { tora::write::ToraWrite::pad(w, #padding, 0)?; };
+        writes = to_repr_c_layout(&wire_fields, writes, pad)?;
+    }
+    let presence = match presence_len {
+        Some(len) => to_write_presence(len, &wire_fields, &values),
+        None => TokenStream::new(),
+    };
+
+    Ok(impl_serialize_io(
         &ident,
         quote! {
-            #( tora::write::ToraWrite::writes(w, &self.#fields)?; )*
+            #presence
+            #( #writes )*
             std::result::Result::Ok(())
         },
-    )
+    ))
 }
 
 /// `derive(WriteEnum)` implementation.
-pub fn impl_write_enum<I>(ident: Ident, id_ty: Type, variants: I) -> TokenStream
+pub fn impl_write_enum<I>(
+    ident: Ident,
+    attrs: ContainerAttrs,
+    id_ty: Type,
+    variants: I,
+) -> Result<TokenStream>
 where
     I: Iterator<Item = Variant>,
 {
+    reject_presence_bitmap(&ident, &attrs, "WriteEnum")?;
+    if attrs.repr_c {
+        return Err(Error::new_spanned(
+            ident,
+            "#[tora(repr_c)] cannot be applied to enums",
+        ));
+    }
+
+    let variants = variants.collect::<Vec<_>>();
+    for variant in &variants {
+        reject_unboxed_recursion(&ident, &variant.fields)?;
+    }
+    let fallback = to_fallback(&variants)?;
+
     let variants = variants
+        .iter()
         .enumerate()
-        .map(|(i, v)| to_write_variant(i, &id_ty, v.ident, v.fields));
+        .map(|(i, v)| match fallback {
+            Some(Fallback {
+                ref variant,
+                member: Some(ref member),
+            }) if *variant == v.ident => {
+                let payload = to_variant_payload(TokenStream::new(), &attrs);
+                Ok(quote! {
+                    Self::#variant { #member: id } => {
+                        tora::write::ToraWrite::writes_with(w, id, config)?;
+                        #payload
+                    }
+                })
+            }
+            _ => to_write_variant(i, &id_ty, &v.ident, &v.fields, &attrs),
+        })
+        .collect::<Result<Vec<_>>>()?;
 
-    impl_serialize_io(
+    Ok(impl_serialize_io(
         &ident,
         quote! {
             match self {
                 #( #variants )*
             }
-            Ok(())
+            std::result::Result::Ok(())
+        },
+    ))
+}
+
+/// Generates a const assertion that the `ConstSize` of `ident` equals `#[tora(assert_size = N)]`.
+pub fn impl_assert_size(ident: &Ident, attrs: &ContainerAttrs) -> TokenStream {
+    let Some(size) = attrs.assert_size else {
+        return TokenStream::new();
+    };
+    let message = format!("The wire size of {ident} is not {size} bytes");
+
+    quote! {
+        const _: () = assert!(
+            <#ident as tora::layout::ConstSize>::SIZE == #size,
+            #message
+        );
+    }
+}
+
+/// Generates a unit test writing the `Default` value of `ident`, reading it back and comparing the
+/// two, if `#[tora(test_roundtrip)]` is set.
+pub fn impl_test_roundtrip(ident: &Ident, attrs: &ContainerAttrs) -> Result<TokenStream> {
+    if !attrs.test_roundtrip {
+        return Ok(TokenStream::new());
+    }
+    if attrs.seed.is_some() {
+        return Err(Error::new_spanned(
+            ident,
+            "#[tora(test_roundtrip)] cannot be combined with #[tora(seed = $ty)]",
+        ));
+    }
+    let test = format_ident!("__tora_roundtrip_{}", ident);
+
+    Ok(quote! {
+        #[cfg(test)]
+        #[test]
+        #[allow(non_snake_case)]
+        fn #test() {
+            let value = <#ident as std::default::Default>::default();
+
+            let mut bytes = std::vec::Vec::new();
+            tora::write::SerializeIo::serialize(&value, &mut bytes).unwrap();
+
+            let mut cursor = std::io::Cursor::new(bytes);
+            let read = <#ident as tora::read::FromReader>::from_reader(&mut cursor).unwrap();
+
+            std::assert_eq!(read, value);
+            std::assert_eq!(
+                cursor.position() as usize,
+                cursor.get_ref().len(),
+                "Not all written bytes were read back"
+            );
+        }
+    })
+}
+
+/// Generates an inherent `from_tora_slice` function reading `ident` from a whole slice, if
+/// `#[tora(inherent)]` is set.
+pub fn impl_inherent_read(ident: &Ident, attrs: &ContainerAttrs) -> Result<TokenStream> {
+    if !attrs.inherent {
+        return Ok(TokenStream::new());
+    }
+    if attrs.seed.is_some() {
+        return Err(Error::new_spanned(
+            ident,
+            "#[tora(inherent)] cannot be combined with #[tora(seed = $ty)]",
+        ));
+    }
+
+    Ok(quote! {
+        impl #ident {
+            /// Reads a value from the whole slice.
+            ///
+            /// Returns `ErrorKind::InvalidData` if bytes are left after the value.
+            pub fn from_tora_slice(bytes: &[u8]) -> std::io::Result<Self> {
+                let mut bytes = bytes;
+                let value = <Self as tora::read::FromReader>::from_reader(&mut bytes)?;
+
+                if !bytes.is_empty() {
+                    return std::result::Result::Err(std::io::Error::new(
+                        std::io::ErrorKind::InvalidData,
+                        "Slice contains trailing bytes",
+                    ));
+                }
+                std::result::Result::Ok(value)
+            }
+        }
+    })
+}
+
+/// Generates an inherent `to_tora_vec` method writing `ident` to a new `Vec`, if
+/// `#[tora(inherent)]` is set.
+pub fn impl_inherent_write(ident: &Ident, attrs: &ContainerAttrs) -> TokenStream {
+    if !attrs.inherent {
+        return TokenStream::new();
+    }
+
+    quote! {
+        impl #ident {
+            /// Writes the value to a new `Vec`.
+            pub fn to_tora_vec(&self) -> std::io::Result<std::vec::Vec<u8>> {
+                let mut bytes = std::vec::Vec::new();
+                tora::write::SerializeIo::serialize(self, &mut bytes)?;
+                std::result::Result::Ok(bytes)
+            }
+        }
+    }
+}
+
+/// `derive(ConstSize)` implementation for structs.
+pub fn impl_const_size_struct(
+    ident: Ident,
+    attrs: ContainerAttrs,
+    fields: Fields,
+) -> Result<TokenStream> {
+    reject_presence_bitmap(&ident, &attrs, "ConstSize")?;
+    reject_extensions(&fields, "ConstSize")?;
+    let wire_fields = to_wire_fields(&fields, &attrs)?;
+
+    let size = if attrs.repr_c {
+        let types = wire_fields.iter().map(|f| &f.field.ty);
+        let assertions = types.clone().map(|ty| {
+            quote! {
+                assert!(
+                    <#ty as tora::layout::ConstSize>::SIZE == std::mem::size_of::<#ty>(),
+                    "#[tora(repr_c)] fields must serialize to their size in memory"
+                );
+            }
+        });
+
+        quote! {{
+            #( #assertions )*
+
+            let mut layout = tora::layout::ReprC::new();
+            #( layout.field::<#types>(); )*
+            layout.finish();
+            layout.size()
+        }}
+    } else {
+        let sizes = wire_fields.iter().map(|f| {
+            let ty = &f.ty;
+            let padding = f.attrs.pad_before + f.attrs.pad_after;
+            quote! { #padding + <#ty as tora::layout::ConstSize>::SIZE }
+        });
+        quote! { 0 #( + #sizes )* }
+    };
+
+    Ok(quote! {
+        impl tora::layout::ConstSize for #ident {
+            const SIZE: usize = #size;
+        }
+    })
+}
+
+/// `derive(ConstSize)` implementation for enums.
+///
+/// Only enums without fields have a constant size, that of their variant ID.
+pub fn impl_const_size_enum<I>(
+    ident: Ident,
+    attrs: ContainerAttrs,
+    id_ty: Type,
+    variants: I,
+) -> Result<TokenStream>
+where
+    I: Iterator<Item = Variant>,
+{
+    reject_presence_bitmap(&ident, &attrs, "ConstSize")?;
+    if attrs.length_prefixed {
+        return Err(Error::new_spanned(
+            ident,
+            "ConstSize cannot be derived for #[tora(length_prefixed)] enums",
+        ));
+    }
+    for variant in variants {
+        if !variant.fields.is_empty() {
+            return Err(Error::new_spanned(
+                variant,
+                "ConstSize can only be derived for enums without fields",
+            ));
+        }
+    }
+
+    Ok(quote! {
+        impl tora::layout::ConstSize for #ident {
+            const SIZE: usize = <#id_ty as tora::layout::ConstSize>::SIZE;
+        }
+    })
+}
+
+/// Generates a `Skip` implementation for the given `ident`.
+///
+/// The `impl_tokens` skip past the value in `r` using the `config` in scope. If a codec is
+/// registered for the type, the value is read with it instead.
+fn impl_skip(ident: &Ident, impl_tokens: TokenStream) -> TokenStream {
+    quote! {
+        impl tora::skip::Skip for #ident {
+            fn skip_with<R>(
+                r: &mut R,
+                config: &tora::config::ToraConfig,
+            ) -> std::io::Result<()>
+            where R: tora::skip::SkipRead
+            {
+                if let std::option::Option::Some(result) = config.read_codec::<Self, _>(r) {
+                    return result.map(std::mem::drop);
+                }
+                #impl_tokens
+            }
+        }
+    }
+}
+
+/// Errors if the container or any of the fields are seeded, as the seed may be needed to skip
+/// them.
+fn reject_seeds(ident: &Ident, attrs: &ContainerAttrs, fields: &Fields) -> Result<()> {
+    if attrs.seed.is_some() {
+        return Err(Error::new_spanned(
+            ident,
+            "Skip cannot be derived for #[tora(seed = $ty)] types",
+        ));
+    }
+    for field in fields {
+        if FieldAttrs::parse(field)?.seed {
+            return Err(Error::new_spanned(
+                field,
+                "Skip cannot be derived for types with #[tora(seed)] fields",
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Generates the statements skipping a single field.
+fn to_skips_field(field: &WireField) -> TokenStream {
+    if field.attrs.extensions {
+        return quote! { std::io::copy(r, &mut std::io::sink())?; };
+    }
+
+    let ty = &field.ty;
+    let mut skips = quote! { <#ty as tora::skip::Skip>::skip_with(r, config)?; };
+
+    if let Some(bit) = field.presence_bit {
+        let (byte, mask) = (bit / 8, 1u8 << (bit % 8));
+        skips = quote! {
+            if presence[#byte] & #mask != 0 {
+                #skips
+            }
+        };
+    }
+
+    let skip = |n: usize| match n {
+        0 => TokenStream::new(),
+        n => quote! { tora::skip::SkipRead::skip_bytes(r, #n)?; },
+    };
+    let (before, after) = (skip(field.attrs.pad_before), skip(field.attrs.pad_after));
+
+    quote! {
+        #before
+        #skips
+        #after
+    }
+}
+
+/// Generates the statements skipping the fields in wire order.
+fn to_skips(fields: &Fields, container: &ContainerAttrs) -> Result<TokenStream> {
+    let mut wire_fields = to_wire_fields(fields, container)?;
+    let presence = match container.presence_bitmap {
+        true => to_read_presence(assign_presence_bits(&mut wire_fields)?, &wire_fields),
+        false => TokenStream::new(),
+    };
+    let mut skips = wire_fields.iter().map(to_skips_field).collect::<Vec<_>>();
+
+    if container.repr_c {
+        let skip = |padding| quote! { tora::skip::SkipRead::skip_bytes(r, #padding)?; };
+        skips = to_repr_c_layout(&wire_fields, skips, skip)?;
+    }
+
+    Ok(quote! {
+        #presence
+        #( #skips )*
+    })
+}
+
+/// `derive(Skip)` implementation for structs.
+pub fn impl_skip_struct(
+    ident: Ident,
+    attrs: ContainerAttrs,
+    fields: Fields,
+) -> Result<TokenStream> {
+    reject_length_prefixed(&ident, &attrs)?;
+    reject_repr_c_presence_bitmap(&ident, &attrs)?;
+    reject_seeds(&ident, &attrs, &fields)?;
+    let skips = to_skips(&fields, &attrs)?;
+
+    Ok(impl_skip(
+        &ident,
+        quote! {
+            #skips
+            std::result::Result::Ok(())
+        },
+    ))
+}
+
+/// `derive(Skip)` implementation for enums.
+///
+/// The fields of `#[tora(length_prefixed)]` enums are skipped past their length prefix, whatever
+/// their variant.
+pub fn impl_skip_enum<I>(
+    ident: Ident,
+    attrs: ContainerAttrs,
+    ty: Type,
+    variants: I,
+) -> Result<TokenStream>
+where
+    I: Iterator<Item = Variant>,
+{
+    reject_presence_bitmap(&ident, &attrs, "Skip")?;
+    if attrs.repr_c {
+        return Err(Error::new_spanned(
+            ident,
+            "#[tora(repr_c)] cannot be applied to enums",
+        ));
+    }
+
+    let variants = variants.collect::<Vec<_>>();
+    for variant in &variants {
+        reject_seeds(&ident, &attrs, &variant.fields)?;
+    }
+
+    if attrs.length_prefixed {
+        return Ok(impl_skip(
+            &ident,
+            quote! {
+                tora::read::ToraRead::reads_with::<#ty>(r, config)?;
+                let len = config.read_length(r)?;
+                tora::skip::SkipRead::skip_bytes(r, len)
+            },
+        ));
+    }
+
+    let fallback = to_fallback(&variants)?;
+    let arms = variants
+        .iter()
+        .enumerate()
+        .filter(|(_, v)| fallback.as_ref().is_none_or(|f| f.variant != v.ident))
+        .map(|(i, v)| {
+            let skips = to_skips(&v.fields, &attrs)?;
+            Ok(quote!(#i => { #skips }))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    // The fallback variant holds the unknown ID, and has no fields on the wire.
+    let fallback_arm = match fallback {
+        Some(_) => quote!(_ => {}),
+        None => quote! {
+            _ => return std::result::Result::Err(
+                std::io::Error::new(std::io::ErrorKind::InvalidInput,
+                format!("Invalid {} variant id", stringify!(#ident)))
+            )
         },
-    )
+    };
+
+    Ok(impl_skip(
+        &ident,
+        quote! {
+            let id = tora::read::ToraRead::reads_with::<#ty>(r, config)?;
+            match id as usize {
+                #( #arms, )*
+                #fallback_arm
+            }
+            std::result::Result::Ok(())
+        },
+    ))
+}
+
+/// Generates the expressions constructing the `FieldSchema` of each field, in wire order.
+///
+/// If the container is `#[tora(repr_c)]`, the alignment padding is computed at runtime.
+fn to_field_schemas(fields: &Fields, container: &ContainerAttrs) -> Result<TokenStream> {
+    let wire_fields = to_wire_fields(fields, container)?;
+
+    let schemas = wire_fields.iter().map(|f| {
+        let ty = &f.ty;
+        let name = match f.member {
+            Member::Named(ref ident) => ident.to_string(),
+            Member::Unnamed(ref index) => index.index.to_string(),
+        };
+        let (before, after) = (f.attrs.pad_before, f.attrs.pad_after);
+
+        let padding = if container.repr_c {
+            quote!(layout.field::<#ty>(), 0)
+        } else {
+            quote!(#before, #after)
+        };
+        quote! {
+            tora::schema::FieldSchema::new(#name, <#ty as tora::schema::Reflect>::schema())
+                .with_padding(#padding)
+        }
+    });
+
+    if !container.repr_c {
+        return Ok(quote!(vec![#( #schemas ),*]));
+    }
+
+    Ok(quote! {{
+        let mut layout = tora::layout::ReprC::new();
+        let mut fields = vec![#( #schemas ),*];
+
+        let tail = layout.finish();
+        if let Some(last) = fields.last_mut() {
+            last.pad_after += tail;
+        }
+        fields
+    }})
+}
+
+/// `derive(Reflect)` implementation for structs.
+pub fn impl_reflect_struct(
+    ident: Ident,
+    attrs: ContainerAttrs,
+    fields: Fields,
+) -> Result<TokenStream> {
+    reject_presence_bitmap(&ident, &attrs, "Reflect")?;
+    reject_reflected_recursion(&ident, &fields)?;
+    reject_extensions(&fields, "Reflect")?;
+    let name = ident.to_string();
+    let fields = to_field_schemas(&fields, &attrs)?;
+
+    Ok(quote! {
+        impl tora::schema::Reflect for #ident {
+            fn schema() -> tora::schema::Schema {
+                tora::schema::Schema::Struct(tora::schema::StructSchema::new(#name, #fields))
+            }
+        }
+    })
+}
+
+/// `derive(Reflect)` implementation for enums.
+///
+/// A `#[tora(fallback)]` variant is described without fields, as its field is not on the wire,
+/// and marked as read in place of unknown variant IDs.
+pub fn impl_reflect_enum<I>(
+    ident: Ident,
+    attrs: ContainerAttrs,
+    id_ty: Type,
+    variants: I,
+) -> Result<TokenStream>
+where
+    I: Iterator<Item = Variant>,
+{
+    reject_presence_bitmap(&ident, &attrs, "Reflect")?;
+    let name = ident.to_string();
+    let length_prefixed = attrs.length_prefixed;
+
+    let variants = variants
+        .enumerate()
+        .map(|(i, v)| {
+            reject_reflected_recursion(&ident, &v.fields)?;
+            reject_extensions(&v.fields, "Reflect")?;
+            let name = v.ident.to_string();
+            let id = i as u64;
+            if VariantAttrs::parse(&v)?.fallback {
+                return Ok(quote! {
+                    tora::schema::VariantSchema::new(#name, #id, vec![]).as_fallback()
+                });
+            }
+            let fields = to_field_schemas(&v.fields, &attrs)?;
+            Ok(quote!(tora::schema::VariantSchema::new(#name, #id, #fields)))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(quote! {
+        impl tora::schema::Reflect for #ident {
+            fn schema() -> tora::schema::Schema {
+                let mut schema = tora::schema::EnumSchema::new(
+                    #name,
+                    <#id_ty as tora::schema::Reflect>::schema(),
+                    vec![#( #variants ),*],
+                );
+                schema.length_prefixed = #length_prefixed;
+                tora::schema::Schema::Enum(schema)
+            }
+        }
+    })
+}
+
+/// `derive(Columnar)` implementation.
+///
+/// Each column is written one value at a time, and read in bulk with `FromReader::from_reader_vec`
+/// before the structs are assembled row by row.
+pub fn impl_columnar(ident: Ident, attrs: ContainerAttrs, fields: Fields) -> Result<TokenStream> {
+    reject_presence_bitmap(&ident, &attrs, "Columnar")?;
+    reject_length_prefixed(&ident, &attrs)?;
+
+    if attrs.repr_c || attrs.seed.is_some() {
+        return Err(Error::new_spanned(
+            ident,
+            "Columnar cannot be derived for #[tora(repr_c)] or seeded structs",
+        ));
+    }
+
+    let wire_fields = to_wire_fields(&fields, &attrs)?;
+    require_accessor(&wire_fields, "get", "Columnar")?;
+    require_accessor(&wire_fields, "set", "Columnar")?;
+
+    for field in &wire_fields {
+        let attrs = &field.attrs;
+
+        if attrs.seed || attrs.extensions || attrs.pad_before != 0 || attrs.pad_after != 0 {
+            return Err(Error::new_spanned(
+                field.field,
+                "Columnar fields cannot use the seed, extensions or padding attributes",
+            ));
+        }
+    }
+
+    let writes = wire_fields.iter().map(|f| {
+        let value = match f.attrs.get {
+            Some(ref get) => quote!(&value.#get()),
+            None => {
+                let member = &f.member;
+                quote!(&value.#member)
+            }
+        };
+        quote! {
+            for value in values {
+                tora::write::ToraWrite::writes_with(w, #value, config)?;
+            }
+        }
+    });
+
+    let columns = (0..wire_fields.len())
+        .map(|i| format_ident!("__column{i}"))
+        .collect::<Vec<_>>();
+    let reads = wire_fields.iter().zip(&columns).map(|(f, column)| {
+        let ty = &f.ty;
+        quote! {
+            let mut #column = <#ty as tora::read::FromReader>::from_reader_vec(r, len, config)?
+                .into_iter();
+        }
+    });
+    let nexts = wire_fields.iter().zip(&columns).map(|(f, column)| {
+        let binding = &f.binding;
+        quote! {
+            let #binding = #column.next().ok_or(std::io::ErrorKind::UnexpectedEof)?;
+        }
+    });
+
+    let values = wire_fields.iter().map(|f| {
+        let (member, binding) = (&f.member, &f.binding);
+        match f.attrs.set.is_some() || f.is_virtual {
+            true => quote!(#member: std::default::Default::default()),
+            false => quote!(#member: #binding),
+        }
+    });
+    let setters = wire_fields.iter().filter_map(|f| {
+        let binding = &f.binding;
+        f.attrs
+            .set
+            .as_ref()
+            .map(|set| quote!(value.#set(#binding);))
+    });
+
+    Ok(quote! {
+        impl tora::columnar::Columnar for #ident {
+            fn write_columns<W>(
+                values: &[Self],
+                w: &mut W,
+                config: &tora::config::ToraConfig,
+            ) -> std::io::Result<()>
+            where W: std::io::Write
+            {
+                #( #writes )*
+                std::result::Result::Ok(())
+            }
+
+            fn read_columns<R>(
+                r: &mut R,
+                len: usize,
+                config: &tora::config::ToraConfig,
+            ) -> std::io::Result<std::vec::Vec<Self>>
+            where R: std::io::Read
+            {
+                #( #reads )*
+                let mut values = std::vec::Vec::new();
+                tora::__private::reserve(&mut values, len)?;
+
+                for _ in 0..len {
+                    #( #nexts )*
+                    #[allow(unused_mut)]
+                    let mut value = Self { #( #values, )* };
+                    #( #setters )*
+                    values.push(value);
+                }
+                std::result::Result::Ok(values)
+            }
+        }
+    })
+}
+
+/// `derive(FromSlice)` implementation.
+///
+/// The struct's lifetime parameter is the lifetime of the slice. Structs without one borrow
+/// nothing and are implemented for any slice lifetime.
+pub fn impl_from_slice(
+    ident: Ident,
+    generics: Generics,
+    attrs: ContainerAttrs,
+    fields: Fields,
+) -> Result<TokenStream> {
+    reject_presence_bitmap(&ident, &attrs, "FromSlice")?;
+    reject_length_prefixed(&ident, &attrs)?;
+
+    if attrs.repr_c || attrs.seed.is_some() {
+        return Err(Error::new_spanned(
+            ident,
+            "FromSlice cannot be derived for #[tora(repr_c)] or seeded structs",
+        ));
+    }
+
+    let mut impl_generics = generics.clone();
+    let lifetime = match generics.lifetimes().count() {
+        0 => {
+            let lifetime = Lifetime::new("'__tora", Span::call_site());
+            impl_generics.params.insert(
+                0,
+                GenericParam::Lifetime(LifetimeParam::new(lifetime.clone())),
+            );
+            lifetime
+        }
+        1 => generics.lifetimes().next().unwrap().lifetime.clone(),
+        _ => {
+            return Err(Error::new_spanned(
+                generics,
+                "FromSlice can only be derived for structs with at most one lifetime parameter",
+            ))
+        }
+    };
+
+    let wire_fields = to_wire_fields(&fields, &attrs)?;
+    let mut reads = Vec::with_capacity(wire_fields.len());
+
+    for field in &wire_fields {
+        let attrs = &field.attrs;
+
+        if attrs.seed || attrs.get.is_some() || attrs.set.is_some() {
+            return Err(Error::new_spanned(
+                field.field,
+                "FromSlice fields cannot use the seed, get or set attributes",
+            ));
+        }
+
+        let (ty, binding) = (&field.field.ty, &field.binding);
+        if attrs.extensions {
+            reads.push(quote! {
+                let (#binding, rest) = (rest.to_vec(), &rest[rest.len()..]);
+            });
+            continue;
+        }
+
+        let skip = |n: usize| match n {
+            0 => TokenStream::new(),
+            n => quote! { let rest = tora::slice::skip(rest, #n)?; },
+        };
+        let (before, after) = (skip(attrs.pad_before), skip(attrs.pad_after));
+
+        reads.push(quote! {
+            #before
+            let (#binding, rest) =
+                <#ty as tora::slice::FromSlice<#lifetime>>::from_slice_with(rest, config)?;
+            #after
+        });
+    }
+
+    let where_clause = impl_generics.make_where_clause();
+    for field in wire_fields.iter().filter(|f| !f.attrs.extensions) {
+        let ty = &field.field.ty;
+        where_clause
+            .predicates
+            .push(parse_quote!(#ty: tora::slice::FromSlice<#lifetime>));
+    }
+
+    let values = wire_fields.iter().map(|f| {
+        let (member, binding) = (&f.member, &f.binding);
+        quote!(#member: #binding)
+    });
+
+    let (impl_generics, _, where_clause) = impl_generics.split_for_impl();
+    let (_, ty_generics, _) = generics.split_for_impl();
+
+    Ok(quote! {
+        impl #impl_generics tora::slice::FromSlice<#lifetime> for #ident #ty_generics
+        #where_clause
+        {
+            fn from_slice_with(
+                bytes: &#lifetime [u8],
+                config: &tora::config::ToraConfig,
+            ) -> std::io::Result<(Self, &#lifetime [u8])> {
+                let rest = bytes;
+                #( #reads )*
+                std::result::Result::Ok((Self { #( #values, )* }, rest))
+            }
+        }
+    })
 }
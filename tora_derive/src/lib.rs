@@ -1,12 +1,15 @@
 use proc_macro::TokenStream;
-use quote::ToTokens;
+use quote::{quote, ToTokens};
 use syn::parse::Parse;
-use syn::spanned::Spanned;
-use syn::{
-    parse_macro_input, parse_quote, Attribute, Error, Fields, ItemEnum, ItemStruct, LitInt, Type,
-};
+use syn::{parse_macro_input, parse_quote, Attribute, Error, Item, ItemEnum, ItemStruct, Type};
 
+use crate::attrs::ContainerAttrs;
+
+mod attrs;
+mod builder;
 mod derive_impl;
+mod patch;
+mod service;
 
 fn get_list_attr_or_default<T>(key: &str, default: T, attributes: &[Attribute]) -> T
 where
@@ -25,6 +28,45 @@ where
     default
 }
 
+/// Returns the type representing the enum variant ID.
+///
+/// Returns an error if the type is a primitive integer too narrow to represent the ID of every
+/// variant, as the IDs would otherwise be silently truncated.
+fn variant_id_type(item: &ItemEnum) -> syn::Result<Type> {
+    let ty: Type = get_list_attr_or_default("type_variant_id", parse_quote!(u8), &item.attrs);
+
+    let capacity: usize = match ty.to_token_stream().to_string().as_str() {
+        "u8" => 1 << 8,
+        "i8" => 1 << 7,
+        "u16" => 1 << 16,
+        "i16" => 1 << 15,
+        _ => usize::MAX,
+    };
+
+    if item.variants.len() <= capacity {
+        return Ok(ty);
+    }
+
+    let specified = item
+        .attrs
+        .iter()
+        .any(|attribute| attribute.path().is_ident("type_variant_id"));
+
+    let message = if specified {
+        format!(
+            "This enum has {} variants, which cannot all be represented by the variant ID type",
+            item.variants.len()
+        )
+    } else {
+        format!(
+            "This enum has {} variants, more than the default u8 variant ID can represent; \
+             specify a wider type with #[type_variant_id($ty)]",
+            item.variants.len()
+        )
+    };
+    Err(Error::new_spanned(&item.ident, message))
+}
+
 fn derive_empty_item_error<T>(tokens: T) -> TokenStream
 where
     T: ToTokens,
@@ -59,8 +101,73 @@ where
 ///
 /// By default, this macro assumes [u8].
 ///
-/// In the case that the enum deriving this macro contains more than [u8::MAX] variants, the user
-/// will be required to specify this attribute manually.
+/// In the case that the enum deriving this macro contains more than 256 variants, the user
+/// will be required to specify this attribute manually; compilation fails otherwise. The same
+/// applies to any primitive integer too narrow to represent every variant ID.
+///
+/// ## `tora(seed = $ty)`
+///
+/// Generates a `FromReaderSeed<$ty>` implementation instead of `FromReader`.
+///
+/// Fields marked with `#[tora(seed)]` are read with the seed, see [ReadStruct].
+///
+/// ## `tora(fallback)`
+///
+/// Applied to a variant, reads unknown variant IDs as this variant instead of failing. The variant
+/// can have a single field of the variant ID type, which receives the unknown ID and is written
+/// back as-is by `WriteEnum`.
+///
+/// This suits `#[non_exhaustive]` enums, whose peers may send variants added in later versions.
+/// The generated code lives in the crate defining the enum, so `#[non_exhaustive]` types need no
+/// other special handling.
+///
+/// ```
+/// use std::io::Cursor;
+///
+/// use tora::read::ToraRead;
+/// use tora_derive::ReadEnum;
+///
+/// #[derive(Debug, PartialEq, ReadEnum)]
+/// #[non_exhaustive]
+/// enum Packet {
+///     Ping,
+///     Pong,
+///     #[tora(fallback)]
+///     Unknown(u8),
+/// }
+///
+/// let packet: Packet = Cursor::new([9]).reads().unwrap();
+/// assert_eq!(packet, Packet::Unknown(9));
+/// ```
+///
+/// ## `tora(length_prefixed)`
+///
+/// Prefixes the fields of each variant with their length in bytes, written after the variant ID
+/// using the configured `LengthPrefix`. Also supported by `WriteEnum`.
+///
+/// Readers skip any bytes of the payload left unread, so variants may gain trailing fields in
+/// later versions. Combined with `tora(fallback)`, the payload of unknown variants is skipped
+/// precisely.
+///
+/// ```
+/// use std::io::Cursor;
+///
+/// use tora::read::ToraRead;
+/// use tora_derive::ReadEnum;
+///
+/// #[derive(Debug, PartialEq, ReadEnum)]
+/// #[tora(length_prefixed)]
+/// enum Packet {
+///     Ping(u8),
+///     #[tora(fallback)]
+///     Unknown(u8),
+/// }
+///
+/// let mut reader = Cursor::new([7, 2, 0, 0, 0, 0xAA, 0xBB, 0, 1, 0, 0, 0, 5]);
+///
+/// assert_eq!(reader.reads::<Packet>().unwrap(), Packet::Unknown(7));
+/// assert_eq!(reader.reads::<Packet>().unwrap(), Packet::Ping(5));
+/// ```
 ///
 /// # Usage
 ///
@@ -101,7 +208,7 @@ where
 ///     }
 /// }
 /// ```
-#[proc_macro_derive(ReadEnum, attributes(type_variant_id))]
+#[proc_macro_derive(ReadEnum, attributes(type_variant_id, tora))]
 pub fn derive_read_enum(tokens: TokenStream) -> TokenStream {
     let item = parse_macro_input!(tokens as ItemEnum);
 
@@ -109,14 +216,340 @@ pub fn derive_read_enum(tokens: TokenStream) -> TokenStream {
         return derive_empty_item_error(item);
     }
 
-    let path = get_list_attr_or_default("type_variant_id", parse_quote!(u8), &item.attrs);
-    derive_impl::impl_read_enum(item.ident, path, item.variants.into_iter()).into()
+    variant_id_type(&item)
+        .and_then(|ty| Ok((ty, ContainerAttrs::parse(&item.attrs)?)))
+        .and_then(|(ty, attrs)| {
+            let inherent = derive_impl::impl_inherent_read(&item.ident, &attrs)?;
+            let from_reader =
+                derive_impl::impl_read_enum(item.ident, attrs, ty, item.variants.into_iter())?;
+            Ok(quote!(#from_reader #inherent))
+        })
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
 }
 
 /// The `ReadStruct` derive macro generates a `FromReader` implementation for structs.
 ///
 /// For enums, use [ReadEnum].
 ///
+/// # Attributes
+///
+/// ## `tora(seed = $ty)`
+///
+/// Generates a `FromReaderSeed<$ty>` implementation instead of `FromReader`, allowing external
+/// state to be passed into the deserialization of chosen fields.
+///
+/// ## `tora(seed)`
+///
+/// Applied to a field, reads the field through `FromReaderSeed` using the container's seed.
+/// Unmarked fields are read through `FromReader` as usual.
+///
+/// ```
+/// use std::io;
+/// use std::io::{Cursor, Read};
+///
+/// use tora::read::{FromReaderSeed, ToraRead};
+/// use tora_derive::ReadStruct;
+///
+/// struct StringTable(Vec<String>);
+///
+/// struct Name(String);
+///
+/// impl FromReaderSeed<StringTable> for Name {
+///     fn from_reader_seed<R>(r: &mut R, seed: &mut StringTable) -> io::Result<Self>
+///     where
+///         R: Read,
+///     {
+///         let index = r.reads::<u8>()? as usize;
+///         Ok(Self(seed.0[index].clone()))
+///     }
+/// }
+///
+/// #[derive(ReadStruct)]
+/// #[tora(seed = StringTable)]
+/// struct Player {
+///     id: u8,
+///     #[tora(seed)]
+///     name: Name,
+/// }
+///
+/// fn main() -> io::Result<()> {
+///     let mut table = StringTable(vec!["John".to_string()]);
+///     let player: Player = Cursor::new([7, 0]).reads_seed(&mut table)?;
+///
+///     assert_eq!(player.name.0, "John");
+///     Ok(())
+/// }
+/// ```
+///
+/// ## `tora(order = N)`
+///
+/// Applied to a field, sets its position on the wire independently of its position in the
+/// struct. Fields are read and written in ascending order; fields without this attribute use
+/// their declaration index. Two fields with the same order are rejected.
+///
+/// The same order must be used by the `WriteStruct` derive, which honors this attribute as well.
+///
+/// ```
+/// use tora_derive::{ReadStruct, WriteStruct};
+///
+/// // Written as `length`, then `kind`, then `checksum`.
+/// #[derive(Debug, PartialEq, ReadStruct, WriteStruct)]
+/// struct Header {
+///     #[tora(order = 1)]
+///     kind: u8,
+///     #[tora(order = 2)]
+///     checksum: u32,
+///     #[tora(order = 0)]
+///     length: u16,
+/// }
+///
+/// let header = Header { kind: 1, checksum: 2, length: 3 };
+///
+/// tora::assert_bytes_eq!(header, "03 00 01 02 00 00 00");
+/// tora::assert_roundtrip!(header);
+/// ```
+///
+/// ## `tora(repr_c)`
+///
+/// Applied to the struct, inserts the alignment padding a `#[repr(C)]` struct would have between
+/// fields and at its end, so the wire format matches the struct's memory layout byte-for-byte.
+/// Every field must implement `ConstSize` and serialize to exactly `size_of` its type: integers,
+/// floats, arrays of them, and other `#[tora(repr_c)]` structs deriving `ConstSize`. Other fields
+/// are rejected at compile time, including `usize` and `isize` on targets where they are not 8
+/// bytes. Combine with a big-endian `ToraConfig` to match big-endian targets.
+///
+/// ```
+/// use tora_derive::{ReadStruct, WriteStruct};
+///
+/// // Matches `struct Sample { uint8_t channel; float value; uint16_t flags; }` in C.
+/// #[derive(ReadStruct, WriteStruct)]
+/// #[tora(repr_c)]
+/// #[repr(C)]
+/// struct Sample {
+///     channel: u8,
+///     value: f32,
+///     flags: u16,
+/// }
+/// ```
+///
+/// ```compile_fail
+/// use tora_derive::WriteStruct;
+///
+/// // Strings are length-prefixed, so have no size in memory to match.
+/// #[derive(WriteStruct)]
+/// #[tora(repr_c)]
+/// #[repr(C)]
+/// struct Named {
+///     id: u8,
+///     name: String,
+/// }
+/// ```
+///
+/// ## `tora(presence_bitmap)`
+///
+/// Applied to the struct, writes a bitmap of the `Option` fields before the fields, one bit per
+/// field in wire order, set if the field is `Some`. The fields are then written without the bool
+/// each `Option` is normally prefixed with, and `None` fields are not written at all. Also
+/// supported by `WriteStruct`.
+///
+/// Bits are packed starting from the least significant bit of the first byte, and the bitmap
+/// takes as many bytes as needed. Reading fails if a bit past the last `Option` field is set.
+///
+/// ```
+/// use tora_derive::{ReadStruct, WriteStruct};
+///
+/// #[derive(Debug, PartialEq, ReadStruct, WriteStruct)]
+/// #[tora(presence_bitmap)]
+/// struct Update {
+///     id: u8,
+///     health: Option<u8>,
+///     position: Option<(u8, u8)>,
+///     name: Option<String>,
+/// }
+///
+/// let update = Update { id: 7, health: None, position: Some((1, 2)), name: None };
+///
+/// tora::assert_bytes_eq!(update, "02 07 01 02");
+/// tora::assert_roundtrip!(update);
+/// ```
+///
+/// ## `tora(pad_before = N)`, `tora(pad_after = N)`
+///
+/// Applied to a field, skips N bytes of padding before or after the field. The `WriteStruct`
+/// derive writes N zero bytes in their place.
+///
+/// ```
+/// use tora_derive::{ReadStruct, WriteStruct};
+///
+/// // Written as a byte, three bytes of padding, then a 4-byte aligned integer.
+/// #[derive(ReadStruct, WriteStruct)]
+/// struct Aligned {
+///     #[tora(pad_after = 3)]
+///     kind: u8,
+///     value: u32,
+/// }
+/// ```
+///
+/// ## `tora(boxed)`
+///
+/// Applied to a field of type `Box<T>`, marks the field as holding its value out of line, such as
+/// the recursive fields of a type. A `Box<T>` is transparent on the wire: it is written and read
+/// the same as a `T`, with or without the attribute, which only checks that the field is a `Box`.
+///
+/// Recursive types need the indirection of a `Box`. Fields holding the type itself inline, even
+/// through an `Option`, are rejected with a hint to box them.
+///
+/// ```
+/// use tora_derive::{ReadEnum, WriteEnum};
+///
+/// #[derive(Debug, PartialEq, ReadEnum, WriteEnum)]
+/// enum Expr {
+///     Literal(i64),
+///     Add(#[tora(boxed)] Box<Expr>, #[tora(boxed)] Box<Expr>),
+/// }
+///
+/// let sum = Expr::Add(Box::new(Expr::Literal(1)), Box::new(Expr::Literal(2)));
+/// tora::assert_roundtrip!(sum);
+/// ```
+///
+/// ```compile_fail
+/// use tora_derive::WriteStruct;
+///
+/// #[derive(WriteStruct)]
+/// struct Node {
+///     #[tora(boxed)]
+///     value: u32,
+/// }
+/// ```
+///
+/// ## `tora(extensions)`
+///
+/// Applied to the last field in wire order, of type `Vec<u8>`, reads every byte left in the
+/// message into the field. The `WriteStruct` and `WriteEnum` derives write the bytes back as-is
+/// after the other fields, so a peer built on an older schema forwards the fields added by newer
+/// peers instead of dropping them. Also supported by `ReadEnum` and `FromSlice`.
+///
+/// The field reads until the end of its input, so the container must declare where that is. The
+/// payload of a `tora(length_prefixed)` enum variant ends at its length prefix. A container
+/// marked `tora(frame)` is only ever read as the whole of a slice or a framed reader's frame, and
+/// never nested within another value, where the field would swallow whatever follows.
+///
+/// ```
+/// use tora::read::ToraRead;
+/// use tora_derive::{ReadEnum, ReadStruct, WriteEnum, WriteStruct};
+///
+/// // The current schema, which added a field.
+/// #[derive(Debug, PartialEq, ReadStruct, WriteStruct)]
+/// struct Player {
+///     id: u8,
+///     level: u16,
+/// }
+///
+/// // An older schema, preserving the fields it does not know of.
+/// #[derive(Debug, PartialEq, ReadStruct, WriteStruct)]
+/// #[tora(frame)]
+/// struct OldPlayer {
+///     id: u8,
+///     #[tora(extensions)]
+///     extensions: Vec<u8>,
+/// }
+///
+/// let bytes = tora::testing::to_bytes(&Player { id: 1, level: 3 });
+/// let old: OldPlayer = bytes.as_slice().reads().unwrap();
+///
+/// assert_eq!(old.extensions, [3, 0]);
+/// assert_eq!(tora::testing::to_bytes(&old), bytes);
+///
+/// #[derive(Debug, PartialEq, ReadEnum, WriteEnum)]
+/// #[tora(length_prefixed)]
+/// enum Packet {
+///     Join {
+///         id: u8,
+///         #[tora(extensions)]
+///         extensions: Vec<u8>,
+///     },
+/// }
+///
+/// tora::assert_roundtrip!(Packet::Join { id: 1, extensions: vec![3, 0] });
+/// ```
+///
+/// ```compile_fail
+/// use tora_derive::ReadStruct;
+///
+/// // Neither length-prefixed nor a whole frame, so nothing bounds the extensions.
+/// #[derive(ReadStruct)]
+/// struct Unbounded {
+///     id: u8,
+///     #[tora(extensions)]
+///     extensions: Vec<u8>,
+/// }
+/// ```
+///
+/// ## `tora(get = "fn")`, `tora(set = "fn")`
+///
+/// Applied to a struct field, `get` makes the `WriteStruct` derive write the value returned by
+/// `self.fn()` in place of the field, such as a length or checksum computed on demand.
+///
+/// `set` makes this derive construct the struct with the field set to `Default::default()`, then
+/// pass the value read to `self.fn(value)` once all fields were read.
+///
+/// ```
+/// use tora_derive::{ReadStruct, WriteStruct};
+///
+/// #[derive(ReadStruct, WriteStruct)]
+/// struct Message {
+///     #[tora(get = "checksum", set = "verify")]
+///     checksum: u8,
+///     content: Vec<u8>,
+/// }
+///
+/// impl Message {
+///     fn checksum(&self) -> u8 {
+///         self.content.iter().fold(0, |sum, b| sum.wrapping_add(*b))
+///     }
+///
+///     fn verify(&mut self, checksum: u8) {
+///         self.checksum = checksum;
+///     }
+/// }
+/// ```
+///
+/// A field of type `PhantomData<T>` with these attributes is virtual: a `T` is written and read
+/// in its place, but never stored in the struct. Virtual fields need `get` to be written and `set`
+/// to be read.
+///
+/// ```
+/// use std::marker::PhantomData;
+///
+/// use tora::read::ToraRead;
+/// use tora::write::ToraWrite;
+/// use tora_derive::{ReadStruct, WriteStruct};
+///
+/// #[derive(ReadStruct, WriteStruct)]
+/// struct Rect {
+///     width: u8,
+///     height: u8,
+///     #[tora(get = "area", set = "ignore_area")]
+///     area: PhantomData<u16>,
+/// }
+///
+/// impl Rect {
+///     fn area(&self) -> u16 {
+///         self.width as u16 * self.height as u16
+///     }
+///
+///     fn ignore_area(&mut self, _area: u16) {}
+/// }
+///
+/// let mut bytes = Vec::new();
+/// bytes.writes(&Rect { width: 20, height: 30, area: PhantomData }).unwrap();
+/// assert_eq!(bytes, [20, 30, 88, 2]);
+///
+/// let rect: Rect = bytes.as_slice().reads().unwrap();
+/// assert_eq!(rect.area(), 600);
+/// ```
+///
 /// # Usage
 ///
 /// ```
@@ -148,7 +581,7 @@ pub fn derive_read_enum(tokens: TokenStream) -> TokenStream {
 ///     }
 /// }
 /// ```
-#[proc_macro_derive(ReadStruct)]
+#[proc_macro_derive(ReadStruct, attributes(tora))]
 pub fn derive_read_struct(tokens: TokenStream) -> TokenStream {
     let item = parse_macro_input!(tokens as ItemStruct);
 
@@ -156,21 +589,102 @@ pub fn derive_read_struct(tokens: TokenStream) -> TokenStream {
         return derive_empty_item_error(item);
     }
 
-    match item.fields {
-        Fields::Named(f) => derive_impl::impl_read_struct_named(
-            item.ident,
-            f.named.into_iter().map(|f| f.ident.unwrap()),
-        ),
-        Fields::Unnamed(f) => {
-            derive_impl::impl_read_struct_tuple(item.ident, f.unnamed.into_iter().map(|f| f.ty))
-        }
-        Fields::Unit => return derive_empty_item_error(item),
-    }
-    .into()
+    ContainerAttrs::parse(&item.attrs)
+        .and_then(|attrs| {
+            let inherent = derive_impl::impl_inherent_read(&item.ident, &attrs)?;
+            let from_reader = derive_impl::impl_read_struct(item.ident, attrs, item.fields)?;
+            Ok(quote!(#from_reader #inherent))
+        })
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
 }
 
 /// The `WriteStruct` derive macro generates a `SerializeIo` implementation for structs.
 ///
+/// # Attributes
+///
+/// ## `tora(builder)`
+///
+/// Generates a `{Struct}Builder` type with a setter for each field, and a `builder` function on
+/// the struct returning it. Only structs with named fields are supported.
+///
+/// Calling `build` on the builder returns `tora::builder::MissingField` if a required field was
+/// not set. Fields of type `Option` default to `None`, other fields are required unless they are
+/// marked with one of the following attributes.
+///
+/// ## `tora(default)`
+///
+/// Applied to a field, defaults the field to `Default::default()` in the builder.
+///
+/// ## `tora(default = $expr)`
+///
+/// Applied to a field, defaults the field to the given expression in the builder.
+///
+/// ```
+/// use tora_derive::WriteStruct;
+///
+/// #[derive(WriteStruct)]
+/// #[tora(builder)]
+/// struct PlayerMove {
+///     id: u8,
+///     #[tora(default)]
+///     sprinting: bool,
+///     #[tora(default = 1.0)]
+///     speed: f32,
+///     note: Option<String>,
+/// }
+///
+/// let packet = PlayerMove::builder().id(5).note("Hello".to_string()).build().unwrap();
+///
+/// assert_eq!(packet.speed, 1.0);
+/// assert!(PlayerMove::builder().build().is_err());
+/// ```
+///
+/// ## `tora(test_roundtrip)`
+///
+/// Generates a `#[cfg(test)]` unit test which writes the `Default` value of the type, reads it
+/// back and asserts both are equal. Also supported by `WriteEnum`.
+///
+/// The type must also implement `FromReader`, `Default`, `Debug` and `PartialEq`.
+///
+/// ```
+/// use tora_derive::{ReadStruct, WriteStruct};
+///
+/// #[derive(Debug, Default, PartialEq, ReadStruct, WriteStruct)]
+/// #[tora(test_roundtrip)]
+/// struct Chat {
+///     sender: u32,
+///     message: String,
+/// }
+/// ```
+///
+/// ## `tora(inherent)`
+///
+/// Generates an inherent `to_tora_vec` method on the type, writing it to a new `Vec`. With
+/// `ReadStruct` and `ReadEnum`, also generates an inherent `from_tora_slice` function, reading
+/// the type from a whole slice and failing with `ErrorKind::InvalidData` if bytes are left after
+/// it. Also supported by `WriteEnum`.
+///
+/// Neither needs `ToraRead` or `ToraWrite` in scope. `from_tora_slice` cannot be combined with
+/// `tora(seed = $ty)`.
+///
+/// ```
+/// use tora_derive::{ReadStruct, WriteStruct};
+///
+/// #[derive(Debug, PartialEq, ReadStruct, WriteStruct)]
+/// #[tora(inherent)]
+/// struct Chat {
+///     sender: u32,
+///     message: String,
+/// }
+///
+/// let chat = Chat { sender: 1, message: "Hi".to_string() };
+/// let bytes = chat.to_tora_vec().unwrap();
+///
+/// assert_eq!(Chat::from_tora_slice(&bytes).unwrap(), chat);
+/// assert!(Chat::from_tora_slice(&[bytes.as_slice(), &[0]].concat()).is_err());
+/// ```
+///
 /// # Usage
 ///
 /// ```
@@ -202,20 +716,31 @@ pub fn derive_read_struct(tokens: TokenStream) -> TokenStream {
 ///     }
 /// }
 /// ```
-#[proc_macro_derive(WriteStruct)]
+#[proc_macro_derive(WriteStruct, attributes(tora))]
 pub fn derive_write_struct(tokens: TokenStream) -> TokenStream {
     let item = parse_macro_input!(tokens as ItemStruct);
 
     if item.fields.is_empty() {
         return derive_empty_item_error(item);
     }
-    let types = item.fields.into_iter().enumerate().map(|(i, f)| {
-        f.ident
-            .as_ref()
-            .map(|i| i.to_token_stream())
-            .unwrap_or_else(|| LitInt::new(&i.to_string(), f.span()).to_token_stream())
-    });
-    derive_impl::impl_write_struct(item.ident, types).into()
+    let attrs = match ContainerAttrs::parse(&item.attrs) {
+        Ok(attrs) => attrs,
+        Err(e) => return e.into_compile_error().into(),
+    };
+    let builder = if attrs.builder {
+        builder::impl_builder(&item).unwrap_or_else(Error::into_compile_error)
+    } else {
+        proc_macro2::TokenStream::new()
+    };
+
+    let assert_size = derive_impl::impl_assert_size(&item.ident, &attrs);
+    let inherent = derive_impl::impl_inherent_write(&item.ident, &attrs);
+    let test_roundtrip = derive_impl::impl_test_roundtrip(&item.ident, &attrs)
+        .unwrap_or_else(Error::into_compile_error);
+    let serialize_io = derive_impl::impl_write_struct(item.ident, attrs, item.fields)
+        .unwrap_or_else(Error::into_compile_error);
+
+    quote!(#serialize_io #builder #assert_size #inherent #test_roundtrip).into()
 }
 
 /// The `WriteEnum` derive macro generates a `SerializeIo` implementation for enums.
@@ -243,9 +768,10 @@ pub fn derive_write_struct(tokens: TokenStream) -> TokenStream {
 ///
 /// By default, this macro assumes [u8].
 ///
-/// In the case that the enum deriving this macro contains more than [u8::MAX] variants, the user
-/// will be required to specify this attribute manually.
-#[proc_macro_derive(WriteEnum, attributes(type_variant_id))]
+/// In the case that the enum deriving this macro contains more than 256 variants, the user
+/// will be required to specify this attribute manually; compilation fails otherwise. The same
+/// applies to any primitive integer too narrow to represent every variant ID.
+#[proc_macro_derive(WriteEnum, attributes(type_variant_id, tora))]
 pub fn derive_write_enum(tokens: TokenStream) -> TokenStream {
     let item = parse_macro_input!(tokens as ItemEnum);
 
@@ -253,6 +779,347 @@ pub fn derive_write_enum(tokens: TokenStream) -> TokenStream {
         return derive_empty_item_error(item);
     }
 
-    let ty: Type = get_list_attr_or_default("type_variant_id", parse_quote!(u8), &item.attrs);
-    derive_impl::impl_write_enum(item.ident, ty, item.variants.into_iter()).into()
+    variant_id_type(&item)
+        .and_then(|ty| Ok((ty, ContainerAttrs::parse(&item.attrs)?)))
+        .and_then(|(ty, attrs)| {
+            let assert_size = derive_impl::impl_assert_size(&item.ident, &attrs);
+            let inherent = derive_impl::impl_inherent_write(&item.ident, &attrs);
+            let test_roundtrip = derive_impl::impl_test_roundtrip(&item.ident, &attrs)?;
+            let serialize_io =
+                derive_impl::impl_write_enum(item.ident, attrs, ty, item.variants.into_iter())?;
+            Ok(quote!(#serialize_io #assert_size #inherent #test_roundtrip))
+        })
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+/// The `ConstSize` derive macro implements `tora::layout::ConstSize` for types which always
+/// serialize to the same amount of bytes.
+///
+/// Structs are supported if all of their fields implement `ConstSize`, padding attributes and
+/// `tora(repr_c)` included. Enums are supported if none of their variants have fields, in which
+/// case the size is that of the `type_variant_id`.
+///
+/// # Attributes
+///
+/// ## `tora(assert_size = N)`
+///
+/// Read by the `WriteStruct` and `WriteEnum` macros, fails compilation if the type does not
+/// serialize to exactly `N` bytes. The type must implement `ConstSize`.
+///
+/// ```
+/// use tora_derive::{ConstSize, WriteStruct};
+///
+/// #[derive(ConstSize, WriteStruct)]
+/// #[tora(assert_size = 8)]
+/// struct Header {
+///     #[tora(pad_after = 1)]
+///     kind: u8,
+///     flags: u16,
+///     length: u32,
+/// }
+/// ```
+///
+/// ```compile_fail
+/// use tora_derive::{ConstSize, WriteStruct};
+///
+/// #[derive(ConstSize, WriteStruct)]
+/// #[tora(assert_size = 8)]
+/// struct Header {
+///     kind: u8,
+///     flags: u16,
+///     length: u32,
+/// }
+/// ```
+#[proc_macro_derive(ConstSize, attributes(type_variant_id, tora))]
+pub fn derive_const_size(tokens: TokenStream) -> TokenStream {
+    let item = parse_macro_input!(tokens as Item);
+
+    match item {
+        Item::Struct(item) => ContainerAttrs::parse(&item.attrs)
+            .and_then(|attrs| derive_impl::impl_const_size_struct(item.ident, attrs, item.fields)),
+        Item::Enum(item) => variant_id_type(&item)
+            .and_then(|ty| Ok((ty, ContainerAttrs::parse(&item.attrs)?)))
+            .and_then(|(ty, attrs)| {
+                derive_impl::impl_const_size_enum(item.ident, attrs, ty, item.variants.into_iter())
+            }),
+        item => Err(Error::new_spanned(
+            item,
+            "ConstSize can only be derived for structs and enums",
+        )),
+    }
+    .unwrap_or_else(Error::into_compile_error)
+    .into()
+}
+
+/// The `Skip` derive macro implements `tora::skip::Skip` for structs and enums, advancing a reader
+/// past them without deserializing their fields.
+///
+/// All field types must implement `Skip`. The attributes of `ReadStruct` and `ReadEnum` affecting
+/// the wire format are honored, except the seed attributes, which are not supported. The fields of
+/// `tora(length_prefixed)` enums are skipped past their length prefix at once.
+///
+/// ```
+/// use tora::skip::Skip;
+/// use tora_derive::{Skip, WriteEnum};
+///
+/// #[derive(Skip, WriteEnum)]
+/// #[tora(length_prefixed)]
+/// enum Message {
+///     Ping,
+///     Upload { name: String, data: Vec<u8> },
+/// }
+///
+/// let upload = Message::Upload { name: "a".to_string(), data: vec![0; 4096] };
+/// let bytes = tora::testing::to_bytes(&upload);
+///
+/// let mut rest = &bytes[..];
+/// Message::skip(&mut rest).unwrap();
+/// assert!(rest.is_empty());
+/// ```
+#[proc_macro_derive(Skip, attributes(type_variant_id, tora))]
+pub fn derive_skip(tokens: TokenStream) -> TokenStream {
+    let item = parse_macro_input!(tokens as Item);
+
+    match item {
+        Item::Struct(item) => ContainerAttrs::parse(&item.attrs)
+            .and_then(|attrs| derive_impl::impl_skip_struct(item.ident, attrs, item.fields)),
+        Item::Enum(item) => variant_id_type(&item)
+            .and_then(|ty| Ok((ty, ContainerAttrs::parse(&item.attrs)?)))
+            .and_then(|(ty, attrs)| {
+                derive_impl::impl_skip_enum(item.ident, attrs, ty, item.variants.into_iter())
+            }),
+        item => Err(Error::new_spanned(
+            item,
+            "Skip can only be derived for structs and enums",
+        )),
+    }
+    .unwrap_or_else(Error::into_compile_error)
+    .into()
+}
+
+/// The `Reflect` derive macro implements `tora::schema::Reflect` for structs and enums, describing
+/// their field names, types and variants at runtime.
+///
+/// All field types must implement `Reflect`. The `tora` attributes affecting the wire format are
+/// reflected in the schema. Recursive types are rejected, as their schema would be infinite.
+///
+/// ```
+/// use tora::schema::{Reflect, Schema};
+/// use tora_derive::Reflect;
+///
+/// #[derive(Reflect)]
+/// enum Packet {
+///     Ping,
+///     Chat { sender: u32, message: String },
+/// }
+///
+/// let Schema::Enum(schema) = Packet::schema() else {
+///     unreachable!()
+/// };
+///
+/// assert_eq!(schema.variants[1].name, "Chat");
+/// assert_eq!(schema.variants[1].fields[1].name, "message");
+/// assert_eq!(schema.variants[1].fields[1].schema, Schema::String);
+/// ```
+#[proc_macro_derive(Reflect, attributes(type_variant_id, tora))]
+pub fn derive_reflect(tokens: TokenStream) -> TokenStream {
+    let item = parse_macro_input!(tokens as Item);
+
+    match item {
+        Item::Struct(item) => ContainerAttrs::parse(&item.attrs)
+            .and_then(|attrs| derive_impl::impl_reflect_struct(item.ident, attrs, item.fields)),
+        Item::Enum(item) => variant_id_type(&item)
+            .and_then(|ty| Ok((ty, ContainerAttrs::parse(&item.attrs)?)))
+            .and_then(|(ty, attrs)| {
+                derive_impl::impl_reflect_enum(item.ident, attrs, ty, item.variants.into_iter())
+            }),
+        item => Err(Error::new_spanned(
+            item,
+            "Reflect can only be derived for structs and enums",
+        )),
+    }
+    .unwrap_or_else(Error::into_compile_error)
+    .into()
+}
+
+/// The `Columnar` derive macro implements `tora::columnar::Columnar` for structs, so a `Vec` of
+/// them can be written column by column when wrapped in `tora::columnar::Columns`.
+///
+/// All field types must implement `FromReader` and `SerializeIo`. The fields are written in their
+/// wire order, and the `get` and `set` attributes are honored. The padding and seed attributes
+/// are not supported.
+///
+/// ```
+/// use tora::columnar::Columns;
+/// use tora_derive::Columnar;
+///
+/// #[derive(Columnar, Debug, PartialEq)]
+/// struct Reading(u8, bool);
+///
+/// let readings = Columns(vec![Reading(4, true), Reading(5, false)]);
+/// tora::assert_bytes_eq!(readings, "02 00 00 00 04 05 01 00");
+/// ```
+#[proc_macro_derive(Columnar, attributes(tora))]
+pub fn derive_columnar(tokens: TokenStream) -> TokenStream {
+    let item = parse_macro_input!(tokens as ItemStruct);
+
+    if item.fields.is_empty() {
+        return derive_empty_item_error(item);
+    }
+
+    ContainerAttrs::parse(&item.attrs)
+        .and_then(|attrs| derive_impl::impl_columnar(item.ident, attrs, item.fields))
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+/// The `FromSlice` derive macro implements `tora::slice::FromSlice` for structs, reading them
+/// from a byte slice without copying their borrowed fields.
+///
+/// The struct may have one lifetime parameter, which is the lifetime of the slice. All field types
+/// must implement `FromSlice`. The fields are read in their wire order, and the `order`,
+/// `pad_before`, `pad_after` and `extensions` attributes are honored, the latter copying the rest
+/// of the slice. The seed, `get`, `set` and `repr_c` attributes are not supported.
+///
+/// ```
+/// use tora::slice::FromSlice;
+/// use tora_derive::FromSlice;
+///
+/// #[derive(FromSlice)]
+/// struct Record<'a> {
+///     id: u16,
+///     #[tora(pad_before = 2)]
+///     key: &'a str,
+///     value: &'a [u8],
+/// }
+///
+/// let bytes = [9, 0, 0, 0, b'a', 0, 2, 0, 0, 0, 1, 2];
+/// let (record, rest) = Record::from_slice(&bytes).unwrap();
+///
+/// assert_eq!((record.id, record.key, record.value), (9, "a", &[1, 2][..]));
+/// assert!(rest.is_empty());
+/// ```
+#[proc_macro_derive(FromSlice, attributes(tora))]
+pub fn derive_from_slice(tokens: TokenStream) -> TokenStream {
+    let item = parse_macro_input!(tokens as ItemStruct);
+
+    if item.fields.is_empty() {
+        return derive_empty_item_error(item);
+    }
+
+    ContainerAttrs::parse(&item.attrs)
+        .and_then(|attrs| {
+            derive_impl::impl_from_slice(item.ident, item.generics, attrs, item.fields)
+        })
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+/// The `Patch` derive macro implements `tora::patch::Patch` for structs with named fields, so
+/// they can be updated by patches holding only the fields that changed.
+///
+/// Generates a `{Struct}Patch` type with an `Option` of each field, implementing `FromReader` and
+/// `SerializeIo`. It is written as a bitmap of the fields present, one bit per field in
+/// declaration order, followed by the present fields. Reading fails if a bit past the last field
+/// is set.
+///
+/// Also generates a `{Struct}Tracker` type wrapping the struct, with a `set_{field}` and a
+/// `{field}_mut` method per field recording that the field changed. Its `take_patch` method
+/// returns a patch holding a clone of the changed fields, so every field type must implement
+/// `Clone`.
+///
+/// ```
+/// use tora::patch::Patch;
+/// use tora_derive::Patch;
+///
+/// #[derive(Default, Patch)]
+/// struct Stats {
+///     kills: u32,
+///     deaths: u32,
+///     title: String,
+/// }
+///
+/// let mut tracker = StatsTracker::new(Stats::default());
+/// tracker.set_kills(3);
+///
+/// let patch = tracker.take_patch();
+/// tora::assert_bytes_eq!(patch, "01 03 00 00 00");
+///
+/// let mut stats = Stats::default();
+/// stats.apply(patch);
+/// assert_eq!(stats.kills, 3);
+/// ```
+#[proc_macro_derive(Patch)]
+pub fn derive_patch(tokens: TokenStream) -> TokenStream {
+    let item = parse_macro_input!(tokens as ItemStruct);
+
+    if item.fields.is_empty() {
+        return derive_empty_item_error(item);
+    }
+
+    patch::impl_patch(&item)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+/// The `service!` macro defines an RPC interface from a trait-like definition.
+///
+/// Each `fn $method($request) -> $response;` declares an endpoint. The macro generates:
+///
+/// * `{Service}Request` and `{Service}Response` enums, with a variant per endpoint named after
+///   its method, deriving `ReadEnum` and `WriteEnum`.
+/// * The `{Service}` trait, with a method per endpoint and a `handle` method dispatching a
+///   request to the method handling it.
+///
+/// Variant IDs follow the order of the endpoints. `derive` and `type_variant_id` attributes are
+/// applied to both enums, other attributes to the trait.
+///
+/// ```
+/// use tora_derive::{ReadStruct, WriteStruct};
+///
+/// #[derive(Debug, ReadStruct, WriteStruct)]
+/// pub struct LoginRequest {
+///     user: String,
+/// }
+///
+/// #[derive(Debug, PartialEq, ReadStruct, WriteStruct)]
+/// pub struct LoginResponse {
+///     token: u64,
+/// }
+///
+/// tora_derive::service! {
+///     #[derive(Debug)]
+///     pub trait Auth {
+///         fn login(LoginRequest) -> LoginResponse;
+///         fn log_out(u64) -> bool;
+///     }
+/// }
+///
+/// struct Server;
+///
+/// impl Auth for Server {
+///     fn login(&mut self, request: LoginRequest) -> LoginResponse {
+///         LoginResponse { token: request.user.len() as u64 }
+///     }
+///
+///     fn log_out(&mut self, token: u64) -> bool {
+///         token != 0
+///     }
+/// }
+///
+/// let request = AuthRequest::Login(LoginRequest { user: "John".to_string() });
+///
+/// match Server.handle(request) {
+///     AuthResponse::Login(response) => assert_eq!(response.token, 4),
+///     AuthResponse::LogOut(_) => unreachable!(),
+/// }
+/// ```
+#[proc_macro]
+pub fn service(tokens: TokenStream) -> TokenStream {
+    let service = parse_macro_input!(tokens as service::Service);
+
+    service::impl_service(service)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
 }
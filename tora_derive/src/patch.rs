@@ -0,0 +1,217 @@
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use syn::{Error, Fields, ItemStruct, Result};
+
+/// `derive(Patch)` implementation.
+///
+/// Generates a `{Ident}Patch` type holding an `Option` per field, written as a bitmap of the
+/// fields present followed by their values, and a `{Ident}Tracker` type recording the fields
+/// changed through its setters.
+pub fn impl_patch(item: &ItemStruct) -> Result<TokenStream> {
+    let Fields::Named(ref fields) = item.fields else {
+        return Err(Error::new_spanned(
+            item,
+            "Patch can only be derived for structs with named fields",
+        ));
+    };
+
+    let vis = &item.vis;
+    let ident = &item.ident;
+    let patch = format_ident!("{ident}Patch");
+    let tracker = format_ident!("{ident}Tracker");
+
+    let idents = fields
+        .named
+        .iter()
+        .map(|f| f.ident.clone().unwrap())
+        .collect::<Vec<_>>();
+    let types = fields.named.iter().map(|f| &f.ty).collect::<Vec<_>>();
+    let setters = idents.iter().map(|i| format_ident!("set_{i}"));
+    let getters_mut = idents.iter().map(|i| format_ident!("{i}_mut"));
+
+    let count = idents.len();
+    let len = count.div_ceil(8);
+    let bytes = (0..idents.len()).map(|i| i / 8).collect::<Vec<_>>();
+    let masks = (0..idents.len())
+        .map(|i| 1u8 << (i % 8))
+        .collect::<Vec<_>>();
+    let indices = (0..idents.len()).collect::<Vec<_>>();
+
+    let unused = match idents.len() % 8 {
+        0 => TokenStream::new(),
+        used => {
+            let last = len - 1;
+            quote! {
+                if mask[#last] >> #used != 0 {
+                    return std::result::Result::Err(std::io::Error::new(
+                        std::io::ErrorKind::InvalidData,
+                        "Unknown bits set in the patch mask",
+                    ));
+                }
+            }
+        }
+    };
+
+    let patch_doc = format!(
+        "The changed fields of [{ident}], written as a mask of the fields present followed by \
+         their values."
+    );
+    let tracker_doc = format!("Wraps [{ident}], recording the fields changed through its setters.");
+    let setter_docs = idents
+        .iter()
+        .map(|i| format!("Sets the `{i}` field, marking it as changed."))
+        .collect::<Vec<_>>();
+    let getter_mut_docs = idents
+        .iter()
+        .map(|i| format!("Returns a mutable reference to the `{i}` field, marking it as changed."))
+        .collect::<Vec<_>>();
+
+    Ok(quote! {
+        #[doc = #patch_doc]
+        #[derive(Clone, Default)]
+        #vis struct #patch {
+            #( pub #idents: std::option::Option<#types>, )*
+        }
+
+        impl #patch {
+            /// Returns true if no fields changed.
+            pub fn is_empty(&self) -> bool {
+                true #( && self.#idents.is_none() )*
+            }
+        }
+
+        impl tora::patch::Patch for #ident {
+            type Patch = #patch;
+
+            fn apply(&mut self, patch: #patch) {
+                #(
+                if let std::option::Option::Some(value) = patch.#idents {
+                    self.#idents = value;
+                }
+                )*
+            }
+        }
+
+        impl tora::read::FromReader for #patch {
+            fn from_reader<R>(r: &mut R) -> std::io::Result<Self>
+            where R: std::io::Read
+            {
+                Self::from_reader_with(r, &tora::config::ToraConfig::DEFAULT)
+            }
+
+            fn from_reader_with<R>(
+                r: &mut R,
+                config: &tora::config::ToraConfig,
+            ) -> std::io::Result<Self>
+            where R: std::io::Read
+            {
+                let mut mask = [0u8; #len];
+                std::io::Read::read_exact(r, &mut mask)?;
+                #unused
+
+                std::result::Result::Ok(Self {
+                    #(
+                    #idents: match mask[#bytes] & #masks != 0 {
+                        true => std::option::Option::Some(
+                            tora::read::ToraRead::reads_with::<#types>(r, config)?
+                        ),
+                        false => std::option::Option::None,
+                    },
+                    )*
+                })
+            }
+        }
+
+        impl tora::write::SerializeIo for #patch {
+            fn serialize<W>(&self, w: &mut W) -> std::io::Result<()>
+            where W: std::io::Write
+            {
+                self.serialize_with(w, &tora::config::ToraConfig::DEFAULT)
+            }
+
+            fn serialize_with<W>(
+                &self,
+                w: &mut W,
+                config: &tora::config::ToraConfig,
+            ) -> std::io::Result<()>
+            where W: std::io::Write
+            {
+                let mut mask = [0u8; #len];
+                #(
+                if self.#idents.is_some() {
+                    mask[#bytes] |= #masks;
+                }
+                )*
+                std::io::Write::write_all(w, &mask)?;
+
+                #(
+                if let std::option::Option::Some(ref value) = self.#idents {
+                    tora::write::ToraWrite::writes_with(w, value, config)?;
+                }
+                )*
+                std::result::Result::Ok(())
+            }
+        }
+
+        #[doc = #tracker_doc]
+        #vis struct #tracker {
+            value: #ident,
+            changed: [bool; #count],
+        }
+
+        impl #tracker {
+            /// Wraps the value, with no fields marked as changed.
+            pub fn new(value: #ident) -> Self {
+                Self {
+                    value,
+                    changed: [false; #count],
+                }
+            }
+
+            /// Returns a reference to the value.
+            pub fn get(&self) -> &#ident {
+                &self.value
+            }
+
+            #(
+            #[doc = #setter_docs]
+            pub fn #setters(&mut self, value: #types) {
+                self.value.#idents = value;
+                self.changed[#indices] = true;
+            }
+
+            #[doc = #getter_mut_docs]
+            pub fn #getters_mut(&mut self) -> &mut #types {
+                self.changed[#indices] = true;
+                &mut self.value.#idents
+            }
+            )*
+
+            /// Returns true if any field changed since the last patch was taken.
+            pub fn is_dirty(&self) -> bool {
+                self.changed.contains(&true)
+            }
+
+            /// Returns a patch holding a copy of the fields changed since the last patch was
+            /// taken, and marks every field as unchanged.
+            pub fn take_patch(&mut self) -> #patch {
+                let changed = std::mem::replace(&mut self.changed, [false; #count]);
+                #patch {
+                    #(
+                    #idents: match changed[#indices] {
+                        true => std::option::Option::Some(
+                            std::clone::Clone::clone(&self.value.#idents)
+                        ),
+                        false => std::option::Option::None,
+                    },
+                    )*
+                }
+            }
+
+            /// Returns the value, discarding the changes not taken.
+            pub fn into_inner(self) -> #ident {
+                self.value
+            }
+        }
+    })
+}
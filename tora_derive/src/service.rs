@@ -0,0 +1,155 @@
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use syn::parse::{Parse, ParseStream};
+use syn::{parenthesized, Attribute, Ident, Result, Token, Type, Visibility};
+
+/// A trait-like definition of an RPC interface.
+pub struct Service {
+    attrs: Vec<Attribute>,
+    vis: Visibility,
+    ident: Ident,
+    endpoints: Vec<Endpoint>,
+}
+
+/// `fn $method($request) -> $response;`
+struct Endpoint {
+    attrs: Vec<Attribute>,
+    method: Ident,
+    request: Type,
+    response: Type,
+}
+
+impl Parse for Service {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let attrs = input.call(Attribute::parse_outer)?;
+        let vis = input.parse()?;
+        input.parse::<Token![trait]>()?;
+        let ident = input.parse()?;
+
+        let content;
+        syn::braced!(content in input);
+
+        let mut endpoints = Vec::new();
+
+        while !content.is_empty() {
+            let attrs = content.call(Attribute::parse_outer)?;
+            content.parse::<Token![fn]>()?;
+            let method = content.parse()?;
+
+            let request;
+            parenthesized!(request in content);
+            let request = request.parse()?;
+
+            content.parse::<Token![->]>()?;
+            let response = content.parse()?;
+            content.parse::<Token![;]>()?;
+
+            endpoints.push(Endpoint {
+                attrs,
+                method,
+                request,
+                response,
+            });
+        }
+
+        Ok(Self {
+            attrs,
+            vis,
+            ident,
+            endpoints,
+        })
+    }
+}
+
+/// Converts a `snake_case` method name to an `UpperCamelCase` variant name.
+fn to_variant(method: &Ident) -> Ident {
+    let name = method
+        .to_string()
+        .split('_')
+        .map(|word| {
+            let mut chars = word.chars();
+            match chars.next() {
+                Some(first) => first.to_uppercase().chain(chars).collect(),
+                None => String::new(),
+            }
+        })
+        .collect::<String>();
+
+    Ident::new(&name, method.span())
+}
+
+pub fn impl_service(service: Service) -> Result<TokenStream> {
+    let Service {
+        attrs,
+        vis,
+        ident,
+        endpoints,
+    } = service;
+
+    if endpoints.is_empty() {
+        return Err(syn::Error::new_spanned(
+            ident,
+            "A service requires at least one endpoint",
+        ));
+    }
+
+    // Derives and the variant ID type apply to the enums, other attributes to the trait.
+    let (enum_attrs, trait_attrs): (Vec<_>, Vec<_>) = attrs.into_iter().partition(|attr| {
+        attr.path().is_ident("derive") || attr.path().is_ident("type_variant_id")
+    });
+
+    let request_enum = format_ident!("{ident}Request");
+    let response_enum = format_ident!("{ident}Response");
+
+    let variants = endpoints
+        .iter()
+        .map(|e| to_variant(&e.method))
+        .collect::<Vec<_>>();
+    let methods = endpoints.iter().map(|e| &e.method).collect::<Vec<_>>();
+    let requests = endpoints.iter().map(|e| &e.request);
+    let responses = endpoints.iter().map(|e| &e.response);
+
+    let method_attrs = endpoints.iter().map(|e| &e.attrs);
+    let signatures = endpoints.iter().map(|e| {
+        let (method, request, response) = (&e.method, &e.request, &e.response);
+        quote!(fn #method(&mut self, request: #request) -> #response)
+    });
+
+    let request_doc = format!("The requests of the [{ident}] service.");
+    let response_doc = format!("The responses of the [{ident}] service.");
+
+    Ok(quote! {
+        #[doc = #request_doc]
+        #[derive(tora::ReadEnum, tora::WriteEnum)]
+        #( #enum_attrs )*
+        #vis enum #request_enum {
+            #( #variants(#requests), )*
+        }
+
+        #[doc = #response_doc]
+        #[derive(tora::ReadEnum, tora::WriteEnum)]
+        #( #enum_attrs )*
+        #vis enum #response_enum {
+            #( #variants(#responses), )*
+        }
+
+        #( #trait_attrs )*
+        #vis trait #ident {
+            #(
+                #( #method_attrs )*
+                #signatures;
+            )*
+
+            /// Passes the request to the method handling it, returning its response.
+            fn handle(&mut self, request: #request_enum) -> #response_enum {
+                match request {
+                    #(
+                        #request_enum::#variants(request) => {
+                            #response_enum::#variants(self.#methods(request))
+                        }
+                    )*
+                }
+            }
+        }
+    })
+}
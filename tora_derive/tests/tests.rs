@@ -1,10 +1,27 @@
+use std::cell::{Cell, RefCell};
 use std::fmt::Debug;
 use std::io;
-use std::io::Cursor;
+use std::io::{Cursor, ErrorKind, Read, Write};
+use std::marker::PhantomData;
+use std::ops::{ControlFlow, Range};
+use std::sync::{LazyLock, Mutex, RwLock};
+use std::task::Poll;
+use std::time::Duration;
 
-use tora::read::{FromReader, ToraRead};
+use tora::codec::{Codec, Codecs, VarInt};
+use tora::columnar::Columns;
+use tora::config::{Endian, IntSequenceFormat, LengthPrefix, StringFormat, ToraConfig};
+use tora::layout::ConstSize;
+use tora::patch::Patch;
+use tora::read::{FromReader, FromReaderSeed, ToraRead};
+use tora::schema::{format_path, walk, Reflect, Segment, Value, Visit, Visitor};
+use tora::skip::{Discarding, Skip};
+use tora::slice::FromSlice;
 use tora::write::{SerializeIo, ToraWrite};
-use tora_derive::{ReadEnum, ReadStruct, WriteEnum, WriteStruct};
+use tora_derive::{
+    Columnar, ConstSize, FromSlice, Patch, ReadEnum, ReadStruct, Reflect, Skip, WriteEnum,
+    WriteStruct,
+};
 
 #[derive(Debug, PartialEq, ReadStruct, WriteStruct)]
 struct StructPacket {
@@ -38,13 +55,7 @@ fn assert_rw_eq<T>(data: T) -> io::Result<()>
 where
     T: SerializeIo + FromReader + PartialEq + Debug,
 {
-    let mut bytes = Vec::new();
-    bytes.writes(&data)?;
-
-    let mut cursor = Cursor::new(bytes);
-    let received = cursor.reads()?;
-
-    assert_eq!(data, received);
+    tora::assert_roundtrip!(data);
     Ok(())
 }
 
@@ -57,6 +68,30 @@ fn struct_packet() -> io::Result<()> {
     })
 }
 
+#[test]
+fn struct_packet_bytes() {
+    let packet = StructPacket {
+        id: 5,
+        sender: "Jo".to_string(),
+        content: vec![1, 2],
+    };
+
+    tora::assert_bytes_eq!(packet, "05 4a 6f 00 02 00 00 00 01 02");
+    tora::testing::assert_truncation_fails(&packet);
+}
+
+#[test]
+fn enum_packet_snapshot() {
+    tora::assert_snapshot!(
+        "enum_packet",
+        EnumPacket::PlayerMove {
+            player_id: 3,
+            destination: [1.0, 2.0, 3.0],
+            feet_position: (1.0, 0.5, 3.0),
+        }
+    );
+}
+
 #[test]
 fn tuple_packet() -> io::Result<()> {
     assert_rw_eq(TuplePacket(
@@ -66,6 +101,136 @@ fn tuple_packet() -> io::Result<()> {
     ))
 }
 
+#[derive(Debug, PartialEq, ReadStruct, WriteStruct)]
+struct StepSnapshot {
+    step: ControlFlow<String, u32>,
+    pending: Poll<Vec<u8>>,
+}
+
+#[test]
+fn control_types() -> io::Result<()> {
+    assert_rw_eq(StepSnapshot {
+        step: ControlFlow::Continue(3),
+        pending: Poll::Pending,
+    })?;
+    assert_rw_eq(StepSnapshot {
+        step: ControlFlow::Break("Done".to_string()),
+        pending: Poll::Ready(vec![1, 2]),
+    })
+}
+
+#[derive(Debug, ReadStruct, WriteStruct)]
+struct SharedState {
+    players: Mutex<Vec<String>>,
+    tick: RwLock<u64>,
+}
+
+#[test]
+fn lock_types() -> io::Result<()> {
+    let state = SharedState {
+        players: Mutex::new(vec!["John".to_string()]),
+        tick: RwLock::new(7),
+    };
+    let bytes = tora::testing::to_bytes(&state);
+    tora::assert_same_bytes!(state, (vec!["John"], 7u64));
+
+    let read = SharedState::from_reader(&mut Cursor::new(bytes))?;
+    assert_eq!(read.players.into_inner().unwrap(), ["John"]);
+    assert_eq!(read.tick.into_inner().unwrap(), 7);
+
+    let _ = std::panic::catch_unwind(|| {
+        let _guard = state.players.lock();
+        panic!();
+    });
+    assert!(state.serialize(&mut Vec::new()).is_err());
+    Ok(())
+}
+
+#[derive(Debug, PartialEq, ReadStruct, WriteStruct)]
+struct GameState {
+    score: Cell<u32>,
+    inventory: RefCell<Vec<String>>,
+}
+
+#[test]
+fn cell_types() -> io::Result<()> {
+    let state = GameState {
+        score: Cell::new(10),
+        inventory: RefCell::new(vec!["Sword".to_string()]),
+    };
+    assert_rw_eq(GameState {
+        score: state.score.clone(),
+        inventory: state.inventory.clone(),
+    })?;
+
+    let _borrow = state.inventory.borrow_mut();
+    assert!(state.serialize(&mut Vec::new()).is_err());
+    Ok(())
+}
+
+#[test]
+fn durations() -> io::Result<()> {
+    assert_rw_eq(Duration::new(5, 999_999_999))?;
+    tora::assert_bytes_eq!(Duration::new(1, 2), "01 00 00 00 00 00 00 00 02 00 00 00");
+    tora::testing::assert_decode_error::<Duration>(
+        &[0, 0, 0, 0, 0, 0, 0, 0, 0x00, 0xCA, 0x9A, 0x3B],
+        ErrorKind::InvalidData,
+    );
+    Ok(())
+}
+
+#[derive(Debug, PartialEq, ReadStruct, WriteStruct)]
+struct AuthPacket {
+    user: u8,
+    hash: [u8; 32],
+}
+
+/// Counts the calls made to the inner reader or writer.
+struct CallCounter<T> {
+    inner: T,
+    calls: usize,
+}
+
+impl<T: Read> Read for CallCounter<T> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.calls += 1;
+        self.inner.read(buf)
+    }
+}
+
+impl<T: Write> Write for CallCounter<T> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.calls += 1;
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[test]
+fn byte_arrays() -> io::Result<()> {
+    let packet = AuthPacket {
+        user: 2,
+        hash: [7; 32],
+    };
+    let mut w = CallCounter {
+        inner: Vec::new(),
+        calls: 0,
+    };
+    w.writes(&packet)?;
+    assert_eq!(w.calls, 2);
+
+    let mut r = CallCounter {
+        inner: Cursor::new(w.inner),
+        calls: 0,
+    };
+    assert_eq!(r.reads::<AuthPacket>()?, packet);
+    assert_eq!(r.calls, 2);
+    Ok(())
+}
+
 #[test]
 fn enum_packet() -> io::Result<()> {
     assert_rw_eq(EnumPacket::PlayerMove {
@@ -83,3 +248,1048 @@ fn enum_packet() -> io::Result<()> {
 fn boxes() -> io::Result<()> {
     assert_rw_eq(Box::new(EnumPacket::Ping))
 }
+
+#[test]
+fn borrowed_values() {
+    let packet = StructPacket {
+        id: 3,
+        sender: "John".to_string(),
+        content: vec![1, 2],
+    };
+    tora::assert_same_bytes!(&&packet, packet);
+    tora::assert_same_bytes!(&mut EnumPacket::Ping, EnumPacket::Ping);
+
+    let content: &[u8] = &packet.content;
+    tora::assert_same_bytes!(
+        (packet.id, (packet.sender.as_str(), content)),
+        (packet.id, (packet.sender.clone(), packet.content.clone())),
+    );
+}
+
+struct NameTable(Vec<String>);
+
+#[derive(Debug, PartialEq)]
+struct Name(String);
+
+impl FromReaderSeed<NameTable> for Name {
+    fn from_reader_seed<R>(r: &mut R, seed: &mut NameTable) -> io::Result<Self>
+    where
+        R: Read,
+    {
+        let index = r.reads::<u8>()? as usize;
+        seed.0
+            .get(index)
+            .map(|name| Self(name.clone()))
+            .ok_or_else(|| io::Error::new(ErrorKind::InvalidData, "Unknown name index"))
+    }
+}
+
+#[derive(Debug, PartialEq, ReadStruct)]
+#[tora(seed = NameTable)]
+struct SeededPacket {
+    id: u8,
+    #[tora(seed)]
+    sender: Name,
+    #[tora(seed)]
+    recipients: Vec<Name>,
+}
+
+#[derive(Debug, PartialEq, ReadEnum)]
+#[tora(seed = NameTable)]
+enum SeededEnum {
+    Anonymous,
+    Named(#[tora(seed)] Name, u8),
+}
+
+#[test]
+fn seeded_reads() -> io::Result<()> {
+    let mut table = NameTable(vec!["John".to_string(), "Joseph".to_string()]);
+
+    let mut cursor = Cursor::new([5, 1, 2, 0, 0, 0, 0, 1]);
+    let packet: SeededPacket = cursor.reads_seed(&mut table)?;
+
+    assert_eq!(
+        packet,
+        SeededPacket {
+            id: 5,
+            sender: Name("Joseph".to_string()),
+            recipients: vec![Name("John".to_string()), Name("Joseph".to_string())],
+        }
+    );
+
+    let mut cursor = Cursor::new([1, 0, 9]);
+    let variant: SeededEnum = cursor.reads_seed(&mut table)?;
+
+    assert_eq!(variant, SeededEnum::Named(Name("John".to_string()), 9));
+    Ok(())
+}
+
+#[test]
+fn configured_packet() -> io::Result<()> {
+    let config = ToraConfig::DEFAULT
+        .endian(Endian::Big)
+        .length_prefix(LengthPrefix::U8)
+        .string_format(StringFormat::LengthPrefixed);
+    let packet = EnumPacket::PlayerJoin(PlayerJoin {
+        id: 1,
+        name: Some("Jo".to_string()),
+    });
+
+    let mut bytes = Vec::new();
+    bytes.writes_with(&packet, &config)?;
+
+    assert_eq!(bytes, [0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 2, b'J', b'o']);
+
+    let mut cursor = Cursor::new(bytes);
+    assert_eq!(packet, cursor.reads_with(&config)?);
+
+    let limited = ToraConfig::DEFAULT.max_length(2);
+    let mut cursor = Cursor::new([3, 0, 0, 0, 1, 2, 3]);
+    let err = cursor.reads_with::<Vec<u8>>(&limited).unwrap_err();
+
+    assert_eq!(err.kind(), ErrorKind::InvalidData);
+    Ok(())
+}
+
+#[derive(Debug, PartialEq, ReadStruct, WriteStruct)]
+#[tora(builder)]
+struct BuiltPacket {
+    id: u8,
+    #[tora(default)]
+    flags: u16,
+    #[tora(default = "Anonymous".to_string())]
+    sender: String,
+    reply_to: Option<u8>,
+}
+
+#[test]
+fn builder() -> io::Result<()> {
+    let packet = BuiltPacket::builder().id(3).flags(7u16).build()?;
+
+    assert_eq!(
+        packet,
+        BuiltPacket {
+            id: 3,
+            flags: 7,
+            sender: "Anonymous".to_string(),
+            reply_to: None,
+        }
+    );
+    assert_eq!(
+        BuiltPacket::builder().build(),
+        Err(tora::builder::MissingField("id"))
+    );
+    assert_rw_eq(packet)
+}
+
+#[derive(Debug, PartialEq, ReadStruct, WriteStruct)]
+struct OrderedPacket {
+    #[tora(order = 2)]
+    kind: u8,
+    #[tora(order = 0)]
+    length: u16,
+    #[tora(order = 1)]
+    flags: u8,
+}
+
+#[derive(Debug, PartialEq, ReadEnum, WriteEnum)]
+enum OrderedEnum {
+    Tuple(#[tora(order = 1)] u8, #[tora(order = 0)] u16),
+    Named {
+        #[tora(order = 1)]
+        w: u8,
+        #[tora(order = 0)]
+        config: u8,
+    },
+}
+
+#[test]
+fn field_order() -> io::Result<()> {
+    let packet = OrderedPacket {
+        kind: 1,
+        length: 2,
+        flags: 3,
+    };
+
+    let mut bytes = Vec::new();
+    bytes.writes(&packet)?;
+
+    assert_eq!(bytes, [2, 0, 3, 1]);
+    assert_rw_eq(packet)?;
+
+    let mut bytes = Vec::new();
+    bytes.writes(&OrderedEnum::Tuple(1, 2))?;
+
+    assert_eq!(bytes, [0, 2, 0, 1]);
+    assert_rw_eq(OrderedEnum::Tuple(1, 2))?;
+    assert_rw_eq(OrderedEnum::Named { w: 1, config: 2 })
+}
+
+#[derive(Debug, PartialEq, ReadStruct, Skip, WriteStruct)]
+struct PaddedPacket {
+    #[tora(pad_after = 3)]
+    kind: u8,
+    #[tora(pad_before = 2, pad_after = 1)]
+    value: u16,
+}
+
+#[test]
+fn field_padding() -> io::Result<()> {
+    let packet = PaddedPacket { kind: 1, value: 2 };
+
+    let mut bytes = Vec::new();
+    bytes.writes(&packet)?;
+
+    assert_eq!(bytes, [1, 0, 0, 0, 0, 0, 2, 0, 0]);
+    assert_rw_eq(packet)
+}
+
+#[derive(ConstSize, Debug, PartialEq, ReadStruct, Reflect, Skip, WriteStruct)]
+#[tora(repr_c, assert_size = 16)]
+#[repr(C)]
+struct ReprCPacket {
+    channel: u8,
+    value: u32,
+    flags: u16,
+    inner: ReprCInner,
+}
+
+#[derive(ConstSize, Debug, PartialEq, ReadStruct, Reflect, Skip, WriteStruct)]
+#[tora(repr_c)]
+#[repr(C)]
+struct ReprCInner(u8, u16);
+
+#[test]
+fn repr_c_layout() -> io::Result<()> {
+    let packet = ReprCPacket {
+        channel: 1,
+        value: 2,
+        flags: 3,
+        inner: ReprCInner(4, 5),
+    };
+
+    let mut bytes = Vec::new();
+    bytes.writes(&packet)?;
+
+    assert_eq!(bytes.len(), std::mem::size_of::<ReprCPacket>());
+    assert_eq!(bytes, [1, 0, 0, 0, 2, 0, 0, 0, 3, 0, 4, 0, 5, 0, 0, 0]);
+    assert_rw_eq(packet)
+}
+
+#[test]
+fn in_place_update() -> io::Result<()> {
+    let mut table = Vec::new();
+    for channel in 0..3 {
+        table.writes(&ReprCPacket {
+            channel,
+            value: 2,
+            flags: 3,
+            inner: ReprCInner(4, 5),
+        })?;
+    }
+
+    let path = [Segment::Field("inner"), Segment::Field("1")];
+    assert_eq!(ReprCPacket::schema().offset_of(&path).unwrap().0, 12);
+
+    tora::layout::update_field::<ReprCPacket, _, _>(
+        &mut Cursor::new(&mut table[..]),
+        2,
+        &path,
+        &9u16,
+    )?;
+    let err = tora::layout::update_field::<ReprCPacket, _, _>(
+        &mut Cursor::new(&mut table[..]),
+        2,
+        &path,
+        &9u32,
+    )
+    .unwrap_err();
+    assert_eq!(err.kind(), ErrorKind::InvalidInput);
+
+    let mut cursor = Cursor::new(&table[ReprCPacket::SIZE * 2..]);
+    assert_eq!(cursor.reads::<ReprCPacket>()?.inner, ReprCInner(4, 9));
+    Ok(())
+}
+
+#[derive(ConstSize, Debug, PartialEq, ReadStruct, WriteStruct)]
+#[tora(assert_size = 12)]
+struct SizedPacket {
+    #[tora(pad_before = 2)]
+    kind: SizedKind,
+    position: [u16; 2],
+    #[tora(pad_after = 1)]
+    alive: bool,
+}
+
+#[derive(ConstSize, Debug, PartialEq, ReadEnum, WriteEnum)]
+#[type_variant_id(u32)]
+enum SizedKind {
+    Player,
+    Entity,
+}
+
+#[test]
+fn const_size() -> io::Result<()> {
+    assert_eq!(SizedKind::SIZE, 4);
+    assert_eq!(ReprCPacket::SIZE, std::mem::size_of::<ReprCPacket>());
+
+    let packet = SizedPacket {
+        kind: SizedKind::Entity,
+        position: [1, 2],
+        alive: true,
+    };
+
+    let mut bytes = Vec::new();
+    bytes.writes(&packet)?;
+
+    assert_eq!(bytes.len(), SizedPacket::SIZE);
+    assert_rw_eq(packet)
+}
+
+#[derive(Debug, Default, PartialEq, ReadStruct, WriteStruct)]
+#[tora(test_roundtrip)]
+struct DefaultPacket {
+    id: u8,
+    kind: DefaultKind,
+    names: Vec<String>,
+}
+
+#[derive(Debug, Default, PartialEq, ReadEnum, WriteEnum)]
+#[tora(test_roundtrip)]
+enum DefaultKind {
+    #[default]
+    Empty,
+    Full(u32),
+}
+
+mod shadowed {
+    use tora_derive::{ReadStruct, WriteStruct};
+
+    /// Shadows the prelude's Vec, which the generated code must not rely on.
+    #[allow(dead_code)]
+    struct Vec;
+
+    #[derive(Debug, Default, PartialEq, ReadStruct, WriteStruct)]
+    #[tora(test_roundtrip)]
+    struct ShadowedPacket {
+        id: u8,
+    }
+}
+
+#[derive(Debug, PartialEq, ReadStruct, WriteStruct)]
+#[non_exhaustive]
+struct FutureProofPacket {
+    kind: FutureProofKind,
+    flags: FallbackFlags,
+}
+
+#[derive(Debug, PartialEq, ReadEnum, Skip, WriteEnum)]
+#[type_variant_id(u16)]
+#[non_exhaustive]
+enum FutureProofKind {
+    Join,
+    #[tora(fallback)]
+    Unknown {
+        id: u16,
+    },
+    Quit,
+}
+
+#[derive(Debug, PartialEq, ReadEnum, WriteEnum)]
+enum FallbackFlags {
+    None,
+    #[tora(fallback)]
+    Other,
+}
+
+#[test]
+fn fallback_variants() -> io::Result<()> {
+    let packet: FutureProofPacket = Cursor::new([2, 0, 1]).reads()?;
+    assert_eq!(packet.kind, FutureProofKind::Quit);
+    assert_eq!(packet.flags, FallbackFlags::Other);
+
+    let packet: FutureProofPacket = Cursor::new([7, 0, 9]).reads()?;
+    assert_eq!(packet.kind, FutureProofKind::Unknown { id: 7 });
+    assert_eq!(packet.flags, FallbackFlags::Other);
+
+    let mut bytes = Vec::new();
+    bytes.writes(&packet)?;
+    assert_eq!(bytes, [7, 0, 1]);
+
+    assert_rw_eq(FutureProofPacket {
+        kind: FutureProofKind::Join,
+        flags: FallbackFlags::None,
+    })
+}
+
+#[derive(Debug, PartialEq, ReadStruct, WriteStruct)]
+struct ComputedPacket {
+    #[tora(get = "name_count")]
+    name_count: u8,
+    names: Vec<String>,
+    #[tora(set = "set_score")]
+    score: u32,
+}
+
+impl ComputedPacket {
+    fn name_count(&self) -> u8 {
+        self.names.len() as u8
+    }
+
+    fn set_score(&mut self, score: u32) {
+        self.score = score * 2;
+    }
+}
+
+#[test]
+fn getters_and_setters() -> io::Result<()> {
+    let packet = ComputedPacket {
+        name_count: 0,
+        names: vec!["a".to_string()],
+        score: 3,
+    };
+
+    let mut bytes = Vec::new();
+    bytes.writes(&packet)?;
+    assert_eq!(bytes, [1, 1, 0, 0, 0, b'a', 0, 3, 0, 0, 0]);
+
+    let read: ComputedPacket = Cursor::new(bytes).reads()?;
+    assert_eq!(read.name_count, 1);
+    assert_eq!(read.score, 6);
+    Ok(())
+}
+
+#[derive(Debug, PartialEq, ReadStruct, Reflect, Skip, WriteStruct)]
+struct VirtualPacket {
+    names: Vec<String>,
+    #[tora(get = "name_count", set = "check_name_count")]
+    name_count: PhantomData<u8>,
+}
+
+impl VirtualPacket {
+    fn name_count(&self) -> u8 {
+        self.names.len() as u8
+    }
+
+    fn check_name_count(&mut self, count: u8) {
+        assert_eq!(count, self.name_count());
+    }
+}
+
+#[test]
+fn virtual_fields() -> io::Result<()> {
+    let packet = VirtualPacket {
+        names: vec!["a".to_string(), "b".to_string()],
+        name_count: PhantomData,
+    };
+
+    let mut bytes = Vec::new();
+    bytes.writes(&packet)?;
+    assert_eq!(bytes, [2, 0, 0, 0, b'a', 0, b'b', 0, 2]);
+
+    let schema = VirtualPacket::schema();
+    let len = walk(
+        &bytes,
+        &schema,
+        &ToraConfig::DEFAULT,
+        &mut Collect::default(),
+    )?;
+    assert_eq!(len, bytes.len());
+
+    let read: VirtualPacket = Cursor::new(bytes).reads()?;
+    assert_skips(read, &ToraConfig::DEFAULT)
+}
+
+#[derive(Debug, PartialEq, ReadEnum, Reflect, WriteEnum)]
+#[tora(length_prefixed)]
+enum PrefixedPacket {
+    Chat(String),
+    Move {
+        x: i32,
+        y: i32,
+    },
+    Quit,
+    #[tora(fallback)]
+    Unknown(u8),
+}
+
+#[test]
+fn length_prefixed_variants() -> io::Result<()> {
+    let mut bytes = Vec::new();
+    bytes.writes(&PrefixedPacket::Move { x: 1, y: -1 })?;
+    assert_eq!(bytes, [1, 8, 0, 0, 0, 1, 0, 0, 0, 255, 255, 255, 255]);
+
+    // A newer Quit variant carrying a trailing field, followed by an unknown variant.
+    let mut reader = Cursor::new([2, 1, 0, 0, 0, 9, 6, 2, 0, 0, 0, 1, 2]);
+    assert_eq!(reader.reads::<PrefixedPacket>()?, PrefixedPacket::Quit);
+    assert_eq!(
+        reader.reads::<PrefixedPacket>()?,
+        PrefixedPacket::Unknown(6)
+    );
+    assert_eq!(reader.position(), 13);
+
+    let config = ToraConfig::DEFAULT.length_prefix(LengthPrefix::U8);
+    let mut bytes = Vec::new();
+    bytes.writes_with(&PrefixedPacket::Chat("Hi".to_string()), &config)?;
+    assert_eq!(bytes, [0, 3, b'H', b'i', 0]);
+
+    assert_rw_eq(PrefixedPacket::Unknown(6))?;
+    assert_rw_eq(PrefixedPacket::Chat("Hello".to_string()))
+}
+
+/// Collects every value visited, with its formatted path.
+#[derive(Default)]
+struct Collect(Vec<(String, Range<usize>, Option<Value>)>);
+
+impl Visitor for Collect {
+    fn visit(&mut self, visit: Visit<'_>) {
+        self.0
+            .push((format_path(visit.path), visit.range, visit.value));
+    }
+}
+
+#[test]
+fn reflect_walk() -> io::Result<()> {
+    let mut bytes = Vec::new();
+    bytes.writes(&ReprCPacket {
+        channel: 1,
+        value: 2,
+        flags: 3,
+        inner: ReprCInner(4, 5),
+    })?;
+
+    let schema = ReprCPacket::schema();
+    let mut visits = Collect::default();
+    let len = walk(&bytes, &schema, &ToraConfig::DEFAULT, &mut visits)?;
+
+    assert_eq!(len, 16);
+    assert_eq!(
+        visits.0[1],
+        ("value".to_string(), 4..8, Some(Value::U32(2)))
+    );
+    assert_eq!(
+        visits.0[4],
+        ("inner.1".to_string(), 12..14, Some(Value::U16(5)))
+    );
+
+    let mut bytes = Vec::new();
+    bytes.writes(&PrefixedPacket::Move { x: 1, y: -1 })?;
+
+    let schema = PrefixedPacket::schema();
+    let mut visits = Collect::default();
+    walk(&bytes, &schema, &ToraConfig::DEFAULT, &mut visits)?;
+
+    let paths = visits.0.into_iter().map(|v| v.0).collect::<Vec<_>>();
+    assert_eq!(paths, ["::Move.x", "::Move.y", ""]);
+    Ok(())
+}
+
+#[test]
+fn walk_fallback_variant() -> io::Result<()> {
+    let schema = PrefixedPacket::schema();
+
+    // An unknown variant 6 with a 2 byte payload.
+    let bytes = [6, 2, 0, 0, 0, 1, 2];
+    let mut visits = Collect::default();
+    assert_eq!(walk(&bytes, &schema, &ToraConfig::DEFAULT, &mut visits)?, 7);
+    assert_eq!(visits.0, [(String::new(), 0..7, None)]);
+
+    let json = tora::json::to_json(&bytes, &schema, &ToraConfig::DEFAULT)?;
+    assert_eq!(json, "{\n  \"Unknown\": 6\n}");
+
+    let written = tora::json::from_json(&json, &schema, &ToraConfig::DEFAULT)?;
+    assert_eq!(
+        written,
+        tora::testing::to_bytes(&PrefixedPacket::Unknown(6))
+    );
+    Ok(())
+}
+
+#[test]
+fn walk_truncated_variant() {
+    let schema = PrefixedPacket::schema();
+
+    // A Quit variant claiming 100 bytes, of which 1 is present.
+    let bytes = [2, 100, 0, 0, 0, 0];
+    let e = walk(
+        &bytes,
+        &schema,
+        &ToraConfig::DEFAULT,
+        &mut Collect::default(),
+    )
+    .unwrap_err();
+    assert_eq!(e.kind(), ErrorKind::UnexpectedEof);
+
+    let config = ToraConfig::DEFAULT.length_prefix(LengthPrefix::U64);
+    let bytes = [2, 255, 255, 255, 255, 255, 255, 255, 255];
+    let e = walk(&bytes, &schema, &config, &mut Collect::default()).unwrap_err();
+    assert_eq!(e.kind(), ErrorKind::UnexpectedEof);
+}
+
+tora_derive::service! {
+    #[derive(Debug, PartialEq)]
+    #[type_variant_id(u16)]
+    trait Inventory {
+        fn add_item(StructPacket) -> u32;
+        fn clear(()) -> Result<(), String>;
+    }
+}
+
+struct InventoryServer(Vec<StructPacket>);
+
+impl Inventory for InventoryServer {
+    fn add_item(&mut self, request: StructPacket) -> u32 {
+        self.0.push(request);
+        self.0.len() as u32
+    }
+
+    fn clear(&mut self, _: ()) -> Result<(), String> {
+        self.0.clear();
+        Ok(())
+    }
+}
+
+#[test]
+fn service_dispatch() -> io::Result<()> {
+    let mut bytes = Vec::new();
+    bytes.writes(&InventoryRequest::AddItem(StructPacket {
+        id: 1,
+        sender: "John".to_string(),
+        content: vec![2],
+    }))?;
+    assert_eq!(&bytes[..2], [0, 0]);
+
+    let mut server = InventoryServer(Vec::new());
+    let response = server.handle(Cursor::new(bytes).reads()?);
+
+    assert_eq!(response, InventoryResponse::AddItem(1));
+    assert_eq!(
+        server.handle(InventoryRequest::Clear(())),
+        InventoryResponse::Clear(Ok(()))
+    );
+    assert_rw_eq(response)
+}
+
+#[derive(Clone, Debug, PartialEq, Columnar)]
+struct Telemetry {
+    #[tora(order = 1)]
+    sensor: u8,
+    #[tora(order = 0)]
+    timestamp: u32,
+    #[tora(order = 2)]
+    label: String,
+}
+
+#[test]
+fn columnar_batches() -> io::Result<()> {
+    let batch = Columns(vec![
+        Telemetry {
+            sensor: 1,
+            timestamp: 100,
+            label: "a".to_string(),
+        },
+        Telemetry {
+            sensor: 2,
+            timestamp: 200,
+            label: "b".to_string(),
+        },
+    ]);
+    tora::assert_bytes_eq!(
+        batch,
+        "02 00 00 00 64 00 00 00 c8 00 00 00 01 02 61 00 62 00"
+    );
+    assert_rw_eq(batch.clone())?;
+
+    let empty: Columns<Telemetry> = Columns(Vec::new());
+    assert_rw_eq(empty)?;
+
+    let truncated = &tora::testing::to_bytes(&batch)[..14];
+    tora::testing::assert_decode_error::<Columns<Telemetry>>(truncated, ErrorKind::UnexpectedEof);
+    Ok(())
+}
+
+/// Writes strings as their length in a u8, then their bytes.
+struct ShortString;
+
+impl Codec<String> for ShortString {
+    fn encode(&self, value: &String, mut w: &mut dyn Write, config: &ToraConfig) -> io::Result<()> {
+        let len = u8::try_from(value.len())
+            .map_err(|_| io::Error::new(ErrorKind::InvalidInput, "String is too long"))?;
+        w.writes_with(&len, config)?;
+        w.write_all(value.as_bytes())
+    }
+
+    fn decode(&self, mut r: &mut dyn Read, config: &ToraConfig) -> io::Result<String> {
+        let len = r.reads_with::<u8>(config)?;
+        let mut buf = vec![0; len as usize];
+        r.read_exact(&mut buf)?;
+        String::from_utf8(buf).map_err(|_| io::Error::new(ErrorKind::InvalidData, "Invalid UTF-8"))
+    }
+}
+
+static WIRE_CODECS: LazyLock<Codecs> = LazyLock::new(|| {
+    let mut codecs = Codecs::new();
+    codecs
+        .register::<u32, _>(VarInt)
+        .register::<String, _>(ShortString);
+    codecs
+});
+
+#[test]
+fn codec_overrides() -> io::Result<()> {
+    let config = ToraConfig::DEFAULT.codecs(&WIRE_CODECS);
+    let packet = StructPacket {
+        id: 3,
+        sender: "John".to_string(),
+        content: vec![1, 2],
+    };
+
+    let mut bytes = Vec::new();
+    bytes.writes_with(&packet, &config)?;
+    assert_eq!(bytes, [3, 4, b'J', b'o', b'h', b'n', 2, 1, 2]);
+    assert_eq!(
+        Cursor::new(bytes).reads_with::<StructPacket>(&config)?,
+        packet
+    );
+
+    let mut borrowed = Vec::new();
+    borrowed.writes_with("John", &config)?;
+    assert_eq!(borrowed, [4, b'J', b'o', b'h', b'n']);
+
+    // Without the registry, the built-in encoding is used.
+    assert_eq!(
+        tora::testing::to_bytes(&packet),
+        [3, b'J', b'o', b'h', b'n', 0, 2, 0, 0, 0, 1, 2]
+    );
+    Ok(())
+}
+
+#[derive(Debug, PartialEq, FromSlice)]
+struct BorrowedPacket<'a> {
+    id: u8,
+    sender: &'a str,
+    content: &'a [u8],
+}
+
+#[derive(Debug, PartialEq, FromSlice)]
+struct Wrapped<'a, T>(#[tora(order = 1)] Option<T>, #[tora(order = 0)] &'a str);
+
+#[derive(Debug, PartialEq, FromSlice)]
+struct Unborrowed(u16, #[tora(pad_after = 1)] bool);
+
+#[test]
+fn slice_reads() -> io::Result<()> {
+    let packet = StructPacket {
+        id: 3,
+        sender: "John".to_string(),
+        content: vec![1, 2],
+    };
+    let mut bytes = tora::testing::to_bytes(&packet);
+    bytes.push(0xFF);
+
+    let (borrowed, rest) = BorrowedPacket::from_slice(&bytes)?;
+    assert_eq!(
+        borrowed,
+        BorrowedPacket {
+            id: 3,
+            sender: "John",
+            content: &[1, 2],
+        }
+    );
+    assert_eq!(rest, [0xFF]);
+
+    let config = ToraConfig::DEFAULT
+        .string_format(StringFormat::LengthPrefixed)
+        .length_prefix(LengthPrefix::U8);
+    let bytes = tora::testing::to_bytes_with(&("Hi", Some(7u32)), &config);
+    let (wrapped, _) = Wrapped::<u32>::from_slice_with(&bytes, &config)?;
+    assert_eq!(wrapped, Wrapped(Some(7), "Hi"));
+
+    let (unborrowed, rest) = Unborrowed::from_slice(&[1, 0, 1, 0])?;
+    assert_eq!(unborrowed, Unborrowed(1, true));
+    assert!(rest.is_empty());
+
+    let e = BorrowedPacket::from_slice(&bytes[..3]).unwrap_err();
+    assert_eq!(e.kind(), ErrorKind::UnexpectedEof);
+    Ok(())
+}
+
+#[derive(Debug, PartialEq, ReadEnum, Skip, WriteEnum)]
+enum Expr {
+    Literal(i8),
+    Negate(#[tora(boxed)] Box<Expr>),
+    Add {
+        #[tora(boxed)]
+        left: Box<Expr>,
+        right: Box<Expr>,
+    },
+}
+
+#[derive(ConstSize, Debug, PartialEq, ReadStruct, Reflect, WriteStruct)]
+struct BoxedHeader {
+    #[tora(boxed)]
+    kind: Box<u16>,
+    length: u8,
+}
+
+#[test]
+fn recursive_fields() -> io::Result<()> {
+    let expr = Expr::Add {
+        left: Box::new(Expr::Literal(1)),
+        right: Box::new(Expr::Negate(Box::new(Expr::Literal(2)))),
+    };
+    assert_eq!(tora::testing::to_bytes(&expr), [2, 0, 1, 1, 0, 2]);
+    assert_rw_eq(expr)?;
+
+    let header = BoxedHeader {
+        kind: Box::new(5),
+        length: 3,
+    };
+    assert_eq!(tora::testing::to_bytes(&header), [5, 0, 3]);
+    assert_eq!(BoxedHeader::SIZE, 3);
+    assert_eq!(BoxedHeader::schema().fixed_size(), Some(3));
+    assert_rw_eq(header)
+}
+
+#[derive(Debug, Default, PartialEq, ReadStruct, Skip, WriteStruct)]
+#[tora(presence_bitmap)]
+struct SparseUpdate {
+    id: u16,
+    a: Option<u8>,
+    b: Option<u8>,
+    c: Option<u8>,
+    d: Option<u8>,
+    e: Option<u8>,
+    f: Option<u8>,
+    g: Option<u8>,
+    h: Option<u8>,
+    i: Option<String>,
+}
+
+#[test]
+fn presence_bitmap() -> io::Result<()> {
+    let update = SparseUpdate {
+        id: 1,
+        b: Some(5),
+        i: Some("Hi".to_string()),
+        ..Default::default()
+    };
+    assert_eq!(
+        tora::testing::to_bytes(&update),
+        [0b10, 0b1, 1, 0, 5, b'H', b'i', 0]
+    );
+    assert_rw_eq(update)?;
+    assert_rw_eq(SparseUpdate::default())?;
+
+    let e = tora::testing::decode_error::<SparseUpdate>(&[0, 0b10, 1, 0]);
+    assert_eq!(e.kind(), ErrorKind::InvalidData);
+    Ok(())
+}
+
+#[derive(Debug, PartialEq, ReadStruct, WriteStruct)]
+struct CurrentRecord {
+    id: u8,
+    name: String,
+    level: u16,
+}
+
+#[derive(Debug, PartialEq, ReadStruct, Skip, WriteStruct, FromSlice)]
+#[tora(frame)]
+struct ForwardedRecord {
+    #[tora(order = 1)]
+    #[tora(extensions)]
+    extensions: Vec<u8>,
+    #[tora(order = 0)]
+    id: u8,
+}
+
+#[derive(Debug, PartialEq, ReadEnum, Skip, WriteEnum)]
+#[tora(length_prefixed)]
+enum ForwardedPacket {
+    Record {
+        id: u8,
+        #[tora(extensions)]
+        extensions: Vec<u8>,
+    },
+    Ping,
+}
+
+#[test]
+fn extensions() -> io::Result<()> {
+    let current = CurrentRecord {
+        id: 1,
+        name: "Hi".to_string(),
+        level: 3,
+    };
+    let bytes = tora::testing::to_bytes(&current);
+
+    let forwarded: ForwardedRecord = Cursor::new(&bytes).reads()?;
+    assert_eq!(forwarded.id, 1);
+    assert_eq!(forwarded.extensions, [b'H', b'i', 0, 3, 0]);
+    assert_eq!(tora::testing::to_bytes(&forwarded), bytes);
+
+    let (sliced, rest) = ForwardedRecord::from_slice(&bytes)?;
+    assert_eq!(sliced, forwarded);
+    assert!(rest.is_empty());
+
+    // Extensions stop at the end of a length-prefixed payload.
+    let packets = (
+        ForwardedPacket::Record {
+            id: 1,
+            extensions: vec![5, 6],
+        },
+        ForwardedPacket::Ping,
+    );
+    assert_rw_eq(packets)
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Patch)]
+struct WideState {
+    f0: u8,
+    f1: u8,
+    f2: u8,
+    f3: u8,
+    f4: u8,
+    f5: u8,
+    f6: u8,
+    f7: u8,
+    name: String,
+}
+
+#[test]
+fn patch() -> io::Result<()> {
+    let mut tracker = WideStateTracker::new(WideState::default());
+    assert!(tracker.take_patch().is_empty());
+
+    tracker.set_f1(4);
+    tracker.name_mut().push_str("Hi");
+    assert!(tracker.is_dirty());
+
+    let patch = tracker.take_patch();
+    assert!(!tracker.is_dirty());
+    assert_eq!(
+        tora::testing::to_bytes(&patch),
+        [0b10, 0b1, 4, b'H', b'i', 0]
+    );
+
+    let mut state = WideState::default();
+    state.apply(tora::testing::roundtrip(&patch));
+    assert_eq!(&state, tracker.get());
+
+    let e = tora::testing::decode_error::<WideStatePatch>(&[0, 0b10]);
+    assert_eq!(e.kind(), ErrorKind::InvalidData);
+    Ok(())
+}
+
+#[derive(Debug, PartialEq, ReadEnum, WriteEnum)]
+#[tora(inherent)]
+enum InherentPacket {
+    Ping,
+    Chat { sender: u8, message: String },
+}
+
+#[test]
+fn inherent() -> io::Result<()> {
+    let packet = InherentPacket::Chat {
+        sender: 3,
+        message: "Hi".to_string(),
+    };
+    let bytes = packet.to_tora_vec()?;
+    assert_eq!(bytes, [1, 3, b'H', b'i', 0]);
+    assert_eq!(InherentPacket::from_tora_slice(&bytes)?, packet);
+
+    let e = InherentPacket::from_tora_slice(&[0, 0]).unwrap_err();
+    assert_eq!(e.kind(), ErrorKind::InvalidData);
+    Ok(())
+}
+
+/// Asserts that skipping the written value stops at the byte following it, through a seekable and
+/// an unseekable reader.
+fn assert_skips<T>(value: T, config: &ToraConfig) -> io::Result<()>
+where
+    T: SerializeIo + Skip,
+{
+    let mut bytes = Vec::new();
+    bytes.writes_with(&value, config)?;
+    bytes.push(0xAA);
+
+    let mut cursor = Cursor::new(&bytes);
+    T::skip_with(&mut cursor, config)?;
+    assert_eq!(cursor.reads::<u8>()?, 0xAA);
+
+    let mut reader = Discarding(bytes.as_slice());
+    T::skip_with(&mut reader, config)?;
+    assert_eq!(reader.reads::<u8>()?, 0xAA);
+    Ok(())
+}
+
+#[test]
+fn skip() -> io::Result<()> {
+    let config = &ToraConfig::DEFAULT;
+
+    assert_skips(PaddedPacket { kind: 1, value: 2 }, config)?;
+    assert_skips(
+        ReprCPacket {
+            channel: 1,
+            value: 2,
+            flags: 3,
+            inner: ReprCInner(4, 5),
+        },
+        config,
+    )?;
+    assert_skips(
+        (FutureProofKind::Unknown { id: 9 }, FutureProofKind::Quit),
+        config,
+    )?;
+    assert_skips(
+        Expr::Add {
+            left: Box::new(Expr::Literal(1)),
+            right: Box::new(Expr::Negate(Box::new(Expr::Literal(2)))),
+        },
+        config,
+    )?;
+    assert_skips(
+        SparseUpdate {
+            id: 1,
+            b: Some(5),
+            i: Some("Hi".to_string()),
+            ..Default::default()
+        },
+        config,
+    )?;
+    assert_skips(
+        ForwardedPacket::Record {
+            id: 1,
+            extensions: vec![5, 6],
+        },
+        config,
+    )?;
+
+    let prefixed = ToraConfig::DEFAULT
+        .length_prefix(LengthPrefix::U8)
+        .string_format(StringFormat::LengthPrefixed)
+        .int_sequence_format(IntSequenceFormat::Packed);
+    assert_skips(
+        (
+            Some("Hello".to_string()),
+            vec![[1u16, 2], [3, 4]],
+            vec![100u64, 90, 110],
+        ),
+        &prefixed,
+    )?;
+
+    // Extensions hold the rest of the reader.
+    let record = ForwardedRecord {
+        extensions: vec![1, 2],
+        id: 3,
+    };
+    let bytes = tora::testing::to_bytes(&record);
+    let mut cursor = Cursor::new(&bytes);
+    cursor.skips::<ForwardedRecord>()?;
+    assert_eq!(cursor.position() as usize, bytes.len());
+
+    let mut truncated = Cursor::new([2u8, 0, 0, 0, 1]);
+    let e = truncated.skips::<Vec<u16>>().unwrap_err();
+    assert_eq!(e.kind(), ErrorKind::UnexpectedEof);
+    Ok(())
+}